@@ -1,138 +1,45 @@
+// This file contains the benchmarks, which compare packed trees with other tree crates.
+// They need the test-util feature for the gen module, so run them with `cargo bench --features test-util`;
+// a plain `cargo bench` skips them.
+
 #[macro_use]
 extern crate criterion;
 
 use criterion::Criterion;
 use criterion::black_box;
 
-use packed_tree::{PackedTree, ExactSizePackedTree, NodeBuilder, ExactSizeNodeBuilder};
+use packed_tree::{ExactSizePackedTree, ExactSizeNodeBuilder};
+use packed_tree::gen::{self, NodeCreator, SimpleNodeCreator, RandomNodeCreator};
 
 use rand::{Rng, SeedableRng};
-use rand::distributions::{Distribution, Uniform};
 
 use failure::Fallible;
 use std::hash::{Hash,Hasher};
 use std::time::Duration;
 use std::marker::PhantomData;
 
-trait NodeCreator : Sized {
-    type ValType;
-    fn val(&self) -> Self::ValType;
-    fn next_child(&mut self, rng: &mut impl Rng) -> Option<Self>;
-}
-
-// Creates a tree where at each level, every node has the same number of children
-struct SimpleNodeCreator<'a> {
-    val: u32,
-    depth: usize,
-    num_children_created: usize,
-    children_per_node_per_level: &'a [usize],
-}
-
-impl<'a> NodeCreator for SimpleNodeCreator<'a> {
-    type ValType = u32;
-
-    fn val(&self) -> Self::ValType {
-        self.val
-    }
-
-    fn next_child(&mut self, rng: &mut impl Rng) -> Option<Self> {
-        self.children_per_node_per_level.get(self.depth).and_then(|max_children| {
-            if self.num_children_created < *max_children {
-                self.num_children_created += 1;
-                Some(SimpleNodeCreator {
-                    val: rng.next_u32(),
-                    depth: self.depth+1,
-                    num_children_created: 0,
-                    children_per_node_per_level: self.children_per_node_per_level,
-                })
-            } else {
-                None
-            }
-        })
-    }
-}
-
 fn _make_flat_tree() -> SimpleNodeCreator<'static> {
-    SimpleNodeCreator {
-        val: 1,
-        depth: 0,
-        num_children_created: 0,
-        children_per_node_per_level: &[1000]
-    }
+    SimpleNodeCreator::new(&[1000])
 }
 
 fn make_small_tree() -> SimpleNodeCreator<'static> {
-    SimpleNodeCreator {
-        val: 1,
-        depth: 0,
-        num_children_created: 0,
-        children_per_node_per_level: &[5,5,5]
-    }
+    SimpleNodeCreator::new(&[5,5,5])
 }
 
 fn make_shallow_tree() -> SimpleNodeCreator<'static> {
-    SimpleNodeCreator {
-        val: 1,
-        depth: 0,
-        num_children_created: 0,
-        children_per_node_per_level: &[100,100]
-    }
+    SimpleNodeCreator::new(&[100,100])
 }
 
 fn make_binary_tree() -> SimpleNodeCreator<'static> {
-    SimpleNodeCreator {
-        val: 1,
-        depth: 0,
-        num_children_created: 0,
-        children_per_node_per_level: &[2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]
-    }
-}
-
-
-// Creates a tree where at each level, every node has the same number of children
-struct RandomNodeCreator<'a> {
-    val: u32,
-    depth: usize,
-    child_chance_per_level: &'a [f64],
-}
-
-impl<'a> NodeCreator for RandomNodeCreator<'a> {
-    type ValType = u32;
-
-    fn val(&self) -> Self::ValType {
-        self.val
-    }
-
-    fn next_child(&mut self, rng: &mut impl Rng) -> Option<Self> {
-        self.child_chance_per_level.get(self.depth).and_then(|child_chance| {
-            let range = Uniform::new(0.0f64, 1.0);
-            if range.sample(rng) < *child_chance {
-                Some(RandomNodeCreator {
-                    val: rng.next_u32(),
-                    depth: self.depth+1,
-                    child_chance_per_level: self.child_chance_per_level,
-                })
-            } else {
-                None
-            }
-        })
-    }
+    SimpleNodeCreator::new(&[2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2])
 }
 
 fn make_wide_random_tree() -> RandomNodeCreator<'static> {
-    RandomNodeCreator {
-        val: 1,
-        depth: 0,
-        child_chance_per_level: &[0.99, 0.99]
-    }
+    RandomNodeCreator::new(&[0.99, 0.99])
 }
 
 fn make_deep_random_tree() -> RandomNodeCreator<'static> {
-    RandomNodeCreator {
-        val: 1,
-        depth: 0,
-        child_chance_per_level: &[2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3.]
-    }
+    RandomNodeCreator::new(&[2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3., 2./3.])
 }
 
 trait TreeVisitor<T, N: VisitableNode<T>> {
@@ -157,7 +64,7 @@ impl<'a,T,N:VisitableNode<T>> TreeVisitor<T,N> for NodesPerLevelCounter<'a> {
             self.nodes_per_level.push(1);
         }
         node.visit_children(NodesPerLevelCounter {
-            nodes_per_level: &mut self.nodes_per_level,
+            nodes_per_level: self.nodes_per_level,
             cur_level: self.cur_level+1
         });
     }
@@ -222,20 +129,6 @@ fn bfs_hash_tree<T:Hash, N: VisitableNode<T>>(root: N) -> u64 {
 
 // ================ Here begin the implementations of the libraries
 
-fn create_packed_tree_rec<C: NodeCreator>(creator: &mut C, rng: &mut impl Rng, packed_node_creator: &mut NodeBuilder<C::ValType>) {
-    while let Some(mut child_creator) = creator.next_child(rng) {
-        packed_node_creator.build_child(child_creator.val(), |child_packed_node_creator| {
-            create_packed_tree_rec(&mut child_creator, rng, child_packed_node_creator);
-        });
-    }
-}
-
-fn create_packed_tree<C: NodeCreator>(mut creator: C, rng: &mut impl Rng) -> PackedTree<C::ValType> {
-    PackedTree::new(creator.val(), |packed_node_creator| {
-        create_packed_tree_rec(&mut creator, rng, packed_node_creator);
-    })
-}
-
 impl<'a,T> VisitableNode<T> for packed_tree::NodeRef<'a,T> {
     fn val(&self) -> &T {
         self.val()
@@ -329,7 +222,7 @@ struct IdTreeNode<'a,T> {
 
 impl<'a,T> VisitableNode<T> for IdTreeNode<'a,T> {
     fn val(&self) -> &T {
-        &self.node.data()
+        self.node.data()
     }
     fn visit_children(self, mut v: impl TreeVisitor<T, Self>) {
         for child in self.node.children() {
@@ -387,7 +280,7 @@ struct VecTreeNode<'a,T> {
 
 impl<'a,T> VisitableNode<T> for VecTreeNode<'a,T> {
     fn val(&self) -> &T {
-        &self.tree.get(self.id).unwrap()
+        self.tree.get(self.id).unwrap()
     }
     fn visit_children(self, mut v: impl TreeVisitor<T, Self>) {
         for child in self.tree.children(self.id) {
@@ -424,7 +317,7 @@ fn create_naive_tree<C: NodeCreator>(mut creator: C, rng: &mut impl Rng) -> Naiv
     root
 }
 
-impl<'a,T> VisitableNode<T> for &'a NaiveNode<T> {
+impl<T> VisitableNode<T> for &NaiveNode<T> {
     fn val(&self) -> &T {
         &self.value
     }
@@ -471,7 +364,7 @@ fn create_ll_tree<C: NodeCreator>(mut creator: C, rng: &mut impl Rng) -> LLNode<
     }
 }
 
-impl<'a,T> VisitableNode<T> for &'a LLNode<T> {
+impl<T> VisitableNode<T> for &LLNode<T> {
     fn val(&self) -> &T {
         &self.value
     }
@@ -536,17 +429,17 @@ fn benchmark_tree_type<C: NodeCreator + 'static>(c: &mut Criterion, creator: fn(
 
     c.bench_function(&format!("make_{}_packed", type_name), move |b| {
         b.iter(|| {
-            create_packed_tree(creator(), &mut black_box(make_rng()))
+            gen::create_tree(creator(), &mut black_box(make_rng()))
         });
     });
     c.bench_function(&format!("hash_{}_packed", type_name), move |b| {
-        let tree = create_packed_tree(creator(), &mut black_box(make_rng()));
+        let tree = gen::create_tree(creator(), &mut black_box(make_rng()));
         b.iter(|| {
             assert_eq!(hash_tree(black_box(tree.root())), hash);
         });
     });
     c.bench_function(&format!("bfs_{}_packed", type_name), move |b| {
-        let tree = create_packed_tree(creator(), &mut black_box(make_rng()));
+        let tree = gen::create_tree(creator(), &mut black_box(make_rng()));
         b.iter(|| {
             assert_eq!(bfs_hash_tree(black_box(tree.root())), bfs_hash);
         });
@@ -0,0 +1,349 @@
+// This file contains ArrayPackedForest, a PackedForest with a fixed capacity that never allocates,
+// and ArrayNodeBuilder, which returns a CapacityError instead of adding nodes past that capacity.
+
+use crate::*;
+
+/// A variant of [`PackedForest`] that stores up to `CAP` nodes inline, and never allocates memory on the heap.
+///
+/// This is useful on targets without an allocator. Adding a node to a full forest returns a [`CapacityError`]
+/// instead of adding the node, so all the methods that add nodes return a [`Result`].
+///
+/// # Example
+/// ```
+/// use packed_tree::{ArrayPackedForest, CapacityError};
+///
+/// let mut forest = ArrayPackedForest::<_, 4>::new();
+/// forest.build_tree(0, |node_builder| {
+///     node_builder.build_child(1, |node_builder| {
+///         node_builder.add_child(2)?;
+///         Ok(())
+///     })
+/// }).unwrap();
+/// assert_eq!(forest.remaining_capacity(), 1);
+///
+/// let result = forest.build_tree(3, |node_builder| {
+///     node_builder.add_child(4)?;
+///     Ok(())
+/// });
+/// assert_eq!(result, Err(CapacityError { capacity: 4 }));
+///
+/// // The tree that didn't fit wasn't added
+/// assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [0, 1, 2]);
+/// ```
+pub struct ArrayPackedForest<T, const CAP: usize> {
+    storage: ArrayNodeStorage<T, CAP>,
+}
+
+impl<T, const CAP: usize> Default for ArrayPackedForest<T, CAP> {
+    #[inline(always)]
+    fn default() -> Self {
+        ArrayPackedForest::new()
+    }
+}
+
+impl<T, const CAP: usize> ArrayPackedForest<T, CAP> {
+    /// Create a new, empty [`ArrayPackedForest`].
+    #[inline(always)]
+    pub fn new() -> ArrayPackedForest<T, CAP> {
+        ArrayPackedForest {
+            storage: ArrayNodeStorage::new(),
+        }
+    }
+
+    /// Returns the maximum number of nodes this forest can hold, which is `CAP`.
+    #[inline(always)]
+    pub fn capacity(&self) -> usize {
+        CAP
+    }
+
+    /// Returns how many more nodes can be added to this forest.
+    #[inline(always)]
+    pub fn remaining_capacity(&self) -> usize {
+        CAP - self.tot_num_nodes()
+    }
+
+    /// Build a tree with the given root value, and add it to the forest.
+    ///
+    /// Like [`PackedForest::build_tree`], but `node_builder_cb` returns a [`Result`], so that the `?` operator
+    /// can be used on the methods of [`ArrayNodeBuilder`]. If it returns `Err`, or the root doesn't fit in the forest,
+    /// no nodes are added and the error is returned.
+    #[inline]
+    pub fn build_tree<R>(
+        &mut self,
+        root_val: T,
+        node_builder_cb: impl FnOnce(&mut ArrayNodeBuilder<T, CAP>) -> Result<R, CapacityError>,
+    ) -> Result<R, CapacityError> {
+        let mut builder = self.get_tree_builder();
+        let ret = node_builder_cb(&mut builder)?;
+        builder.finish(root_val)?;
+        Ok(ret)
+    }
+
+    /// Build a tree, where value of the root node comes from the return value of the given closure, and add it to the forest.
+    ///
+    /// See [`build_tree`](ArrayPackedForest::build_tree).
+    #[inline]
+    pub fn build_tree_by_ret_val(
+        &mut self,
+        node_builder_cb: impl FnOnce(&mut ArrayNodeBuilder<T, CAP>) -> Result<T, CapacityError>,
+    ) -> Result<(), CapacityError> {
+        let mut builder = self.get_tree_builder();
+        let root_val = node_builder_cb(&mut builder)?;
+        builder.finish(root_val)?;
+        Ok(())
+    }
+
+    /// Add a tree with only a single node to the forest. The parameter `val` is the value of that single node.
+    #[inline]
+    pub fn add_single_node_tree(&mut self, val: T) -> Result<(), CapacityError> {
+        self.get_tree_builder().finish(val)?;
+        Ok(())
+    }
+
+    /// Get an [`ArrayNodeBuilder`] that can be used to build a tree that will be added to this forest.
+    ///
+    /// See [`PackedForest::get_tree_builder`].
+    #[inline(always)]
+    pub fn get_tree_builder(&mut self) -> ArrayNodeBuilder<'_, T, CAP> {
+        ArrayNodeBuilder {
+            sub_node_builder: self.storage.get_tree_builder(),
+        }
+    }
+
+    /// Returns an iterator that iterates over (a [`NodeRef`] to) all the trees in this forest.
+    #[inline(always)]
+    pub fn iter_trees(&self) -> NodeIter<'_, T> {
+        self.storage.iter_trees()
+    }
+
+    /// Returns an iterator that iterates over (a [`NodeRefMut`] to) all the trees in this forest.
+    #[inline(always)]
+    pub fn iter_trees_mut(&mut self) -> NodeIterMut<'_, T> {
+        self.storage.iter_trees_mut()
+    }
+
+    /// Get a [`NodeRef`] to the node with the given index, or `None` if the index is out of bounds.
+    ///
+    /// See [`PackedForest::get`].
+    #[inline(always)]
+    pub fn get(&self, index: impl Into<NodeId>) -> Option<NodeRef<'_, T>> {
+        self.storage.get(index.into().index())
+    }
+
+    /// Get a [`NodeRefMut`] to the node with the given index, or `None` if the index is out of bounds.
+    ///
+    /// See [`PackedForest::get_mut`].
+    #[inline(always)]
+    pub fn get_mut(&mut self, index: impl Into<NodeId>) -> Option<NodeRefMut<'_, T>> {
+        self.storage.get_mut(index.into().index())
+    }
+
+    /// Iterate over all the values in all the nodes in all the trees in this forest, in pre-order order.
+    #[inline(always)]
+    pub fn iter_flattened(&self) -> FlattenedIter<'_, T> {
+        FlattenedIter::new(self.storage.as_slice())
+    }
+
+    /// Returns a read-only view over the raw data of this forest. See [`PackedForest::raw_data`].
+    #[inline(always)]
+    pub fn raw_data(&self) -> &[NodeData<T>] {
+        self.storage.as_slice()
+    }
+
+    /// Returns how many nodes are currently in all the trees in this forest in O(1) time.
+    #[inline(always)]
+    pub fn tot_num_nodes(&self) -> usize {
+        self.storage.as_slice().len()
+    }
+
+    /// Remove all nodes from the forest.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.storage.clear()
+    }
+}
+
+/// A struct that lets you add children to a node that is currently being added to an [`ArrayPackedForest`].
+///
+/// Like [`NodeBuilder`], but the methods that add nodes return a [`CapacityError`] when the forest is full.
+pub struct ArrayNodeBuilder<'a, T, const CAP: usize> {
+    sub_node_builder: NodeBuilder<'a, T, ArrayNodeStorage<T, CAP>>,
+}
+
+impl<'a, T, const CAP: usize> ArrayNodeBuilder<'a, T, CAP> {
+    /// Returns the index of the node that is being built.
+    #[inline(always)]
+    pub fn index(&self) -> usize {
+        self.sub_node_builder.index()
+    }
+
+    /// Returns the depth of the node that is being built. See [`NodeBuilder::depth`].
+    #[inline(always)]
+    pub fn depth(&self) -> usize {
+        self.sub_node_builder.depth()
+    }
+
+    /// Returns the number of children that have been added to the node that is being built so far.
+    /// See [`NodeBuilder::num_children_so_far`].
+    #[inline(always)]
+    pub fn num_children_so_far(&self) -> usize {
+        self.sub_node_builder.num_children_so_far()
+    }
+
+    /// Returns the number of nodes that have been added to the subtree of the node that is being built so far,
+    /// including the node itself. See [`NodeBuilder::subtree_size_so_far`].
+    #[inline(always)]
+    pub fn subtree_size_so_far(&self) -> usize {
+        self.sub_node_builder.subtree_size_so_far()
+    }
+
+    /// Returns how many more descendants can be added to the node that is being built before the forest is full.
+    #[inline(always)]
+    pub fn remaining_capacity(&self) -> usize {
+        CAP.saturating_sub(self.next_child_index())
+    }
+
+    // The index the next child will have.
+    #[inline(always)]
+    fn next_child_index(&self) -> usize {
+        self.index() + self.subtree_size_so_far()
+    }
+
+    /// Build a child node with the given value, and add it to the tree as a child of the node
+    /// that is being built by the current [`ArrayNodeBuilder`].
+    ///
+    /// If `child_builder_cb` returns `Err`, or the child doesn't fit in the forest,
+    /// all nodes that were added to the child are dropped, no child is added, and the error is returned.
+    ///
+    /// See [`NodeBuilder::build_child`].
+    #[inline]
+    pub fn build_child<R>(
+        &mut self,
+        val: T,
+        child_builder_cb: impl FnOnce(&mut ArrayNodeBuilder<T, CAP>) -> Result<R, CapacityError>,
+    ) -> Result<R, CapacityError> {
+        if self.next_child_index() >= CAP {
+            return Err(CapacityError { capacity: CAP });
+        }
+        let mut builder = self.get_child_builder();
+        let ret = child_builder_cb(&mut builder)?;
+        builder.finish(val)?;
+        Ok(ret)
+    }
+
+    /// Add a child node with the given value to the tree as a child of the node that is being built by the current [`ArrayNodeBuilder`].
+    /// Returns a [`NodeRefMut`] to the added child node, or an error if the forest is full.
+    #[inline]
+    pub fn add_child(&mut self, val: T) -> Result<NodeRefMut<'_, T>, CapacityError> {
+        self.get_child_builder().finish(val)
+    }
+
+    /// Add a leaf child node for each of the given values, as children of the node that is being built.
+    ///
+    /// If the forest gets full, the children that were added before that are kept, and an error is returned.
+    pub fn add_leaf_children(&mut self, vals: impl IntoIterator<Item = T>) -> Result<(), CapacityError> {
+        for val in vals {
+            self.add_child(val)?;
+        }
+        Ok(())
+    }
+
+    /// Add a leaf child node for each of the values in the given slice, as children of the node that is being built.
+    ///
+    /// If not all of the values fit in the forest, no children are added and an error is returned.
+    ///
+    /// See [`NodeBuilder::add_leaf_children_from_slice`].
+    pub fn add_leaf_children_from_slice(&mut self, vals: &[T]) -> Result<(), CapacityError> where T: Copy {
+        if vals.len() > self.remaining_capacity() {
+            return Err(CapacityError { capacity: CAP });
+        }
+        self.sub_node_builder.add_leaf_children_from_slice(vals);
+        Ok(())
+    }
+
+    /// Get an [`ArrayNodeBuilder`] that builds a child that will be added as a child of the node
+    /// that is being built by the current [`ArrayNodeBuilder`].
+    ///
+    /// See [`NodeBuilder::get_child_builder`].
+    #[inline(always)]
+    pub fn get_child_builder<'b>(&'b mut self) -> ArrayNodeBuilder<'b, T, CAP> {
+        ArrayNodeBuilder {
+            sub_node_builder: self.sub_node_builder.get_child_builder(),
+        }
+    }
+
+    /// Finish building the node that this [`ArrayNodeBuilder`] was building, giving it its value
+    /// and adding its nodes to the forest or the parent [`ArrayNodeBuilder`].
+    /// Returns a [`NodeRefMut`] to the node that was added.
+    ///
+    /// If the node doesn't fit in the forest, `val` and all nodes that were added to this node are dropped,
+    /// and an error is returned.
+    ///
+    /// See [`NodeBuilder::finish`].
+    #[inline]
+    pub fn finish(self, val: T) -> Result<NodeRefMut<'a, T>, CapacityError> {
+        // The descendants were only added if they fit, so only the node itself can be out of capacity.
+        if self.index() >= CAP {
+            return Err(CapacityError { capacity: CAP });
+        }
+        Ok(self.sub_node_builder.finish(val))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_array_forest() {
+        let mut forest = ArrayPackedForest::<String, 5>::new();
+        assert_eq!(forest.capacity(), 5);
+        forest.build_tree("a".to_string(), |node_builder| {
+            node_builder.add_child("b".to_string())?;
+            node_builder.build_child("c".to_string(), |node_builder| {
+                assert_eq!(node_builder.remaining_capacity(), 2);
+                node_builder.add_child("d".to_string())?.val_mut().push('!');
+                Ok(())
+            })
+        }).unwrap();
+        assert_eq!(forest.remaining_capacity(), 1);
+
+        // A tree that fits except for its root
+        let result = forest.build_tree("e".to_string(), |node_builder| {
+            node_builder.add_child("f".to_string())?;
+            Ok(())
+        });
+        assert_eq!(result, Err(CapacityError { capacity: 5 }));
+        assert_eq!(forest.tot_num_nodes(), 4);
+        forest.build_tree_by_ret_val(|node_builder| {
+            assert!(node_builder.get_child_builder().finish("g".to_string()).is_err());
+            Ok("e".to_string())
+        }).unwrap();
+        assert!(forest.add_single_node_tree("f".to_string()).is_err());
+        assert_eq!(forest.iter_flattened().collect::<Vec<_>>(), ["a", "b", "c", "d!", "e"]);
+        assert_eq!(forest.get(2).unwrap().children().map(|child| child.val().as_str()).collect::<Vec<_>>(), ["d!"]);
+        forest.get_mut(4).unwrap().val_mut().push('?');
+        assert_eq!(forest.iter_trees().map(|tree| tree.val().as_str()).collect::<Vec<_>>(), ["a", "e?"]);
+        assert!(forest.get(5).is_none());
+
+        forest.clear();
+        assert_eq!(forest.tot_num_nodes(), 0);
+        let result = forest.build_tree("root".to_string(), |node_builder| {
+            node_builder.add_leaf_children((0..10).map(|i| i.to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(forest.tot_num_nodes(), 0);
+
+        let mut forest = ArrayPackedForest::<u32, 3>::new();
+        forest.build_tree(0, |node_builder| {
+            assert!(node_builder.add_leaf_children_from_slice(&[1, 2, 3]).is_err());
+            assert_eq!(node_builder.subtree_size_so_far(), 1);
+            node_builder.add_leaf_children_from_slice(&[1, 2])
+        }).unwrap();
+        assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [0, 1, 2]);
+        for mut tree in forest.iter_trees_mut() {
+            *tree.val_mut() += 10;
+        }
+        assert_eq!(*forest.raw_data()[0].val(), 10);
+    }
+}
@@ -0,0 +1,231 @@
+// This file contains ChangeSet, which finds the regions that differ between two forests,
+// using a hash of every subtree (like a Merkle tree) to skip the subtrees that are identical.
+
+use crate::*;
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+/// A hash of every subtree of a [`PackedForest`], indexed by the pre-order index of the root of the subtree.
+///
+/// The hash of a subtree is computed from the value of its root and the hashes of the subtrees of its children,
+/// so equal subtrees have equal hashes, and subtrees with equal hashes are equal with very high probability.
+/// The hashes only depend on the values and the structure of the subtrees, not on the forest they are in
+/// or on the run of the program, so they can be kept and compared with the hashes of a later version of the forest
+/// (see [`ChangeSet::between_with_hashes`]).
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct SubtreeHashes {
+    hashes: Vec<u64>,
+}
+
+impl SubtreeHashes {
+    /// Computes the hashes of all subtrees of the given forest in O(n) time, where n is the number of nodes.
+    pub fn new<T: Hash>(forest: &PackedForest<T>) -> SubtreeHashes {
+        let data = forest.raw_data();
+        let mut hashes = vec![0; data.len()];
+        // The children of a node come after it in pre-order, so their hashes are known when it is reached in reverse.
+        for index in (0..data.len()).rev() {
+            let mut hasher = DefaultHasher::new();
+            data[index].val().hash(&mut hasher);
+            let end = index + data[index].subtree_size().get();
+            let mut child_index = index + 1;
+            while child_index < end {
+                hasher.write_u64(hashes[child_index]);
+                child_index += data[child_index].subtree_size().get();
+            }
+            hashes[index] = hasher.finish();
+        }
+        SubtreeHashes { hashes }
+    }
+
+    /// Returns the hash of the subtree of the node with the given pre-order index, or `None` if the index is out of bounds.
+    #[inline(always)]
+    pub fn get(&self, index: usize) -> Option<u64> {
+        self.hashes.get(index).copied()
+    }
+
+    /// Returns the hashes of the subtrees of all nodes, in pre-order.
+    #[inline(always)]
+    pub fn as_slice(&self) -> &[u64] {
+        &self.hashes
+    }
+}
+
+/// A region that differs between two forests, see [`ChangeSet`].
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Change {
+    /// The pre-order indices of the nodes of the region in the old forest.
+    pub old_range: Range<usize>,
+    /// The pre-order indices of the nodes of the region in the new forest.
+    pub new_range: Range<usize>,
+}
+
+/// The regions that differ between two forests, see [`ChangeSet::between`].
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Default)]
+pub struct ChangeSet {
+    changes: Vec<Change>,
+}
+
+// The forests and the hashes of their subtrees.
+struct ChangeFinder<'a, T> {
+    old: &'a PackedForest<T>,
+    old_hashes: &'a SubtreeHashes,
+    new: &'a PackedForest<T>,
+    new_hashes: &'a SubtreeHashes,
+    changes: Vec<Change>,
+}
+
+impl<'a, T: PartialEq> ChangeFinder<'a, T> {
+    // Compares two lists of nodes that start at the given indices, pairing them up by position.
+    fn compare_lists(&mut self, old_nodes: NodeIter<'a, T>, mut old_index: usize, new_nodes: NodeIter<'a, T>, mut new_index: usize) {
+        let mut old_nodes = old_nodes.peekable();
+        let mut new_nodes = new_nodes.peekable();
+        while let (Some(old_node), Some(new_node)) = (old_nodes.peek(), new_nodes.peek()) {
+            self.compare_nodes(*old_node, old_index, *new_node, new_index);
+            old_index += old_node.num_descendants_incl_self();
+            new_index += new_node.num_descendants_incl_self();
+            old_nodes.next();
+            new_nodes.next();
+        }
+        // The remaining nodes of the longer list (if any) are all changed
+        let old_rest: usize = old_nodes.map(|node| node.num_descendants_incl_self()).sum();
+        let new_rest: usize = new_nodes.map(|node| node.num_descendants_incl_self()).sum();
+        if old_rest > 0 || new_rest > 0 {
+            self.changes.push(Change {
+                old_range: old_index..old_index + old_rest,
+                new_range: new_index..new_index + new_rest,
+            });
+        }
+    }
+
+    fn compare_nodes(&mut self, old_node: NodeRef<'a, T>, old_index: usize, new_node: NodeRef<'a, T>, new_index: usize) {
+        if self.old_hashes.hashes[old_index] == self.new_hashes.hashes[new_index] {
+            return;
+        }
+        if old_node.children().count() != new_node.children().count() {
+            self.changes.push(Change {
+                old_range: old_index..old_index + old_node.num_descendants_incl_self(),
+                new_range: new_index..new_index + new_node.num_descendants_incl_self(),
+            });
+            return;
+        }
+        if old_node.val() != new_node.val() {
+            self.changes.push(Change {
+                old_range: old_index..old_index + 1,
+                new_range: new_index..new_index + 1,
+            });
+        }
+        self.compare_lists(old_node.children(), old_index + 1, new_node.children(), new_index + 1);
+    }
+}
+
+impl ChangeSet {
+    /// Finds the regions that differ between `old` and `new`.
+    ///
+    /// The trees of the forests, and the children of nodes, are compared by position. Subtrees whose hashes are equal
+    /// (see [`SubtreeHashes`]) are skipped in O(1) time. When a node has a different number of children in the two forests,
+    /// its whole subtree is reported as changed; otherwise, if only its value differs, only the node itself is reported.
+    /// Extra trees at the end of one of the forests are reported as a single change, where the range in the other forest is empty.
+    ///
+    /// This takes O(n) time to compute the hashes, where n is the total number of nodes.
+    /// To reuse the hashes of a forest that doesn't change, see [`between_with_hashes`](ChangeSet::between_with_hashes).
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedForest, ChangeSet, Change};
+    ///
+    /// let build = |label: &'static str| {
+    ///     let mut forest = PackedForest::new();
+    ///     forest.build_tree("window", |node_builder| {
+    ///         node_builder.build_child("toolbar", |node_builder| {
+    ///             node_builder.add_leaf_children(["open", "save"]);
+    ///         });
+    ///         node_builder.build_child("status", |node_builder| {
+    ///             node_builder.add_child(label);
+    ///         });
+    ///     });
+    ///     forest
+    /// };
+    ///
+    /// let changes = ChangeSet::between(&build("ready"), &build("saving"));
+    /// assert_eq!(changes.changes(), [Change { old_range: 5..6, new_range: 5..6 }]);
+    /// assert!(ChangeSet::between(&build("ready"), &build("ready")).is_empty());
+    /// ```
+    pub fn between<T: Hash + PartialEq>(old: &PackedForest<T>, new: &PackedForest<T>) -> ChangeSet {
+        ChangeSet::between_with_hashes(old, &SubtreeHashes::new(old), new, &SubtreeHashes::new(new))
+    }
+
+    /// Like [`between`](ChangeSet::between), but with the hashes of the subtrees of the forests already computed.
+    ///
+    /// # Panics
+    /// Panics if the hashes don't have the same number of nodes as their forest.
+    pub fn between_with_hashes<T: PartialEq>(
+        old: &PackedForest<T>,
+        old_hashes: &SubtreeHashes,
+        new: &PackedForest<T>,
+        new_hashes: &SubtreeHashes,
+    ) -> ChangeSet {
+        assert_eq!(old.tot_num_nodes(), old_hashes.hashes.len(), "the hashes don't belong to the old forest");
+        assert_eq!(new.tot_num_nodes(), new_hashes.hashes.len(), "the hashes don't belong to the new forest");
+        let mut finder = ChangeFinder { old, old_hashes, new, new_hashes, changes: Vec::new() };
+        finder.compare_lists(finder.old.iter_trees(), 0, finder.new.iter_trees(), 0);
+        ChangeSet { changes: finder.changes }
+    }
+
+    /// Returns the regions that differ, in pre-order.
+    #[inline(always)]
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Returns `true` if the forests are the same.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(trees: &[(u32, &[u32])]) -> PackedForest<u32> {
+        let mut forest = PackedForest::new();
+        for (root, children) in trees {
+            forest.build_tree(*root, |node_builder| {
+                node_builder.add_leaf_children_from_slice(children);
+            });
+        }
+        forest
+    }
+
+    #[test]
+    fn test_change_set() {
+        let old = build(&[(1, &[2, 3]), (4, &[5]), (6, &[])]);
+        let old_hashes = SubtreeHashes::new(&old);
+        assert_eq!(old_hashes.as_slice().len(), 6);
+        // Equal subtrees have equal hashes, in different forests too
+        assert_eq!(SubtreeHashes::new(&build(&[(6, &[])])).get(0), old_hashes.get(5));
+        assert_ne!(old_hashes.get(1), old_hashes.get(2));
+
+        let same = build(&[(1, &[2, 3]), (4, &[5]), (6, &[])]);
+        assert!(ChangeSet::between_with_hashes(&old, &old_hashes, &same, &SubtreeHashes::new(&same)).is_empty());
+
+        let new = build(&[(1, &[2, 30]), (4, &[5, 7]), (6, &[]), (8, &[9])]);
+        let changes = ChangeSet::between(&old, &new);
+        assert_eq!(changes.changes(), [
+            Change { old_range: 2..3, new_range: 2..3 },
+            Change { old_range: 3..5, new_range: 3..6 },
+            Change { old_range: 6..6, new_range: 7..9 },
+        ]);
+
+        // A changed root value with the same children
+        let new = build(&[(10, &[2, 3]), (4, &[5]), (6, &[])]);
+        assert_eq!(ChangeSet::between(&old, &new).changes(), [Change { old_range: 0..1, new_range: 0..1 }]);
+
+        // Removed trees
+        let new = build(&[(1, &[2, 3])]);
+        assert_eq!(ChangeSet::between(&old, &new).changes(), [Change { old_range: 3..6, new_range: 3..3 }]);
+    }
+}
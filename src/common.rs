@@ -0,0 +1,148 @@
+// This file contains functions to find subtrees that occur in two trees,
+// by giving every distinct subtree an id, so that equal subtrees can be compared in O(1) time.
+
+use crate::*;
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// The location of a subtree that occurs in two trees, see [`PackedTree::largest_common_subtree`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct CommonSubtree {
+    /// The pre-order index of the root of the subtree in the first tree.
+    pub index_in_self: usize,
+    /// The pre-order index of the root of the subtree in the second tree.
+    pub index_in_other: usize,
+    /// The number of nodes in the subtree.
+    pub num_nodes: usize,
+}
+
+// Gives every distinct subtree a unique id, where two subtrees are equal if their roots have equal values
+// and their children are equal subtrees, in the same order.
+struct SubtreeIds<'t, T> {
+    ids: HashMap<(&'t T, Vec<usize>), usize>,
+}
+
+impl<'t, T: Hash + Eq> SubtreeIds<'t, T> {
+    // Returns the id of the subtree of every node in the tree, indexed by pre-order index.
+    fn ids_of(&mut self, tree: &'t PackedTree<T>) -> Vec<usize> {
+        let mut result = vec![0; tree.tot_num_nodes()];
+        // The children of a node come after it in pre-order, so their ids are known when it is reached in reverse.
+        for index in (0..tree.tot_num_nodes()).rev() {
+            let node = tree.get(index).unwrap();
+            let mut child_ids = Vec::new();
+            let mut child_index = index + 1;
+            for child in node.children() {
+                child_ids.push(result[child_index]);
+                child_index += child.num_descendants_incl_self();
+            }
+            let next_id = self.ids.len();
+            result[index] = *self.ids.entry((node.val(), child_ids)).or_insert(next_id);
+        }
+        result
+    }
+}
+
+impl<T: Hash + Eq> PackedTree<T> {
+    /// Returns the largest subtree (a node with all its descendants) that occurs both in this tree and in `other`,
+    /// or `None` if no value occurs in both trees. If there are several largest common subtrees,
+    /// the one that comes first in pre-order in this tree (and then in `other`) is returned.
+    ///
+    /// This takes O(n+m) expected time, where n and m are the number of nodes in the trees.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedTree, CommonSubtree};
+    ///
+    /// let a = PackedTree::new("fn main", |node_builder| {
+    ///     node_builder.build_child("loop", |node_builder| {
+    ///         node_builder.add_child("read");
+    ///         node_builder.add_child("print");
+    ///     });
+    /// });
+    /// let b = PackedTree::new("fn run", |node_builder| {
+    ///     node_builder.add_child("init");
+    ///     node_builder.build_child("loop", |node_builder| {
+    ///         node_builder.add_child("read");
+    ///         node_builder.add_child("print");
+    ///     });
+    /// });
+    ///
+    /// let common = a.largest_common_subtree(&b).unwrap();
+    /// assert_eq!(common, CommonSubtree { index_in_self: 1, index_in_other: 2, num_nodes: 3 });
+    /// ```
+    pub fn largest_common_subtree(&self, other: &PackedTree<T>) -> Option<CommonSubtree> {
+        let mut subtree_ids = SubtreeIds { ids: HashMap::new() };
+        let self_ids = subtree_ids.ids_of(self);
+        let other_ids = subtree_ids.ids_of(other);
+
+        let mut first_index_in_self = HashMap::new();
+        for (index, id) in self_ids.iter().enumerate() {
+            first_index_in_self.entry(*id).or_insert(index);
+        }
+
+        let mut result: Option<CommonSubtree> = None;
+        for (index_in_other, id) in other_ids.iter().enumerate() {
+            if let Some(index_in_self) = first_index_in_self.get(id) {
+                let num_nodes = other.get(index_in_other).unwrap().num_descendants_incl_self();
+                let candidate = CommonSubtree { index_in_self: *index_in_self, index_in_other, num_nodes };
+                let is_better = match result {
+                    None => true,
+                    Some(best) => {
+                        (num_nodes, std::cmp::Reverse((candidate.index_in_self, index_in_other)))
+                            > (best.num_nodes, std::cmp::Reverse((best.index_in_self, best.index_in_other)))
+                    }
+                };
+                if is_better {
+                    result = Some(candidate);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_largest_common_subtree() {
+        let a = PackedTree::new(0, |node_builder| {
+            node_builder.add_child(1);
+            node_builder.build_child(2, |node_builder| {
+                node_builder.add_child(3);
+                node_builder.add_child(4);
+            });
+            node_builder.build_child(5, |node_builder| {
+                node_builder.add_child(3);
+            });
+        });
+        let b = PackedTree::new(9, |node_builder| {
+            node_builder.build_child(5, |node_builder| {
+                node_builder.add_child(3);
+            });
+            node_builder.build_child(2, |node_builder| {
+                node_builder.add_child(3);
+                // a different last child, so the subtrees of 2 differ
+                node_builder.add_child(6);
+            });
+        });
+        assert_eq!(
+            a.largest_common_subtree(&b),
+            Some(CommonSubtree { index_in_self: 5, index_in_other: 1, num_nodes: 2 })
+        );
+        assert_eq!(
+            b.largest_common_subtree(&a),
+            Some(CommonSubtree { index_in_self: 1, index_in_other: 5, num_nodes: 2 })
+        );
+        assert_eq!(a.largest_common_subtree(&a), Some(CommonSubtree { index_in_self: 0, index_in_other: 0, num_nodes: 7 }));
+
+        // Only single nodes in common: the first one in pre-order of a
+        let c = PackedTree::new(4, |node_builder| {
+            node_builder.add_child(1);
+        });
+        assert_eq!(a.largest_common_subtree(&c), Some(CommonSubtree { index_in_self: 1, index_in_other: 1, num_nodes: 1 }));
+        assert_eq!(a.largest_common_subtree(&PackedTree::new(7, |_| {})), None);
+    }
+}
@@ -0,0 +1,164 @@
+#![cfg(any(feature = "zstd", feature = "lz4_flex", test))]
+
+// This file contains write_compressed and read_compressed, which write and read the versioned binary format
+// through a zstd or LZ4 compressor. Each algorithm requires the cargo feature with the name of its crate.
+
+use crate::*;
+
+use std::io::{self, Read, Write};
+
+// The bytes at the start of a zstd frame and an LZ4 frame, which read_compressed uses to recognize the algorithm.
+#[cfg(any(feature = "zstd", test))]
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+#[cfg(any(feature = "lz4_flex", test))]
+const LZ4_MAGIC: [u8; 4] = [0x04, 0x22, 0x4D, 0x18];
+
+/// The compression algorithm used by [`PackedForest::write_compressed`].
+///
+/// The variants depend on the enabled features, and another crate can enable more of them,
+/// so matching on a [`Compression`] needs a wildcard arm.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Zstandard compression with the given level, from 1 (fastest) to 22 (smallest). Requires the `zstd` feature.
+    ///
+    /// Level 0 uses zstd's default level, which is currently 3.
+    #[cfg(any(feature = "zstd", test))]
+    Zstd {
+        /// The compression level.
+        level: i32,
+    },
+    /// LZ4 compression, using the LZ4 frame format. This is faster than zstd, but compresses less. Requires the `lz4_flex` feature.
+    #[cfg(any(feature = "lz4_flex", test))]
+    Lz4,
+}
+
+impl<T> PackedForest<T> {
+    /// Writes this forest in the versioned binary format (see [`FORMAT_VERSION`]) like
+    /// [`write_versioned`](PackedForest::write_versioned), but compressed with the given algorithm.
+    ///
+    /// The nodes are encoded straight into the compressor, so the uncompressed bytes are never stored in memory.
+    /// Forests with many similar values often compress very well. To write compressed trees while they are being built,
+    /// pass a compressor (e.g. a `zstd::stream::write::Encoder`) to [`StreamingTreeWriter::with_header`] instead.
+    ///
+    /// The forest can be read again with [`read_compressed`](PackedForest::read_compressed).
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "zstd")] {
+    /// use packed_tree::{PackedForest, Compression};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree(0u8, |node_builder| {
+    ///     for _ in 0..1000 {
+    ///         node_builder.add_child(1);
+    ///     }
+    /// });
+    ///
+    /// let mut bytes = Vec::new();
+    /// forest.write_compressed(&mut bytes, Compression::Zstd { level: 3 }, |val: &u8, w: &mut dyn Write| w.write_all(&[*val])).unwrap();
+    /// assert!(bytes.len() < 100);
+    ///
+    /// let read_forest = PackedForest::read_compressed(bytes.as_slice(), |r: &mut dyn Read| {
+    ///     let mut buf = [0];
+    ///     r.read_exact(&mut buf)?;
+    ///     Ok(buf[0])
+    /// }).unwrap();
+    /// assert!(read_forest == forest);
+    /// # }
+    /// ```
+    pub fn write_compressed(
+        &self,
+        writer: impl Write,
+        compression: Compression,
+        val_encoder: impl FnMut(&T, &mut dyn Write) -> io::Result<()>,
+    ) -> io::Result<()> {
+        match compression {
+            #[cfg(any(feature = "zstd", test))]
+            Compression::Zstd { level } => {
+                let mut encoder = zstd::stream::write::Encoder::new(writer, level)?;
+                self.write_versioned(&mut encoder, val_encoder)?;
+                encoder.finish()?;
+            }
+            #[cfg(any(feature = "lz4_flex", test))]
+            Compression::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(writer);
+                self.write_versioned(&mut encoder, val_encoder)?;
+                encoder.finish()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a forest that was written by [`write_compressed`](PackedForest::write_compressed),
+    /// decoding the values of the nodes with `val_decoder`.
+    ///
+    /// The algorithm is recognized from the first bytes of the stream, and the stream is decompressed while it is parsed.
+    /// Streams that aren't compressed, like the ones written by [`write_versioned`](PackedForest::write_versioned),
+    /// are read as well. An algorithm whose feature isn't enabled isn't recognized, so its streams give
+    /// [`FormatError::InvalidMagic`].
+    pub fn read_compressed(
+        mut reader: impl Read,
+        val_decoder: impl FnMut(&mut dyn Read) -> io::Result<T>,
+    ) -> Result<PackedForest<T>, FormatError> {
+        let mut magic = [0; 4];
+        if let Err(err) = reader.read_exact(&mut magic) {
+            return Err(match err.kind() {
+                io::ErrorKind::UnexpectedEof => FormatError::InvalidMagic,
+                _ => FormatError::Io(err),
+            });
+        }
+        // Put the bytes that were already read back in front of the rest of the stream
+        let reader = io::Cursor::new(magic).chain(reader);
+        #[cfg(any(feature = "zstd", test))]
+        {
+            if magic == ZSTD_MAGIC {
+                return PackedForest::read_versioned(zstd::stream::read::Decoder::new(reader)?, val_decoder);
+            }
+        }
+        #[cfg(any(feature = "lz4_flex", test))]
+        {
+            if magic == LZ4_MAGIC {
+                return PackedForest::read_versioned(lz4_flex::frame::FrameDecoder::new(reader), val_decoder);
+            }
+        }
+        PackedForest::read_versioned(reader, val_decoder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(val: &u32, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(&val.to_le_bytes())
+    }
+
+    fn decode(r: &mut dyn Read) -> io::Result<u32> {
+        let mut buf = [0; 4];
+        r.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    #[test]
+    fn test_compressed() {
+        let forest = PackedForest::unfold(vec![0u32; 50], |n| (*n, if *n < 4 { vec![n + 1; 3] } else { vec![] }));
+        let mut uncompressed = Vec::new();
+        forest.write_versioned(&mut uncompressed, encode).unwrap();
+
+        for compression in [Compression::Zstd { level: 0 }, Compression::Zstd { level: 19 }, Compression::Lz4] {
+            let mut bytes = Vec::new();
+            forest.write_compressed(&mut bytes, compression, encode).unwrap();
+            assert!(bytes.len() * 4 < uncompressed.len(), "{:?}", compression);
+            assert!(PackedForest::read_compressed(bytes.as_slice(), decode).unwrap() == forest);
+
+            // A stream that ends early is an error
+            bytes.truncate(bytes.len() / 2);
+            assert!(PackedForest::read_compressed(bytes.as_slice(), decode).is_err());
+        }
+
+        assert!(PackedForest::read_compressed(uncompressed.as_slice(), decode).unwrap() == forest);
+        assert!(matches!(PackedForest::read_compressed(&[0x28, 0xB5][..], decode), Err(FormatError::InvalidMagic)));
+    }
+}
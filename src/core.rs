@@ -2,8 +2,9 @@
 // It should be kept as small as possible.
 // No bugs outside of core.rs should lead to memory unsafety.
 
-// TODO: indexing
-// TODO: check safety of overflow
+// A note on overflow: the sums of indices and subtree sizes in NodeBuilder are bounded by the number of nodes
+// that have actually been written, which can't overflow since every node takes up at least 1 byte.
+// The only exception is reserving space for many children at once, which is checked (see checked_num_nodes).
 
 // TODO: #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug, Hash)]?
 // TODO: clippy
@@ -11,9 +12,11 @@
 // TODO: some more tests?
 // TODO: update dep versions
 
-use std::iter::Iterator;
+use std::iter::{FusedIterator, Iterator};
 use std::num::NonZeroUsize;
 
+use crate::{ChildCountPolicy, NoMeta, NodeCount, NodeInfo, NodePolicy};
+
 /// Split off the first n elements of the pointed-to slice, modifying it.
 /// Does *not* check that n <= len.
 /// Implementation is similar to std::slice::split_at_mut.
@@ -46,7 +49,7 @@ unsafe fn slice_split_off_first_n_unchecked_mut<'a,T>(slice_ref: &mut &'a mut [T
 /// Does *not* check that the slice isn't empty.
 #[inline(always)]
 unsafe fn slice_split_first_unchecked<T>(slice: &[T]) -> (&T,&[T]) {
-    debug_assert!(slice.len() > 0);
+    debug_assert!(!slice.is_empty());
     (slice.get_unchecked(0),slice.get_unchecked(1..))
 }
 
@@ -82,10 +85,10 @@ unsafe fn slice_split_first_unchecked_mut<T>(slice: &mut [T]) -> (&mut T,&mut [T
 ///
 /// // Add two trees
 /// forest.build_tree("node 1", |node_builder| {
-/// 	node_builder.add_child("node 1.1");
-/// 	node_builder.build_child("node 1.2", |node_builder| {
-/// 		node_builder.add_child("node 1.2.1");
-/// 	});
+///     node_builder.add_child("node 1.1");
+///     node_builder.build_child("node 1.2", |node_builder| {
+///         node_builder.add_child("node 1.2.1");
+///     });
 /// });
 /// forest.build_tree("node 2", |node_builder| {
 ///     node_builder.add_child("node 2.1");
@@ -93,11 +96,11 @@ unsafe fn slice_split_first_unchecked_mut<T>(slice: &mut [T]) -> (&mut T,&mut [T
 ///
 /// // Iterate it, counting the number of nodes
 /// fn count_num_nodes(node: NodeRef<&'static str>) -> usize {
-/// 	let mut result = 1;
-/// 	for child in node.children() {
-/// 		result += count_num_nodes(child);
-/// 	}
-/// 	result
+///     let mut result = 1;
+///     for child in node.children() {
+///         result += count_num_nodes(child);
+///     }
+///     result
 /// }
 ///
 /// let num_nodes_in_each_tree : Vec<_> = forest
@@ -108,6 +111,21 @@ unsafe fn slice_split_first_unchecked_mut<T>(slice: &mut [T]) -> (&mut T,&mut [T
 /// assert_eq!(num_nodes_in_each_tree, [4, 2]);
 /// ```
 ///
+/// A `PackedForest<T>` is a [`MetaPackedForest`] with the [`NoMeta`] policy, which is where its methods are documented.
+pub type PackedForest<T> = MetaPackedForest<T, NoMeta>;
+
+/// A variant of [`PackedForest`] that stores extra data for every node next to its value,
+/// where the [`NodePolicy`] `P` decides what that data is.
+///
+/// For example, [`ExactSizePackedForest`](crate::ExactSizePackedForest) is a [`MetaPackedForest`] with the
+/// [`ChildCount`](crate::ChildCount) policy, which keeps track of how many children each node has.
+/// That allows iterators of a node's children to be [`ExactSizeIterator`]s in addition to being regular [`Iterator`]s.
+/// A [`PackedForest`] is a [`MetaPackedForest`] with the [`NoMeta`] policy, which doesn't store anything.
+///
+/// The methods that change the structure of the trees after they've been built (like
+/// [`detach_subtree`](PackedForest::detach_subtree)) are only available without extra data,
+/// since they would leave the data of the nodes outdated.
+//
 // =============== IMPLEMENTATION SAFETY NOTES ===================
 //
 // A PackedForest consists of a Vec of the nodes of the forest, stored in "pre-order" order,
@@ -119,6 +137,9 @@ unsafe fn slice_split_first_unchecked_mut<T>(slice: &mut [T]) -> (&mut T,&mut [T
 // The `subtree_size`s of nodes inside the `len` of the `Vec` owned by this `PackedForest`
 // must at all times be correct and form a valid forest.
 //
+// A `subtree_size` only depends on the descendants of a node, not on where the node is stored,
+// so whole trees can be moved around (or between forests) without updating any `subtree_size`s.
+//
 // There are 2 cases where there may be extra data outside the `len` of this `Vec`:
 //
 // Adding trees (and nodes) to the forest happens through the method `get_tree_builder`,
@@ -131,30 +152,49 @@ unsafe fn slice_split_first_unchecked_mut<T>(slice: &mut [T]) -> (&mut T,&mut [T
 // is set to 0, but a `NodeListDrain` is returned that borrows the forest mutably, which
 // can read, move data out of, and drop nodes that used to be inside the `len` of the `Vec`.
 // See `NodeDrain` and `NodeListDrain`'s comments for more details.
+//
+// The number of trees is kept in `num_trees` if the policy counts nodes (see NodePolicy::Count).
 #[derive(Default, Eq, PartialEq, Hash, Clone)]
-pub struct PackedForest<T> {
-    data: Vec<NodeData<T>>,
+pub struct MetaPackedForest<T, P: NodePolicy> {
+    data: Vec<NodeData<T, P::Meta>>,
+    num_trees: P::Count,
 }
 
-impl<T> PackedForest<T> {
+impl<T, P: NodePolicy> MetaPackedForest<T, P> {
     /// Create a new, empty [`PackedForest`].
     /// 
     /// Note that [`PackedForest`] implements [`Default`].
+    ///
+    /// This is a `const fn`, so it can be used to initialize a `static` or a `const`:
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// static EMPTY: PackedForest<i32> = PackedForest::new();
+    /// assert_eq!(EMPTY.tot_num_nodes(), 0);
+    /// ```
     #[inline(always)]
-    pub fn new() -> PackedForest<T> {
-        PackedForest {
+    pub const fn new() -> MetaPackedForest<T, P> {
+        MetaPackedForest {
             data: Vec::new(),
+            num_trees: P::Count::ZERO,
         }
     }
 
     /// Create a new [`PackedForest`] with the specified capacity for the inner `Vec` which stores the nodes (see [`Vec::with_capacity`]).
     #[inline(always)]
-    pub fn with_capacity(capacity: usize) -> PackedForest<T> {
-        PackedForest {
+    pub fn with_capacity(capacity: usize) -> MetaPackedForest<T, P> {
+        MetaPackedForest {
             data: Vec::with_capacity(capacity),
+            num_trees: P::Count::ZERO,
         }
     }
 
+    /// Returns the number of nodes the forest can hold without reallocating (see [`Vec::capacity`]).
+    #[inline(always)]
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
     /// Get a [`NodeBuilder`] that can be used to build a tree that will be added to this forest.
     /// 
     /// After adding nodes to the tree, you must call [`finish`](`NodeBuilder::finish`) on the
@@ -172,25 +212,16 @@ impl<T> PackedForest<T> {
     /// 
     /// See [`NodeBuilder::get_child_builder`] for an example.
     #[inline]
-    pub fn get_tree_builder(&mut self) -> NodeBuilder<T> {
-        // NodeBuilder's invariants (see comments at structure definition of NodeBuilder):
-        // Invariant 1 is satisfied because the new NodeBuilder's subtree_size is 1,
-        // so there are no elements with those indices.
-        // Invariant 2 is satisfied, as child.index is set to self.data.len()
-        let new_root_index = self.data.len();
-        NodeBuilder {
-            forest: self,
-            index: new_root_index,
-            subtree_size: NonZeroUsize::new(1).unwrap(),
-            parent_subtree_size: None,
-        }
+    pub fn get_tree_builder(&mut self) -> MetaNodeBuilder<'_, T, P> {
+        tree_builder(&mut self.data, Some(&mut self.num_trees))
     }
 
     /// Returns an iterator that iterates over (a [`NodeRef`] to) all the trees in this forest.
     #[inline(always)]
-    pub fn iter_trees(&self) -> NodeIter<T> {
-        NodeIter {
+    pub fn iter_trees(&self) -> MetaNodeIter<'_, T, P> {
+        MetaNodeIter {
             remaining_nodes: &self.data,
+            len: self.num_trees,
         }
     }
 
@@ -198,9 +229,10 @@ impl<T> PackedForest<T> {
     /// With this iterator you can change values of nodes in the tree (see [`NodeRefMut::val_mut`]),
     /// but you can't change the structure of the tree.
     #[inline(always)]
-    pub fn iter_trees_mut(&mut self) -> NodeIterMut<T> {
-        NodeIterMut {
+    pub fn iter_trees_mut(&mut self) -> MetaNodeIterMut<'_, T, P> {
+        MetaNodeIterMut {
             remaining_nodes: &mut self.data[..],
+            len: self.num_trees,
         }
     }
 
@@ -214,9 +246,10 @@ impl<T> PackedForest<T> {
     /// without iterating over all the values in it, then the values of the nodes that were not iterated over
     /// will also be leaked (their `drop` method won't be called). They will still be removed from the forest though.
     #[inline(always)]
-    pub fn drain_trees(&mut self) -> NodeListDrain<'_, T> {
+    pub fn drain_trees(&mut self) -> MetaNodeListDrain<'_, T, P> {
         // first, get the current length of the data vector.
         let old_len = self.data.len();
+        let len = std::mem::replace(&mut self.num_trees, P::Count::ZERO);
         unsafe {
             // Now we set the length to 0.
             // If we would stop here, this would leak all the values in the vector.
@@ -237,8 +270,9 @@ impl<T> PackedForest<T> {
             // it drops whatever data wasn't iterated over yet.
             // NOTE: NodeListDrain mutably borrows this PackedForest, so no changes
             // to the vector can happen while the NodeListDrain exists.
-            NodeListDrain {
+            MetaNodeListDrain {
                 remaining_nodes: mut_slice,
+                len,
             }
         }
     }
@@ -249,8 +283,11 @@ impl<T> PackedForest<T> {
     /// them in a depth-first search. So the index of the first tree's root node is 0,
     /// the index of its first child (if any) is 1, the index of that first child's
     /// first child (if any) is 2, etc.
+    ///
+    /// `index` can be a plain `usize` or a [`NodeId`](crate::NodeId).
     #[inline(always)]
-    pub fn get(&self, index: usize) -> Option<NodeRef<T>> {
+    pub fn get(&self, index: impl Into<crate::NodeId>) -> Option<MetaNodeRef<'_, T, P>> {
+        let index = index.into().index();
         if index < self.data.len() {
             Some(unsafe { self.get_unchecked(index) })
         } else {
@@ -264,8 +301,11 @@ impl<T> PackedForest<T> {
     /// them in a depth-first search. So the index of the first tree's root node is 0,
     /// the index of its first child (if any) is 1, the index of that first child's
     /// first child (if any) is 2, etc.
+    ///
+    /// `index` can be a plain `usize` or a [`NodeId`](crate::NodeId).
     #[inline(always)]
-    pub fn get_mut(&mut self, index: usize) -> Option<NodeRefMut<T>> {
+    pub fn get_mut(&mut self, index: impl Into<crate::NodeId>) -> Option<MetaNodeRefMut<'_, T, P>> {
+        let index = index.into().index();
         if index < self.data.len() {
             Some(unsafe { self.get_unchecked_mut(index) })
         } else {
@@ -276,10 +316,15 @@ impl<T> PackedForest<T> {
     /// Get a [`NodeRef`] to the node with the given index.
     /// 
     /// Does **not** check that the given index is in bounds, and is therefore unsafe.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be smaller than [`tot_num_nodes`](Self::tot_num_nodes).
     #[inline(always)]
-    pub unsafe fn get_unchecked(&self, index: usize) -> NodeRef<T> {
+    pub unsafe fn get_unchecked(&self, index: impl Into<crate::NodeId>) -> MetaNodeRef<'_, T, P> {
+        let index = index.into().index();
         let subtree_size = self.data.get_unchecked(index).subtree_size.get();
-        NodeRef {
+        MetaNodeRef {
             slice: self.data.get_unchecked(index..(index+subtree_size))
         }
     }
@@ -287,10 +332,15 @@ impl<T> PackedForest<T> {
     /// Get a [`NodeRefMut`] to the node with the given index.
     /// 
     /// Does **not** check that the given index is in bounds, and is therefore unsafe.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be smaller than [`tot_num_nodes`](Self::tot_num_nodes).
     #[inline(always)]
-    pub unsafe fn get_unchecked_mut(&mut self, index: usize) -> NodeRefMut<T> {
+    pub unsafe fn get_unchecked_mut(&mut self, index: impl Into<crate::NodeId>) -> MetaNodeRefMut<'_, T, P> {
+        let index = index.into().index();
         let subtree_size = self.data.get_unchecked(index).subtree_size.get();
-        NodeRefMut {
+        MetaNodeRefMut {
             slice: self.data.get_unchecked_mut(index..(index+subtree_size))
         }
     }
@@ -298,27 +348,49 @@ impl<T> PackedForest<T> {
     /// Remove all nodes from the forest.
     #[inline]
     pub fn clear(&mut self) {
-        self.data.clear()
+        self.data.clear();
+        self.num_trees = P::Count::ZERO;
+    }
+
+    /// Converts this forest into an iterator over its trees, each of which is moved into its own [`PackedTree`](crate::PackedTree).
+    ///
+    /// This takes O(n) time in total, where n is the number of nodes in the forest.
+    #[inline(always)]
+    pub fn into_trees(self) -> MetaIntoTrees<T, P> {
+        MetaIntoTrees {
+            remaining_nodes: self.data.into_iter(),
+            len: self.num_trees,
+        }
     }
 
     /// Iterate over all the values in all the nodes of all the trees in this forest, in pre-order order.
     #[inline(always)]
-    pub fn iter_flattened<'t>(
-        &'t self,
-    ) -> std::iter::Map<std::slice::Iter<'t, NodeData<T>>, impl FnMut(&'t NodeData<T>) -> &'t T>
-    {
-        self.data.iter().map(|node_data| &node_data.val)
+    pub fn iter_flattened(&self) -> FlattenedIter<'_, T, P::Meta> {
+        FlattenedIter::new(&self.data)
     }
 
     /// Iterate mutably over all the values in all the nodes of all the trees in this forest, in pre-order order.
     #[inline(always)]
-    pub fn iter_flattened_mut<'t>(
-        &'t mut self,
-    ) -> std::iter::Map<
-        std::slice::IterMut<'t, NodeData<T>>,
-        impl FnMut(&'t mut NodeData<T>) -> &'t mut T,
-    > {
-        self.data.iter_mut().map(|node_data| &mut node_data.val)
+    pub fn iter_flattened_mut(&mut self) -> FlattenedIterMut<'_, T, P::Meta> {
+        FlattenedIterMut {
+            nodes: self.data.iter_mut(),
+        }
+    }
+
+    /// Iterate over all the values in all the nodes of all the trees in this forest, in pre-order order,
+    /// together with the index of each node (see [`get`](PackedForest::get)).
+    ///
+    /// The indices are `0..tot_num_nodes()`, so they can be used to index side tables with one entry per node.
+    #[inline(always)]
+    pub fn iter_flattened_enumerated(&self) -> std::iter::Enumerate<FlattenedIter<'_, T, P::Meta>> {
+        self.iter_flattened().enumerate()
+    }
+
+    /// Iterate mutably over all the values in all the nodes of all the trees in this forest, in pre-order order,
+    /// together with the index of each node (see [`get_mut`](PackedForest::get_mut)).
+    #[inline(always)]
+    pub fn iter_flattened_enumerated_mut(&mut self) -> std::iter::Enumerate<FlattenedIterMut<'_, T, P::Meta>> {
+        self.iter_flattened_mut().enumerate()
     }
 
     /// Returns a draining iterator over all the values in all the nodes of all the trees in this forest, in pre-order order.
@@ -328,16 +400,17 @@ impl<T> PackedForest<T> {
     /// **WARNING:** Leaking the returned iterator without iterating over all of its values will leak the
     /// values that were not iterated over. They will still be removed from the tree though.
     #[inline(always)]
-    pub fn drain_flattened(
-        &mut self,
-    ) -> std::iter::Map<std::vec::Drain<NodeData<T>>, impl FnMut(NodeData<T>) -> T> {
-        self.data.drain(..).map(|node_data| node_data.val)
+    pub fn drain_flattened(&mut self) -> FlattenedDrain<'_, T, P::Meta> {
+        self.num_trees = P::Count::ZERO;
+        FlattenedDrain {
+            nodes: self.data.drain(..),
+        }
     }
 
     /// Returns a read-only view over the raw data stored internally by this `PackedForest`.
     /// This is not really recommended to be used except for very advanced use cases.
     #[inline(always)]
-    pub fn raw_data(&self) -> &Vec<NodeData<T>> {
+    pub fn raw_data(&self) -> &Vec<NodeData<T, P::Meta>> {
         &self.data
     }
 
@@ -346,6 +419,414 @@ impl<T> PackedForest<T> {
     pub fn tot_num_nodes(&self) -> usize {
         self.data.len()
     }
+
+    /// Returns the number of trees in this forest.
+    ///
+    /// This takes O(1) time if the [`NodePolicy`] counts the children of every node
+    /// (see [`ChildCountPolicy`](crate::ChildCountPolicy)), and otherwise counts the trees.
+    #[inline]
+    pub fn num_trees(&self) -> usize {
+        self.num_trees.get().unwrap_or_else(|| count_sibling_nodes(&self.data))
+    }
+
+    /// Returns `true` if this forest doesn't contain any trees.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl<T> PackedForest<T> {
+    // Create a forest from nodes that form a valid forest.
+    #[inline(always)]
+    fn from_data(data: Vec<NodeData<T>>) -> PackedForest<T> {
+        PackedForest { data, num_trees: () }
+    }
+
+    /// Returns an iterator over the subtrees whose nodes have the pre-order indices in the given range
+    /// (see [`get`](PackedForest::get)), or `None` if the range doesn't consist of whole subtrees or is out of bounds.
+    ///
+    /// This turns a range that was returned by [`NodeRef::preorder_range`] (or the range of several consecutive subtrees)
+    /// back into an iterator, e.g. after storing the range instead of a [`NodeRef`] that borrows the forest.
+    /// This takes O(k) time, where k is the number of subtrees in the range.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let tree = PackedTree::new("a", |node_builder| {
+    ///     node_builder.add_child("b");
+    ///     node_builder.build_child("c", |node_builder| {
+    ///         node_builder.add_child("d");
+    ///     });
+    /// });
+    /// let range = tree.root().children().nth(1).unwrap().preorder_range(tree.as_ref());
+    ///
+    /// let mut nodes = tree.as_ref().iter_range(range).unwrap();
+    /// assert_eq!(*nodes.next().unwrap().val(), "c");
+    /// assert!(nodes.next().is_none());
+    ///
+    /// assert_eq!(tree.as_ref().iter_range(1..4).unwrap().count(), 2);
+    /// // The range ends in the middle of the subtree of "c"
+    /// assert!(tree.as_ref().iter_range(1..3).is_none());
+    /// ```
+    pub fn iter_range(&self, range: std::ops::Range<usize>) -> Option<NodeIter<'_, T>> {
+        let remaining_nodes = self.data.get(range.clone())?;
+        let mut index = range.start;
+        while index < range.end {
+            index += self.data[index].subtree_size.get();
+        }
+        if index != range.end {
+            return None;
+        }
+        Some(NodeIter { remaining_nodes, len: () })
+    }
+
+    /// Swap the subtrees that have the nodes with indices `a_index` and `b_index` as their roots, in O(k) time,
+    /// where k is the number of nodes in each subtree.
+    ///
+    /// This is only possible when the subtrees are disjoint and have the same number of nodes,
+    /// otherwise a [`SwapError`](crate::SwapError) is returned and the forest is left unchanged.
+    /// Swapping a subtree with itself does nothing.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let mut tree = PackedTree::new(0, |node_builder| {
+    ///     node_builder.build_child(1, |node_builder| {
+    ///         node_builder.add_child(2);
+    ///     });
+    ///     node_builder.build_child(3, |node_builder| {
+    ///         node_builder.add_child(4);
+    ///     });
+    /// });
+    ///
+    /// tree.swap_subtrees(1, 3).unwrap();
+    /// assert_eq!(tree.iter_flattened().copied().collect::<Vec<_>>(), [0, 3, 4, 1, 2]);
+    /// assert!(tree.swap_subtrees(0, 1).is_err());
+    /// ```
+    pub fn swap_subtrees(&mut self, a_index: usize, b_index: usize) -> Result<(), crate::SwapError> {
+        let a_size = self.data.get(a_index).ok_or(crate::SwapError::IndexOutOfBounds { index: a_index })?.subtree_size.get();
+        let b_size = self.data.get(b_index).ok_or(crate::SwapError::IndexOutOfBounds { index: b_index })?.subtree_size.get();
+        if a_index == b_index {
+            return Ok(());
+        }
+        let (first, second) = if a_index < b_index { (a_index, b_index) } else { (b_index, a_index) };
+        let first_size = if a_index < b_index { a_size } else { b_size };
+        if first + first_size > second {
+            return Err(crate::SwapError::Overlapping);
+        }
+        if a_size != b_size {
+            return Err(crate::SwapError::SizeMismatch { a_size, b_size });
+        }
+        // Since both subtrees have the same size, all subtree sizes stay valid when swapping their nodes.
+        let (left, right) = self.data.split_at_mut(second);
+        left[first..(first + a_size)].swap_with_slice(&mut right[..a_size]);
+        Ok(())
+    }
+
+    /// Swap the positions of the trees with indices `a` and `b` (i.e. the `a`th and the `b`th tree, starting from 0),
+    /// like [`slice::swap`], in O(k) time, where k is the number of nodes from the start of the first tree
+    /// to the end of the second one. The trees may have different sizes; the trees in between them are moved
+    /// if they do. Swapping a tree with itself does nothing.
+    ///
+    /// # Panics
+    /// Panics if `a` or `b` is not smaller than the number of trees.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.add_single_node_tree(1);
+    /// forest.add_single_node_tree(2);
+    /// forest.build_tree(3, |node_builder| {
+    ///     node_builder.add_child(4);
+    /// });
+    ///
+    /// forest.swap_trees(0, 2);
+    /// assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [3, 4, 2, 1]);
+    /// ```
+    pub fn swap_trees(&mut self, a: usize, b: usize) {
+        let (first, second) = if a < b { (a, b) } else { (b, a) };
+        // Find the range of nodes of the first and the second tree
+        let mut first_range = None;
+        let mut tree_index = 0;
+        let mut index = 0;
+        let second_range = loop {
+            assert!(index < self.data.len(), "tree index {} out of bounds for a forest with {} trees", second, tree_index);
+            let end = index + self.data[index].subtree_size.get();
+            if tree_index == first {
+                first_range = Some(index..end);
+            }
+            if tree_index == second {
+                break index..end;
+            }
+            tree_index += 1;
+            index = end;
+        };
+        let first_range = first_range.unwrap();
+        if first == second {
+            return;
+        }
+        // Turn [first, middle, second] into [second, middle, first].
+        let nodes = &mut self.data[first_range.start..second_range.end];
+        let first_len = first_range.len();
+        let second_len = second_range.len();
+        nodes.rotate_left(first_len);
+        let middle_and_second = nodes.len() - first_len;
+        nodes[..middle_and_second].rotate_right(second_len);
+    }
+
+    /// Sorts the trees of this forest by the key that `key_fn` returns for their roots, keeping every tree intact.
+    ///
+    /// The sort is stable, and `key_fn` is called only once for every tree. The nodes are moved tree by tree
+    /// into a new buffer, so this takes O(n + m log m) time, where n is the number of nodes and m the number of trees.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree("b", |node_builder| {
+    ///     node_builder.add_child("b1");
+    /// });
+    /// forest.add_single_node_tree("c");
+    /// forest.build_tree("a", |node_builder| {
+    ///     node_builder.add_child("a1");
+    /// });
+    ///
+    /// forest.sort_trees_by_key(|tree| *tree.val());
+    /// assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), ["a", "a1", "b", "b1", "c"]);
+    /// ```
+    pub fn sort_trees_by_key<K: Ord>(&mut self, mut key_fn: impl FnMut(NodeRef<'_, T>) -> K) {
+        // The key, the index of the root and the number of nodes of every tree
+        let mut trees = Vec::new();
+        let mut index = 0;
+        for tree in self.iter_trees() {
+            let num_nodes = tree.num_descendants_incl_self();
+            trees.push((key_fn(tree), index, num_nodes));
+            index += num_nodes;
+        }
+        // sort_by is stable
+        trees.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+
+        let len = self.data.len();
+        let mut sorted_data: Vec<NodeData<T>> = Vec::with_capacity(len);
+        unsafe {
+            // The trees cover every node exactly once, so every node is moved to sorted_data exactly once.
+            // Nothing can panic in between, so no node can be dropped twice.
+            let mut sorted_len = 0;
+            for &(_, root_index, num_nodes) in &trees {
+                std::ptr::copy_nonoverlapping(self.data.as_ptr().add(root_index), sorted_data.as_mut_ptr().add(sorted_len), num_nodes);
+                sorted_len += num_nodes;
+            }
+            self.data.set_len(0);
+            sorted_data.set_len(len);
+        }
+        self.data = sorted_data;
+    }
+
+    /// Splits this forest into the trees for which `predicate` returns `true` for their roots, and the other trees.
+    /// Both forests keep the trees in their original order.
+    ///
+    /// `predicate` is called once for every tree, after which all nodes are moved to one of the two forests
+    /// in a single pass, so this takes O(n) time, where n is the number of nodes.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree(1, |node_builder| {
+    ///     node_builder.add_child(10);
+    /// });
+    /// forest.add_single_node_tree(2);
+    /// forest.build_tree(3, |node_builder| {
+    ///     node_builder.add_child(30);
+    /// });
+    ///
+    /// let (odd, even) = forest.partition(|tree| *tree.val() % 2 == 1);
+    /// assert_eq!(odd.iter_flattened().copied().collect::<Vec<_>>(), [1, 10, 3, 30]);
+    /// assert_eq!(even.iter_flattened().copied().collect::<Vec<_>>(), [2]);
+    /// ```
+    pub fn partition(self, mut predicate: impl FnMut(NodeRef<'_, T>) -> bool) -> (PackedForest<T>, PackedForest<T>) {
+        let trees: Vec<(usize, bool)> = self.iter_trees().map(|tree| (tree.num_descendants_incl_self(), predicate(tree))).collect();
+        let mut matching = Vec::new();
+        let mut rest = Vec::new();
+        let mut nodes = self.data.into_iter();
+        for (num_nodes, is_match) in trees {
+            let target = if is_match { &mut matching } else { &mut rest };
+            target.extend(nodes.by_ref().take(num_nodes));
+        }
+        (PackedForest::from_data(matching), PackedForest::from_data(rest))
+    }
+
+    /// Remove the node with the given index and all of its descendants from the forest, and return them as a new [`PackedTree`](crate::PackedTree).
+    /// Returns `None` if the index is out of bounds.
+    ///
+    /// The nodes after the removed subtree are moved to fill the gap, so this takes O(n) time,
+    /// where n is the number of nodes in the forest. The indices of those nodes decrease by the size of the removed subtree.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedForest, PackedTree};
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree(0, |node_builder| {
+    ///     node_builder.build_child(1, |node_builder| {
+    ///         node_builder.add_child(2);
+    ///     });
+    ///     node_builder.add_child(3);
+    /// });
+    ///
+    /// let detached = forest.detach_subtree(1).unwrap();
+    /// assert_eq!(detached.iter_flattened().copied().collect::<Vec<_>>(), [1, 2]);
+    /// assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [0, 3]);
+    /// assert_eq!(forest.iter_trees().next().unwrap().num_descendants_incl_self(), 2);
+    /// ```
+    pub fn detach_subtree(&mut self, index: usize) -> Option<crate::PackedTree<T>> {
+        let size = self.data.get(index)?.subtree_size.get();
+
+        // Walk down from the roots to the node, shrinking the subtree of every ancestor.
+        let mut i = 0;
+        while i < index {
+            let subtree_size = self.data[i].subtree_size.get();
+            if i + subtree_size > index {
+                // Node i is an ancestor, which contains the removed subtree as well as itself,
+                // so its new subtree size is at least 1.
+                self.data[i].subtree_size = NonZeroUsize::new(subtree_size - size).unwrap();
+                i += 1;
+            } else {
+                i += subtree_size;
+            }
+        }
+
+        let detached = PackedForest::from_data(self.data.drain(index..(index + size)).collect());
+        crate::PackedTree::try_from_forest(detached)
+    }
+
+    /// Create a new [`PackedForest`] with the trees of all the given forests, in order.
+    ///
+    /// This reserves memory for all nodes at once and then moves the nodes of every forest over with a single memory copy,
+    /// so it takes O(n) time but is about as fast as copying n values can be, where n is the total number of nodes.
+    ///
+    /// This makes it possible to build a forest on multiple threads: every thread builds its own forest
+    /// with a disjoint set of trees, and the forests are merged afterwards.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let forests: Vec<PackedForest<u32>> = std::thread::scope(|scope| {
+    ///     let workers: Vec<_> = (0..4).map(|worker| scope.spawn(move || {
+    ///         let mut forest = PackedForest::new();
+    ///         for i in worker * 10..worker * 10 + 10 {
+    ///             forest.build_tree(i, |node_builder| {
+    ///                 node_builder.add_leaf_children(0..i % 3);
+    ///             });
+    ///         }
+    ///         forest
+    ///     })).collect();
+    ///     workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+    /// });
+    ///
+    /// let forest = PackedForest::merge(forests);
+    /// assert_eq!(forest.iter_trees().count(), 40);
+    /// assert!(forest.iter_trees().map(|tree| *tree.val()).eq(0..40));
+    /// ```
+    pub fn merge(forests: Vec<PackedForest<T>>) -> PackedForest<T> {
+        let tot_num_nodes = forests.iter().map(|forest| forest.tot_num_nodes()).sum();
+        let mut merged = PackedForest::with_capacity(tot_num_nodes);
+        for mut forest in forests {
+            merged.append(&mut forest);
+        }
+        merged
+    }
+
+    /// Moves all the trees of `other` to the end of this forest, leaving `other` empty, like [`Vec::append`].
+    ///
+    /// This moves the nodes with a single memory copy. See also the `+` and `+=` operators,
+    /// which take the other forest (or tree) by value.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.add_single_node_tree(1);
+    /// let mut other = PackedForest::new();
+    /// other.build_tree(2, |node_builder| {
+    ///     node_builder.add_child(3);
+    /// });
+    ///
+    /// forest.append(&mut other);
+    /// assert_eq!(forest.iter_trees().count(), 2);
+    /// assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [1, 2, 3]);
+    /// assert_eq!(other.tot_num_nodes(), 0);
+    /// ```
+    #[inline]
+    pub fn append(&mut self, other: &mut PackedForest<T>) {
+        self.data.append(&mut other.data);
+    }
+
+    /// Returns a copy of this forest, like [`clone`](Clone::clone), but since `T` is [`Copy`],
+    /// all nodes are copied with a single memory copy instead of being cloned one by one.
+    ///
+    /// The copy has the same capacity as the number of nodes.
+    #[inline]
+    pub fn duplicate(&self) -> PackedForest<T> where T: Copy {
+        // NodeData<T> is Copy too, so this copies the whole slice at once.
+        PackedForest::from_data(self.data.as_slice().to_vec())
+    }
+
+    // Add a node at the end of the forest, and return its index.
+    // The node starts out as a leaf. Any nodes that are pushed after it become its descendants
+    // once close_node is called with its index, which can be used to build trees without recursion.
+    // Until then the forest is still valid, the descendants just look like trees that follow it.
+    #[inline]
+    pub(crate) fn push_open_node(&mut self, val: T) -> usize {
+        self.data.push(NodeData {
+            val,
+            subtree_size: NonZeroUsize::new(1).unwrap(),
+            meta: (),
+        });
+        self.data.len() - 1
+    }
+
+    // Make all nodes that were pushed after the node with the given index its descendants, see push_open_node.
+    // Nodes with a greater index that are still open must be closed first.
+    #[inline]
+    pub(crate) fn close_node(&mut self, index: usize) {
+        let subtree_size = self.data.len() - index;
+        self.data[index].subtree_size = NonZeroUsize::new(subtree_size).unwrap();
+    }
+
+    // Add a clone of a subtree (e.g. the raw_slice of a NodeRef) at the end of the forest, as a new tree.
+    // The subtree sizes are relative, so the nodes stay valid wherever they are copied to.
+    #[inline]
+    pub(crate) fn push_cloned_subtree(&mut self, nodes: &[NodeData<T>]) where T: Clone {
+        self.data.extend_from_slice(nodes);
+    }
+
+    // Remove all nodes with an index of at least `len`, which must be the index of the root of a tree (or the number of nodes).
+    #[inline]
+    pub(crate) fn truncate_trees(&mut self, len: usize) {
+        self.data.truncate(len)
+    }
+}
+
+impl<T> PackedForest<Option<T>> {
+    // Unwrap the values of all nodes, for decoders that only know the value of a node after its descendants.
+    // Panics if any node has no value.
+    pub(crate) fn unwrap_vals(self) -> PackedForest<T> {
+        PackedForest::from_data(self.data.into_iter().map(|node| NodeData {
+            val: node.val.unwrap(),
+            subtree_size: node.subtree_size,
+            meta: (),
+        }).collect())
+    }
 }
 
 /// The data that a [`PackedForest`] or [`PackedTree`](crate::PackedTree) internally stores per node:
@@ -354,13 +835,18 @@ impl<T> PackedForest<T> {
 /// This type is not really intended to be used directly if you're a user of this library,
 /// but it is nevertheless exposed if there is a reason you want to access it
 /// (see e.g. [`PackedForest::raw_data`] and [`PackedTree::raw_data`](crate::PackedTree::raw_data))
+///
+/// With the `ffi` feature, this type is `#[repr(C)]`, so its layout is the same as that of a C struct
+/// with the fields `val` and `subtree_size` (a `size_t`), in that order. See the [`ffi`](crate::ffi) module.
 #[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
-pub struct NodeData<T> {
+#[cfg_attr(feature = "ffi", repr(C))]
+pub struct NodeData<T, M = ()> {
     val: T,
     subtree_size: NonZeroUsize,
+    meta: M,
 }
 
-impl<T> NodeData<T> {
+impl<T, M> NodeData<T, M> {
     /// The value of the node.
     #[inline(always)]
     pub fn val(&self) -> &T {
@@ -372,132 +858,786 @@ impl<T> NodeData<T> {
     pub fn subtree_size(&self) -> NonZeroUsize {
         self.subtree_size
     }
+
+    /// The metadata that the [`NodePolicy`] of the forest computed for this node when it was built.
+    #[inline(always)]
+    pub fn meta(&self) -> &M {
+        &self.meta
+    }
+
+    // The offsets of the fields in bytes, see the ffi module.
+    #[cfg(any(feature = "ffi", test))]
+    pub(crate) const VAL_OFFSET: usize = std::mem::offset_of!(NodeData<T>, val);
+    #[cfg(any(feature = "ffi", test))]
+    pub(crate) const SUBTREE_SIZE_OFFSET: usize = std::mem::offset_of!(NodeData<T>, subtree_size);
 }
 
-/// `NodeBuilder` is a struct that lets you add children to a node that is currently being added
-/// to a [`PackedTree`](crate::PackedTree) or a [`PackedForest`].
-/// 
-/// See [`PackedTree::new`](crate::PackedTree::new), [`PackedForest::build_tree`], [`PackedForest::get_tree_builder`], etc.
-/// 
-// IMPLEMENTATION NOTES:
-// The fields of the struct are:
-// - forest: mutable ref to the forest to which we're adding this node.
-// - index: the index where the node that we're adding will end up in self.forest.data
-// - subtree_size: the number of elements in the subtree that has this node as root,
-//   not counting children that haven't had finish() called on their NodeBuilder instances yet.
-// - parent_subtree_size: mutable reference to the parent's Node subtree_size (or None if no parent)
+// Make sure `data` has a capacity of at least `needed_capacity`, keeping all the data past its len (but inside its capacity).
 //
-// INVARIANTS:
-// 1. The values in the Vec forest.data between indices index+1 (inclusive) and index+subtree_size (exclusive)
-//    are initialized, valid, and within the capacity of the Vec but outside of the len of the Vec.
-// 2. If this node has a parent, self.index must be equal to parent.index + parent.subtree_size,
-//    otherwise index must be equal to forest.data.len().
-#[derive(destructure)]
-pub struct NodeBuilder<'a, T> {
-    forest: &'a mut PackedForest<T>,
-    index: usize,
-    subtree_size: NonZeroUsize,
-    parent_subtree_size: Option<&'a mut NonZeroUsize>,
+// I'd like to just call data.reserve(needed_capacity - data.len()) and be done with it.
+// Unfortunately, if there's a reallocation, the data between data.len() and data.capacity() is not
+// guaranteed to be copied over (under the current implementation at the time of writing it is,
+// but it's not guaranteed to be).
+//
+// So what we do instead is this:
+//
+// First, check if the current capacity is already enough. If so, do nothing.
+// Otherwise, we allocate a new Vec ourselves and copy over everything up to the old capacity,
+// which guarantees that the data that we've been writing past the len gets copied over.
+// Some of the copied memory may be uninitialized, which is fine for a raw copy.
+//
+// Safety: the caller must make sure that no references into `data` are alive,
+// since they are invalidated by a reallocation.
+#[inline]
+unsafe fn reserve_past_len<T, M>(data: &mut Vec<NodeData<T, M>>, needed_capacity: usize) {
+    let cur_capacity = data.capacity();
+    if needed_capacity > cur_capacity {
+        let data_len = data.len();
+        let new_capacity = std::cmp::max(needed_capacity, cur_capacity.saturating_mul(2));
+        let mut new_data = Vec::with_capacity(new_capacity);
+        std::ptr::copy_nonoverlapping(data.as_ptr(), new_data.as_mut_ptr(), cur_capacity);
+
+        // The old Vec must not drop anything anymore, as all of its values now live in new_data.
+        data.set_len(0);
+        new_data.set_len(data_len);
+        *data = new_data;
+    }
 }
 
-impl<'a, T> Drop for NodeBuilder<'a, T> {
-    #[inline]
-    fn drop(&mut self) {
-        unsafe {
-            let data = &mut self.forest.data;
+/// Returns the maximum number of nodes that can be stored in a single [`PackedForest`],
+/// which is limited by the maximum size of an allocation (`isize::MAX` bytes).
+#[inline(always)]
+pub(crate) const fn max_num_nodes<T, M>() -> usize {
+    isize::MAX as usize / std::mem::size_of::<NodeData<T, M>>()
+}
 
-            // Drop the elements in the Vec on indices [index+1 .. index+subtree_size]
-            // These are initialized, valid, and within the capacity of the Vec due to invariant 1,
-            // but they are outside the len of the Vec so we can drop the data.
-            //
-            // Also, if this node has a parent, then we must make sure that the parent NodeBuilder won't also drop these nodes.
-            // Luckily, this is the case, because self.index = parent.index+parent.subtree_size due to invariant 2,
-            // so the parent's slice does *not* contain the nodes that we're about to drop due to the parent's invariant 1.
-            for i in 1..self.subtree_size.get() {
-                // Calculate where to read the NodeData to drop.
-                // This is safe since self.index+i < data.capacity < isize::MAX
-                let ptr = data.as_mut_ptr().add(self.index+i);
-                let node_data : NodeData<T> = std::ptr::read(ptr);
-                drop(node_data);
-            }
+// Returns a + b if that doesn't exceed the maximum number of nodes.
+#[inline(always)]
+fn checked_num_nodes<T, M>(a: usize, b: usize) -> Result<usize, crate::CapacityError> {
+    match a.checked_add(b) {
+        Some(num_nodes) if num_nodes <= max_num_nodes::<T, M>() => Ok(num_nodes),
+        _ => Err(crate::CapacityError { capacity: max_num_nodes::<T, M>() }),
+    }
+}
+
+// Returns the depth of the deepest node in the subtree stored in `nodes`, relative to its root.
+fn subtree_height<T, M>(nodes: &[NodeData<T, M>]) -> usize {
+    // The end indices of the subtrees of the ancestors of the current node
+    let mut ancestor_ends: Vec<usize> = Vec::new();
+    let mut height = 0;
+    for (index, node) in nodes.iter().enumerate() {
+        while ancestor_ends.last().is_some_and(|end| *end <= index) {
+            ancestor_ends.pop();
         }
+        height = height.max(ancestor_ends.len());
+        ancestor_ends.push(index + node.subtree_size.get());
     }
+    height
 }
 
-impl<'a, T> NodeBuilder<'a, T> {
-    /// Returns the index of the node that is being built.
-    /// 
-    /// See also [`PackedForest::get`] and [`PackedForest::get_mut`].
+mod storage {
+    use super::NodeData;
+
+    /// Implemented by the types that a NodeBuilder can write nodes to, see NodeStorage.
+    /// This trait is in a private module, so NodeStorage can't be implemented outside of this crate.
+    ///
+    /// # Safety
+    /// as_mut_ptr must point to memory that can hold at least the capacity
+    /// that was ensured by the last call to reserve_past_len (and at least len elements),
+    /// and the elements between len and that capacity must not be touched by the storage,
+    /// except for reserve_past_len, which must keep them (like the function reserve_past_len).
+    pub unsafe trait RawNodeStorage<T, M = ()> {
+        fn len(&self) -> usize;
+        fn as_mut_ptr(&mut self) -> *mut NodeData<T, M>;
+        unsafe fn set_len(&mut self, new_len: usize);
+        unsafe fn reserve_past_len(&mut self, needed_capacity: usize);
+    }
+}
+
+/// The storage that a [`NodeBuilder`] writes the nodes it builds to.
+///
+/// This trait is sealed: it is implemented by `Vec<NodeData<T, M>>` (used by [`MetaPackedForest`]),
+/// [`SmallNodeStorage`] (used by [`SmallPackedTree`](crate::SmallPackedTree))
+/// and [`ArrayNodeStorage`] (used by [`ArrayPackedForest`](crate::ArrayPackedForest)), and can't be implemented outside of this crate.
+pub trait NodeStorage<T, M = ()>: storage::RawNodeStorage<T, M> {}
+
+unsafe impl<T, M> storage::RawNodeStorage<T, M> for Vec<NodeData<T, M>> {
     #[inline(always)]
-    pub fn index(&self) -> usize {
-        self.index
+    fn len(&self) -> usize {
+        Vec::len(self)
     }
 
-    /// Get a [`NodeBuilder`] to build a node that will become a child of the node
-    /// currently being built by this [`NodeBuilder`].
-    /// 
-    /// You must call [`finish`](`NodeBuilder::finish`) on the returned [`NodeBuilder`]
-    /// with the value that the child node will have. Simply dropping the [`NodeBuilder`]
-    /// without calling [`finish`](`NodeBuilder::finish`) results in no nodes being added.
-    /// 
-    /// **WARNING:** Leaking the returned [`NodeBuilder`] (i.e. through [`std::mem::forget`])
-    /// after adding child nodes to it leaks the values of those node (their `drop` method
-    /// won't be called). Leaking is considered "safe" in Rust, so this function is safe,
-    /// but you still probably want to avoid doing that.
-    /// 
-    /// For most use cases, using [`build_tree`](`PackedForest::build_tree`) or
-    /// [`build_tree_by_ret_val`](`PackedForest::build_tree_by_ret_val`) is probably more ergonomic.
-    /// 
-    /// # Example:
-    /// ```
-    /// use packed_tree::{PackedTree, PackedForest, NodeRef, NodeRefMut, NodeBuilder};
-    /// 
-    /// // Assume you already have some kind of tree with floating point values, like this:
-    /// let value_tree = PackedTree::new(1.2, |node_builder| {
-    ///     node_builder.build_child(3.4, |node_builder| {
-    ///         node_builder.add_child(5.6);
-    ///     });
-    ///     node_builder.add_child(7.8);
-    /// });
-    /// 
-    /// // Build a tree from the previous tree,
-    /// // where the value of a node is the sum of the values
-    /// // of all the values of all the nodes below it (including itself).
-    /// // Returns a NodeRefMut to the new node in the sum tree.
-    /// fn process_node<'t>(
-    ///     value_node: NodeRef<f64>,
-    ///     mut sum_node_builder: NodeBuilder<'t,f64>
-    /// ) -> NodeRefMut<'t,f64> {
-    ///     let mut sum = *value_node.val();
-    ///     for value_child in value_node.children() {
-    ///         let sum_child_builder = sum_node_builder.get_child_builder();
-    ///         let sum_child_node_ref = process_node(value_child, sum_child_builder);
-    ///         sum += *sum_child_node_ref.val();
-    ///     }
-    ///     sum_node_builder.finish(sum)
-    /// }
-    /// 
-    /// let mut sum_forest = PackedForest::new();
-    /// let root_builder = sum_forest.get_tree_builder();
-    /// process_node(value_tree.root(), root_builder);
-    /// 
-    /// let sum_tree = PackedTree::try_from_forest(sum_forest).unwrap();
-    /// 
-    /// assert_eq!(*sum_tree.root().val(), 1.2+3.4+5.6+7.8);
-    /// ```
-    #[inline]
-    pub fn get_child_builder<'b>(&'b mut self) -> NodeBuilder<'b, T> {
-        // Invariant 1 is satisfied because the child's NodeBuilder's subtree_size is 1,
-        // so there are no elements with those indices.
-        // Invariant 2 is satisfied, as child.index is set to self.index + self.subtree_size
-        NodeBuilder {
-            forest: &mut self.forest,
-            index: self.index + self.subtree_size.get(),
-            subtree_size: NonZeroUsize::new(1).unwrap(),
-            parent_subtree_size: Some(&mut self.subtree_size),
+    #[inline(always)]
+    fn as_mut_ptr(&mut self) -> *mut NodeData<T, M> {
+        Vec::as_mut_ptr(self)
+    }
+
+    #[inline(always)]
+    unsafe fn set_len(&mut self, new_len: usize) {
+        Vec::set_len(self, new_len)
+    }
+
+    #[inline(always)]
+    unsafe fn reserve_past_len(&mut self, needed_capacity: usize) {
+        reserve_past_len(self, needed_capacity)
+    }
+}
+
+impl<T, M> NodeStorage<T, M> for Vec<NodeData<T, M>> {}
+
+/// The storage of a [`SmallPackedTree`](crate::SmallPackedTree), which keeps up to `N` nodes inline,
+/// and moves them to a `Vec` on the heap when more nodes are added.
+pub struct SmallNodeStorage<T, const N: usize> {
+    // The nodes are stored in `inline` as long as `heap` is None.
+    // Only the first `inline_len` nodes of `inline` are part of the storage,
+    // the rest is uninitialized or written by a NodeBuilder past the len.
+    inline: [std::mem::MaybeUninit<NodeData<T>>; N],
+    inline_len: usize,
+    heap: Option<Vec<NodeData<T>>>,
+}
+
+impl<T, const N: usize> SmallNodeStorage<T, N> {
+    #[inline(always)]
+    pub(crate) fn new() -> SmallNodeStorage<T, N> {
+        SmallNodeStorage {
+            // An array of MaybeUninit doesn't need to be initialized.
+            inline: unsafe { std::mem::MaybeUninit::uninit().assume_init() },
+            inline_len: 0,
+            heap: None,
         }
     }
 
+    #[inline(always)]
+    pub(crate) fn is_inline(&self) -> bool {
+        self.heap.is_none()
+    }
+
+    #[inline(always)]
+    pub(crate) fn as_slice(&self) -> &[NodeData<T>] {
+        match &self.heap {
+            // The first inline_len nodes are initialized.
+            None => unsafe { std::slice::from_raw_parts(self.inline.as_ptr() as *const NodeData<T>, self.inline_len) },
+            Some(heap) => heap,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [NodeData<T>] {
+        match &mut self.heap {
+            // The first inline_len nodes are initialized.
+            None => unsafe { std::slice::from_raw_parts_mut(self.inline.as_mut_ptr() as *mut NodeData<T>, self.inline_len) },
+            Some(heap) => heap,
+        }
+    }
+
+    /// Returns a [`NodeBuilder`] that adds a tree to this storage.
+    #[inline(always)]
+    pub(crate) fn get_tree_builder(&mut self) -> NodeBuilder<'_, T, Self> {
+        tree_builder(self, None)
+    }
+
+    /// Returns an iterator over the trees in this storage.
+    #[inline(always)]
+    pub(crate) fn iter_trees(&self) -> NodeIter<'_, T> {
+        NodeIter {
+            remaining_nodes: self.as_slice(),
+            len: (),
+        }
+    }
+
+    /// Returns an iterator over the trees in this storage that allows changing their values.
+    #[inline(always)]
+    pub(crate) fn iter_trees_mut(&mut self) -> NodeIterMut<'_, T> {
+        NodeIterMut {
+            remaining_nodes: self.as_mut_slice(),
+            len: (),
+        }
+    }
+
+    /// Moves the nodes into a [`PackedForest`].
+    pub(crate) fn into_forest(mut self) -> PackedForest<T> {
+        let data = match self.heap.take() {
+            Some(heap) => heap,
+            None => {
+                let mut data = Vec::with_capacity(self.inline_len);
+                unsafe {
+                    // Move the nodes out, and make sure they won't be dropped by self anymore.
+                    std::ptr::copy_nonoverlapping(self.inline.as_ptr() as *const NodeData<T>, data.as_mut_ptr(), self.inline_len);
+                    data.set_len(self.inline_len);
+                    self.inline_len = 0;
+                }
+                data
+            }
+        };
+        PackedForest::from_data(data)
+    }
+}
+
+impl<T, const N: usize> Drop for SmallNodeStorage<T, N> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.heap.is_none() {
+            // The first inline_len nodes are initialized and owned by self.
+            unsafe { std::ptr::drop_in_place(self.as_mut_slice()) }
+        }
+    }
+}
+
+unsafe impl<T, const N: usize> storage::RawNodeStorage<T> for SmallNodeStorage<T, N> {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        match &self.heap {
+            None => self.inline_len,
+            Some(heap) => heap.len(),
+        }
+    }
+
+    #[inline(always)]
+    fn as_mut_ptr(&mut self) -> *mut NodeData<T> {
+        match &mut self.heap {
+            None => self.inline.as_mut_ptr() as *mut NodeData<T>,
+            Some(heap) => heap.as_mut_ptr(),
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn set_len(&mut self, new_len: usize) {
+        match &mut self.heap {
+            None => {
+                debug_assert!(new_len <= N);
+                self.inline_len = new_len;
+            }
+            Some(heap) => heap.set_len(new_len),
+        }
+    }
+
+    #[inline]
+    unsafe fn reserve_past_len(&mut self, needed_capacity: usize) {
+        match &mut self.heap {
+            None => {
+                if needed_capacity > N {
+                    // Move everything to the heap, including the data past the len, like reserve_past_len does.
+                    // The uninitialized parts are copied too, which is fine for a raw copy.
+                    let new_capacity = std::cmp::max(needed_capacity, N.saturating_mul(2));
+                    let mut heap = Vec::with_capacity(new_capacity);
+                    std::ptr::copy_nonoverlapping(self.inline.as_ptr() as *const NodeData<T>, heap.as_mut_ptr(), N);
+                    heap.set_len(self.inline_len);
+                    // The nodes now live in heap, so they must not be dropped from the inline storage.
+                    self.inline_len = 0;
+                    self.heap = Some(heap);
+                }
+            }
+            Some(heap) => reserve_past_len(heap, needed_capacity),
+        }
+    }
+}
+
+impl<T, const N: usize> NodeStorage<T> for SmallNodeStorage<T, N> {}
+
+/// The storage of an [`ArrayPackedForest`](crate::ArrayPackedForest), which keeps up to `CAP` nodes inline
+/// and never allocates. Trying to reserve more than `CAP` nodes panics,
+/// which is why [`ArrayNodeBuilder`](crate::ArrayNodeBuilder) checks the capacity before adding any node.
+pub struct ArrayNodeStorage<T, const CAP: usize> {
+    // Only the first `len` nodes of `nodes` are part of the storage,
+    // the rest is uninitialized or written by a NodeBuilder past the len.
+    nodes: [std::mem::MaybeUninit<NodeData<T>>; CAP],
+    len: usize,
+}
+
+impl<T, const CAP: usize> ArrayNodeStorage<T, CAP> {
+    #[inline(always)]
+    pub(crate) fn new() -> ArrayNodeStorage<T, CAP> {
+        ArrayNodeStorage {
+            // An array of MaybeUninit doesn't need to be initialized.
+            nodes: unsafe { std::mem::MaybeUninit::uninit().assume_init() },
+            len: 0,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn as_slice(&self) -> &[NodeData<T>] {
+        // The first len nodes are initialized.
+        unsafe { std::slice::from_raw_parts(self.nodes.as_ptr() as *const NodeData<T>, self.len) }
+    }
+
+    #[inline(always)]
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [NodeData<T>] {
+        // The first len nodes are initialized.
+        unsafe { std::slice::from_raw_parts_mut(self.nodes.as_mut_ptr() as *mut NodeData<T>, self.len) }
+    }
+
+    /// Returns a [`NodeBuilder`] that adds a tree to this storage.
+    #[inline(always)]
+    pub(crate) fn get_tree_builder(&mut self) -> NodeBuilder<'_, T, Self> {
+        tree_builder(self, None)
+    }
+
+    /// Returns an iterator over the trees in this storage.
+    #[inline(always)]
+    pub(crate) fn iter_trees(&self) -> NodeIter<'_, T> {
+        NodeIter {
+            remaining_nodes: self.as_slice(),
+            len: (),
+        }
+    }
+
+    /// Returns an iterator over the trees in this storage that allows changing their values.
+    #[inline(always)]
+    pub(crate) fn iter_trees_mut(&mut self) -> NodeIterMut<'_, T> {
+        NodeIterMut {
+            remaining_nodes: self.as_mut_slice(),
+            len: (),
+        }
+    }
+
+    /// Returns a [`NodeRef`] to the node with the given index, or `None` if the index is out of bounds.
+    #[inline(always)]
+    pub(crate) fn get(&self, index: usize) -> Option<NodeRef<'_, T>> {
+        let slice = self.as_slice();
+        let subtree_size = slice.get(index)?.subtree_size.get();
+        Some(NodeRef {
+            slice: &slice[index..(index + subtree_size)],
+        })
+    }
+
+    /// Returns a [`NodeRefMut`] to the node with the given index, or `None` if the index is out of bounds.
+    #[inline(always)]
+    pub(crate) fn get_mut(&mut self, index: usize) -> Option<NodeRefMut<'_, T>> {
+        let slice = self.as_mut_slice();
+        let subtree_size = slice.get(index)?.subtree_size.get();
+        Some(NodeRefMut {
+            slice: &mut slice[index..(index + subtree_size)],
+        })
+    }
+
+    /// Drops all the nodes.
+    #[inline]
+    pub(crate) fn clear(&mut self) {
+        let len = self.len;
+        // Set the len first, so that the nodes won't be dropped twice if dropping one of them panics.
+        self.len = 0;
+        unsafe {
+            std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(self.nodes.as_mut_ptr() as *mut NodeData<T>, len));
+        }
+    }
+}
+
+impl<T, const CAP: usize> Drop for ArrayNodeStorage<T, CAP> {
+    #[inline]
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+unsafe impl<T, const CAP: usize> storage::RawNodeStorage<T> for ArrayNodeStorage<T, CAP> {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline(always)]
+    fn as_mut_ptr(&mut self) -> *mut NodeData<T> {
+        self.nodes.as_mut_ptr() as *mut NodeData<T>
+    }
+
+    #[inline(always)]
+    unsafe fn set_len(&mut self, new_len: usize) {
+        debug_assert!(new_len <= CAP);
+        self.len = new_len;
+    }
+
+    #[inline(always)]
+    unsafe fn reserve_past_len(&mut self, needed_capacity: usize) {
+        // Panicking here is safe, since nothing has been written past the reserved capacity yet.
+        assert!(needed_capacity <= CAP, "the capacity of {} nodes was exceeded", CAP);
+    }
+}
+
+impl<T, const CAP: usize> NodeStorage<T> for ArrayNodeStorage<T, CAP> {}
+
+// Returns a NodeBuilder that adds a new tree at the end of `data`.
+// If the tree is added to a forest, `forest_num_trees` is its number of trees, which is incremented when the tree is finished.
+#[inline(always)]
+fn tree_builder<'a, T, P: NodePolicy, S: NodeStorage<T, P::Meta>>(data: &'a mut S, forest_num_trees: Option<&'a mut P::Count>) -> MetaNodeBuilder<'a, T, P, S> {
+    // NodeBuilder's invariants (see comments at structure definition of NodeBuilder):
+    // Invariant 1 is satisfied because the new NodeBuilder's subtree_size is 1,
+    // so there are no elements with those indices.
+    // Invariant 2 is satisfied, as child.index is set to data.len()
+    let new_root_index = data.len();
+    MetaNodeBuilder {
+        data,
+        index: new_root_index,
+        subtree_size: NonZeroUsize::new(1).unwrap(),
+        num_children: 0,
+        depth: 0,
+        max_depth: None,
+        parent_subtree_size: None,
+        parent_num_children: None,
+        forest_num_trees,
+        _val: std::marker::PhantomData,
+    }
+}
+
+/// A [`MetaNodeBuilder`] for a [`PackedForest`] or [`PackedTree`](crate::PackedTree), where it is documented.
+pub type NodeBuilder<'a, T, S = Vec<NodeData<T>>> = MetaNodeBuilder<'a, T, NoMeta, S>;
+
+/// `NodeBuilder` is a struct that lets you add children to a node that is currently being added
+/// to a [`PackedTree`](crate::PackedTree) or a [`PackedForest`].
+/// 
+/// See [`PackedTree::new`](crate::PackedTree::new), [`PackedForest::build_tree`], [`PackedForest::get_tree_builder`], etc.
+///
+/// A [`NodeBuilder`] for a child borrows the [`NodeBuilder`] of its parent, so building a tree that is n levels deep
+/// needs n nested builders, which usually means n nested function calls (e.g. of the callbacks of
+/// [`build_child`](NodeBuilder::build_child)). The builders themselves are small, but for trees that are
+/// hundreds of thousands of levels deep the call stack can overflow. To build such trees, see
+/// [`PackedTree::unfold_iterative`](crate::PackedTree::unfold_iterative).
+///
+// IMPLEMENTATION NOTES:
+// The fields of the struct are:
+// - data: mutable ref to the storage of the forest to which we're adding this node (usually the Vec of a PackedForest).
+// - index: the index where the node that we're adding will end up in self.data
+// - subtree_size: the number of elements in the subtree that has this node as root,
+//   not counting children that haven't had finish() called on their NodeBuilder instances yet.
+// - num_children: the number of children of this node,
+//   not counting children that haven't had finish() called on their NodeBuilder instances yet.
+// - depth: the distance between this node and the root of the tree that is being built.
+// - max_depth: the maximum depth of the nodes that may be added through this NodeBuilder (or None if there is no limit),
+//   which is inherited by the NodeBuilders of the children.
+// - parent_subtree_size: mutable reference to the parent's Node subtree_size (or None if no parent)
+// - parent_num_children: mutable reference to the parent's Node num_children (or None if no parent)
+// - forest_num_trees: mutable reference to the number of trees of the forest (or None if this node has a parent,
+//   or if it's not added to a MetaPackedForest), which is incremented when the root is finished.
+// - _val: marker for the type of the values, which are stored in data
+//
+// INVARIANTS:
+// 1. The values in data between indices index+1 (inclusive) and index+subtree_size (exclusive)
+//    are initialized, valid, and within the capacity of the storage but outside of its len.
+// 2. If this node has a parent, self.index must be equal to parent.index + parent.subtree_size,
+//    otherwise index must be equal to data.len().
+#[derive(destructure)]
+pub struct MetaNodeBuilder<'a, T, P: NodePolicy, S: NodeStorage<T, P::Meta> = Vec<NodeData<T, <P as NodePolicy>::Meta>>> {
+    data: &'a mut S,
+    index: usize,
+    subtree_size: NonZeroUsize,
+    num_children: usize,
+    depth: usize,
+    max_depth: Option<usize>,
+    parent_subtree_size: Option<&'a mut NonZeroUsize>,
+    parent_num_children: Option<&'a mut usize>,
+    forest_num_trees: Option<&'a mut P::Count>,
+    // S stores NodeData<T>s, but the compiler needs T to be used directly.
+    _val: std::marker::PhantomData<T>,
+}
+
+impl<'a, T, P: NodePolicy, S: NodeStorage<T, P::Meta>> Drop for MetaNodeBuilder<'a, T, P, S> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            let data = &mut *self.data;
+
+            // Drop the elements in the Vec on indices [index+1 .. index+subtree_size]
+            // These are initialized, valid, and within the capacity of the Vec due to invariant 1,
+            // but they are outside the len of the Vec so we can drop the data.
+            //
+            // Also, if this node has a parent, then we must make sure that the parent NodeBuilder won't also drop these nodes.
+            // Luckily, this is the case, because self.index = parent.index+parent.subtree_size due to invariant 2,
+            // so the parent's slice does *not* contain the nodes that we're about to drop due to the parent's invariant 1.
+            for i in 1..self.subtree_size.get() {
+                // Calculate where to read the NodeData to drop.
+                // This is safe since self.index+i < data.capacity < isize::MAX
+                let ptr = data.as_mut_ptr().add(self.index+i);
+                let node_data : NodeData<T, P::Meta> = std::ptr::read(ptr);
+                drop(node_data);
+            }
+        }
+    }
+}
+
+impl<'a, T, P: NodePolicy, S: NodeStorage<T, P::Meta>> MetaNodeBuilder<'a, T, P, S> {
+    /// Returns the index of the node that is being built.
+    /// 
+    /// See also [`PackedForest::get`] and [`PackedForest::get_mut`].
+    #[inline(always)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the depth of the node that is being built, i.e. its distance from the root of the tree
+    /// that is being built. The root itself has depth 0.
+    #[inline(always)]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the maximum depth of the nodes that can be added through this [`NodeBuilder`],
+    /// or `None` if there is no limit (which is the default). See [`set_max_depth`](NodeBuilder::set_max_depth).
+    #[inline(always)]
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Limits the depth of the nodes that can be added through this [`NodeBuilder`] and the [`NodeBuilder`]s
+    /// of its descendants to `max_depth`, where the root of the tree has depth 0 (see [`depth`](NodeBuilder::depth)).
+    ///
+    /// The limit can only be tightened: if this [`NodeBuilder`] already has a lower limit (e.g. one it inherited from
+    /// the [`NodeBuilder`] of its parent), that limit is kept. This way, code that gets a [`NodeBuilder`] can't lift
+    /// the limit that its caller set.
+    ///
+    /// Adding a child that would be deeper than the limit panics, or returns a [`DepthError`](crate::DepthError)
+    /// when using [`try_get_child_builder`](NodeBuilder::try_get_child_builder).
+    /// This is a backstop against pathological nesting, e.g. when building a tree from untrusted input.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedForest, DepthError};
+    ///
+    /// let mut forest = PackedForest::new();
+    /// let result = forest.build_tree(0, |node_builder| {
+    ///     node_builder.set_max_depth(1);
+    ///     node_builder.add_child(1);
+    ///     node_builder.build_child(2, |node_builder| {
+    ///         node_builder.try_get_child_builder().map(|child_builder| child_builder.finish(3)).err()
+    ///     })
+    /// });
+    /// assert_eq!(result, Some(DepthError { max_depth: 1 }));
+    /// assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [0, 1, 2]);
+    /// ```
+    #[inline(always)]
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = Some(self.max_depth.map_or(max_depth, |current| current.min(max_depth)));
+    }
+
+    // Computes the meta of a leaf child that is added at the given index.
+    #[inline(always)]
+    fn leaf_child_meta(&self, index: usize) -> P::Meta {
+        P::meta(&NodeInfo {
+            index,
+            depth: self.depth + 1,
+            num_children: 0,
+            subtree_size: 1,
+        })
+    }
+
+    // Returns an error if a node that is `extra_depth` levels below the children of this node would be too deep.
+    #[inline(always)]
+    fn check_child_depth(&self, extra_depth: usize) -> Result<(), crate::DepthError> {
+        match self.max_depth {
+            Some(max_depth) if self.depth + 1 + extra_depth > max_depth => Err(crate::DepthError { max_depth }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the number of children that have been added to the node that is being built so far.
+    /// 
+    /// Children whose [`NodeBuilder`] hasn't had [`finish`](NodeBuilder::finish) called on it yet are not counted.
+    #[inline(always)]
+    pub fn num_children_so_far(&self) -> usize {
+        self.num_children
+    }
+
+    /// Returns the number of nodes that have been added to the subtree of the node that is being built so far,
+    /// including the node itself.
+    /// 
+    /// Nodes whose [`NodeBuilder`] hasn't had [`finish`](NodeBuilder::finish) called on it yet are not counted.
+    #[inline(always)]
+    pub fn subtree_size_so_far(&self) -> usize {
+        self.subtree_size.get()
+    }
+
+    /// Get a [`NodeBuilder`] to build a node that will become a child of the node
+    /// currently being built by this [`NodeBuilder`].
+    /// 
+    /// You must call [`finish`](`NodeBuilder::finish`) on the returned [`NodeBuilder`]
+    /// with the value that the child node will have. Simply dropping the [`NodeBuilder`]
+    /// without calling [`finish`](`NodeBuilder::finish`) results in no nodes being added.
+    /// 
+    /// **WARNING:** Leaking the returned [`NodeBuilder`] (i.e. through [`std::mem::forget`])
+    /// after adding child nodes to it leaks the values of those node (their `drop` method
+    /// won't be called). Leaking is considered "safe" in Rust, so this function is safe,
+    /// but you still probably want to avoid doing that.
+    /// 
+    /// For most use cases, using [`build_tree`](`PackedForest::build_tree`) or
+    /// [`build_tree_by_ret_val`](`PackedForest::build_tree_by_ret_val`) is probably more ergonomic.
+    /// 
+    /// # Example:
+    /// ```
+    /// use packed_tree::{PackedTree, PackedForest, NodeRef, NodeRefMut, NodeBuilder};
+    /// 
+    /// // Assume you already have some kind of tree with floating point values, like this:
+    /// let value_tree = PackedTree::new(1.2, |node_builder| {
+    ///     node_builder.build_child(3.4, |node_builder| {
+    ///         node_builder.add_child(5.6);
+    ///     });
+    ///     node_builder.add_child(7.8);
+    /// });
+    /// 
+    /// // Build a tree from the previous tree,
+    /// // where the value of a node is the sum of the values
+    /// // of all the values of all the nodes below it (including itself).
+    /// // Returns a NodeRefMut to the new node in the sum tree.
+    /// fn process_node<'t>(
+    ///     value_node: NodeRef<f64>,
+    ///     mut sum_node_builder: NodeBuilder<'t,f64>
+    /// ) -> NodeRefMut<'t,f64> {
+    ///     let mut sum = *value_node.val();
+    ///     for value_child in value_node.children() {
+    ///         let sum_child_builder = sum_node_builder.get_child_builder();
+    ///         let sum_child_node_ref = process_node(value_child, sum_child_builder);
+    ///         sum += *sum_child_node_ref.val();
+    ///     }
+    ///     sum_node_builder.finish(sum)
+    /// }
+    /// 
+    /// let mut sum_forest = PackedForest::new();
+    /// let root_builder = sum_forest.get_tree_builder();
+    /// process_node(value_tree.root(), root_builder);
+    /// 
+    /// let sum_tree = PackedTree::try_from_forest(sum_forest).unwrap();
+    /// 
+    /// assert_eq!(*sum_tree.root().val(), 1.2+3.4+5.6+7.8);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the child would be deeper than the [maximum depth](NodeBuilder::set_max_depth),
+    /// see [`try_get_child_builder`](NodeBuilder::try_get_child_builder).
+    #[inline]
+    pub fn get_child_builder<'b>(&'b mut self) -> MetaNodeBuilder<'b, T, P, S> {
+        match self.try_get_child_builder() {
+            Ok(child_builder) => child_builder,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Like [`get_child_builder`](NodeBuilder::get_child_builder), but returns a [`DepthError`](crate::DepthError)
+    /// instead of panicking if the child would be deeper than the [maximum depth](NodeBuilder::set_max_depth).
+    #[inline]
+    pub fn try_get_child_builder<'b>(&'b mut self) -> Result<MetaNodeBuilder<'b, T, P, S>, crate::DepthError> {
+        self.check_child_depth(0)?;
+        // Invariant 1 is satisfied because the child's NodeBuilder's subtree_size is 1,
+        // so there are no elements with those indices.
+        // Invariant 2 is satisfied, as child.index is set to self.index + self.subtree_size
+        Ok(MetaNodeBuilder {
+            data: self.data,
+            index: self.index + self.subtree_size.get(),
+            subtree_size: NonZeroUsize::new(1).unwrap(),
+            num_children: 0,
+            depth: self.depth + 1,
+            max_depth: self.max_depth,
+            parent_subtree_size: Some(&mut self.subtree_size),
+            parent_num_children: Some(&mut self.num_children),
+            forest_num_trees: None,
+            _val: std::marker::PhantomData,
+        })
+    }
+
+    /// Add a leaf child node for each of the given values, as children of the node that is being built.
+    ///
+    /// This is equivalent to calling [`add_child`](NodeBuilder::add_child) for each value,
+    /// but reserves space for all children at once (using the lower bound of the iterator's
+    /// [`size_hint`](Iterator::size_hint)), which is faster for nodes with many leaf children.
+    ///
+    /// See also [`add_leaf_children_from_slice`](NodeBuilder::add_leaf_children_from_slice).
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are values and the children would be deeper than the [maximum depth](NodeBuilder::set_max_depth).
+    pub fn add_leaf_children(&mut self, vals: impl IntoIterator<Item = T>) {
+        let vals = vals.into_iter();
+        unsafe {
+            // No references into the data can exist, since self borrows the forest mutably.
+            // The size hint is only used to reserve space up front, so it's skipped if it's too large.
+            if let Ok(needed_capacity) = checked_num_nodes::<T, P::Meta>(self.index + self.subtree_size.get(), vals.size_hint().0) {
+                self.data.reserve_past_len(needed_capacity);
+            }
+            for val in vals {
+                if let Err(err) = self.check_child_depth(0) {
+                    panic!("{}", err);
+                }
+                let child_index = self.index + self.subtree_size.get();
+                self.data.reserve_past_len(child_index + 1);
+
+                // Write the child right after the nodes that are already part of this subtree.
+                // This is outside the len, but inside the capacity.
+                let meta = self.leaf_child_meta(child_index);
+                std::ptr::write(self.data.as_mut_ptr().add(child_index), NodeData {
+                    val,
+                    subtree_size: NonZeroUsize::new(1).unwrap(),
+                    meta,
+                });
+
+                // Invariant 1 is upheld, since the node at index+subtree_size was initialized above.
+                // We update subtree_size after each child, so that if the iterator panics,
+                // the children that were already added are dropped along with this NodeBuilder.
+                self.subtree_size = NonZeroUsize::new_unchecked(self.subtree_size.get() + 1);
+                self.num_children += 1;
+            }
+        }
+    }
+
+    /// Add a leaf child node for each of the values in the given slice, as children of the node that is being built.
+    ///
+    /// This reserves space for all children at once, and then copies the values in a tight loop,
+    /// which is faster than calling [`add_child`](NodeBuilder::add_child) for each value.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let tree = PackedTree::new(0, |node_builder| {
+    ///     node_builder.add_leaf_children_from_slice(&[1, 2, 3]);
+    /// });
+    ///
+    /// let vals: Vec<_> = tree.root().children().map(|child| *child.val()).collect();
+    /// assert_eq!(vals, [1, 2, 3]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the total number of nodes would overflow, see
+    /// [`try_add_leaf_children_from_slice`](NodeBuilder::try_add_leaf_children_from_slice),
+    /// or if the slice isn't empty and the children would be deeper than the [maximum depth](NodeBuilder::set_max_depth).
+    pub fn add_leaf_children_from_slice(&mut self, vals: &[T]) where T: Copy {
+        if let Err(err) = self.try_add_leaf_children_from_slice(vals) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like [`add_leaf_children_from_slice`](NodeBuilder::add_leaf_children_from_slice), but returns an
+    /// [`AddNodesError`](crate::AddNodesError) instead of panicking if the slice isn't empty and the children would be
+    /// deeper than the [maximum depth](NodeBuilder::set_max_depth), or if the total number of nodes would exceed
+    /// the maximum number of nodes that fit in memory (which can only happen for huge slices of a zero-sized type `T`).
+    /// Nothing is added if an error is returned.
+    pub fn try_add_leaf_children_from_slice(&mut self, vals: &[T]) -> Result<(), crate::AddNodesError> where T: Copy {
+        if !vals.is_empty() {
+            self.check_child_depth(0)?;
+        }
+        let first_child_index = self.index + self.subtree_size.get();
+        let needed_capacity = checked_num_nodes::<T, P::Meta>(first_child_index, vals.len())?;
+        unsafe {
+            // No references into the data can exist, since self borrows the forest mutably.
+            self.data.reserve_past_len(needed_capacity);
+
+            // Write the children right after the nodes that are already part of this subtree.
+            // This is outside the len, but inside the capacity.
+            // Copying T and the meta can't panic, but the policy can, so subtree_size is updated after every child.
+            let ptr = self.data.as_mut_ptr().add(first_child_index);
+            for (i, val) in vals.iter().enumerate() {
+                let meta = self.leaf_child_meta(first_child_index + i);
+                std::ptr::write(ptr.add(i), NodeData {
+                    val: *val,
+                    subtree_size: NonZeroUsize::new(1).unwrap(),
+                    meta,
+                });
+                // Invariant 1 is upheld, since the node at index+subtree_size was initialized above.
+                self.subtree_size = NonZeroUsize::new_unchecked(self.subtree_size.get() + 1);
+            }
+
+            self.num_children += vals.len();
+        }
+        Ok(())
+    }
+
     /// Finish building the node that this [`NodeBuilder`] was building, giving it its value
     /// and adding its nodes to the tree, forest or the parent [`NodeBuilder`].
     /// Returns a [`NodeRefMut`] to the node that was added.
@@ -516,181 +1656,549 @@ impl<'a, T> NodeBuilder<'a, T> {
     /// 
     /// See [`get_child_builder`](NodeBuilder::get_child_builder) for an example of how to use this.
     #[inline]
-    pub fn finish(self, val: T) -> NodeRefMut<'a,T> {
+    pub fn finish(self, val: T) -> MetaNodeRefMut<'a,T, P> {
+        // The policy can panic, so the meta is computed while self still drops the descendants on a panic.
+        let meta = P::meta(&NodeInfo {
+            index: self.index,
+            depth: self.depth,
+            num_children: self.num_children,
+            subtree_size: self.subtree_size.get(),
+        });
         unsafe {
             // Destructure self, preventing it from being dropped.
             // We do this as the very first thing so that if at any point during this function there is a panic,
             // we can be sure that there won't be a double drop (worst case scenario there's a leak, which is safe).
-            let (forest, index, subtree_size, mut parent_subtree_size_ref_mut) = self.destructure();
+            let (data, index, subtree_size, _num_children, _depth, _max_depth, mut parent_subtree_size_ref_mut, parent_num_children, forest_num_trees, _val) = self.destructure();
+
+            let data_len = data.len();
+
+            // Check (part of) invariant 1
+            debug_assert!(index >= data_len);
+
+            // Make sure data can hold at least self.index + self.subtree_size elements
+            data.reserve_past_len(index + subtree_size.get());
+            
+            // Calculate where to write the data.
+            // This is safe since self.index < data.capacity < isize::MAX
+            let ptr = data.as_mut_ptr().add(index);
+
+            // Write NodeData to the forest at calculated location
+            // This is outside the len, but inside the capacity
+            std::ptr::write(ptr, NodeData {
+                val,
+                subtree_size,
+                meta,
+            });
+
+            if let Some(ref mut parent_subtree_size) = parent_subtree_size_ref_mut {
+                // There is a parent, so we should update its subtree_size to include this Node and descendants.
+                // Since this node has self.subtree_size descendants (including itself), this means adding
+                // self.subtree_size to parent.subtree_size.
+                **parent_subtree_size = NonZeroUsize::new_unchecked(parent_subtree_size.get() + subtree_size.get());
+                if let Some(parent_num_children) = parent_num_children {
+                    *parent_num_children += 1;
+                }
+
+                // We need to prove that the parent's invariants are not violated here.
+                //
+                // Let's give things some shorter names to be able to talk about them more easily.
+                //   SI = self.index
+                //   SS = self.subtree_size
+                //   PI = parent.index
+                //   POS = parent's old subtree_size
+                //   PNS = parent's new subtree_size = POS+SS
+                //
+                // The parent's invariants require that the nodes at indices [PI+1..PI+PNS]
+                // are valid and initialized and within the capacity of the Vec.
+                //
+                // Due to its invariant 1, [PI+1..PI+POS] were already initialized,
+                // so we only need to prove that [PI+POS..PI+PNS] are initialized.
+                //
+                // Due to our invariant 2, SI == PI+POS, and because PNS=POS+SS,
+                // what we really need to prove is that [SI..SI+SS] are initialized.
+                //
+                // Due to our invariant 1, [SI+1..SI+SS] are initialized,
+                // and the node at index SI was initialized above using ptr::write.
+                //
+                // The capacity was also set to (at least) SI+SS = PI+POS+SS = PI+PNS above,
+                // so the capacity is also ok.
+            } else {
+                // When this node has no parent, we're done initializing all nodes and
+                // can update the len of the forest's data vector.
+                
+                // The current len should be equal to self.index (see invariant 2)
+                debug_assert_eq!(index, data_len);
+
+                // We now add self.subtree_size to that length.
+                //
+                // Safety requirements of set_len():
+                //
+                // 1. new_len must be less than or equal to capacity().
+                // We made sure above that the capacity is at least this many elements.
+                //
+                // 2. The elements at old_len..new_len must be initialized.
+                // There's no data between old_len and self.index (see above),
+                // the data at index self.index was initialized earlier in this function,
+                // and the data at indices [self.index+1..self.index+self.subtree_size]
+                // are initialized due to invariant 1.
+                data.set_len(index + subtree_size.get());
+
+                if let Some(num_trees) = forest_num_trees {
+                    *num_trees = num_trees.add(1);
+                }
+            }
+            
+            // The nodes of the subtree may lie outside the len of the Vec (but inside its capacity),
+            // so we can't get the slice through the Vec itself.
+            MetaNodeRefMut {
+                slice: std::slice::from_raw_parts_mut(data.as_mut_ptr().add(index), subtree_size.get())
+            }
+        }
+    }
+}
+
+impl<'a, T, S: NodeStorage<T>> NodeBuilder<'a, T, S> {
+    /// Add a copy of the given node and all of its descendants as a child of the node that is being built.
+    ///
+    /// Since `T` is [`Copy`], the nodes of the subtree are copied with a single memory copy,
+    /// which is a lot faster than adding them one by one.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let template = PackedTree::new(1, |node_builder| {
+    ///     node_builder.add_leaf_children_from_slice(&[2, 3]);
+    /// });
+    /// let tree = PackedTree::new(0, |node_builder| {
+    ///     node_builder.add_subtree_copy(template.root());
+    ///     node_builder.add_subtree_copy(template.root());
+    /// });
+    /// assert_eq!(tree.iter_flattened().copied().collect::<Vec<_>>(), [0, 1, 2, 3, 1, 2, 3]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the total number of nodes would exceed the maximum number of nodes that fit in memory,
+    /// which can only happen for a zero-sized type `T`, or if any of the copied nodes would be deeper than
+    /// the [maximum depth](NodeBuilder::set_max_depth).
+    pub fn add_subtree_copy(&mut self, node: NodeRef<'_, T>) where T: Copy {
+        let nodes = node.raw_slice();
+        if self.max_depth.is_some() {
+            if let Err(err) = self.check_child_depth(subtree_height(nodes)) {
+                panic!("{}", err);
+            }
+        }
+        let first_index = self.index + self.subtree_size.get();
+        let needed_capacity = match checked_num_nodes::<T, ()>(first_index, nodes.len()) {
+            Ok(needed_capacity) => needed_capacity,
+            Err(err) => panic!("{}", err),
+        };
+        unsafe {
+            // No references into the data can exist, since self borrows the forest mutably.
+            // For the same reason, `nodes` can't be part of the data, so the memory doesn't overlap.
+            self.data.reserve_past_len(needed_capacity);
+
+            // Write the nodes right after the nodes that are already part of this subtree.
+            // This is outside the len, but inside the capacity.
+            // The subtree sizes of the nodes only depend on their descendants, so they stay valid.
+            std::ptr::copy_nonoverlapping(nodes.as_ptr(), self.data.as_mut_ptr().add(first_index), nodes.len());
+
+            // Invariant 1 is upheld, since all nodes up to index+subtree_size were initialized above.
+            self.subtree_size = NonZeroUsize::new_unchecked(self.subtree_size.get() + nodes.len());
+            self.num_children += 1;
+        }
+    }
+
+    // Add a clone of the given node and all of its descendants as a child of the node that is being built,
+    // like add_subtree_copy, but cloning the nodes one by one. This doesn't recurse, so it works for subtrees of any depth.
+    pub(crate) fn add_cloned_subtree(&mut self, node: NodeRef<'_, T>) where T: Clone {
+        let nodes = node.raw_slice();
+        if self.max_depth.is_some() {
+            if let Err(err) = self.check_child_depth(subtree_height(nodes)) {
+                panic!("{}", err);
+            }
+        }
+        let first_index = self.index + self.subtree_size.get();
+        let needed_capacity = match checked_num_nodes::<T, ()>(first_index, nodes.len()) {
+            Ok(needed_capacity) => needed_capacity,
+            Err(err) => panic!("{}", err),
+        };
+        unsafe {
+            // No references into the data can exist, since self borrows the forest mutably.
+            self.data.reserve_past_len(needed_capacity);
+
+            // Write the clones right after the nodes that are already part of this subtree.
+            // The subtree sizes of the nodes only depend on their descendants, so they stay valid.
+            let ptr = self.data.as_mut_ptr();
+            for node_data in nodes {
+                std::ptr::write(ptr.add(self.index + self.subtree_size.get()), node_data.clone());
+                // Invariant 1 is upheld after every node, so if a clone panics, the nodes cloned so far are dropped.
+                self.subtree_size = NonZeroUsize::new_unchecked(self.subtree_size.get() + 1);
+            }
+            self.num_children += 1;
+        }
+    }
+}
+
+/// A [`MetaNodeIter`] over the nodes of a [`PackedForest`] or [`PackedTree`](crate::PackedTree), where it is documented.
+pub type NodeIter<'t, T> = MetaNodeIter<'t, T, NoMeta>;
+
+/// Iterates a list of nodes in a [`PackedForest`] or [`PackedTree`](crate::PackedTree), usually the list
+/// of children of a node, or the list of root nodes in a [`PackedForest`].
+/// 
+/// See e.g. [`PackedForest::iter_trees`] and [`NodeRef::children`].
+pub struct MetaNodeIter<'t, T, P: NodePolicy> {
+    remaining_nodes: &'t [NodeData<T, P::Meta>], // contains (only) the nodes in the iterator and all their descendants
+    len: P::Count, // the number of nodes in the iterator, if the policy counts them
+}
+
+// Not using #[derive(Copy)] because it adds the T:Copy bound, which is unnecessary
+impl<'t, T, P: NodePolicy> Copy for MetaNodeIter<'t,T, P> {}
+
+// Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
+impl<'t, T, P: NodePolicy> Clone for MetaNodeIter<'t, T, P> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'t, T, P: NodePolicy> MetaNodeIter<'t, T, P> {
+    /// Returns the number of nodes (also counting all descendants) remaining in this iterator in O(1) time.
+    #[inline(always)]
+    pub fn num_remaining_nodes_incl_descendants(&self) -> usize {
+        self.remaining_nodes.len()
+    }
+
+    /// Returns the number of nodes remaining in this iterator, not counting their descendants.
+    ///
+    /// This takes O(n) time, where n is the returned number, since only the remaining nodes themselves are visited.
+    #[inline]
+    pub fn num_remaining_nodes(&self) -> usize {
+        self.len.get().unwrap_or_else(|| count_sibling_nodes(self.remaining_nodes))
+    }
+
+    /// Splits this iterator into two iterators: one over the first `n` nodes of this iterator,
+    /// and one over the rest. If there are fewer than `n` nodes, the second iterator is empty.
+    ///
+    /// This takes O(n) time. See also [`NodeIterMut::split_at`].
+    pub fn split_at(self, n: usize) -> (MetaNodeIter<'t, T, P>, MetaNodeIter<'t, T, P>) {
+        let mut offset = 0;
+        let mut num_first = 0;
+        while num_first < n {
+            match self.remaining_nodes.get(offset) {
+                Some(node) => offset += node.subtree_size.get(),
+                None => break,
+            }
+            num_first += 1;
+        }
+        let (first, rest) = self.remaining_nodes.split_at(offset);
+        (
+            MetaNodeIter { remaining_nodes: first, len: P::Count::new(num_first) },
+            MetaNodeIter { remaining_nodes: rest, len: self.len.sub(num_first) },
+        )
+    }
+
+    /// Returns a read-only view over the raw data of the remaining nodes of this iterator and their descendants, in pre-order order.
+    ///
+    /// See [`NodeRef::raw_slice`].
+    #[inline(always)]
+    pub fn raw_slice(&self) -> &'t [NodeData<T, P::Meta>] {
+        self.remaining_nodes
+    }
+
+    /// Returns the next node of this iterator without advancing it, or `None` if the iterator is finished.
+    ///
+    /// This is cheap since [`NodeIter`] is [`Copy`], so there is no need to wrap it in a [`Peekable`](std::iter::Peekable).
+    #[inline(always)]
+    pub fn peek(&self) -> Option<MetaNodeRef<'t, T, P>> {
+        let mut iter = *self;
+        iter.next()
+    }
+}
+
+impl<'t, T, P: NodePolicy> Iterator for MetaNodeIter<'t, T, P> {
+    type Item = MetaNodeRef<'t, T, P>;
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let cur_node = self.remaining_nodes.first()?;
+        self.len = self.len.sub(1);
+        Some(MetaNodeRef {
+            slice: unsafe { slice_split_off_first_n_unchecked(&mut self.remaining_nodes, cur_node.subtree_size.get()) }
+        })
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        size_hint(self.len)
+    }
+}
+
+impl<'t, T, P: ChildCountPolicy> ExactSizeIterator for MetaNodeIter<'t, T, P> {}
+
+// The size hint of an iterator over a list of nodes, which is exact if the policy counts the nodes.
+#[inline(always)]
+fn size_hint<C: NodeCount>(len: C) -> (usize, Option<usize>) {
+    match len.get() {
+        Some(len) => (len, Some(len)),
+        None => (0, None),
+    }
+}
+
+// The number of children of the node with the given meta, as counted by the policy.
+#[inline(always)]
+fn child_count<P: NodePolicy>(meta: &P::Meta) -> P::Count {
+    P::num_children(meta).map_or(P::Count::ZERO, P::Count::new)
+}
+
+// Returns the number of nodes in a list of nodes that contains (only) these nodes and all their descendants.
+#[inline]
+fn count_sibling_nodes<T, M>(nodes: &[NodeData<T, M>]) -> usize {
+    let mut count = 0;
+    let mut offset = 0;
+    while let Some(node) = nodes.get(offset) {
+        offset += node.subtree_size.get();
+        count += 1;
+    }
+    count
+}
+
+/// An iterator over the values of a list of nodes and all their descendants, in pre-order order.
+///
+/// See e.g. [`PackedForest::iter_flattened`].
+pub struct FlattenedIter<'t, T, M = ()> {
+    nodes: std::slice::Iter<'t, NodeData<T, M>>,
+}
+
+// Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
+impl<'t, T, M> Clone for FlattenedIter<'t, T, M> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        FlattenedIter {
+            nodes: self.nodes.clone(),
+        }
+    }
+}
+
+impl<'t, T, M> FlattenedIter<'t, T, M> {
+    #[inline(always)]
+    pub(crate) fn new(nodes: &'t [NodeData<T, M>]) -> FlattenedIter<'t, T, M> {
+        FlattenedIter {
+            nodes: nodes.iter(),
+        }
+    }
+}
+
+impl<'t, T, M> Iterator for FlattenedIter<'t, T, M> {
+    type Item = &'t T;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<&'t T> {
+        self.nodes.next().map(|node_data| &node_data.val)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.nodes.size_hint()
+    }
+
+    #[inline(always)]
+    fn nth(&mut self, n: usize) -> Option<&'t T> {
+        self.nodes.nth(n).map(|node_data| &node_data.val)
+    }
+}
 
-            let data = &mut forest.data;
-            let data_len = data.len();
+impl<'t, T, M> DoubleEndedIterator for FlattenedIter<'t, T, M> {
+    #[inline(always)]
+    fn next_back(&mut self) -> Option<&'t T> {
+        self.nodes.next_back().map(|node_data| &node_data.val)
+    }
+}
 
-            // Check (part of) invariant 1
-            debug_assert!(index >= data_len);
+impl<'t, T, M> ExactSizeIterator for FlattenedIter<'t, T, M> {}
 
-            // Make sure data can hold at least self.index + self.subtree_size elements
-            // I'd like to just call data.reserve(self.index + self.subtree_size.get() - data_len) and be done with it.
-            // Unfortunately, if there's a reallocation, the data between data.capacity() and data.len() is not
-            // guaranteed to be copied over (under the current implementation at the time of writing it is,
-            // but it's not guaranteed to be).
-            //
-            // So what we do instead is this:
-            //
-            // First, check if the current capacity is already enough. If so, do nothing.
-            let needed_capacity = index + subtree_size.get();
-            let cur_capacity = data.capacity();
-            if needed_capacity > cur_capacity {
-                // In this branch the current capacity is not enough.
-
-                // We use set_len() to guarantee that if there is a reallocation,
-                // the data that we've been writing gets copied over.
-                data.set_len(cur_capacity);
-                data.reserve(needed_capacity - data_len);
-                data.set_len(data_len);
-
-                // TODO: rework using from_raw_parts
-            }
-            
-            // Calculate where to write the data.
-            // This is safe since self.index < data.capacity < isize::MAX
-            let ptr = data.as_mut_ptr().add(index);
+impl<'t, T, M> FusedIterator for FlattenedIter<'t, T, M> {}
 
-            // Write NodeData to the forest at calculated location
-            // This is outside the len, but inside the capacity
-            std::ptr::write(ptr, NodeData {
-                val,
-                subtree_size
-            });
+/// An iterator over mutable references to the values of a list of nodes and all their descendants, in pre-order order.
+///
+/// See e.g. [`PackedForest::iter_flattened_mut`].
+pub struct FlattenedIterMut<'t, T, M = ()> {
+    nodes: std::slice::IterMut<'t, NodeData<T, M>>,
+}
 
-            if let Some(ref mut parent_subtree_size) = parent_subtree_size_ref_mut {
-                // There is a parent, so we should update its subtree_size to include this Node and descendants.
-                // Since this node has self.subtree_size descendants (including itself), this means adding
-                // self.subtree_size to parent.subtree_size.
-                std::mem::replace(*parent_subtree_size, NonZeroUsize::new_unchecked(parent_subtree_size.get() + subtree_size.get()));
+impl<'t, T, M> Iterator for FlattenedIterMut<'t, T, M> {
+    type Item = &'t mut T;
 
-                // We need to prove that the parent's invariants are not violated here.
-                //
-                // Let's give things some shorter names to be able to talk about them more easily.
-                //   SI = self.index
-                //   SS = self.subtree_size
-                //   PI = parent.index
-                //   POS = parent's old subtree_size
-                //   PNS = parent's new subtree_size = POS+SS
-                //
-                // The parent's invariants require that the nodes at indices [PI+1..PI+PNS]
-                // are valid and initialized and within the capacity of the Vec.
-                //
-                // Due to its invariant 1, [PI+1..PI+POS] were already initialized,
-                // so we only need to prove that [PI+POS..PI+PNS] are initialized.
-                //
-                // Due to our invariant 2, SI == PI+POS, and because PNS=POS+SS,
-                // what we really need to prove is that [SI..SI+SS] are initialized.
-                //
-                // Due to our invariant 1, [SI+1..SI+SS] are initialized,
-                // and the node at index SI was initialized above using ptr::write.
-                //
-                // The capacity was also set to (at least) SI+SS = PI+POS+SS = PI+PNS above,
-                // through data.reserve(...), so the capacity is also ok.
-            } else {
-                // When this node has no parent, we're done initializing all nodes and
-                // can update the len of the forest's data vector.
-                
-                // The current len should be equal to self.index (see invariant 2)
-                debug_assert_eq!(index, data_len);
+    #[inline(always)]
+    fn next(&mut self) -> Option<&'t mut T> {
+        self.nodes.next().map(|node_data| &mut node_data.val)
+    }
 
-                // We now add self.subtree_size to that length.
-                //
-                // Safety requirements of set_len():
-                //
-                // 1. new_len must be less than or equal to capacity().
-                // We called data.reserve() above requesting precisely this many elements of capacity.
-                //
-                // 2. The elements at old_len..new_len must be initialized.
-                // There's no data between old_len and self.index (see above),
-                // the data at index self.index was initialized earlier in this function,
-                // and the data at indices [self.index+1..self.index+self.subtree_size]
-                // are initialized due to invariant 1.
-                data.set_len(index + subtree_size.get());
-            }
-            
-            NodeRefMut {
-                slice: forest.data.get_unchecked_mut(index .. (index+subtree_size.get()))
-            }
-        }
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.nodes.size_hint()
+    }
+
+    #[inline(always)]
+    fn nth(&mut self, n: usize) -> Option<&'t mut T> {
+        self.nodes.nth(n).map(|node_data| &mut node_data.val)
     }
 }
 
-/// Iterates a list of nodes in a [`PackedForest`] or [`PackedTree`](crate::PackedTree), usually the list
-/// of children of a node, or the list of root nodes in a [`PackedForest`].
-/// 
-/// See e.g. [`PackedForest::iter_trees`] and [`NodeRef::children`].
-pub struct NodeIter<'t, T> {
-    remaining_nodes: &'t [NodeData<T>], // contains (only) the nodes in the iterator and all their descendants
+impl<'t, T, M> DoubleEndedIterator for FlattenedIterMut<'t, T, M> {
+    #[inline(always)]
+    fn next_back(&mut self) -> Option<&'t mut T> {
+        self.nodes.next_back().map(|node_data| &mut node_data.val)
+    }
 }
 
-// Not using #[derive(Copy)] because it adds the T:Copy bound, which is unnecessary
-impl<'t,T> Copy for NodeIter<'t,T> {}
+impl<'t, T, M> ExactSizeIterator for FlattenedIterMut<'t, T, M> {}
+
+impl<'t, T, M> FusedIterator for FlattenedIterMut<'t, T, M> {}
+
+/// A draining iterator over the values of all the nodes of a forest, in pre-order order.
+///
+/// Dropping the iterator drops the values that haven't been iterated over yet.
+/// See e.g. [`PackedForest::drain_flattened`].
+pub struct FlattenedDrain<'t, T, M = ()> {
+    nodes: std::vec::Drain<'t, NodeData<T, M>>,
+}
+
+impl<'t, T, M> Iterator for FlattenedDrain<'t, T, M> {
+    type Item = T;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<T> {
+        self.nodes.next().map(|node_data| node_data.val)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.nodes.size_hint()
+    }
+}
+
+impl<'t, T, M> DoubleEndedIterator for FlattenedDrain<'t, T, M> {
+    #[inline(always)]
+    fn next_back(&mut self) -> Option<T> {
+        self.nodes.next_back().map(|node_data| node_data.val)
+    }
+}
+
+impl<'t, T, M> ExactSizeIterator for FlattenedDrain<'t, T, M> {}
+
+impl<'t, T, M> FusedIterator for FlattenedDrain<'t, T, M> {}
+
+/// An iterator over the values of the children of a node. See [`NodeRef::children_values`].
+pub struct ChildrenValues<'t, T, P: NodePolicy = NoMeta> {
+    pub(crate) children: MetaNodeIter<'t, T, P>,
+}
 
 // Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
-impl<'t, T> Clone for NodeIter<'t, T> {
+impl<'t, T, P: NodePolicy> Clone for ChildrenValues<'t, T, P> {
     #[inline(always)]
     fn clone(&self) -> Self {
-        *self
+        ChildrenValues {
+            children: self.children,
+        }
     }
 }
 
-impl<'t, T> NodeIter<'t, T> {
-    /// Returns the number of nodes (also counting all descendants) remaining in this iterator in O(1) time.
+impl<'t, T, P: NodePolicy> Iterator for ChildrenValues<'t, T, P> {
+    type Item = &'t T;
+
     #[inline(always)]
-    pub fn num_remaining_nodes_incl_descendants(&self) -> usize {
-        self.remaining_nodes.len()
+    fn next(&mut self) -> Option<&'t T> {
+        self.children.next().map(|child| child.val())
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.children.size_hint()
     }
 }
 
-impl<'t, T> Iterator for NodeIter<'t, T> {
-    type Item = NodeRef<'t, T>;
+/// An iterator over mutable references to the values of the children of a node. See [`NodeRefMut::children_values_mut`].
+pub struct ChildrenValuesMut<'t, T, P: NodePolicy = NoMeta> {
+    pub(crate) children: MetaNodeIterMut<'t, T, P>,
+}
+
+impl<'t, T, P: NodePolicy> Iterator for ChildrenValuesMut<'t, T, P> {
+    type Item = &'t mut T;
+
     #[inline(always)]
-    fn next(&mut self) -> Option<Self::Item> {
-        self.remaining_nodes.get(0).map(|cur_node| {
-            NodeRef {
-                slice: unsafe { slice_split_off_first_n_unchecked(&mut self.remaining_nodes, cur_node.subtree_size.get()) }
-            }
-        })
+    fn next(&mut self) -> Option<&'t mut T> {
+        self.children.next().map(|child| child.into_val_mut())
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.children.size_hint()
     }
 }
 
+/// A [`MetaNodeRef`] to a node in a [`PackedForest`] or [`PackedTree`](crate::PackedTree), where it is documented.
+pub type NodeRef<'t, T> = MetaNodeRef<'t, T, NoMeta>;
+
 /// A shared reference to a node in a [`PackedForest`] or [`PackedTree`](crate::PackedTree).
-pub struct NodeRef<'t, T> {
-    slice: &'t [NodeData<T>], // contains (only) the current node and all its descendants
+pub struct MetaNodeRef<'t, T, P: NodePolicy> {
+    slice: &'t [NodeData<T, P::Meta>], // contains (only) the current node and all its descendants
 }
 
 // Not using #[derive(Copy)] because it adds the T:Copy bound, which is unnecessary
-impl<'t,T> Copy for NodeRef<'t,T> {}
+impl<'t, T, P: NodePolicy> Copy for MetaNodeRef<'t,T, P> {}
 
 // Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
-impl<'t,T> Clone for NodeRef<'t,T> {
+impl<'t, T, P: NodePolicy> Clone for MetaNodeRef<'t,T, P> {
     #[inline(always)]
     fn clone(&self) -> Self {
         *self
     }
 }
 
-impl<'t, T> NodeRef<'t, T> {
+impl<'t, T, P: NodePolicy> MetaNodeRef<'t, T, P> {
+    // Returns an iterator that only contains this node.
+    #[inline(always)]
+    pub(crate) fn as_node_iter(&self) -> MetaNodeIter<'t, T, P> {
+        MetaNodeIter {
+            remaining_nodes: self.slice,
+            len: P::Count::new(1),
+        }
+    }
+
     /// Returns an iterator to the children of this node.
     #[inline(always)]
-    pub fn children(&self) -> NodeIter<'t, T> {
-        let (_, remaining_nodes) = unsafe { slice_split_first_unchecked(self.slice) };
-        NodeIter { remaining_nodes }
+    pub fn children(&self) -> MetaNodeIter<'t, T, P> {
+        let (node, remaining_nodes) = unsafe { slice_split_first_unchecked(self.slice) };
+        MetaNodeIter { remaining_nodes, len: child_count::<P>(&node.meta) }
     }
 
     /// Returns a reference to the value of this node.
+    ///
+    /// The reference has the lifetime of the tree, so it can outlive this [`NodeRef`].
     #[inline(always)]
-    pub fn val(&self) -> &T {
-        debug_assert!(self.slice.len() > 0);
+    pub fn val(&self) -> &'t T {
+        debug_assert!(!self.slice.is_empty());
         unsafe { &self.slice.get_unchecked(0).val }
     }
 
+    /// Returns a reference to the extra data that the [`NodePolicy`] stores for this node.
+    #[inline(always)]
+    pub fn meta(&self) -> &'t P::Meta {
+        debug_assert!(!self.slice.is_empty());
+        unsafe { &self.slice.get_unchecked(0).meta }
+    }
+
+    /// Returns the number of children of this node.
+    ///
+    /// This takes O(1) time if the [`NodePolicy`] records the number of children
+    /// (see [`ChildCountPolicy`]), and otherwise counts the children.
+    #[inline(always)]
+    pub fn num_children(&self) -> usize {
+        self.children().num_remaining_nodes()
+    }
+
     /// Counts the number of descendants of this node (also counting the node itself) in O(1) time.
     #[inline(always)]
     pub fn num_descendants_incl_self(&self) -> usize {
@@ -702,34 +2210,113 @@ impl<'t, T> NodeRef<'t, T> {
     pub fn num_descendants_excl_self(&self) -> usize {
         self.slice.len() - 1
     }
+
+    /// Get a [`NodeRef`] to the descendant of this node that comes `offset` nodes after this node in pre-order,
+    /// or `None` if `offset` is not smaller than [`num_descendants_incl_self`](NodeRef::num_descendants_incl_self).
+    /// An `offset` of 0 returns this node itself, an `offset` of 1 its first child, etc.
+    ///
+    /// This is the equivalent of [`PackedForest::get`] with indices relative to this node.
+    #[inline]
+    pub fn descendant_at_offset(&self, offset: usize) -> Option<MetaNodeRef<'t, T, P>> {
+        self.slice.get(offset).map(|node_data| {
+            MetaNodeRef {
+                slice: &self.slice[offset..(offset + node_data.subtree_size.get())]
+            }
+        })
+    }
+
+    /// Returns the range of pre-order indices in `forest` (see [`PackedForest::get`]) covered by this node and its descendants.
+    ///
+    /// Since the nodes of a subtree are stored contiguously, this range can be used to slice side tables
+    /// that store extra data for every node of the forest, indexed by pre-order index.
+    /// To use this with a [`PackedTree`](crate::PackedTree), pass `tree.as_ref()`.
+    ///
+    /// This takes O(1) time.
+    ///
+    /// # Panics
+    /// Panics if this node is not part of `forest`.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let tree = PackedTree::new("a", |node_builder| {
+    ///     node_builder.add_child("b");
+    ///     node_builder.build_child("c", |node_builder| {
+    ///         node_builder.add_child("d");
+    ///     });
+    /// });
+    /// let lengths: Vec<usize> = tree.iter_flattened().map(|val| val.len()).collect();
+    ///
+    /// let node = tree.root().children().nth(1).unwrap();
+    /// assert_eq!(node.preorder_range(tree.as_ref()), 2..4);
+    /// assert_eq!(lengths[node.preorder_range(tree.as_ref())].len(), 2);
+    /// ```
+    pub fn preorder_range(&self, forest: &MetaPackedForest<T, P>) -> std::ops::Range<usize> {
+        // Different forests never share memory, so the node is part of the forest
+        // exactly when its nodes lie within the forest's nodes.
+        let forest_range = forest.data.as_ptr_range();
+        let node_range = self.slice.as_ptr_range();
+        assert!(
+            forest_range.start <= node_range.start && node_range.end <= forest_range.end,
+            "node is not part of the given forest"
+        );
+        // Safety: both pointers are in the same allocation (see above), and since NodeData<T>
+        // is never zero-sized, the distance between them is a whole number of nodes.
+        let start = unsafe { node_range.start.offset_from(forest_range.start) } as usize;
+        start..(start + self.slice.len())
+    }
+
+    /// Returns a read-only view over the raw data of this node and its descendants, in pre-order order.
+    /// The first element is this node itself, and the slice has [`num_descendants_incl_self`](NodeRef::num_descendants_incl_self) elements.
+    ///
+    /// This is the part of [`PackedForest::raw_data`] that belongs to this subtree, and just like that method,
+    /// it is not really recommended to be used except for very advanced use cases.
+    #[inline(always)]
+    pub fn raw_slice(&self) -> &'t [NodeData<T, P::Meta>] {
+        self.slice
+    }
 }
 
+/// A [`MetaNodeIterMut`] over the nodes of a [`PackedForest`] or [`PackedTree`](crate::PackedTree), where it is documented.
+pub type NodeIterMut<'t, T> = MetaNodeIterMut<'t, T, NoMeta>;
+
 /// A mutable reference to a node in a [`PackedForest`] or [`PackedTree`](crate::PackedTree).
-pub struct NodeIterMut<'t, T> {
-    remaining_nodes: &'t mut [NodeData<T>], // contains (only) the nodes in the iterator and all their descendants
+pub struct MetaNodeIterMut<'t, T, P: NodePolicy> {
+    remaining_nodes: &'t mut [NodeData<T, P::Meta>], // contains (only) the nodes in the iterator and all their descendants
+    len: P::Count, // the number of nodes in the iterator, if the policy counts them
 }
 
-impl<'t, T> Iterator for NodeIterMut<'t, T> {
-    type Item = NodeRefMut<'t, T>;
+impl<'t, T, P: NodePolicy> Iterator for MetaNodeIterMut<'t, T, P> {
+    type Item = MetaNodeRefMut<'t, T, P>;
     #[inline(always)]
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(cur_node) = self.remaining_nodes.get(0) {
+        if let Some(cur_node) = self.remaining_nodes.first() {
             let cur_node_subtree_size = cur_node.subtree_size.get();
-            Some(NodeRefMut {
+            self.len = self.len.sub(1);
+            Some(MetaNodeRefMut {
                 slice: unsafe { slice_split_off_first_n_unchecked_mut(&mut self.remaining_nodes, cur_node_subtree_size) }
             })
         } else {
             None
         }
     }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        size_hint(self.len)
+    }
 }
 
-impl<'t, T> NodeIterMut<'t, T> {
+impl<'t, T, P: ChildCountPolicy> ExactSizeIterator for MetaNodeIterMut<'t, T, P> {}
+
+impl<'t, T, P: NodePolicy> MetaNodeIterMut<'t, T, P> {
     /// Reborrow this [`NodeIterMut`] as a [`NodeIter`].
     #[inline(always)]
-    pub fn reborrow_shared(&self) -> NodeIter<T> {
-        NodeIter {
-            remaining_nodes: &self.remaining_nodes
+    pub fn reborrow_shared(&self) -> MetaNodeIter<'_, T, P> {
+        MetaNodeIter {
+            remaining_nodes: self.remaining_nodes,
+            len: self.len,
         }
     }
 
@@ -738,34 +2325,127 @@ impl<'t, T> NodeIterMut<'t, T> {
     pub fn num_remaining_nodes_incl_descendants(&self) -> usize {
         self.remaining_nodes.len()
     }
+
+    /// Returns the number of nodes remaining in this iterator, not counting their descendants.
+    ///
+    /// This takes O(n) time, where n is the returned number, since only the remaining nodes themselves are visited.
+    #[inline]
+    pub fn num_remaining_nodes(&self) -> usize {
+        self.len.get().unwrap_or_else(|| count_sibling_nodes(self.remaining_nodes))
+    }
+
+    /// Returns a shared reference to the next node of this iterator without advancing it,
+    /// or `None` if the iterator is finished.
+    #[inline(always)]
+    pub fn peek(&self) -> Option<MetaNodeRef<'_, T, P>> {
+        self.reborrow_shared().next()
+    }
+
+    /// Returns a mutable reference to the next node of this iterator without advancing it,
+    /// or `None` if the iterator is finished.
+    ///
+    /// The returned reference borrows this iterator, so unlike [`Peekable`](std::iter::Peekable)
+    /// this allows looking ahead while still being able to take the node with [`next`](Iterator::next) afterwards.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let mut tree = PackedTree::new(0, |node_builder| {
+    ///     node_builder.add_leaf_children([1, 2, 3]);
+    /// });
+    ///
+    /// let mut children = tree.root_mut().into_children();
+    /// while let Some(mut child) = children.peek_mut() {
+    ///     if *child.val() == 2 {
+    ///         break;
+    ///     }
+    ///     *child.val_mut() *= 10;
+    ///     children.next();
+    /// }
+    /// assert_eq!(*children.next().unwrap().val(), 2);
+    /// assert_eq!(tree.iter_flattened().copied().collect::<Vec<_>>(), [0, 10, 2, 3]);
+    /// ```
+    #[inline]
+    pub fn peek_mut(&mut self) -> Option<MetaNodeRefMut<'_, T, P>> {
+        let subtree_size = self.remaining_nodes.first()?.subtree_size.get();
+        Some(MetaNodeRefMut {
+            slice: &mut self.remaining_nodes[..subtree_size]
+        })
+    }
+
+    /// Splits this iterator into two independent iterators: one over the first `n` nodes of this iterator,
+    /// and one over the rest. If there are fewer than `n` nodes, the second iterator is empty.
+    ///
+    /// Since the two iterators refer to disjoint subtrees, they can be used at the same time,
+    /// e.g. to change the values of different children of a node on different threads.
+    ///
+    /// This takes O(n) time.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let mut tree = PackedTree::new(0, |node_builder| {
+    ///     node_builder.add_leaf_children(1..=4);
+    /// });
+    ///
+    /// let (first, rest) = tree.root_mut().into_children().split_at(1);
+    /// std::thread::scope(|scope| {
+    ///     scope.spawn(|| first.for_each(|mut child| *child.val_mut() *= 10));
+    ///     scope.spawn(|| rest.for_each(|mut child| *child.val_mut() *= 100));
+    /// });
+    /// assert_eq!(tree.iter_flattened().copied().collect::<Vec<_>>(), [0, 10, 200, 300, 400]);
+    /// ```
+    pub fn split_at(self, n: usize) -> (MetaNodeIterMut<'t, T, P>, MetaNodeIterMut<'t, T, P>) {
+        let mut offset = 0;
+        let mut num_first = 0;
+        while num_first < n {
+            match self.remaining_nodes.get(offset) {
+                Some(node) => offset += node.subtree_size.get(),
+                None => break,
+            }
+            num_first += 1;
+        }
+        // offset is at the start of a node (or the end), so both halves contain whole subtrees.
+        let (first, rest) = self.remaining_nodes.split_at_mut(offset);
+        (
+            MetaNodeIterMut { remaining_nodes: first, len: P::Count::new(num_first) },
+            MetaNodeIterMut { remaining_nodes: rest, len: self.len.sub(num_first) },
+        )
+    }
 }
 
-impl<'t,T> From<NodeIterMut<'t,T>> for NodeIter<'t,T> {
+impl<'t, T, P: NodePolicy> From<MetaNodeIterMut<'t,T, P>> for MetaNodeIter<'t,T, P> {
     #[inline(always)]
-    fn from(val: NodeIterMut<'t,T>) -> Self {
-        NodeIter {
-            remaining_nodes: val.remaining_nodes
+    fn from(val: MetaNodeIterMut<'t,T, P>) -> Self {
+        MetaNodeIter {
+            remaining_nodes: val.remaining_nodes,
+            len: val.len,
         }
     }
 }
 
+/// A [`MetaNodeRefMut`] to a node in a [`PackedForest`] or [`PackedTree`](crate::PackedTree), where it is documented.
+pub type NodeRefMut<'t, T> = MetaNodeRefMut<'t, T, NoMeta>;
+
 /// A mutable reference to a node in a [`PackedForest`] or a [`PackedTree`](crate::PackedTree).
 /// 
 /// This reference only allows mutable access to the values in the nodes, not the structure of the node,
 /// so you can't add or remove children from this node.
-pub struct NodeRefMut<'t, T> {
-    slice: &'t mut [NodeData<T>], // contains (only) the current node and all its descendants
+pub struct MetaNodeRefMut<'t, T, P: NodePolicy> {
+    slice: &'t mut [NodeData<T, P::Meta>], // contains (only) the current node and all its descendants
 }
 
-impl<'t, T> NodeRefMut<'t, T> {
+impl<'t, T, P: NodePolicy> MetaNodeRefMut<'t, T, P> {
     /// Returns an iterator to the children of this node.
     /// 
     /// The difference between this and [`NodeRefMut::children`] is that this method
     /// consumes self and is therefore able to return a broader lifetime.
     #[inline(always)]
-    pub fn into_children(self) -> NodeIterMut<'t, T> {
-        let (_, remaining_nodes) = unsafe { slice_split_first_unchecked_mut(self.slice) };
-        NodeIterMut { remaining_nodes }
+    pub fn into_children(self) -> MetaNodeIterMut<'t, T, P> {
+        let (node, remaining_nodes) = unsafe { slice_split_first_unchecked_mut(self.slice) };
+        MetaNodeIterMut { remaining_nodes, len: child_count::<P>(&node.meta) }
     }
 
     /// Returns an iterator to the children of this node.
@@ -774,30 +2454,102 @@ impl<'t, T> NodeRefMut<'t, T> {
     /// reborrows self, so the lifetime of the returned iterator is that of the
     /// mutable reference passed to this function.
     #[inline(always)]
-    pub fn children(&mut self) -> NodeIterMut<T> {
-        let (_, remaining_nodes) = unsafe { slice_split_first_unchecked_mut(self.slice) };
-        NodeIterMut { remaining_nodes }
+    pub fn children(&mut self) -> MetaNodeIterMut<'_, T, P> {
+        let (node, remaining_nodes) = unsafe { slice_split_first_unchecked_mut(self.slice) };
+        MetaNodeIterMut { remaining_nodes, len: child_count::<P>(&node.meta) }
     }
 
     /// Returns a shared reference to the value of this node.
     #[inline(always)]
     pub fn val(&self) -> &T {
-        debug_assert!(self.slice.len() > 0);
+        debug_assert!(!self.slice.is_empty());
         unsafe { &self.slice.get_unchecked(0).val }
     }
 
+    /// Returns a reference to the extra data that the [`NodePolicy`] stores for this node.
+    #[inline(always)]
+    pub fn meta(&self) -> &P::Meta {
+        debug_assert!(!self.slice.is_empty());
+        unsafe { &self.slice.get_unchecked(0).meta }
+    }
+
+    /// Returns the number of children of this node.
+    ///
+    /// This takes O(1) time if the [`NodePolicy`] records the number of children
+    /// (see [`ChildCountPolicy`]), and otherwise counts the children.
+    #[inline(always)]
+    pub fn num_children(&self) -> usize {
+        self.reborrow_shared().num_children()
+    }
+
     /// Returns a mutable reference to the value of this node.
     #[inline(always)]
     pub fn val_mut(&mut self) -> &mut T {
-        debug_assert!(self.slice.len() > 0);
+        debug_assert!(!self.slice.is_empty());
+        unsafe { &mut self.slice.get_unchecked_mut(0).val }
+    }
+
+    /// Replaces the value of this node with the result of calling `f` on the old value, which is moved into `f`.
+    ///
+    /// Unlike [`take_val`](NodeRefMut::take_val), this doesn't require `T` to implement [`Default`], since the node isn't
+    /// given a temporary value while `f` runs. Because of that, the process is aborted if `f` panics,
+    /// as the node would be left without a value otherwise.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let mut tree = PackedTree::new(vec![1, 2], |node_builder| {
+    ///     node_builder.add_child(vec![3]);
+    /// });
+    ///
+    /// // Moves the Vec into the closure instead of cloning it
+    /// tree.root_mut().map_val_in_place(|mut val| {
+    ///     val.push(4);
+    ///     val
+    /// });
+    /// let old_val = tree.root_mut().into_children().next().unwrap().replace_val(vec![5]);
+    /// assert_eq!(old_val, [3]);
+    /// assert_eq!(tree.root_mut().take_val(), [1, 2, 4]);
+    /// assert_eq!(tree.iter_flattened().collect::<Vec<_>>(), [&vec![], &vec![5]]);
+    /// ```
+    #[inline]
+    pub fn map_val_in_place(&mut self, f: impl FnOnce(T) -> T) {
+        // Aborts the process when it's dropped, which only happens if f panics.
+        struct AbortOnPanic;
+
+        impl Drop for AbortOnPanic {
+            fn drop(&mut self) {
+                std::process::abort();
+            }
+        }
+
+        let val_ref = self.val_mut();
+        let guard = AbortOnPanic;
+        unsafe {
+            // The value is moved out, so until the new value is written, the node doesn't contain a valid value.
+            // If f panics, the guard aborts the process before the node can be used or dropped.
+            let val = std::ptr::read(val_ref);
+            std::ptr::write(val_ref, f(val));
+        }
+        std::mem::forget(guard);
+    }
+
+    /// Returns a mutable reference to the value of this node.
+    ///
+    /// The difference between this and [`NodeRefMut::val_mut`] is that this method
+    /// consumes self and is therefore able to return a broader lifetime.
+    #[inline(always)]
+    pub fn into_val_mut(self) -> &'t mut T {
+        debug_assert!(!self.slice.is_empty());
         unsafe { &mut self.slice.get_unchecked_mut(0).val }
     }
 
     /// Reborrow this [`NodeRefMut`] as a [`NodeRef`].
     #[inline(always)]
-    pub fn reborrow_shared(&self) -> NodeRef<T> {
-        NodeRef {
-            slice: &self.slice
+    pub fn reborrow_shared(&self) -> MetaNodeRef<'_, T, P> {
+        MetaNodeRef {
+            slice: self.slice
         }
     }
 
@@ -814,15 +2566,18 @@ impl<'t, T> NodeRefMut<'t, T> {
     }
 }
 
-impl<'t,T> From<NodeRefMut<'t,T>> for NodeRef<'t,T> {
+impl<'t, T, P: NodePolicy> From<MetaNodeRefMut<'t,T, P>> for MetaNodeRef<'t,T, P> {
     #[inline(always)]
-    fn from(val: NodeRefMut<'t,T>) -> Self {
-        NodeRef {
+    fn from(val: MetaNodeRefMut<'t,T, P>) -> Self {
+        MetaNodeRef {
             slice: val.slice
         }
     }
 }
 
+/// A [`MetaNodeListDrain`] of a [`PackedForest`] or [`PackedTree`](crate::PackedTree), where it is documented.
+pub type NodeListDrain<'t, T> = MetaNodeListDrain<'t, T, NoMeta>;
+
 /// A draining iterator of a list of nodes in a [`PackedForest`] or [`PackedTree`](crate::PackedTree).
 /// 
 /// When this iterator is dropped, the nodes remaining in the iterator will be dropped.
@@ -830,33 +2585,34 @@ impl<'t,T> From<NodeRefMut<'t,T>> for NodeRef<'t,T> {
 /// these nodes also will be leaked instead.
 /// 
 /// See [`PackedForest::drain_trees`] and [`PackedTree::drain`](crate::PackedTree::drain).
-pub struct NodeListDrain<'t, T> {
+pub struct MetaNodeListDrain<'t, T, P: NodePolicy> {
     // `remaining_nodes` is a slice containing (only) the remaining nodes in the iterator and all their descendants.
     // Normally slices don't own data, but not in this case.
     // The data is actually owned by the Vec that this NodeListDrain borrows, but it's out of the bounds of that Vec (but still inside its capacity).
     // Therefore the NodeListDrain can pretend like it owns the data in this slice, it can drop them in drop(),
     // and it can move out values using ptr::read (as long as it makes sure to update the slice to prevent a double drop)
-    remaining_nodes: &'t mut [NodeData<T>],
+    remaining_nodes: &'t mut [NodeData<T, P::Meta>],
+    len: P::Count, // the number of nodes in the iterator, if the policy counts them
 }
 
-impl<'t, T> Drop for NodeListDrain<'t, T> {
+impl<'t, T, P: NodePolicy> Drop for MetaNodeListDrain<'t, T, P> {
     #[inline(always)]
     fn drop(&mut self) {
         // read out all values in the slice and drop them
         for node in self.remaining_nodes.iter_mut() {
             unsafe {
-                let value: NodeData<T> = std::ptr::read(node);
+                let value: NodeData<T, P::Meta> = std::ptr::read(node);
                 std::mem::drop(value); // not strictly needed
             }
         }
     }
 }
 
-impl<'t, T> Iterator for NodeListDrain<'t, T> {
-    type Item = NodeDrain<'t, T>;
+impl<'t, T, P: NodePolicy> Iterator for MetaNodeListDrain<'t, T, P> {
+    type Item = MetaNodeDrain<'t, T, P>;
     #[inline(always)]
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(cur_node) = self.remaining_nodes.get(0) {
+        if let Some(cur_node) = self.remaining_nodes.first() {
             let cur_node_subtree_size = cur_node.subtree_size.get();
             unsafe {
                 // Split off the first cur_node_subtree_size elements from the slice.
@@ -869,11 +2625,14 @@ impl<'t, T> Iterator for NodeListDrain<'t, T> {
                 // Read out the data from this first node.
                 // No other slices contain this node anymore.
                 let val: T = std::ptr::read(&cur_node_data_ref.val);
+                let children_len = child_count::<P>(&cur_node_data_ref.meta);
+                self.len = self.len.sub(1);
 
-                Some(NodeDrain {
+                Some(MetaNodeDrain {
                     val,
-                    children: NodeListDrain {
-                        remaining_nodes: cur_node_children_slice
+                    children: MetaNodeListDrain {
+                        remaining_nodes: cur_node_children_slice,
+                        len: children_len,
                     }
                 })
             }
@@ -881,19 +2640,193 @@ impl<'t, T> Iterator for NodeListDrain<'t, T> {
             None
         }
     }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        size_hint(self.len)
+    }
 }
 
-impl<'t, T> NodeListDrain<'t, T> {
+impl<'t, T, P: ChildCountPolicy> ExactSizeIterator for MetaNodeListDrain<'t, T, P> {}
+
+impl<'t, T, P: NodePolicy> MetaNodeListDrain<'t, T, P> {
     /// Returns the number of nodes (also counting all descendants) remaining in this iterator in O(1) time.
     #[inline(always)]
     pub fn num_remaining_nodes_incl_descendants(&self) -> usize {
         self.remaining_nodes.len()
     }
+
+    /// Returns the number of nodes remaining in this iterator, not counting their descendants.
+    ///
+    /// This takes O(n) time, where n is the returned number, since only the remaining nodes themselves are visited.
+    #[inline]
+    pub fn num_remaining_nodes(&self) -> usize {
+        self.len.get().unwrap_or_else(|| count_sibling_nodes(self.remaining_nodes))
+    }
 }
 
+/// A [`MetaNodeDrain`] of a [`PackedForest`] or [`PackedTree`](crate::PackedTree), where it is documented.
+pub type NodeDrain<'t, T> = MetaNodeDrain<'t, T, NoMeta>;
+
 /// A node in a [`PackedForest`] or [`PackedTree`](crate::PackedTree) that is being drained.
 /// You can move out its fields `val` and `children` (which is a [`NodeListDrain`]) directly.
-pub struct NodeDrain<'t, T> {
+pub struct MetaNodeDrain<'t, T, P: NodePolicy> {
     pub val: T,
-    pub children: NodeListDrain<'t, T>
+    pub children: MetaNodeListDrain<'t, T, P>
+}
+
+/// The [`MetaIntoTrees`] iterator of a [`PackedForest`], where it is documented.
+pub type IntoTrees<T> = MetaIntoTrees<T, NoMeta>;
+
+/// An iterator that moves the trees out of a [`PackedForest`], see [`PackedForest::into_trees`].
+pub struct MetaIntoTrees<T, P: NodePolicy> {
+    remaining_nodes: std::vec::IntoIter<NodeData<T, P::Meta>>,
+    len: P::Count, // the number of trees in the iterator, if the policy counts them
+}
+
+impl<T, P: NodePolicy> Iterator for MetaIntoTrees<T, P> {
+    type Item = crate::MetaPackedTree<T, P>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let root = self.remaining_nodes.next()?;
+        let subtree_size = root.subtree_size.get();
+        let mut data = Vec::with_capacity(subtree_size);
+        data.push(root);
+        data.extend(self.remaining_nodes.by_ref().take(subtree_size - 1));
+        self.len = self.len.sub(1);
+        crate::MetaPackedTree::try_from_forest(MetaPackedForest { data, num_trees: P::Count::new(1) })
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        size_hint(self.len)
+    }
+}
+
+impl<T, P: ChildCountPolicy> ExactSizeIterator for MetaIntoTrees<T, P> {}
+
+impl<T, P: NodePolicy> MetaIntoTrees<T, P> {
+    /// Returns the number of nodes (also counting all descendants) remaining in this iterator in O(1) time.
+    #[inline(always)]
+    pub fn num_remaining_nodes_incl_descendants(&self) -> usize {
+        self.remaining_nodes.len()
+    }
+}
+
+// Checks that the given subtree sizes form a valid forest of `len` nodes,
+// i.e. that every subtree is non-empty and fits inside the subtree of its parent (or the forest).
+// Returns the index of the first invalid node otherwise.
+#[cfg(any(feature = "bytemuck", test))]
+fn validate_subtree_sizes(len: usize, subtree_size: impl Fn(usize) -> usize) -> Result<(), usize> {
+    // The indices one past the end of the subtrees of the ancestors of the current node
+    let mut ancestor_ends: Vec<usize> = Vec::new();
+    for index in 0..len {
+        while ancestor_ends.last() == Some(&index) {
+            ancestor_ends.pop();
+        }
+        let end = ancestor_ends.last().copied().unwrap_or(len);
+        let size = subtree_size(index);
+        if size == 0 || size > end - index {
+            return Err(index);
+        }
+        ancestor_ends.push(index + size);
+    }
+    Ok(())
+}
+
+// Checks that the bytes can be reinterpreted as a slice of valid nodes, except for their alignment.
+#[cfg(any(feature = "bytemuck", test))]
+fn validate_node_bytes<T: bytemuck::Pod>(bytes: &[u8]) -> Result<usize, crate::FromBytesError> {
+    let node_size = std::mem::size_of::<NodeData<T>>();
+    if !bytes.len().is_multiple_of(node_size) {
+        return Err(crate::FromBytesError::InvalidLength { len: bytes.len() });
+    }
+    let len = bytes.len() / node_size;
+    let offset = std::mem::offset_of!(NodeData<T>, subtree_size);
+    validate_subtree_sizes(len, |index| {
+        let start = index * node_size + offset;
+        bytemuck::pod_read_unaligned::<usize>(&bytes[start..(start + std::mem::size_of::<usize>())])
+    })
+    .map_err(|index| crate::FromBytesError::InvalidSubtreeSize { index })?;
+    Ok(len)
+}
+
+#[cfg(any(feature = "bytemuck", test))]
+impl<T: bytemuck::Pod> PackedForest<T> {
+    /// Returns the raw bytes of all the nodes in this forest, without copying them.
+    ///
+    /// The bytes can be turned back into a forest with [`try_from_bytes`](PackedForest::try_from_bytes),
+    /// or iterated without copying with [`NodeIter::try_from_bytes`]. They are only meant to be read
+    /// on a platform with the same endianness and pointer width, by the same version of this crate.
+    ///
+    /// Requires the `bytemuck` feature.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a [`NodeData<T>`] contains padding bytes, which happens when the size of `T`
+    /// is not a multiple of the alignment of `usize`.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedForest, NodeIter};
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree(1u64, |node_builder| {
+    ///     node_builder.add_child(2);
+    ///     node_builder.add_child(3);
+    /// });
+    ///
+    /// let bytes = forest.as_bytes().to_vec();
+    /// assert!(PackedForest::<u64>::try_from_bytes(&bytes).unwrap() == forest);
+    /// ```
+    pub fn as_bytes(&self) -> &[u8] {
+        assert_eq!(
+            std::mem::size_of::<NodeData<T>>(),
+            std::mem::size_of::<T>() + std::mem::size_of::<usize>(),
+            "NodeData contains padding bytes"
+        );
+        // There are no padding bytes (checked above), T is Pod, and NonZeroUsize has no uninitialized bytes,
+        // so all the bytes of the data are initialized.
+        unsafe {
+            std::slice::from_raw_parts(self.data.as_ptr() as *const u8, std::mem::size_of_val(self.data.as_slice()))
+        }
+    }
+
+    /// Creates a forest from bytes that were returned by [`as_bytes`](PackedForest::as_bytes), copying them.
+    ///
+    /// Unlike [`NodeIter::try_from_bytes`], the bytes don't need to be aligned.
+    /// Returns an error if the bytes don't describe a valid forest.
+    ///
+    /// Requires the `bytemuck` feature.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<PackedForest<T>, crate::FromBytesError> {
+        let len = validate_node_bytes::<T>(bytes)?;
+        let mut data: Vec<NodeData<T>> = Vec::with_capacity(len);
+        unsafe {
+            // Any bytes are a valid T since T is Pod, and the subtree sizes were validated above,
+            // so the copied nodes satisfy the invariants of PackedForest.
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), data.as_mut_ptr() as *mut u8, bytes.len());
+            data.set_len(len);
+        }
+        Ok(PackedForest::from_data(data))
+    }
+}
+
+#[cfg(any(feature = "bytemuck", test))]
+impl<'t, T: bytemuck::Pod> NodeIter<'t, T> {
+    /// Reinterprets bytes that were returned by [`PackedForest::as_bytes`] as an iterator over the trees, without copying them.
+    ///
+    /// Returns an error if the bytes aren't aligned for [`NodeData<T>`], or if they don't describe a valid forest.
+    ///
+    /// Requires the `bytemuck` feature.
+    pub fn try_from_bytes(bytes: &'t [u8]) -> Result<NodeIter<'t, T>, crate::FromBytesError> {
+        let len = validate_node_bytes::<T>(bytes)?;
+        if !(bytes.as_ptr() as usize).is_multiple_of(std::mem::align_of::<NodeData<T>>()) {
+            return Err(crate::FromBytesError::Misaligned);
+        }
+        // The bytes are aligned (checked above), any bytes are a valid T since T is Pod,
+        // and the subtree sizes were validated above, so they are valid nodes of a forest.
+        let remaining_nodes = unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const NodeData<T>, len) };
+        Ok(NodeIter { remaining_nodes, len: () })
+    }
 }
@@ -0,0 +1,95 @@
+// This file contains DepthIndex, which caches the depth of every node of a PackedForest.
+
+use crate::*;
+
+/// The depth of every node of a [`PackedForest`] (or [`PackedTree`]), to look up the depth of nodes in O(1) time.
+///
+/// [`PackedForest::depth_of`] finds the depth of a single node without any extra memory, but it has to walk down
+/// from the root of the tree. Building a [`DepthIndex`] takes O(n) time and memory, where n is the number of nodes
+/// in the forest, which pays off when the depth of many nodes is needed.
+///
+/// The index doesn't borrow the forest, and refers to nodes by their pre-order index (see [`PackedForest::get`]).
+/// It is only valid for the forest it was built from.
+///
+/// # Example
+/// ```
+/// use packed_tree::PackedTree;
+///
+/// let tree = PackedTree::new("root", |node_builder| {
+///     node_builder.build_child("a", |node_builder| {
+///         node_builder.add_child("a.1"); // index 2
+///     });
+///     node_builder.add_child("b"); // index 3
+/// });
+///
+/// let depth_index = tree.depth_index();
+/// assert_eq!(depth_index.depth_of(2), Some(2));
+/// assert_eq!(depth_index.depth_of(3), Some(1));
+/// assert_eq!(depth_index.depth_of(4), None);
+/// assert_eq!(depth_index.depth_of(2), tree.depth_of(2));
+/// ```
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct DepthIndex {
+    depths: Vec<usize>,
+}
+
+impl DepthIndex {
+    /// Build a [`DepthIndex`] for the given forest.
+    #[inline]
+    pub fn new<T>(forest: &PackedForest<T>) -> DepthIndex {
+        DepthIndex {
+            depths: forest.depths(),
+        }
+    }
+
+    /// Returns the depth of the node with the given index (0 for the roots of the trees),
+    /// or `None` if the index is out of bounds.
+    #[inline]
+    pub fn depth_of(&self, index: impl Into<NodeId>) -> Option<usize> {
+        self.depths.get(index.into().index()).copied()
+    }
+
+    /// Returns the depth of every node, indexed by pre-order index, like [`PackedForest::depths`].
+    #[inline(always)]
+    pub fn depths(&self) -> &[usize] {
+        &self.depths
+    }
+
+    /// Returns the number of nodes in the forest this index was built from.
+    #[inline(always)]
+    pub fn num_nodes(&self) -> usize {
+        self.depths.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_depth_of() {
+        let mut forest = PackedForest::new();
+        for tree in 0..2 {
+            forest.build_tree(tree, |node_builder| {
+                node_builder.build_child(1, |node_builder| {
+                    node_builder.add_child(2);
+                    node_builder.build_child(3, |node_builder| {
+                        node_builder.add_child(4);
+                    });
+                });
+                node_builder.add_child(5);
+            });
+        }
+        forest.add_single_node_tree(6);
+
+        let depth_index = forest.depth_index();
+        assert_eq!(depth_index.num_nodes(), forest.tot_num_nodes());
+        for (index, depth) in forest.depths().into_iter().enumerate() {
+            assert_eq!(forest.depth_of(index), Some(depth));
+            assert_eq!(depth_index.depth_of(NodeId::new(index)), Some(depth));
+        }
+        assert_eq!(forest.depth_of(forest.tot_num_nodes()), None);
+        assert_eq!(depth_index.depth_of(forest.tot_num_nodes()), None);
+        assert_eq!(PackedForest::<i32>::new().depth_of(0), None);
+    }
+}
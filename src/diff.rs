@@ -0,0 +1,397 @@
+// This file contains TreePatch, a representation of the differences between two PackedTrees.
+// Since the structure of a PackedTree can't be changed, a patch is applied by rebuilding the tree.
+
+use crate::*;
+
+use std::collections::HashMap;
+
+/// A single operation of a [`TreePatch`].
+///
+/// All indices are the pre-order indices of nodes in the *old* tree (see [`PackedTree::get`]).
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub enum PatchOp<T> {
+    /// Replace the value of the node with index `index` by `val`.
+    Update { index: usize, val: T },
+    /// Remove the node with index `index`, together with all its descendants.
+    Delete { index: usize },
+    /// Replace the node with index `index` and all its descendants by `subtree`.
+    Replace { index: usize, subtree: PackedTree<T> },
+    /// Insert `subtree` as a child of the node with index `parent`, such that its root becomes
+    /// child number `position` of that node in the patched tree.
+    Insert { parent: usize, position: usize, subtree: PackedTree<T> },
+}
+
+/// A list of operations that turns one [`PackedTree`] into another.
+///
+/// A `TreePatch` is usually created with [`TreePatch::diff`], and then applied with [`TreePatch::apply`],
+/// which builds a new tree, as the structure of a [`PackedTree`] can't be modified in place.
+///
+/// # Example
+/// ```
+/// use packed_tree::{PackedTree, TreePatch};
+///
+/// let old = PackedTree::new(1, |node_builder| {
+///     node_builder.add_child(2);
+///     node_builder.add_child(3);
+/// });
+/// let new = PackedTree::new(1, |node_builder| {
+///     node_builder.add_child(20);
+///     node_builder.add_child(3);
+///     node_builder.add_child(4);
+/// });
+///
+/// let patch = TreePatch::diff(&old, &new);
+/// assert_eq!(patch.ops().len(), 2);
+/// assert_eq!(patch.apply(&old), new);
+/// ```
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct TreePatch<T> {
+    ops: Vec<PatchOp<T>>,
+}
+
+impl<T> Default for TreePatch<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        TreePatch::new()
+    }
+}
+
+impl<T> TreePatch<T> {
+    /// Create a new, empty [`TreePatch`].
+    #[inline(always)]
+    pub fn new() -> TreePatch<T> {
+        TreePatch {
+            ops: Vec::new(),
+        }
+    }
+
+    /// Add an operation to this patch.
+    #[inline]
+    pub fn push(&mut self, op: PatchOp<T>) {
+        self.ops.push(op);
+    }
+
+    /// Returns the operations in this patch, in the order in which they were added.
+    #[inline(always)]
+    pub fn ops(&self) -> &[PatchOp<T>] {
+        &self.ops
+    }
+
+    /// Returns `true` if this patch contains no operations, i.e. if applying it doesn't change anything.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+impl<T: Clone + PartialEq> TreePatch<T> {
+    /// Compute a [`TreePatch`] that turns `old` into `new`.
+    ///
+    /// The diff is positional: the n-th child of a node in `old` is compared with the n-th child of
+    /// the corresponding node in `new`. Nodes whose values differ result in a [`PatchOp::Update`],
+    /// extra children in `old` result in a [`PatchOp::Delete`], and extra children in `new` result
+    /// in a [`PatchOp::Insert`].
+    pub fn diff(old: &PackedTree<T>, new: &PackedTree<T>) -> TreePatch<T> {
+        let mut patch = TreePatch::new();
+        // The pairs of nodes whose children are being compared, from the roots down.
+        // This doesn't recurse, so it works for trees of any depth.
+        let mut stack = vec![patch.diff_node(old.root(), 0, new.root())];
+        while let Some(frame) = stack.last_mut() {
+            match (frame.old_children.next(), frame.new_children.next()) {
+                (Some(old_child), Some(new_child)) => {
+                    let old_child_index = frame.old_child_index;
+                    frame.old_child_index += old_child.num_descendants_incl_self();
+                    frame.position += 1;
+                    let child_frame = patch.diff_node(old_child, old_child_index, new_child);
+                    stack.push(child_frame);
+                }
+                (Some(old_child), None) => {
+                    patch.ops.push(PatchOp::Delete {
+                        index: frame.old_child_index,
+                    });
+                    frame.old_child_index += old_child.num_descendants_incl_self();
+                    frame.position += 1;
+                }
+                (None, Some(new_child)) => {
+                    patch.ops.push(PatchOp::Insert {
+                        parent: frame.old_index,
+                        position: frame.position,
+                        subtree: PackedTree::new_by_ret_val(|node_builder| {
+                            for child in new_child.children() {
+                                node_builder.add_cloned_subtree(child);
+                            }
+                            new_child.val().clone()
+                        }),
+                    });
+                    frame.position += 1;
+                }
+                (None, None) => {
+                    stack.pop();
+                }
+            }
+        }
+        patch
+    }
+
+    // Compares the values of two nodes, and returns the frame to compare their children with.
+    fn diff_node<'t>(&mut self, old_node: NodeRef<'t, T>, old_index: usize, new_node: NodeRef<'t, T>) -> DiffFrame<'t, T> {
+        if old_node.val() != new_node.val() {
+            self.ops.push(PatchOp::Update {
+                index: old_index,
+                val: new_node.val().clone(),
+            });
+        }
+        DiffFrame {
+            old_index,
+            old_children: old_node.children(),
+            new_children: new_node.children(),
+            old_child_index: old_index + 1,
+            position: 0,
+        }
+    }
+}
+
+// A pair of nodes whose children are being compared by TreePatch::diff.
+struct DiffFrame<'t, T> {
+    old_index: usize,
+    old_children: NodeIter<'t, T>,
+    new_children: NodeIter<'t, T>,
+    // The index of the next old child
+    old_child_index: usize,
+    // The position of the next child in the patched tree
+    position: usize,
+}
+
+// The operations of a TreePatch, indexed by the node they apply to.
+struct PatchLookup<'p, T> {
+    updates: HashMap<usize, &'p T>,
+    // `None` if the subtree is deleted, the new subtree if it is replaced
+    subtrees: HashMap<usize, Option<&'p PackedTree<T>>>,
+    inserts: HashMap<usize, Vec<(usize, &'p PackedTree<T>)>>,
+}
+
+impl<'p, T: Clone> PatchLookup<'p, T> {
+    fn new(ops: &'p [PatchOp<T>]) -> PatchLookup<'p, T> {
+        let mut lookup = PatchLookup {
+            updates: HashMap::new(),
+            subtrees: HashMap::new(),
+            inserts: HashMap::new(),
+        };
+        for op in ops {
+            match op {
+                PatchOp::Update { index, val } => {
+                    lookup.updates.insert(*index, val);
+                }
+                PatchOp::Delete { index } => {
+                    lookup.subtrees.insert(*index, None);
+                }
+                PatchOp::Replace { index, subtree } => {
+                    lookup.subtrees.insert(*index, Some(subtree));
+                }
+                PatchOp::Insert { parent, position, subtree } => {
+                    lookup.inserts.entry(*parent).or_default().push((*position, subtree));
+                }
+            }
+        }
+        for inserts in lookup.inserts.values_mut() {
+            // sort_by_key is stable, so inserts at the same position keep their order
+            inserts.sort_by_key(|(position, _)| *position);
+        }
+        lookup
+    }
+
+    // Adds the patched version of old_node and its descendants at the end of `patched`, as a new tree,
+    // or nothing if old_node is deleted. This doesn't recurse, so it works for trees of any depth.
+    fn add_patched<'t>(&self, old_node: NodeRef<'t, T>, old_index: usize, patched: &mut PackedForest<T>) {
+        let mut stack: Vec<ApplyFrame<'t, '_, T>> = self.open_node(old_node, old_index, patched).into_iter().collect();
+        while let Some(frame) = stack.last_mut() {
+            let position = frame.position;
+            if let Some((_, subtree)) = frame.inserts.next_if(|(insert_position, _)| *insert_position <= position) {
+                patched.push_cloned_subtree(subtree.root().raw_slice());
+                frame.position += 1;
+            } else if let Some(old_child) = frame.old_children.next() {
+                let old_child_index = frame.old_child_index;
+                frame.old_child_index += old_child.num_descendants_incl_self();
+                if !matches!(self.subtrees.get(&old_child_index), Some(None)) {
+                    frame.position += 1;
+                }
+                if let Some(child_frame) = self.open_node(old_child, old_child_index, patched) {
+                    stack.push(child_frame);
+                }
+            } else {
+                // Inserts past the last child add the subtree as the last child
+                for (_, subtree) in &mut frame.inserts {
+                    patched.push_cloned_subtree(subtree.root().raw_slice());
+                }
+                patched.close_node(frame.patched_index);
+                stack.pop();
+            }
+        }
+    }
+
+    // Adds the patched version of old_node to `patched`. If its children still have to be added,
+    // it is left open and the frame to add them with is returned.
+    fn open_node<'t, 'l>(&'l self, old_node: NodeRef<'t, T>, old_index: usize, patched: &mut PackedForest<T>) -> Option<ApplyFrame<'t, 'l, T>> {
+        match self.subtrees.get(&old_index) {
+            Some(None) => None,
+            Some(Some(subtree)) => {
+                patched.push_cloned_subtree(subtree.root().raw_slice());
+                None
+            }
+            None => Some(ApplyFrame {
+                patched_index: patched.push_open_node(self.val(old_node, old_index)),
+                old_children: old_node.children(),
+                old_child_index: old_index + 1,
+                position: 0,
+                inserts: self.inserts.get(&old_index).map(|inserts| &inserts[..]).unwrap_or(&[]).iter().peekable(),
+            }),
+        }
+    }
+
+    fn val(&self, old_node: NodeRef<T>, old_index: usize) -> T {
+        self.updates.get(&old_index).copied().unwrap_or_else(|| old_node.val()).clone()
+    }
+}
+
+// A node of the patched tree whose children are being added by PatchLookup::add_patched.
+struct ApplyFrame<'t, 'l, T> {
+    // The index of the node in the patched forest
+    patched_index: usize,
+    old_children: NodeIter<'t, T>,
+    // The index of the next old child
+    old_child_index: usize,
+    // The position of the next child in the patched tree
+    position: usize,
+    // The inserts that haven't been added yet
+    inserts: std::iter::Peekable<std::slice::Iter<'l, (usize, &'l PackedTree<T>)>>,
+}
+
+impl<T: Clone> TreePatch<T> {
+    /// Apply this patch to `old`, returning the patched tree.
+    ///
+    /// Operations that refer to indices that aren't in `old` are ignored.
+    /// Inserts at a position past the last child of a node add the subtree as the last child.
+    /// Multiple inserts at the same position end up in the order in which they were added to the patch.
+    /// If a node is both deleted and replaced, or replaced more than once, the operation that was added last wins.
+    /// Other operations on the descendants of a deleted or replaced node are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the patch contains a [`PatchOp::Delete`] of the root node (index 0) that isn't overridden
+    /// by a later [`PatchOp::Replace`], since a [`PackedTree`] can't be empty.
+    pub fn apply(&self, old: &PackedTree<T>) -> PackedTree<T> {
+        PackedTree::try_from_forest(self.apply_to_forest(old.as_ref())).expect("can't delete the root of a PackedTree")
+    }
+
+    /// Apply this patch to all the trees of `old`, returning the patched forest. Deleting the root of a tree
+    /// removes the whole tree from the forest. See [`apply`](TreePatch::apply) for how the operations are combined.
+    ///
+    /// This is what [`EditList::apply`] does.
+    pub fn apply_to_forest(&self, old: &PackedForest<T>) -> PackedForest<T> {
+        let lookup = PatchLookup::new(&self.ops);
+        let mut patched = PackedForest::with_capacity(old.tot_num_nodes());
+        let mut old_index = 0;
+        for old_tree in old.iter_trees() {
+            lookup.add_patched(old_tree, old_index, &mut patched);
+            old_index += old_tree.num_descendants_incl_self();
+        }
+        patched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_tree(vals: &[i32], extra_grandchild: bool) -> PackedTree<i32> {
+        PackedTree::new(0, |node_builder| {
+            for (i, val) in vals.iter().enumerate() {
+                node_builder.build_child(*val, |node_builder| {
+                    if i == 0 && extra_grandchild {
+                        node_builder.add_child(100);
+                    }
+                });
+            }
+        })
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let tree = build_tree(&[1, 2, 3], true);
+        let patch = TreePatch::diff(&tree, &tree.clone());
+        assert!(patch.is_empty());
+        assert_eq!(patch.apply(&tree), tree);
+    }
+
+    #[test]
+    fn test_diff_roundtrip() {
+        let trees = [
+            build_tree(&[], false),
+            build_tree(&[1], true),
+            build_tree(&[1, 2, 3], false),
+            build_tree(&[1, 5, 3, 4], true),
+            build_tree(&[7], false),
+        ];
+        for old in &trees {
+            for new in &trees {
+                let patch = TreePatch::diff(old, new);
+                assert_eq!(&patch.apply(old), new);
+            }
+        }
+    }
+
+    #[test]
+    fn test_apply_manual_patch() {
+        let old = build_tree(&[1, 2, 3], false);
+        let mut patch = TreePatch::new();
+        patch.push(PatchOp::Delete { index: 2 });
+        patch.push(PatchOp::Insert { parent: 0, position: 0, subtree: PackedTree::new(9, |_| {}) });
+        patch.push(PatchOp::Update { index: 3, val: 30 });
+        let new = patch.apply(&old);
+        assert_eq!(new.iter_flattened().copied().collect::<Vec<_>>(), [0, 9, 1, 30]);
+    }
+
+    #[test]
+    fn test_apply_replace() {
+        let old = build_tree(&[1, 2, 3], true);
+        let mut patch = TreePatch::new();
+        patch.push(PatchOp::Update { index: 2, val: 50 });
+        patch.push(PatchOp::Replace { index: 1, subtree: PackedTree::new(8, |node_builder| { node_builder.add_child(9); }) });
+        patch.push(PatchOp::Delete { index: 3 });
+        assert_eq!(patch.apply(&old).iter_flattened().copied().collect::<Vec<_>>(), [0, 8, 9, 3]);
+
+        // Replacing the root is allowed, even after deleting it
+        let mut patch = TreePatch::new();
+        patch.push(PatchOp::Delete { index: 0 });
+        patch.push(PatchOp::Replace { index: 0, subtree: PackedTree::new(7, |_| {}) });
+        assert_eq!(patch.apply(&old).iter_flattened().copied().collect::<Vec<_>>(), [7]);
+    }
+
+    #[test]
+    fn test_deep_chain() {
+        // A chain of 1 million nodes that ends in the given leaves
+        let chain = |leaves: &[i32]| {
+            let mut stack = TreeBuilderStack::new();
+            for i in 0..1_000_000 {
+                stack.push(i);
+            }
+            for leaf in leaves {
+                stack.leaf(*leaf);
+            }
+            PackedTree::try_from_forest(stack.finish()).unwrap()
+        };
+        let old = chain(&[-1]);
+        let new = chain(&[-2, -3]);
+        let patch = TreePatch::diff(&old, &new);
+        assert_eq!(patch.ops().len(), 2);
+        assert_eq!(patch.apply(&old), new);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_apply_delete_root() {
+        let mut patch = TreePatch::new();
+        patch.push(PatchOp::Delete { index: 0 });
+        patch.apply(&build_tree(&[1], false));
+    }
+}
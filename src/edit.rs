@@ -0,0 +1,324 @@
+// This file contains TreeEditor, which modifies a PackedTree by rebuilding it,
+// letting a callback decide what happens to each node along the way.
+
+use crate::*;
+
+enum SubtreeEdit<T> {
+    Keep,
+    Delete,
+    Replace(PackedTree<T>),
+}
+
+/// The changes to make to a single node when editing a tree with a [`TreeEditor`].
+///
+/// By default, a node and its subtree are kept as they are.
+pub struct NodeEdit<T> {
+    val: Option<T>,
+    subtree: SubtreeEdit<T>,
+    before: Vec<PackedTree<T>>,
+    after: Vec<PackedTree<T>>,
+    appended_children: Vec<PackedTree<T>>,
+}
+
+impl<T> Default for NodeEdit<T> {
+    #[inline]
+    fn default() -> Self {
+        NodeEdit {
+            val: None,
+            subtree: SubtreeEdit::Keep,
+            before: Vec::new(),
+            after: Vec::new(),
+            appended_children: Vec::new(),
+        }
+    }
+}
+
+impl<T> NodeEdit<T> {
+    /// Replace the value of the node, keeping its children.
+    #[inline]
+    pub fn set_val(&mut self, val: T) {
+        self.val = Some(val);
+    }
+
+    /// Remove the node and all of its descendants.
+    ///
+    /// The descendants of the node won't be visited by the [`TreeEditor`].
+    #[inline]
+    pub fn delete(&mut self) {
+        self.subtree = SubtreeEdit::Delete;
+    }
+
+    /// Replace the node and all of its descendants by the given tree.
+    ///
+    /// The descendants of the node won't be visited by the [`TreeEditor`].
+    #[inline]
+    pub fn replace(&mut self, tree: PackedTree<T>) {
+        self.subtree = SubtreeEdit::Replace(tree);
+    }
+
+    /// Insert the given tree as a sibling right before the node.
+    /// Trees inserted by multiple calls end up in the order of the calls.
+    #[inline]
+    pub fn insert_before(&mut self, tree: PackedTree<T>) {
+        self.before.push(tree);
+    }
+
+    /// Insert the given tree as a sibling right after the node.
+    /// Trees inserted by multiple calls end up in the order of the calls.
+    #[inline]
+    pub fn insert_after(&mut self, tree: PackedTree<T>) {
+        self.after.push(tree);
+    }
+
+    /// Add the given tree as the last child of the node, after its existing children.
+    /// This has no effect if the node is deleted or replaced.
+    #[inline]
+    pub fn append_child(&mut self, tree: PackedTree<T>) {
+        self.appended_children.push(tree);
+    }
+
+    #[inline]
+    fn is_unchanged(&self) -> bool {
+        self.val.is_none()
+            && matches!(self.subtree, SubtreeEdit::Keep)
+            && self.before.is_empty()
+            && self.after.is_empty()
+            && self.appended_children.is_empty()
+    }
+}
+
+/// Modifies a [`PackedTree`] by rebuilding it, see [`PackedTree::edit`].
+pub struct TreeEditor<T> {
+    tree: PackedTree<T>,
+}
+
+impl<T> PackedTree<T> {
+    /// Returns a [`TreeEditor`] that can be used to create a modified version of this tree.
+    ///
+    /// Since the nodes of a [`PackedTree`] are packed together, adding or removing nodes requires rebuilding the tree.
+    /// The [`TreeEditor`] does this in O(n) time, moving the values of the existing nodes into the new tree.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let tree = PackedTree::new(0, |node_builder| {
+    ///     node_builder.build_child(1, |node_builder| {
+    ///         node_builder.add_child(2);
+    ///     });
+    ///     node_builder.add_child(3);
+    /// });
+    ///
+    /// let edited = tree.edit().apply(|node, edit| {
+    ///     match *node.val() {
+    ///         1 => edit.delete(),
+    ///         3 => {
+    ///             edit.set_val(30);
+    ///             edit.insert_after(PackedTree::new(4, |_| {}));
+    ///         }
+    ///         _ => {}
+    ///     }
+    /// });
+    ///
+    /// let expected = PackedTree::new(0, |node_builder| {
+    ///     node_builder.add_child(30);
+    ///     node_builder.add_child(4);
+    /// });
+    /// assert_eq!(edited, expected);
+    /// ```
+    #[inline(always)]
+    pub fn edit(self) -> TreeEditor<T> {
+        TreeEditor { tree: self }
+    }
+}
+
+// A node of the edited tree whose descendants are still being added, see TreeEditor::apply.
+struct OpenNode<T> {
+    // The index of the node in the edited forest.
+    index: usize,
+    // The index one past the last descendant of the node in the original tree.
+    end: usize,
+    appended_children: Vec<PackedTree<T>>,
+    after: Vec<PackedTree<T>>,
+}
+
+// Add the appended children of the node, and then the trees that are inserted after it as its siblings.
+fn close_open_node<T>(forest: &mut PackedForest<T>, node: OpenNode<T>) {
+    forest.extend(node.appended_children);
+    forest.close_node(node.index);
+    forest.extend(node.after);
+}
+
+impl<T> TreeEditor<T> {
+    /// Create the edited tree.
+    ///
+    /// `edit_fn` is called on every node of the original tree in pre-order, except for the descendants of nodes
+    /// that are deleted or replaced. It is passed the node and a [`NodeEdit`] on which it can specify how to change that node.
+    ///
+    /// # Panics
+    /// Panics if `edit_fn` deletes the root, or inserts siblings before or after the root,
+    /// since the result wouldn't be a single tree. Replacing the root is allowed.
+    pub fn apply(self, mut edit_fn: impl FnMut(NodeRef<'_, T>, &mut NodeEdit<T>)) -> PackedTree<T> {
+        let num_nodes = self.tree.tot_num_nodes();
+        let mut edits: Vec<Option<NodeEdit<T>>> = Vec::with_capacity(num_nodes);
+        edits.resize_with(num_nodes, || None);
+
+        // First decide on the edits, while the original tree can still be inspected.
+        let root = self.tree.root();
+        let mut index = 0;
+        while let Some(node) = root.descendant_at_offset(index) {
+            let mut edit = NodeEdit::default();
+            edit_fn(node, &mut edit);
+            let next_index = match edit.subtree {
+                SubtreeEdit::Keep => index + 1,
+                _ => index + node.num_descendants_incl_self(),
+            };
+            if !edit.is_unchanged() {
+                edits[index] = Some(edit);
+            }
+            index = next_index;
+        }
+
+        // Then move the values of the original tree into the new tree, in pre-order.
+        // The nodes of the new tree are added one by one at the end of a forest, and a node is closed
+        // once all of its descendants have been added, so this works for trees of any depth.
+        let root_edit = edits[0].take().unwrap_or_default();
+        assert!(root_edit.before.is_empty() && root_edit.after.is_empty(), "can't insert siblings of the root of a tree");
+        match root_edit.subtree {
+            SubtreeEdit::Keep => {}
+            SubtreeEdit::Delete => panic!("can't delete the root of a tree"),
+            SubtreeEdit::Replace(tree) => return tree,
+        }
+        edits[0] = Some(root_edit);
+
+        let subtree_sizes: Vec<usize> = self.tree.raw_data().iter().map(|node_data| node_data.subtree_size().get()).collect();
+        let mut forest = PackedForest::from(self.tree);
+        let mut edited = PackedForest::with_capacity(num_nodes);
+        let mut open_nodes: Vec<OpenNode<T>> = Vec::new();
+        // The descendants of a deleted or replaced node are skipped up to this index in the original tree
+        let mut skip_until = 0;
+        for (index, val) in forest.drain_flattened().enumerate() {
+            while open_nodes.last().is_some_and(|node| node.end <= index) {
+                close_open_node(&mut edited, open_nodes.pop().unwrap());
+            }
+            if index < skip_until {
+                continue;
+            }
+
+            let edit = edits[index].take().unwrap_or_default();
+            edited.extend(edit.before);
+            let end = index + subtree_sizes[index];
+            match edit.subtree {
+                SubtreeEdit::Keep => open_nodes.push(OpenNode {
+                    index: edited.push_open_node(edit.val.unwrap_or(val)),
+                    end,
+                    appended_children: edit.appended_children,
+                    after: edit.after,
+                }),
+                SubtreeEdit::Delete => {
+                    skip_until = end;
+                    edited.extend(edit.after);
+                }
+                SubtreeEdit::Replace(tree) => {
+                    skip_until = end;
+                    edited += tree;
+                    edited.extend(edit.after);
+                }
+            }
+        }
+        while let Some(node) = open_nodes.pop() {
+            close_open_node(&mut edited, node);
+        }
+        PackedTree::try_from_forest(edited).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_tree() -> PackedTree<String> {
+        PackedTree::new("root".to_string(), |node_builder| {
+            node_builder.build_child("a".to_string(), |node_builder| {
+                node_builder.add_child("a1".to_string());
+                node_builder.add_child("a2".to_string());
+            });
+            node_builder.build_child("b".to_string(), |node_builder| {
+                node_builder.add_child("b1".to_string());
+            });
+            node_builder.add_child("c".to_string());
+        })
+    }
+
+    fn leaf(val: &str) -> PackedTree<String> {
+        PackedTree::new(val.to_string(), |_| {})
+    }
+
+    fn vals(tree: &PackedTree<String>) -> Vec<&str> {
+        tree.iter_flattened().map(|val| val.as_str()).collect()
+    }
+
+    #[test]
+    fn test_edit_unchanged() {
+        let tree = build_tree();
+        let mut visited = Vec::new();
+        let edited = build_tree().edit().apply(|node, _| visited.push(node.val().clone()));
+        assert_eq!(edited, tree);
+        assert_eq!(visited.len(), tree.tot_num_nodes());
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut visited = Vec::new();
+        let edited = build_tree().edit().apply(|node, edit| {
+            visited.push(node.val().clone());
+            match node.val().as_str() {
+                "a" => edit.delete(),
+                "b" => {
+                    edit.set_val("B".to_string());
+                    edit.insert_before(leaf("before b"));
+                    edit.append_child(leaf("b2"));
+                }
+                "b1" => edit.replace(PackedTree::new("x".to_string(), |node_builder| {
+                    node_builder.add_child("y".to_string());
+                })),
+                "c" => {
+                    edit.insert_after(leaf("d"));
+                    edit.insert_after(leaf("e"));
+                }
+                "root" => edit.append_child(leaf("f")),
+                _ => {}
+            }
+        });
+        assert_eq!(visited, ["root", "a", "b", "b1", "c"]);
+        assert_eq!(vals(&edited), ["root", "before b", "B", "x", "y", "b2", "c", "d", "e", "f"]);
+        assert_eq!(edited.get(2).unwrap().children().count(), 2);
+    }
+
+    #[test]
+    fn test_edit_root() {
+        let edited = build_tree().edit().apply(|node, edit| {
+            if node.val() == "root" {
+                edit.replace(leaf("new root"));
+            }
+        });
+        assert_eq!(edited, leaf("new root"));
+    }
+
+    #[test]
+    fn test_edit_deep_chain() {
+        let chain = |len: u32| PackedTree::unfold_iterative(0, |n| (*n, if *n + 1 < len { vec![n + 1] } else { vec![] }));
+        let edited = chain(1_000_000).edit().apply(|node, edit| {
+            if *node.val() == 999_999 {
+                edit.append_child(PackedTree::new(1_000_000, |_| {}));
+            }
+        });
+        assert_eq!(edited, chain(1_000_001));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_edit_delete_root() {
+        build_tree().edit().apply(|_, edit| edit.delete());
+    }
+}
@@ -0,0 +1,173 @@
+// This file contains EditList, which collects structural changes to a PackedForest
+// and then makes all of them at once by rebuilding the forest, like applying a TreePatch.
+
+use crate::*;
+
+/// A list of changes to a [`PackedForest`], that are all made at once by [`apply`](EditList::apply).
+///
+/// Nodes are addressed by their pre-order index in the original forest (see [`PackedForest::get`]),
+/// so indices stay valid no matter which other edits are added. Since the nodes of a [`PackedForest`] are packed together,
+/// changing the structure of a forest requires rebuilding it. Collecting the edits first means that
+/// the forest is rebuilt only once, in O(n) time, instead of once per edit.
+///
+/// The edits are stored as a [`TreePatch`], see [`as_patch`](EditList::as_patch).
+///
+/// # Example
+/// ```
+/// use packed_tree::{EditList, PackedForest, PackedTree};
+///
+/// let mut forest = PackedForest::new();
+/// forest.build_tree(0, |node_builder| {
+///     node_builder.add_child(1);
+///     node_builder.add_child(2);
+/// });
+/// forest.add_single_node_tree(3);
+///
+/// let mut edits = EditList::new();
+/// edits.delete_subtree(1);
+/// edits.update_value(2, 20);
+/// edits.insert_child_at(0, 0, PackedTree::new(4, |_| {}));
+/// edits.replace_subtree(3, PackedTree::new(30, |node_builder| {
+///     node_builder.add_child(31);
+/// }));
+///
+/// let edited = edits.apply(&forest);
+/// assert_eq!(edited.iter_flattened().copied().collect::<Vec<_>>(), [0, 4, 20, 30, 31]);
+/// assert_eq!(edited.iter_trees().count(), 2);
+/// ```
+pub struct EditList<T> {
+    patch: TreePatch<T>,
+}
+
+impl<T> Default for EditList<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        EditList::new()
+    }
+}
+
+impl<T> EditList<T> {
+    /// Create a new, empty [`EditList`].
+    #[inline(always)]
+    pub fn new() -> EditList<T> {
+        EditList {
+            patch: TreePatch::new(),
+        }
+    }
+
+    /// Replace the value of the node with index `index` by `val`, keeping its children.
+    #[inline]
+    pub fn update_value(&mut self, index: usize, val: T) {
+        self.patch.push(PatchOp::Update { index, val });
+    }
+
+    /// Replace the node with index `index` and all of its descendants by `tree`.
+    ///
+    /// Other edits of the node or its descendants are ignored.
+    #[inline]
+    pub fn replace_subtree(&mut self, index: usize, tree: PackedTree<T>) {
+        self.patch.push(PatchOp::Replace { index, subtree: tree });
+    }
+
+    /// Remove the node with index `index` together with all of its descendants.
+    /// If the node is the root of a tree, the whole tree is removed from the forest.
+    ///
+    /// Other edits of the node or its descendants are ignored.
+    #[inline]
+    pub fn delete_subtree(&mut self, index: usize) {
+        self.patch.push(PatchOp::Delete { index });
+    }
+
+    /// Insert `tree` as a child of the node with index `parent`, such that its root becomes
+    /// child number `position` of that node in the edited forest, like [`PatchOp::Insert`].
+    ///
+    /// Inserts at a position past the last child add the tree as the last child.
+    /// Multiple inserts at the same position end up in the order in which they were added.
+    #[inline]
+    pub fn insert_child_at(&mut self, parent: usize, position: usize, tree: PackedTree<T>) {
+        self.patch.push(PatchOp::Insert { parent, position, subtree: tree });
+    }
+
+    /// Returns the number of edits in this list.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.patch.ops().len()
+    }
+
+    /// Returns `true` if this list contains no edits, i.e. if applying it doesn't change anything.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.patch.is_empty()
+    }
+
+    /// Returns the edits in this list as a [`TreePatch`].
+    #[inline(always)]
+    pub fn as_patch(&self) -> &TreePatch<T> {
+        &self.patch
+    }
+}
+
+impl<T: Clone> EditList<T> {
+    /// Returns a copy of `forest` with all the edits in this list made to it, see [`TreePatch::apply_to_forest`].
+    ///
+    /// If a node is both deleted and replaced, or replaced more than once, the edit that was added last wins.
+    /// The same goes for multiple updates of the value of a node. Edits that refer to indices that aren't in
+    /// `forest` are ignored.
+    #[inline]
+    pub fn apply(&self, forest: &PackedForest<T>) -> PackedForest<T> {
+        self.patch.apply_to_forest(forest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_forest() -> PackedForest<i32> {
+        let mut forest = PackedForest::new();
+        forest.build_tree(0, |node_builder| {
+            node_builder.build_child(1, |node_builder| {
+                node_builder.add_child(2);
+            });
+            node_builder.add_child(3);
+        });
+        forest.build_tree(4, |node_builder| {
+            node_builder.add_child(5);
+        });
+        forest
+    }
+
+    fn leaf(val: i32) -> PackedTree<i32> {
+        PackedTree::new(val, |_| {})
+    }
+
+    #[test]
+    fn test_edit_list() {
+        let forest = build_forest();
+        let edits = EditList::new();
+        assert!(edits.is_empty());
+        assert!(edits.apply(&forest) == forest);
+
+        let mut edits = EditList::new();
+        edits.update_value(2, 20);
+        edits.insert_child_at(0, 1, leaf(6));
+        edits.insert_child_at(0, 1, leaf(7));
+        edits.insert_child_at(0, 10, leaf(8));
+        edits.replace_subtree(3, leaf(9));
+        edits.delete_subtree(3);
+        edits.replace_subtree(3, leaf(30));
+        edits.insert_child_at(5, 0, leaf(50));
+        edits.update_value(100, 0);
+        assert_eq!(edits.len(), 9);
+        let edited = edits.apply(&forest);
+        assert_eq!(edited.iter_flattened().copied().collect::<Vec<_>>(), [0, 1, 20, 6, 7, 30, 8, 4, 5, 50]);
+        assert_eq!(edited.iter_trees().next().unwrap().children().count(), 5);
+
+        // Edits inside a deleted tree are ignored
+        let mut edits = EditList::new();
+        edits.update_value(2, 20);
+        edits.delete_subtree(0);
+        edits.replace_subtree(4, leaf(40));
+        assert_eq!(edits.apply(&forest).iter_flattened().copied().collect::<Vec<_>>(), [40]);
+    }
+}
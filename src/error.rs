@@ -0,0 +1,389 @@
+// This file contains the error types returned by fallible operations on PackedForest and related types.
+
+use std::fmt::{Display, Formatter};
+use std::io;
+
+/// The error returned by [`PackedForest::swap_subtrees`](crate::PackedForest::swap_subtrees) when the subtrees can't be swapped.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum SwapError {
+    /// There is no node with the given index.
+    IndexOutOfBounds {
+        /// The index that was out of bounds.
+        index: usize,
+    },
+    /// One of the subtrees contains the other.
+    Overlapping,
+    /// The subtrees don't have the same number of nodes.
+    SizeMismatch {
+        /// The number of nodes in the first subtree.
+        a_size: usize,
+        /// The number of nodes in the second subtree.
+        b_size: usize,
+    },
+}
+
+impl Display for SwapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SwapError::IndexOutOfBounds { index } => write!(f, "no node with index {}", index),
+            SwapError::Overlapping => write!(f, "the subtrees overlap"),
+            SwapError::SizeMismatch { a_size, b_size } => {
+                write!(f, "the subtrees have different sizes ({} and {} nodes)", a_size, b_size)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SwapError {}
+
+/// The error returned when adding a node to a fixed-capacity forest, like an [`ArrayPackedForest`](crate::ArrayPackedForest), that is full,
+/// or when adding nodes would exceed the maximum number of nodes that fit in memory
+/// (see [`NodeBuilder::try_add_leaf_children_from_slice`](crate::NodeBuilder::try_add_leaf_children_from_slice)).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct CapacityError {
+    /// The maximum number of nodes.
+    pub capacity: usize,
+}
+
+impl Display for CapacityError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the capacity of {} nodes was exceeded", self.capacity)
+    }
+}
+
+impl std::error::Error for CapacityError {}
+
+/// The error returned when adding a node that would be deeper than the maximum depth that was set with
+/// [`NodeBuilder::set_max_depth`](crate::NodeBuilder::set_max_depth).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct DepthError {
+    /// The maximum depth.
+    pub max_depth: usize,
+}
+
+impl Display for DepthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the maximum depth of {} was exceeded", self.max_depth)
+    }
+}
+
+impl std::error::Error for DepthError {}
+
+/// The error returned by [`NodeBuilder::try_add_leaf_children_from_slice`](crate::NodeBuilder::try_add_leaf_children_from_slice)
+/// when the children can't be added.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum AddNodesError {
+    /// The total number of nodes would exceed the maximum number of nodes that fit in memory.
+    Capacity(CapacityError),
+    /// The nodes would be deeper than the maximum depth.
+    Depth(DepthError),
+}
+
+impl Display for AddNodesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddNodesError::Capacity(err) => Display::fmt(err, f),
+            AddNodesError::Depth(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl std::error::Error for AddNodesError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AddNodesError::Capacity(err) => Some(err),
+            AddNodesError::Depth(err) => Some(err),
+        }
+    }
+}
+
+impl From<CapacityError> for AddNodesError {
+    #[inline]
+    fn from(err: CapacityError) -> Self {
+        AddNodesError::Capacity(err)
+    }
+}
+
+impl From<DepthError> for AddNodesError {
+    #[inline]
+    fn from(err: DepthError) -> Self {
+        AddNodesError::Depth(err)
+    }
+}
+
+/// The error returned when a forest can't be converted into a tree because it doesn't have exactly one tree,
+/// e.g. by the [`TryFrom`](std::convert::TryFrom) implementation of [`PackedTree`](crate::PackedTree).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum TreeConvertError {
+    /// The forest has no trees.
+    Empty,
+    /// The forest has more than one tree.
+    MultipleRoots {
+        /// The number of trees in the forest.
+        count: usize,
+    },
+}
+
+impl Display for TreeConvertError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeConvertError::Empty => write!(f, "the forest has no trees, but a tree needs exactly one root"),
+            TreeConvertError::MultipleRoots { count } => write!(f, "the forest has {} trees, but a tree needs exactly one root", count),
+        }
+    }
+}
+
+impl std::error::Error for TreeConvertError {}
+
+/// The error returned when a forest can't be built from a description of its nodes,
+/// see [`PackedForest::from_depth_values`](crate::PackedForest::from_depth_values).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum BuildError {
+    /// A node is more than one level deeper than the node before it, or the first node doesn't have depth 0.
+    InvalidDepth {
+        /// The index of the node, in the order in which the nodes were given.
+        index: usize,
+        /// The depth of the node.
+        depth: usize,
+        /// The largest depth the node could have had, which is one more than the depth of the node before it.
+        max_depth: usize,
+    },
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::InvalidDepth { index, depth, max_depth } => {
+                write!(f, "the node with index {} has depth {}, but its depth can be at most {}", index, depth, max_depth)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// The error returned by [`TreeWriter::finish`](crate::TreeWriter::finish) and
+/// [`TreeBuilderStack::try_finish`](crate::TreeBuilderStack::try_finish) when the nodes that were opened
+/// and closed don't match up.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum BalanceError {
+    /// [`end_node`](crate::TreeWriter::end_node) was called while no node was open.
+    UnmatchedEnd {
+        /// The number of nodes that had been written when the first unmatched call was made.
+        num_nodes: usize,
+    },
+    /// Nodes were still open when the writer was finished.
+    UnclosedNodes {
+        /// The number of nodes that were still open.
+        count: usize,
+    },
+}
+
+impl Display for BalanceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BalanceError::UnmatchedEnd { num_nodes } => {
+                write!(f, "a node was ended while no node was open, after {} nodes were written", num_nodes)
+            }
+            BalanceError::UnclosedNodes { count } => write!(f, "{} nodes were never ended", count),
+        }
+    }
+}
+
+impl std::error::Error for BalanceError {}
+
+/// The error returned when indented text can't be parsed as a tree,
+/// see [`PackedTree::parse_indented`](crate::PackedTree::parse_indented).
+///
+/// Lines are numbered from 1.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum ParseError {
+    /// The indentation of a line is not a whole number of indentation units.
+    InvalidIndentation {
+        /// The number of the line.
+        line: usize,
+    },
+    /// A line is indented more than one level deeper than the line before it, or the first line is indented.
+    InvalidDepth {
+        /// The number of the line.
+        line: usize,
+        /// The depth of the line, i.e. its number of indentation units.
+        depth: usize,
+        /// The largest depth the line could have had.
+        max_depth: usize,
+    },
+    /// The lines don't form exactly one tree: there are no lines that aren't empty,
+    /// or there is more than one line that isn't indented.
+    NotATree {
+        /// Why the parsed forest isn't a tree.
+        error: TreeConvertError,
+        /// The number of the second line that isn't indented, if there is more than one tree.
+        second_root_line: Option<usize>,
+    },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::InvalidIndentation { line } => write!(f, "line {} has invalid indentation", line),
+            ParseError::InvalidDepth { line, depth, max_depth } => {
+                write!(f, "line {} is indented {} levels, but it can be indented at most {} levels", line, depth, max_depth)
+            }
+            ParseError::NotATree { error, second_root_line: None } => Display::fmt(error, f),
+            ParseError::NotATree { error, second_root_line: Some(line) } => {
+                write!(f, "{} (line {} is the second root)", error, line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::NotATree { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// The error returned when a forest can't be decoded from its nodes in pre-order together with their subtree sizes,
+/// e.g. by [`PackedForest::try_from_flat_nodes`](crate::PackedForest::try_from_flat_nodes). When the `serde` feature is enabled,
+/// deserializing a [`PackedForest`](crate::PackedForest) from a format that isn't human-readable (like bincode)
+/// fails with a custom error with the message of this error.
+///
+/// Nodes are numbered by their pre-order index, starting from 0.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum DecodeError {
+    /// The subtree size of a node is 0, but every subtree contains at least the node itself.
+    ZeroSubtreeSize {
+        /// The index of the node.
+        index: usize,
+    },
+    /// The subtree of a node doesn't fit in the subtree of its parent, or its end doesn't fit in a `usize`.
+    SubtreeOverflow {
+        /// The index of the node.
+        index: usize,
+        /// The subtree size of the node.
+        subtree_size: usize,
+        /// The largest subtree size the node could have had.
+        max_subtree_size: usize,
+    },
+    /// The nodes ended before the end of the subtree of a node.
+    Truncated {
+        /// The number of nodes.
+        len: usize,
+        /// The number of nodes that the subtree sizes require.
+        expected_len: usize,
+    },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::ZeroSubtreeSize { index } => write!(f, "the node with index {} has subtree size 0", index),
+            DecodeError::SubtreeOverflow { index, subtree_size, max_subtree_size } => write!(
+                f,
+                "the node with index {} has subtree size {}, but its subtree size can be at most {}",
+                index, subtree_size, max_subtree_size
+            ),
+            DecodeError::Truncated { len, expected_len } => {
+                write!(f, "the data ended after {} nodes, but the subtree sizes require {} nodes", len, expected_len)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// The error returned when bytes can't be reinterpreted as nodes,
+/// see [`PackedForest::try_from_bytes`](crate::PackedForest::try_from_bytes).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum FromBytesError {
+    /// The number of bytes is not a multiple of the size of a node.
+    InvalidLength {
+        /// The number of bytes.
+        len: usize,
+    },
+    /// The bytes are not aligned for the nodes.
+    Misaligned,
+    /// The subtree size of a node is 0, or its subtree doesn't fit in the subtree of its parent or in the forest.
+    InvalidSubtreeSize {
+        /// The index of the node.
+        index: usize,
+    },
+}
+
+impl Display for FromBytesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FromBytesError::InvalidLength { len } => write!(f, "{} bytes is not a whole number of nodes", len),
+            FromBytesError::Misaligned => write!(f, "the bytes are not aligned"),
+            FromBytesError::InvalidSubtreeSize { index } => write!(f, "the node with index {} has an invalid subtree size", index),
+        }
+    }
+}
+
+impl std::error::Error for FromBytesError {}
+
+/// The error returned when reading a forest in the versioned binary format fails,
+/// see [`PackedForest::read_versioned`](crate::PackedForest::read_versioned).
+#[derive(Debug)]
+pub enum FormatError {
+    /// Reading from the underlying reader or decoding a value failed.
+    Io(io::Error),
+    /// The stream doesn't start with [`FORMAT_MAGIC`](crate::FORMAT_MAGIC) and a version.
+    InvalidMagic,
+    /// The stream was written in a version of the format that is not supported, see [`FORMAT_VERSION`](crate::FORMAT_VERSION).
+    UnsupportedVersion {
+        /// The version of the stream.
+        version: u8,
+    },
+    /// A node starts with an invalid tag.
+    InvalidTag {
+        /// The invalid tag.
+        tag: u8,
+    },
+    /// The end of a node was found outside of any node.
+    UnmatchedEnd,
+    /// The stream ended inside a tree.
+    UnexpectedEnd,
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatError::Io(err) => write!(f, "{}", err),
+            FormatError::InvalidMagic => write!(f, "the stream doesn't start with a valid header"),
+            FormatError::UnsupportedVersion { version } => write!(f, "unsupported format version {}", version),
+            FormatError::InvalidTag { tag } => write!(f, "invalid tag {}", tag),
+            FormatError::UnmatchedEnd => write!(f, "unmatched end of node"),
+            FormatError::UnexpectedEnd => write!(f, "stream ended inside a tree"),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FormatError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FormatError {
+    #[inline]
+    fn from(err: io::Error) -> Self {
+        FormatError::Io(err)
+    }
+}
+
+impl From<FormatError> for io::Error {
+    fn from(err: FormatError) -> Self {
+        match err {
+            FormatError::Io(err) => err,
+            FormatError::UnexpectedEnd => io::Error::new(io::ErrorKind::UnexpectedEof, err),
+            _ => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
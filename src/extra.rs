@@ -4,8 +4,11 @@
 use crate::*;
 
 use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::ops::{Add, AddAssign, Index, IndexMut};
 
-impl<T> PackedForest<T> {
+impl<T, P: NodePolicy> MetaPackedForest<T, P> {
     /// Build a tree with the given root value, and add it to the forest.
     ///
     /// The parameter `root_val` is the value that the root node of the tree will have.
@@ -18,7 +21,7 @@ impl<T> PackedForest<T> {
     pub fn build_tree<R>(
         &mut self,
         root_val: T,
-        node_builder_cb: impl FnOnce(&mut NodeBuilder<T>) -> R,
+        node_builder_cb: impl FnOnce(&mut MetaNodeBuilder<T, P>) -> R,
     ) -> R {
         let mut builder = self.get_tree_builder();
         let ret = node_builder_cb(&mut builder);
@@ -35,21 +38,513 @@ impl<T> PackedForest<T> {
     #[inline]
     pub fn build_tree_by_ret_val(
         &mut self,
-        node_builder_cb: impl FnOnce(&mut NodeBuilder<T>) -> T,
+        node_builder_cb: impl FnOnce(&mut MetaNodeBuilder<T, P>) -> T,
     ) {
         let mut builder = self.get_tree_builder();
         let root_val = node_builder_cb(&mut builder);
         builder.finish(root_val);
     }
 
+    /// Build a tree with the given root value, and add it to the forest only if building it succeeds.
+    ///
+    /// Like [`build_tree`](PackedForest::build_tree), but `node_builder_cb` returns a [`Result`].
+    /// If it returns `Ok`, the tree is added to the forest and the value inside the `Ok` is returned.
+    /// If it returns `Err`, all nodes that were added to the tree so far are dropped, the forest is left unchanged,
+    /// and the error is returned.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// let result: Result<(), std::num::ParseIntError> = forest.build_tree_result(0, |node_builder| {
+    ///     for s in &["1", "2", "three"] {
+    ///         node_builder.add_child(s.parse()?);
+    ///     }
+    ///     Ok(())
+    /// });
+    /// assert!(result.is_err());
+    /// assert_eq!(forest.tot_num_nodes(), 0);
+    /// ```
+    #[inline]
+    pub fn build_tree_result<R, E>(
+        &mut self,
+        root_val: T,
+        node_builder_cb: impl FnOnce(&mut MetaNodeBuilder<T, P>) -> Result<R, E>,
+    ) -> Result<R, E> {
+        let mut builder = self.get_tree_builder();
+        let ret = node_builder_cb(&mut builder)?;
+        builder.finish(root_val);
+        Ok(ret)
+    }
+
     /// Add a tree with only a single node to the forest. The parameter `val` is the value of that single node.
     #[inline]
     pub fn add_single_node_tree(&mut self, val: T) {
         self.get_tree_builder().finish(val);
     }
+
+    /// Returns an iterator over all the trees in this forest, together with the index of each tree.
+    /// The first tree has index 0, the second tree index 1, and so on.
+    ///
+    /// Note that the index of a tree is not the same as the index of its root node (see [`get`](PackedForest::get)).
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree("a", |node_builder| {
+    ///     node_builder.add_child("a1");
+    /// });
+    /// forest.add_single_node_tree("b");
+    ///
+    /// let roots: Vec<_> = forest.iter_trees_indexed().map(|(tree_index, tree)| (tree_index, *tree.val())).collect();
+    /// assert_eq!(roots, [(0, "a"), (1, "b")]);
+    /// ```
+    #[inline(always)]
+    pub fn iter_trees_indexed(&self) -> std::iter::Enumerate<MetaNodeIter<'_, T, P>> {
+        self.iter_trees().enumerate()
+    }
+
+    /// Returns an iterator that iterates mutably over all the trees in this forest, together with the index of each tree.
+    /// See [`iter_trees_indexed`](PackedForest::iter_trees_indexed) and [`iter_trees_mut`](PackedForest::iter_trees_mut).
+    #[inline(always)]
+    pub fn iter_trees_mut_indexed(&mut self) -> std::iter::Enumerate<MetaNodeIterMut<'_, T, P>> {
+        self.iter_trees_mut().enumerate()
+    }
+
+    /// Returns a draining iterator over the trees of this forest, together with the index of each tree.
+    /// See [`iter_trees_indexed`](PackedForest::iter_trees_indexed) and [`drain_trees`](PackedForest::drain_trees).
+    #[inline(always)]
+    pub fn drain_trees_indexed(&mut self) -> std::iter::Enumerate<MetaNodeListDrain<'_, T, P>> {
+        self.drain_trees().enumerate()
+    }
+}
+
+impl<T> PackedForest<T> {
+    /// Returns the depth of every node in the forest, indexed by the pre-order index of the node
+    /// (see [`get`](PackedForest::get)). The roots of the trees have depth 0.
+    ///
+    /// This takes O(n) time, where n is the number of nodes in the forest.
+    pub fn depths(&self) -> Vec<usize> {
+        let mut result = Vec::with_capacity(self.tot_num_nodes());
+        // For each ancestor of the current node, the index one past the end of its subtree
+        let mut ancestor_ends: Vec<usize> = Vec::new();
+        for (index, node_data) in self.raw_data().iter().enumerate() {
+            while ancestor_ends.last().is_some_and(|end| *end <= index) {
+                ancestor_ends.pop();
+            }
+            result.push(ancestor_ends.len());
+            ancestor_ends.push(index + node_data.subtree_size().get());
+        }
+        result
+    }
+
+    /// Returns the depth of the node with the given index (0 for the roots of the trees),
+    /// or `None` if the index is out of bounds.
+    ///
+    /// This walks down from the root of the tree that contains the node, skipping the subtrees of siblings,
+    /// so it takes O(d * s) time, where d is the depth of the node and s is the maximum number of siblings along
+    /// the way. For many queries on a forest that doesn't change, build a [`DepthIndex`] once instead.
+    pub fn depth_of(&self, index: impl Into<crate::NodeId>) -> Option<usize> {
+        let index = index.into().index();
+        if index >= self.tot_num_nodes() {
+            return None;
+        }
+        let data = self.raw_data();
+        let mut depth = 0;
+        let mut cur = 0;
+        while cur < index {
+            let end = cur + data[cur].subtree_size().get();
+            if end > index {
+                // `cur` is an ancestor of the node, go down into its first child
+                depth += 1;
+                cur += 1;
+            } else {
+                cur = end;
+            }
+        }
+        Some(depth)
+    }
+
+    /// Build a [`DepthIndex`] for this forest, to look up the depth of nodes in O(1) time.
+    #[inline]
+    pub fn depth_index(&self) -> DepthIndex {
+        DepthIndex::new(self)
+    }
+
+    /// Returns the index of the parent of every node in the forest, indexed by the pre-order index of the node
+    /// (see [`get`](PackedForest::get)). The roots of the trees have no parent, so their entry is `None`.
+    ///
+    /// This takes O(n) time, where n is the number of nodes in the forest.
+    pub fn parents(&self) -> Vec<Option<usize>> {
+        let mut result = Vec::with_capacity(self.tot_num_nodes());
+        // For each ancestor of the current node, its index and the index one past the end of its subtree
+        let mut ancestors: Vec<(usize, usize)> = Vec::new();
+        for (index, node_data) in self.raw_data().iter().enumerate() {
+            while ancestors.last().is_some_and(|(_, end)| *end <= index) {
+                ancestors.pop();
+            }
+            result.push(ancestors.last().map(|(ancestor_index, _)| *ancestor_index));
+            ancestors.push((index, index + node_data.subtree_size().get()));
+        }
+        result
+    }
+
+    /// Returns the first node, in pre-order, for which `predicate` returns `true`, or `None` if there is no such node.
+    #[inline]
+    pub fn find<'t>(&'t self, predicate: impl FnMut(NodeRef<'t, T>) -> bool) -> Option<NodeRef<'t, T>> {
+        self.position(predicate).map(|index| self.get(index).unwrap())
+    }
+
+    /// Returns the index of the first node, in pre-order, for which `predicate` returns `true`,
+    /// or `None` if there is no such node.
+    pub fn position<'t>(&'t self, mut predicate: impl FnMut(NodeRef<'t, T>) -> bool) -> Option<usize> {
+        (0..self.tot_num_nodes()).find(|index| predicate(self.get(*index).unwrap()))
+    }
+
+    /// Returns a copy of this forest where the children of every node are sorted by the key that `key_fn`
+    /// returns for their values. The sort is stable, and the order of the trees themselves is unchanged.
+    ///
+    /// `key_fn` is called only once for every node that isn't a root. The nodes are copied in runs of consecutive
+    /// nodes like in [`map_children_order`](PackedForest::map_children_order), without recursion.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree("root", |node_builder| {
+    ///     node_builder.build_child("b", |node_builder| {
+    ///         node_builder.add_child("b2");
+    ///         node_builder.add_child("b1");
+    ///     });
+    ///     node_builder.add_child("a");
+    /// });
+    ///
+    /// let sorted = forest.sorted_by_key(|val| *val);
+    /// assert_eq!(sorted.iter_flattened().copied().collect::<Vec<_>>(), ["root", "a", "b", "b1", "b2"]);
+    /// ```
+    pub fn sorted_by_key<K: Ord>(&self, mut key_fn: impl FnMut(&T) -> K) -> PackedForest<T> where T: Clone {
+        self.map_children_order(|_, children, order| order.sort_by_cached_key(|&i| key_fn(children[i].val())))
+    }
+
+    /// Returns a copy of this forest where the children of every node are reordered by `f`.
+    /// The order of the trees themselves is unchanged.
+    ///
+    /// `f` is called once for every node that has children, with the node, a slice of its children in their
+    /// original order, and the order in which the children will be copied, which starts out as `0, 1, 2, ...`.
+    /// `f` can permute the order in place (e.g. with [`reverse`](slice::reverse) or [`sort_by_key`](slice::sort_by_key)).
+    /// Every child is then copied together with its descendants, whose children are reordered in the same way.
+    ///
+    /// Reordering children doesn't change the size of any subtree, so the nodes are copied as they are,
+    /// in runs of consecutive nodes. This takes O(n) time (plus the time spent in `f`), and doesn't use recursion.
+    ///
+    /// See also [`reverse_children`](PackedForest::reverse_children), [`rotate_children`](PackedForest::rotate_children)
+    /// and [`sorted_by_key`](PackedForest::sorted_by_key).
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree(0, |node_builder| {
+    ///     node_builder.build_child(1, |node_builder| {
+    ///         node_builder.add_child(3);
+    ///         node_builder.add_child(2);
+    ///     });
+    ///     node_builder.add_child(4);
+    /// });
+    ///
+    /// // Move the child with the most descendants to the end
+    /// let reordered = forest.map_children_order(|_, children, order| {
+    ///     order.sort_by_key(|&i| children[i].num_descendants_incl_self());
+    /// });
+    /// assert_eq!(reordered.iter_flattened().copied().collect::<Vec<_>>(), [0, 4, 1, 3, 2]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` leaves an order that is not a permutation of the indices of the children,
+    /// since that would leave out or duplicate a child.
+    pub fn map_children_order<'t>(
+        &'t self,
+        mut f: impl FnMut(NodeRef<'t, T>, &[NodeRef<'t, T>], &mut [usize]),
+    ) -> PackedForest<T> where T: Clone {
+        let raw_data = self.raw_data();
+        // The indices of the nodes in this forest, in the order in which they are copied
+        let mut new_order = Vec::with_capacity(raw_data.len());
+        // For every node whose children are being copied, the indices of its children in the order in which they are copied
+        let mut stack: Vec<std::vec::IntoIter<usize>> = Vec::new();
+        let mut children = Vec::new();
+        let mut child_indices = Vec::new();
+        let mut order = Vec::new();
+        let mut seen = Vec::new();
+
+        let mut next_root = 0;
+        loop {
+            // The iterators on the stack are never empty, see below
+            let index = match stack.last_mut() {
+                Some(children) => children.next().unwrap(),
+                None if next_root < raw_data.len() => next_root,
+                None => break,
+            };
+            let end = index + raw_data[index].subtree_size().get();
+            if stack.is_empty() {
+                next_root = end;
+            }
+            new_order.push(index);
+
+            children.clear();
+            child_indices.clear();
+            let mut child_index = index + 1;
+            while child_index < end {
+                children.push(self.get(child_index).unwrap());
+                child_indices.push(child_index);
+                child_index += raw_data[child_index].subtree_size().get();
+            }
+            if !children.is_empty() {
+                order.clear();
+                order.extend(0..children.len());
+                f(self.get(index).unwrap(), &children, &mut order);
+                seen.clear();
+                seen.resize(children.len(), false);
+                for &i in &order {
+                    assert!(i < seen.len() && !seen[i], "the order of the children is not a permutation");
+                    seen[i] = true;
+                }
+                stack.push(order.iter().map(|&i| child_indices[i]).collect::<Vec<_>>().into_iter());
+            }
+            while stack.last().is_some_and(|children| children.len() == 0) {
+                stack.pop();
+            }
+        }
+
+        let mut result = PackedForest::with_capacity(raw_data.len());
+        let mut run_start = 0;
+        for i in 1..=new_order.len() {
+            if i == new_order.len() || new_order[i] != new_order[i - 1] + 1 {
+                result.push_cloned_subtree(&raw_data[new_order[run_start]..=new_order[i - 1]]);
+                run_start = i;
+            }
+        }
+        result
+    }
+
+    /// Returns a copy of this forest where the order of the children of every node is reversed.
+    /// The order of the trees themselves is unchanged.
+    ///
+    /// See [`map_children_order`](PackedForest::map_children_order).
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree(0, |node_builder| {
+    ///     node_builder.build_child(1, |node_builder| {
+    ///         node_builder.add_child(2);
+    ///         node_builder.add_child(3);
+    ///     });
+    ///     node_builder.add_child(4);
+    /// });
+    ///
+    /// let reversed = forest.reverse_children();
+    /// assert_eq!(reversed.iter_flattened().copied().collect::<Vec<_>>(), [0, 4, 1, 3, 2]);
+    /// ```
+    #[inline]
+    pub fn reverse_children(&self) -> PackedForest<T> where T: Clone {
+        self.map_children_order(|_, _, order| order.reverse())
+    }
+
+    /// Returns a copy of this forest where the children of every node are rotated to the left by `n` places,
+    /// like [`slice::rotate_left`], so that child number `n` becomes the first child.
+    /// The order of the trees themselves is unchanged.
+    ///
+    /// For nodes with `n` or fewer children, the children are rotated by `n` modulo the number of children.
+    ///
+    /// See [`map_children_order`](PackedForest::map_children_order).
+    #[inline]
+    pub fn rotate_children(&self, n: usize) -> PackedForest<T> where T: Clone {
+        self.map_children_order(|_, _, order| {
+            let len = order.len();
+            order.rotate_left(n % len);
+        })
+    }
+
+    /// Returns a copy of this forest without the nodes for which `predicate` returns `true`, where the children of
+    /// every removed node take its place among the children of its parent (or among the trees, if it is a root).
+    /// This is sometimes called "unwrapping" a node, e.g. to remove grouping nodes from a syntax tree.
+    ///
+    /// `predicate` is called once for every node, in pre-order, and gets the node as it is in this forest.
+    /// The descendants of a removed node are kept unless `predicate` returns `true` for them too.
+    /// This takes O(n) time, and doesn't use recursion.
+    ///
+    /// To remove a node from a [`PackedTree`], call this on [`as_ref`](AsRef::as_ref) of the tree.
+    /// The result is a forest, since removing the root can leave more than one tree.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree("+", |node_builder| {
+    ///     node_builder.build_child("()", |node_builder| {
+    ///         node_builder.build_child("*", |node_builder| {
+    ///             node_builder.add_child("1");
+    ///             node_builder.add_child("2");
+    ///         });
+    ///     });
+    ///     node_builder.add_child("3");
+    /// });
+    ///
+    /// let unwrapped = forest.unwrap_nodes(|node| *node.val() == "()");
+    /// assert_eq!(unwrapped.iter_flattened().copied().collect::<Vec<_>>(), ["+", "*", "1", "2", "3"]);
+    /// assert_eq!(unwrapped.iter_trees().next().unwrap().children().count(), 2);
+    /// ```
+    pub fn unwrap_nodes<'t>(&'t self, mut predicate: impl FnMut(NodeRef<'t, T>) -> bool) -> PackedForest<T> where T: Clone {
+        let mut result = PackedForest::with_capacity(self.tot_num_nodes());
+        // The ancestors of the current node, as the index one past their last descendant in this forest,
+        // and their index in the result, or `None` if they are removed.
+        let mut ancestors: Vec<(usize, Option<usize>)> = Vec::new();
+        for (index, node_data) in self.raw_data().iter().enumerate() {
+            while let Some(&(end, result_index)) = ancestors.last() {
+                if end > index {
+                    break;
+                }
+                if let Some(result_index) = result_index {
+                    result.close_node(result_index);
+                }
+                ancestors.pop();
+            }
+            let end = index + node_data.subtree_size().get();
+            if predicate(self.get(index).unwrap()) {
+                ancestors.push((end, None));
+            } else {
+                ancestors.push((end, Some(result.push_open_node(node_data.val().clone()))));
+            }
+        }
+        for (_, result_index) in ancestors.into_iter().rev() {
+            if let Some(result_index) = result_index {
+                result.close_node(result_index);
+            }
+        }
+        result
+    }
+
+    /// Returns a copy of this forest with only the nodes for which `predicate` returns `true`, together with
+    /// all their ancestors, so that the nodes keep their place in the hierarchy (like search results that
+    /// are shown within their directories). The descendants of a matching node are only kept if they match too.
+    ///
+    /// `predicate` is called once for every node, in pre-order. This takes O(n) time, and doesn't use recursion.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree("/", |node_builder| {
+    ///     node_builder.build_child("src", |node_builder| {
+    ///         node_builder.add_child("lib.rs");
+    ///         node_builder.add_child("main.c");
+    ///     });
+    ///     node_builder.build_child("docs", |node_builder| {
+    ///         node_builder.add_child("index.md");
+    ///     });
+    ///     node_builder.add_child("build.rs");
+    /// });
+    ///
+    /// let filtered = forest.filter_with_ancestors(|node| node.val().ends_with(".rs"));
+    /// assert_eq!(filtered.iter_flattened().copied().collect::<Vec<_>>(), ["/", "src", "lib.rs", "build.rs"]);
+    /// ```
+    pub fn filter_with_ancestors<'t>(&'t self, mut predicate: impl FnMut(NodeRef<'t, T>) -> bool) -> PackedForest<T> where T: Clone {
+        let num_nodes = self.tot_num_nodes();
+        let matches: Vec<bool> = (0..num_nodes).map(|index| predicate(self.get(index).unwrap())).collect();
+
+        // Mark the nodes to keep bottom-up: a node is kept if the first matching node at or after it
+        // is part of its subtree.
+        let mut keep = vec![false; num_nodes];
+        let mut next_match = usize::MAX;
+        for index in (0..num_nodes).rev() {
+            if matches[index] {
+                next_match = index;
+            }
+            keep[index] = next_match < index + self.raw_data()[index].subtree_size().get();
+        }
+
+        let mut result = PackedForest::new();
+        // The index one past the last descendant of every ancestor of the current node in this forest,
+        // and its index in the result.
+        let mut ancestors: Vec<(usize, usize)> = Vec::new();
+        let mut index = 0;
+        while index < num_nodes {
+            while let Some(&(end, result_index)) = ancestors.last() {
+                if end > index {
+                    break;
+                }
+                result.close_node(result_index);
+                ancestors.pop();
+            }
+            let node_data = &self.raw_data()[index];
+            if keep[index] {
+                ancestors.push((index + node_data.subtree_size().get(), result.push_open_node(node_data.val().clone())));
+                index += 1;
+            } else {
+                // None of the descendants are kept either
+                index += node_data.subtree_size().get();
+            }
+        }
+        for (_, result_index) in ancestors.into_iter().rev() {
+            result.close_node(result_index);
+        }
+        result
+    }
+
+    /// Returns a new forest with a copy of every subtree whose root matches `predicate`, each as its own tree,
+    /// in pre-order. Only maximal subtrees are copied: once a node matches, its descendants are copied along with it
+    /// and `predicate` isn't called for them.
+    ///
+    /// Every subtree is copied as a single slice of nodes, so this takes O(n) time, where n is the number of nodes in this forest.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree("fn main", |node_builder| {
+    ///     node_builder.build_child("unsafe", |node_builder| {
+    ///         node_builder.add_child("deref");
+    ///         node_builder.build_child("unsafe", |node_builder| {
+    ///             node_builder.add_child("call");
+    ///         });
+    ///     });
+    ///     node_builder.add_child("let");
+    /// });
+    /// forest.build_tree("fn f", |node_builder| {
+    ///     node_builder.add_child("unsafe");
+    /// });
+    ///
+    /// let unsafe_blocks = forest.collect_subtrees(|node| *node.val() == "unsafe");
+    /// assert_eq!(unsafe_blocks.iter_trees().count(), 2);
+    /// assert_eq!(unsafe_blocks.iter_flattened().copied().collect::<Vec<_>>(), ["unsafe", "deref", "unsafe", "call", "unsafe"]);
+    /// ```
+    pub fn collect_subtrees<'t>(&'t self, mut predicate: impl FnMut(NodeRef<'t, T>) -> bool) -> PackedForest<T> where T: Clone {
+        let mut result = PackedForest::new();
+        let mut index = 0;
+        while let Some(node) = self.get(index) {
+            if predicate(node) {
+                result.push_cloned_subtree(node.raw_slice());
+                index += node.num_descendants_incl_self();
+            } else {
+                index += 1;
+            }
+        }
+        result
+    }
 }
 
-fn fmt_node<T: Debug>(node: NodeRef<T>, f: &mut Formatter<'_>) -> std::fmt::Result {
+fn fmt_node<T: Debug, P: NodePolicy>(node: MetaNodeRef<T, P>, f: &mut Formatter<'_>) -> std::fmt::Result {
     write!(f, "{{ value: {:?}, children: [", node.val())?;
     for child in node.children() {
         fmt_node(child, f)?;
@@ -57,9 +552,9 @@ fn fmt_node<T: Debug>(node: NodeRef<T>, f: &mut Formatter<'_>) -> std::fmt::Resu
     write!(f, "]}}")
 }
 
-impl<T: Debug> Debug for PackedForest<T> {
+impl<T: Debug, P: NodePolicy> Debug for MetaPackedForest<T, P> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "PackedForest [")?;
+        write!(f, "{}PackedForest [", P::NAME_PREFIX)?;
         for tree in self.iter_trees() {
             fmt_node(tree, f)?;
         }
@@ -67,39 +562,200 @@ impl<T: Debug> Debug for PackedForest<T> {
     }
 }
 
-impl<T: Debug> Debug for PackedTree<T> {
+impl<T: Debug, P: NodePolicy> Debug for MetaPackedTree<T, P> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "PackedTree")?;
+        write!(f, "{}PackedTree", P::NAME_PREFIX)?;
         fmt_node(self.root(), f)
     }
 }
 
-fn fmt_exact_size_node<T: Debug>(node: ExactSizeNodeRef<T>, f: &mut Formatter<'_>) -> std::fmt::Result {
-    write!(f, "{{ value: {:?}, children: [", node.val())?;
-    for child in node.children() {
-        fmt_exact_size_node(child, f)?;
+impl<T, P: NodePolicy> Index<usize> for MetaPackedForest<T, P> {
+    type Output = T;
+
+    /// Returns a reference to the value of the node with the given pre-order index. See [`PackedForest::get`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    #[inline]
+    fn index(&self, index: usize) -> &T {
+        match self.get(index) {
+            Some(node) => node.val(),
+            None => panic!("index out of bounds: the forest has {} nodes but the index is {}", self.tot_num_nodes(), index),
+        }
     }
-    write!(f, "]}}")
 }
 
-impl<T: Debug> Debug for ExactSizePackedForest<T> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "ExactSizePackedForest [")?;
-        for tree in self.iter_trees() {
-            fmt_exact_size_node(tree, f)?;
+impl<T, P: NodePolicy> IndexMut<usize> for MetaPackedForest<T, P> {
+    /// Returns a mutable reference to the value of the node with the given pre-order index. See [`PackedForest::get_mut`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        let tot_num_nodes = self.tot_num_nodes();
+        match self.get_mut(index) {
+            Some(node) => node.into_val_mut(),
+            None => panic!("index out of bounds: the forest has {} nodes but the index is {}", tot_num_nodes, index),
         }
-        write!(f, "]")
     }
 }
 
-impl<T: Debug> Debug for ExactSizePackedTree<T> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "ExactSizePackedTree")?;
-        fmt_exact_size_node(self.root(), f)
+impl<T, P: NodePolicy> Index<usize> for MetaPackedTree<T, P> {
+    type Output = T;
+
+    /// Returns a reference to the value of the node with the given pre-order index. See [`PackedTree::get`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    #[inline]
+    fn index(&self, index: usize) -> &T {
+        match self.get(index) {
+            Some(node) => node.val(),
+            None => panic!("index out of bounds: the tree has {} nodes but the index is {}", self.tot_num_nodes(), index),
+        }
+    }
+}
+
+impl<T, P: NodePolicy> IndexMut<usize> for MetaPackedTree<T, P> {
+    /// Returns a mutable reference to the value of the node with the given pre-order index. See [`PackedTree::get_mut`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        let tot_num_nodes = self.tot_num_nodes();
+        match self.get_mut(index) {
+            Some(node) => node.into_val_mut(),
+            None => panic!("index out of bounds: the tree has {} nodes but the index is {}", tot_num_nodes, index),
+        }
+    }
+}
+
+/// Concatenates two forests: the result contains the trees of `self`, followed by the trees of `other`.
+/// See [`PackedForest::append`].
+///
+/// # Example
+/// ```
+/// use packed_tree::{PackedForest, PackedTree};
+///
+/// let forest = PackedForest::from(PackedTree::new(1, |_| {}));
+/// let mut forest = forest + PackedTree::new(2, |_| {});
+/// forest += vec![3, 4].into_iter().map(|val| PackedTree::new(val, |_| {})).collect::<PackedForest<_>>();
+/// assert_eq!(forest.iter_trees().map(|tree| *tree.val()).collect::<Vec<_>>(), [1, 2, 3, 4]);
+/// ```
+impl<T> Add<PackedForest<T>> for PackedForest<T> {
+    type Output = PackedForest<T>;
+
+    #[inline]
+    fn add(mut self, other: PackedForest<T>) -> PackedForest<T> {
+        self += other;
+        self
+    }
+}
+
+/// Adds the tree to the end of the forest.
+impl<T> Add<PackedTree<T>> for PackedForest<T> {
+    type Output = PackedForest<T>;
+
+    #[inline]
+    fn add(mut self, tree: PackedTree<T>) -> PackedForest<T> {
+        self += tree;
+        self
+    }
+}
+
+/// Moves the trees of `other` to the end of this forest. See [`PackedForest::append`].
+impl<T> AddAssign<PackedForest<T>> for PackedForest<T> {
+    #[inline]
+    fn add_assign(&mut self, mut other: PackedForest<T>) {
+        self.append(&mut other);
+    }
+}
+
+/// Moves the tree to the end of this forest.
+impl<T> AddAssign<PackedTree<T>> for PackedForest<T> {
+    #[inline]
+    fn add_assign(&mut self, tree: PackedTree<T>) {
+        *self += PackedForest::from(tree);
+    }
+}
+
+/// Adds the trees to the end of this forest, in order.
+impl<T> Extend<PackedTree<T>> for PackedForest<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = PackedTree<T>>>(&mut self, trees: I) {
+        for tree in trees {
+            *self += tree;
+        }
     }
 }
 
-impl<'a,T> NodeBuilder<'a,T> {
+/// Creates a forest with the given trees, in order.
+impl<T> FromIterator<PackedTree<T>> for PackedForest<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = PackedTree<T>>>(trees: I) -> Self {
+        let mut forest = PackedForest::new();
+        forest.extend(trees);
+        forest
+    }
+}
+
+/// Two [`NodeRef`]s are equal if their subtrees are equal, i.e. if their values are equal
+/// and their children are equal, in the same order. Where the nodes are located doesn't matter,
+/// so subtrees of different trees (or different subtrees of the same tree) can be equal.
+impl<'t, T: PartialEq> PartialEq for NodeRef<'t, T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.raw_slice() == other.raw_slice()
+    }
+}
+
+impl<'t, T: Eq> Eq for NodeRef<'t, T> {}
+
+/// Hashes the values and the structure of the whole subtree, consistently with [`PartialEq`],
+/// so that [`NodeRef`]s can be used as keys in a [`HashMap`](std::collections::HashMap), e.g. for memoization.
+impl<'t, T: Hash> Hash for NodeRef<'t, T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.raw_slice().hash(state)
+    }
+}
+
+impl<'t, T, P: NodePolicy> IntoIterator for &'t MetaPackedForest<T, P> {
+    type Item = MetaNodeRef<'t, T, P>;
+    type IntoIter = MetaNodeIter<'t, T, P>;
+
+    #[inline(always)]
+    fn into_iter(self) -> MetaNodeIter<'t, T, P> {
+        self.iter_trees()
+    }
+}
+
+impl<'t, T, P: NodePolicy> IntoIterator for &'t mut MetaPackedForest<T, P> {
+    type Item = MetaNodeRefMut<'t, T, P>;
+    type IntoIter = MetaNodeIterMut<'t, T, P>;
+
+    #[inline(always)]
+    fn into_iter(self) -> MetaNodeIterMut<'t, T, P> {
+        self.iter_trees_mut()
+    }
+}
+
+impl<T, P: NodePolicy> IntoIterator for MetaPackedForest<T, P> {
+    type Item = MetaPackedTree<T, P>;
+    type IntoIter = MetaIntoTrees<T, P>;
+
+    #[inline(always)]
+    fn into_iter(self) -> MetaIntoTrees<T, P> {
+        self.into_trees()
+    }
+}
+
+impl<'a, T, P: NodePolicy, S: NodeStorage<T, P::Meta>> MetaNodeBuilder<'a, T, P, S> {
     /// Build a child node with the given value, and add it to the tree as a child of the node
     /// that is being built by the current [`NodeBuilder`].
     ///
@@ -113,7 +769,7 @@ impl<'a,T> NodeBuilder<'a,T> {
     pub fn build_child<R>(
         &mut self,
         val: T,
-        child_builder_cb: impl FnOnce(&mut NodeBuilder<T>) -> R,
+        child_builder_cb: impl FnOnce(&mut MetaNodeBuilder<T, P, S>) -> R,
     ) -> R {
         let mut builder = self.get_child_builder();
         let ret = child_builder_cb(&mut builder);
@@ -167,13 +823,47 @@ impl<'a,T> NodeBuilder<'a,T> {
     #[inline]
     pub fn build_child_by_ret_val(
         &mut self,
-        child_builder_cb: impl FnOnce(&mut NodeBuilder<T>) -> T,
-    ) -> NodeRefMut<T> {
+        child_builder_cb: impl FnOnce(&mut MetaNodeBuilder<T, P, S>) -> T,
+    ) -> MetaNodeRefMut<'_, T, P> {
         let mut builder = self.get_child_builder();
         let val = child_builder_cb(&mut builder);
         builder.finish(val)
     }
 
+    /// Build a child node with the given value, and add it as a child of the node that is being built
+    /// by the current [`NodeBuilder`] only if building it succeeds.
+    ///
+    /// Like [`build_child`](NodeBuilder::build_child), but `child_builder_cb` returns a [`Result`].
+    /// If it returns `Err`, all nodes that were added to the child so far are dropped, no child is added,
+    /// and the error is returned. This makes it possible to use the `?` operator while building a tree.
+    #[inline]
+    pub fn try_build_child<R, E>(
+        &mut self,
+        val: T,
+        child_builder_cb: impl FnOnce(&mut MetaNodeBuilder<T, P, S>) -> Result<R, E>,
+    ) -> Result<R, E> {
+        let mut builder = self.get_child_builder();
+        let ret = child_builder_cb(&mut builder)?;
+        builder.finish(val);
+        Ok(ret)
+    }
+
+    /// Build a child node, whose value is the value inside the `Ok` returned by the given closure, and add it as a child
+    /// of the node that is being built by the current [`NodeBuilder`] only if building it succeeds.
+    ///
+    /// Like [`build_child_by_ret_val`](NodeBuilder::build_child_by_ret_val), but `child_builder_cb` returns a [`Result`].
+    /// If it returns `Err`, all nodes that were added to the child so far are dropped, no child is added,
+    /// and the error is returned.
+    #[inline]
+    pub fn try_build_child_by_ret_val<E>(
+        &mut self,
+        child_builder_cb: impl FnOnce(&mut MetaNodeBuilder<T, P, S>) -> Result<T, E>,
+    ) -> Result<MetaNodeRefMut<'_, T, P>, E> {
+        let mut builder = self.get_child_builder();
+        let val = child_builder_cb(&mut builder)?;
+        Ok(builder.finish(val))
+    }
+
     /// Add a child node with the given value to the tree as a child of the node that is being built by the current [`NodeBuilder`].
     /// 
     /// There is no way to add children to this new child node. Use [`build_child`](`NodeBuilder::build_child`)
@@ -181,12 +871,167 @@ impl<'a,T> NodeBuilder<'a,T> {
     /// 
     /// Returns a [`NodeRefMut`] to the added child node.
     #[inline]
-    pub fn add_child(&mut self, val: T) -> NodeRefMut<T> {
+    pub fn add_child(&mut self, val: T) -> MetaNodeRefMut<'_, T, P> {
         self.get_child_builder().finish(val)
     }
+
+    /// Finish building the node that this [`NodeBuilder`] was building, like [`finish`](NodeBuilder::finish),
+    /// but return the pre-order index of the node (see [`PackedForest::get`]) instead of a [`NodeRefMut`].
+    ///
+    /// Unlike the [`NodeRefMut`], the index doesn't borrow the forest or the parent [`NodeBuilder`],
+    /// so the index of a child can be kept while its parent is still being built.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// let mut root_builder = forest.get_tree_builder();
+    /// let mut indices = Vec::new();
+    /// for val in ["a", "b", "c"] {
+    ///     indices.push(root_builder.get_child_builder().finish_idx(val));
+    /// }
+    /// let root_index = root_builder.finish_idx("root");
+    ///
+    /// assert_eq!(root_index, 0);
+    /// assert_eq!(indices, [1, 2, 3]);
+    /// assert_eq!(forest[indices[1]], "b");
+    /// ```
+    #[inline]
+    pub fn finish_idx(self, val: T) -> usize {
+        let index = self.index();
+        self.finish(val);
+        index
+    }
+
+    /// Stop building the node that this [`NodeBuilder`] was building, without adding it to the tree, forest or parent [`NodeBuilder`].
+    /// All nodes that were added to it are dropped.
+    ///
+    /// This is equivalent to dropping the [`NodeBuilder`] without calling [`finish`](NodeBuilder::finish),
+    /// but makes the intent explicit.
+    ///
+    /// Returns the number of nodes that were discarded, not counting the node being built itself (which never got a value).
+    #[inline]
+    pub fn cancel(self) -> usize {
+        self.subtree_size_so_far() - 1
+    }
+}
+
+impl<'t, T, P: NodePolicy> MetaNodeRef<'t, T, P> {
+    /// Returns the first node, in pre-order, among this node and its descendants for which `predicate` returns `true`,
+    /// or `None` if there is no such node.
+    pub fn find_descendant(&self, mut predicate: impl FnMut(MetaNodeRef<'t, T, P>) -> bool) -> Option<MetaNodeRef<'t, T, P>> {
+        (0..self.num_descendants_incl_self())
+            .map(|offset| self.descendant_at_offset(offset).unwrap())
+            .find(|node| predicate(*node))
+    }
+
+    /// Returns the descendant of this node that is reached by following the given path of child indices,
+    /// or `None` if there is no such node. For example, the path `[2, 0]` refers to the first child
+    /// of the third child of this node. An empty path refers to this node itself.
+    pub fn descendant_at_path(&self, path: &[usize]) -> Option<MetaNodeRef<'t, T, P>> {
+        let mut node = *self;
+        for child_index in path {
+            node = node.children().nth(*child_index)?;
+        }
+        Some(node)
+    }
+
+    /// Visits this node and its descendants in pre-order, skipping entire subtrees.
+    ///
+    /// For each node, `descend` is called first. If it returns `false`, the node and all of its descendants
+    /// are skipped in O(1) time. Otherwise, `visit` is called on the node, and the search continues with its children.
+    ///
+    /// This is useful for spatial partitioning trees (like BVHs or quadtrees), where a query only needs to look
+    /// inside the nodes whose bounds intersect the query region.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// // Each node holds the range of values in its subtree
+    /// let tree = PackedTree::new(0..8, |node_builder| {
+    ///     node_builder.build_child(0..4, |node_builder| {
+    ///         node_builder.add_child(0..2);
+    ///         node_builder.add_child(2..4);
+    ///     });
+    ///     node_builder.build_child(4..8, |node_builder| {
+    ///         node_builder.add_child(4..6);
+    ///         node_builder.add_child(6..8);
+    ///     });
+    /// });
+    ///
+    /// let mut visited = Vec::new();
+    /// tree.root().search_pruned(|node| node.val().contains(&5), |node| visited.push(node.val().clone()));
+    /// assert_eq!(visited, [0..8, 4..8, 4..6]);
+    /// ```
+    pub fn search_pruned(&self, mut descend: impl FnMut(MetaNodeRef<'t, T, P>) -> bool, mut visit: impl FnMut(MetaNodeRef<'t, T, P>)) {
+        let mut offset = 0;
+        while let Some(node) = self.descendant_at_offset(offset) {
+            if descend(node) {
+                visit(node);
+                offset += 1;
+            } else {
+                offset += node.num_descendants_incl_self();
+            }
+        }
+    }
+
+    /// Returns an iterator over the values of the children of this node (not of any deeper descendants).
+    ///
+    /// This is the same as `node.children().map(|child| child.val())`.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let tree = PackedTree::new(0, |node_builder| {
+    ///     node_builder.build_child(1, |node_builder| {
+    ///         node_builder.add_child(2);
+    ///     });
+    ///     node_builder.add_child(3);
+    /// });
+    ///
+    /// assert_eq!(tree.root().children_values().copied().collect::<Vec<_>>(), [1, 3]);
+    /// ```
+    #[inline(always)]
+    pub fn children_values(&self) -> ChildrenValues<'t, T, P> {
+        ChildrenValues {
+            children: self.children(),
+        }
+    }
+}
+
+impl<'t, T, P: NodePolicy> MetaNodeRefMut<'t, T, P> {
+    /// Returns an iterator over mutable references to the values of the children of this node
+    /// (not of any deeper descendants).
+    ///
+    /// This is the same as `node.children().map(|child| child.into_val_mut())`.
+    #[inline(always)]
+    pub fn children_values_mut(&mut self) -> ChildrenValuesMut<'_, T, P> {
+        ChildrenValuesMut {
+            children: self.children(),
+        }
+    }
+
+    /// Replaces the value of this node with `val`, and returns the old value.
+    ///
+    /// This is the same as `std::mem::replace(node.val_mut(), val)`.
+    #[inline(always)]
+    pub fn replace_val(&mut self, val: T) -> T {
+        std::mem::replace(self.val_mut(), val)
+    }
+
+    /// Takes the value out of this node, leaving [`T::default()`](Default::default) in its place.
+    ///
+    /// This is the same as `std::mem::take(node.val_mut())`.
+    #[inline(always)]
+    pub fn take_val(&mut self) -> T where T: Default {
+        std::mem::take(self.val_mut())
+    }
 }
 
-impl<'t, T> NodeDrain<'t, T> {
+impl<'t, T, P: NodePolicy> MetaNodeDrain<'t, T, P> {
     /// Counts the number of descendants of this node (also counting the node itself) in O(1) time.
     #[inline(always)]
     pub fn num_descendants_incl_self(&self) -> usize {
@@ -0,0 +1,116 @@
+//! Access to the nodes of a forest from other languages, like C or C++.
+//!
+//! Requires the `ffi` feature, which also makes [`NodeData`] `#[repr(C)]`. Without it, the layout of a node is unspecified.
+//!
+//! The nodes of a [`PackedForest`] or [`PackedTree`] are stored in a single buffer, in pre-order.
+//! Every node is a [`NodeData<T>`], which has the same layout as this C struct
+//! (if `T` has the same layout as the C type `T`, e.g. because it is a primitive or a `#[repr(C)]` type):
+//! ```c
+//! struct Node {
+//!     T val;
+//!     size_t subtree_size; // The number of nodes in the subtree of this node, including itself. Never 0.
+//! };
+//! ```
+//! The first child of a node (if any) directly follows it in the buffer, and the next sibling of a node (if any)
+//! comes `subtree_size` nodes after it. The roots of the trees of a forest follow each other in the same way.
+//! So the children of the node at index `i` can be visited like this:
+//! ```c
+//! for (size_t child = i + 1; child < i + nodes[i].subtree_size; child += nodes[child].subtree_size) {
+//!     visit(&nodes[child]);
+//! }
+//! ```
+//!
+//! A [`RawNodeBuffer`] contains the location of the buffer, its length, and the size and field offsets of a node,
+//! so that it can be passed to C code as a single struct.
+//!
+//! # Example
+//! ```
+//! use packed_tree::PackedTree;
+//! use packed_tree::ffi::RawNodeBuffer;
+//!
+//! let tree = PackedTree::new(1u32, |node_builder| {
+//!     node_builder.add_child(2);
+//!     node_builder.add_child(3);
+//! });
+//! let buffer = RawNodeBuffer::from_nodes(tree.raw_data());
+//! assert_eq!(buffer.len, 3);
+//! assert_eq!(buffer.stride, std::mem::size_of::<usize>() * 2);
+//! assert_eq!(buffer.val_offset, 0);
+//! assert_eq!(buffer.subtree_size_offset, std::mem::size_of::<usize>());
+//! ```
+
+use crate::*;
+
+use std::ffi::c_void;
+
+/// The location and layout of a buffer of nodes, to pass to code written in another language. See the [module docs](self).
+///
+/// This is only valid as long as the nodes aren't moved, modified or dropped. Foreign code must only read the nodes.
+#[repr(C)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct RawNodeBuffer {
+    /// A pointer to the first node. If there are no nodes, this is a dangling pointer that must not be read.
+    pub nodes: *const c_void,
+    /// The number of nodes in the buffer.
+    pub len: usize,
+    /// The size of a node in bytes, i.e. the distance between the start of two consecutive nodes.
+    pub stride: usize,
+    /// The offset of the value of a node from the start of the node, in bytes.
+    pub val_offset: usize,
+    /// The offset of the `size_t` subtree size of a node from the start of the node, in bytes.
+    pub subtree_size_offset: usize,
+}
+
+impl RawNodeBuffer {
+    /// Returns the location and layout of the given nodes, e.g. of [`PackedForest::raw_data`],
+    /// [`PackedTree::raw_data`] or [`NodeRef::raw_slice`].
+    #[inline]
+    pub fn from_nodes<T>(nodes: &[NodeData<T>]) -> RawNodeBuffer {
+        RawNodeBuffer {
+            nodes: nodes.as_ptr() as *const c_void,
+            len: nodes.len(),
+            stride: std::mem::size_of::<NodeData<T>>(),
+            val_offset: NodeData::<T>::VAL_OFFSET,
+            subtree_size_offset: NodeData::<T>::SUBTREE_SIZE_OFFSET,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads the node with the given index like foreign code would.
+    unsafe fn read_node(buffer: &RawNodeBuffer, index: usize) -> (u8, usize) {
+        let node = (buffer.nodes as *const u8).add(index * buffer.stride);
+        (*node.add(buffer.val_offset), *(node.add(buffer.subtree_size_offset) as *const usize))
+    }
+
+    #[test]
+    fn test_raw_node_buffer() {
+        let mut forest = PackedForest::new();
+        forest.build_tree(1u8, |node_builder| {
+            node_builder.build_child(2, |node_builder| {
+                node_builder.add_child(3);
+            });
+            node_builder.add_child(4);
+        });
+        forest.build_tree(5, |_| {});
+
+        let buffer = RawNodeBuffer::from_nodes(forest.raw_data());
+        assert_eq!(buffer.len, 5);
+        let nodes: Vec<_> = (0..buffer.len).map(|index| unsafe { read_node(&buffer, index) }).collect();
+        assert_eq!(nodes, [(1, 4), (2, 2), (3, 1), (4, 1), (5, 1)]);
+
+        // The children of the root, found by following the subtree sizes
+        let mut children = Vec::new();
+        let mut child = 1;
+        while child < nodes[0].1 {
+            children.push(nodes[child].0);
+            child += nodes[child].1;
+        }
+        assert_eq!(children, [2, 4]);
+
+        assert_eq!(RawNodeBuffer::from_nodes::<u8>(&[]).len, 0);
+    }
+}
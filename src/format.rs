@@ -0,0 +1,485 @@
+// This file contains TreeFormatter, which writes a tree as indented text,
+// optionally truncating it to a maximum depth and a maximum number of children per node,
+// and write_tree, which does the same without truncating or allocating. TreeFormatter can also write Markdown lists.
+// It also contains parse_indented, which reads indented text back into a tree.
+
+use crate::*;
+
+use std::convert::TryFrom;
+use std::fmt::{self, Debug, Display, Write};
+
+type ValFormatter<'f, T> = Box<dyn Fn(&T, &mut dyn Write) -> fmt::Result + 'f>;
+
+/// Writes a [`PackedTree`] or [`PackedForest`] as text, with one node per line, indented by its depth.
+///
+/// Unlike the [`Debug`] implementation of [`PackedTree`], the output can be truncated (see [`max_depth`](TreeFormatter::max_depth)
+/// and [`max_children`](TreeFormatter::max_children)), and the values can be written in a custom way
+/// (see [`val_formatter`](TreeFormatter::val_formatter)), which is useful when logging large trees.
+///
+/// # Example
+/// ```
+/// use packed_tree::{PackedTree, TreeFormatter};
+///
+/// let tree = PackedTree::new("root", |node_builder| {
+///     node_builder.build_child("a", |node_builder| {
+///         node_builder.add_child("a1");
+///     });
+///     node_builder.add_child("b");
+///     node_builder.add_child("c");
+///     node_builder.add_child("d");
+/// });
+///
+/// let formatter = TreeFormatter::new()
+///     .indent("- ")
+///     .max_depth(1)
+///     .max_children(2)
+///     .val_formatter(|val: &&str, f| f.write_str(&val.to_uppercase()));
+///
+/// let mut output = String::new();
+/// formatter.format(&tree, &mut output).unwrap();
+/// assert_eq!(output, "ROOT\n- A\n- - ... (1 more descendant)\n- B\n- ... (2 more children)\n");
+/// ```
+pub struct TreeFormatter<'f, T> {
+    indent: String,
+    bullet: String,
+    max_depth: Option<usize>,
+    max_children: Option<usize>,
+    val_formatter: ValFormatter<'f, T>,
+}
+
+impl<'f, T: Debug> Default for TreeFormatter<'f, T> {
+    #[inline(always)]
+    fn default() -> Self {
+        TreeFormatter::new()
+    }
+}
+
+impl<'f, T: Debug> TreeFormatter<'f, T> {
+    /// Create a new [`TreeFormatter`], which writes the values of the nodes using their [`Debug`] implementation,
+    /// indents each level by two spaces, and doesn't truncate the tree.
+    #[inline]
+    pub fn new() -> TreeFormatter<'f, T> {
+        TreeFormatter::with_val_formatter(|val, f| write!(f, "{:?}", val))
+    }
+}
+
+impl<'f, T: Display> TreeFormatter<'f, T> {
+    /// Create a new [`TreeFormatter`] that writes a nested Markdown list, where every node is a `- ` list item
+    /// indented by two spaces per level of depth, and the values of the nodes are written using their [`Display`] implementation.
+    /// This is also a valid list in org-mode.
+    ///
+    /// See also [`PackedForest::to_markdown_list`].
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedTree, TreeFormatter};
+    ///
+    /// let tree = PackedTree::new("Release", |node_builder| {
+    ///     node_builder.build_child("Features", |node_builder| {
+    ///         node_builder.add_child("Markdown lists");
+    ///     });
+    ///     node_builder.add_child("Fixes");
+    /// });
+    ///
+    /// let formatter = TreeFormatter::markdown_list()
+    ///     .indent("    ")
+    ///     .val_formatter(|val: &&str, f| write!(f, "**{}**", val));
+    /// let mut output = String::new();
+    /// formatter.format(&tree, &mut output).unwrap();
+    /// assert_eq!(output, "- **Release**\n    - **Features**\n        - **Markdown lists**\n    - **Fixes**\n");
+    /// ```
+    #[inline]
+    pub fn markdown_list() -> TreeFormatter<'f, T> {
+        TreeFormatter::with_val_formatter(|val, f| write!(f, "{}", val)).bullet("- ")
+    }
+}
+
+impl<'f, T> TreeFormatter<'f, T> {
+    /// Create a new [`TreeFormatter`] that writes the values of the nodes with the given closure.
+    /// Otherwise it's the same as [`TreeFormatter::new`], but it doesn't require `T` to implement [`Debug`].
+    #[inline]
+    pub fn with_val_formatter(val_formatter: impl Fn(&T, &mut dyn Write) -> fmt::Result + 'f) -> TreeFormatter<'f, T> {
+        TreeFormatter {
+            indent: "  ".to_string(),
+            bullet: String::new(),
+            max_depth: None,
+            max_children: None,
+            val_formatter: Box::new(val_formatter),
+        }
+    }
+
+    /// Set the string that is written once per level of depth before each node.
+    #[inline]
+    pub fn indent(mut self, indent: impl Into<String>) -> Self {
+        self.indent = indent.into();
+        self
+    }
+
+    /// Set the string that is written before each node, after the indentation, like the `- ` of a Markdown list item.
+    /// By default, nothing is written.
+    #[inline]
+    pub fn bullet(mut self, bullet: impl Into<String>) -> Self {
+        self.bullet = bullet.into();
+        self
+    }
+
+    /// Only write nodes up to the given depth, where the roots have depth 0.
+    /// The descendants of deeper nodes are summarized in a single line.
+    #[inline]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Only write the first `max_children` children of each node.
+    /// The remaining children are summarized in a single line.
+    #[inline]
+    pub fn max_children(mut self, max_children: usize) -> Self {
+        self.max_children = Some(max_children);
+        self
+    }
+
+    /// Set the closure that writes the value of a node.
+    #[inline]
+    pub fn val_formatter(mut self, val_formatter: impl Fn(&T, &mut dyn Write) -> fmt::Result + 'f) -> Self {
+        self.val_formatter = Box::new(val_formatter);
+        self
+    }
+
+    /// Write the given tree to `f`.
+    #[inline]
+    pub fn format(&self, tree: &PackedTree<T>, f: &mut impl Write) -> fmt::Result {
+        self.format_node(tree.root(), f)
+    }
+
+    /// Write all the trees in the given forest to `f`, one after the other.
+    pub fn format_forest(&self, forest: &PackedForest<T>, f: &mut impl Write) -> fmt::Result {
+        for tree in forest.iter_trees() {
+            self.format_node(tree, f)?;
+        }
+        Ok(())
+    }
+
+    /// Write the given node and its descendants to `f`, as if the node was the root of a tree.
+    #[inline]
+    pub fn format_node(&self, node: NodeRef<'_, T>, f: &mut impl Write) -> fmt::Result {
+        self.write_node(node, 0, f)
+    }
+
+    // Writes the indentation and the bullet of a line.
+    fn write_indent(&self, depth: usize, f: &mut dyn Write) -> fmt::Result {
+        for _ in 0..depth {
+            f.write_str(&self.indent)?;
+        }
+        f.write_str(&self.bullet)
+    }
+
+    fn write_node(&self, node: NodeRef<'_, T>, depth: usize, f: &mut dyn Write) -> fmt::Result {
+        self.write_indent(depth, f)?;
+        (self.val_formatter)(node.val(), f)?;
+        f.write_char('\n')?;
+
+        if node.num_descendants_excl_self() == 0 {
+            return Ok(());
+        }
+        if self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            let num_descendants = node.num_descendants_excl_self();
+            self.write_indent(depth + 1, f)?;
+            let noun = if num_descendants == 1 { "descendant" } else { "descendants" };
+            return writeln!(f, "... ({} more {})", num_descendants, noun);
+        }
+
+        let mut children = node.children();
+        for child in children.by_ref().take(self.max_children.unwrap_or(usize::MAX)) {
+            self.write_node(child, depth + 1, f)?;
+        }
+        let num_remaining = children.count();
+        if num_remaining > 0 {
+            self.write_indent(depth + 1, f)?;
+            let noun = if num_remaining == 1 { "child" } else { "children" };
+            writeln!(f, "... ({} more {})", num_remaining, noun)?;
+        }
+        Ok(())
+    }
+}
+
+// Writes a node and its descendants with one node per line, like TreeFormatter::new, but without allocating.
+fn write_node_lines<T>(
+    node: NodeRef<'_, T>,
+    depth: usize,
+    w: &mut dyn Write,
+    f: &mut dyn FnMut(&T, &mut dyn Write) -> fmt::Result,
+) -> fmt::Result {
+    for _ in 0..depth {
+        w.write_str("  ")?;
+    }
+    f(node.val(), w)?;
+    w.write_char('\n')?;
+    for child in node.children() {
+        write_node_lines(child, depth + 1, w, f)?;
+    }
+    Ok(())
+}
+
+impl<T> PackedForest<T> {
+    /// Write all the trees in this forest to `w`, with one node per line, indented by two spaces per level of depth.
+    /// The value of every node is written by calling `f` with the value and the writer.
+    ///
+    /// Nothing is allocated, so this can be used to write into any [`Write`] (like a [`String`] or a log buffer)
+    /// in a custom way without the overhead of [`TreeFormatter`], which can also truncate the output.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree(1, |node_builder| {
+    ///     node_builder.add_child(2);
+    /// });
+    /// forest.build_tree(3, |_| {});
+    ///
+    /// let mut output = String::new();
+    /// forest.write_tree(&mut output, |val, w| write!(w, "#{}", val)).unwrap();
+    /// assert_eq!(output, "#1\n  #2\n#3\n");
+    /// ```
+    pub fn write_tree(&self, w: &mut impl Write, mut f: impl FnMut(&T, &mut dyn Write) -> fmt::Result) -> fmt::Result {
+        for tree in self.iter_trees() {
+            write_node_lines(tree, 0, w, &mut f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Display> PackedForest<T> {
+    /// Returns the trees of this forest as a nested Markdown list, with a `- ` list item for every node,
+    /// indented by two spaces per level of depth.
+    ///
+    /// To change the indentation or how the values are written, see [`TreeFormatter::markdown_list`].
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree("a", |node_builder| {
+    ///     node_builder.add_child("b");
+    /// });
+    /// forest.build_tree("c", |_| {});
+    /// assert_eq!(forest.to_markdown_list(), "- a\n  - b\n- c\n");
+    /// ```
+    pub fn to_markdown_list(&self) -> String {
+        let mut output = String::new();
+        TreeFormatter::markdown_list().format_forest(self, &mut output).unwrap();
+        output
+    }
+}
+
+impl<T: Display> PackedTree<T> {
+    /// Returns this tree as a nested Markdown list, see [`PackedForest::to_markdown_list`].
+    #[inline]
+    pub fn to_markdown_list(&self) -> String {
+        self.as_ref().to_markdown_list()
+    }
+}
+
+impl<T> PackedTree<T> {
+    /// Write this tree to `w`, with one node per line, indented by two spaces per level of depth.
+    /// The value of every node is written by calling `f` with the value and the writer.
+    ///
+    /// See [`PackedForest::write_tree`].
+    #[inline]
+    pub fn write_tree(&self, w: &mut impl Write, mut f: impl FnMut(&T, &mut dyn Write) -> fmt::Result) -> fmt::Result {
+        write_node_lines(self.root(), 0, w, &mut f)
+    }
+}
+
+impl PackedForest<String> {
+    /// Parse indented text, like the output of [`write_tree`](PackedForest::write_tree) or [`TreeFormatter`],
+    /// into a forest with a node for every line that isn't empty.
+    ///
+    /// The depth of a line is the number of times `indent_unit` occurs at its start, and the rest of the line
+    /// is the value of the node. A line is a child of the last line before it that is one level less deep.
+    /// Lines that only contain whitespace are skipped.
+    ///
+    /// Returns a [`ParseError`] if the indentation of a line is not a whole number of indentation units (i.e. it is
+    /// followed by whitespace), or if a line is indented more than one level deeper than the line before it.
+    ///
+    /// See [`PackedTree::parse_indented`] for an example.
+    ///
+    /// # Panics
+    /// Panics if `indent_unit` is empty.
+    pub fn parse_indented(text: &str, indent_unit: &str) -> Result<PackedForest<String>, ParseError> {
+        parse_indented_lines(text, indent_unit).map(|(forest, _)| forest)
+    }
+}
+
+// Parses indented text like PackedForest::parse_indented, and also returns the line number of every node, in pre-order.
+fn parse_indented_lines(text: &str, indent_unit: &str) -> Result<(PackedForest<String>, Vec<usize>), ParseError> {
+    assert!(!indent_unit.is_empty(), "the indentation unit must not be empty");
+    let mut depth_values = Vec::new();
+    let mut line_numbers = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut val = line;
+        let mut depth = 0;
+        while let Some(rest) = val.strip_prefix(indent_unit) {
+            val = rest;
+            depth += 1;
+        }
+        if val.starts_with(char::is_whitespace) {
+            return Err(ParseError::InvalidIndentation { line: line_index + 1 });
+        }
+        depth_values.push((depth, val.to_string()));
+        line_numbers.push(line_index + 1);
+    }
+    let forest = PackedForest::from_depth_values(depth_values).map_err(|err| match err {
+        BuildError::InvalidDepth { index, depth, max_depth } => {
+            ParseError::InvalidDepth { line: line_numbers[index], depth, max_depth }
+        }
+    })?;
+    Ok((forest, line_numbers))
+}
+
+impl PackedTree<String> {
+    /// Parse indented text, like the output of [`write_tree`](PackedTree::write_tree) or [`TreeFormatter`],
+    /// into a tree with a node for every line that isn't empty.
+    ///
+    /// Returns [`ParseError::NotATree`] if there are no lines, or if more than one line isn't indented.
+    /// Otherwise this is the same as [`PackedForest::parse_indented`].
+    ///
+    /// # Panics
+    /// Panics if `indent_unit` is empty.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedTree, ParseError, TreeConvertError};
+    ///
+    /// let text = "\
+    /// project
+    ///     src
+    ///         main.rs
+    ///     README.md
+    /// ";
+    /// let tree = PackedTree::parse_indented(text, "    ").unwrap();
+    /// assert_eq!(tree.iter_flattened().collect::<Vec<_>>(), ["project", "src", "main.rs", "README.md"]);
+    ///
+    /// // Writing the tree with the same indentation gives back the same text
+    /// let mut output = String::new();
+    /// tree.write_tree(&mut output, |val, w| w.write_str(val)).unwrap();
+    /// assert_eq!(output.replace("  ", "    "), text);
+    ///
+    /// assert_eq!(
+    ///     PackedTree::parse_indented("a\n  b\nc", "  "),
+    ///     Err(ParseError::NotATree { error: TreeConvertError::MultipleRoots { count: 2 }, second_root_line: Some(3) })
+    /// );
+    /// ```
+    pub fn parse_indented(text: &str, indent_unit: &str) -> Result<PackedTree<String>, ParseError> {
+        let (forest, line_numbers) = parse_indented_lines(text, indent_unit)?;
+        // The second root comes right after the nodes of the first tree.
+        let second_root_line = forest.iter_trees().next().and_then(|root| line_numbers.get(root.num_descendants_incl_self()).copied());
+        PackedTree::try_from(forest).map_err(|error| ParseError::NotATree { error, second_root_line })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_tree() -> PackedTree<u32> {
+        PackedTree::new(0, |node_builder| {
+            node_builder.build_child(1, |node_builder| {
+                node_builder.build_child(2, |node_builder| {
+                    node_builder.add_child(3);
+                });
+                node_builder.add_child(4);
+            });
+            node_builder.add_child(5);
+            node_builder.add_child(6);
+        })
+    }
+
+    fn format(formatter: &TreeFormatter<u32>, tree: &PackedTree<u32>) -> String {
+        let mut output = String::new();
+        formatter.format(tree, &mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_format() {
+        let tree = build_tree();
+        assert_eq!(format(&TreeFormatter::new(), &tree), "0\n  1\n    2\n      3\n    4\n  5\n  6\n");
+        assert_eq!(
+            format(&TreeFormatter::new().max_depth(1), &tree),
+            "0\n  1\n    ... (3 more descendants)\n  5\n  6\n"
+        );
+        assert_eq!(
+            format(&TreeFormatter::new().max_children(1).indent("|"), &tree),
+            "0\n|1\n||2\n|||3\n||... (1 more child)\n|... (2 more children)\n"
+        );
+        assert_eq!(format(&TreeFormatter::new().max_depth(0).max_children(0), &tree), "0\n  ... (6 more descendants)\n");
+
+        let formatter = TreeFormatter::with_val_formatter(|val: &u32, f| write!(f, "<{}>", val)).max_children(0);
+        assert_eq!(format(&formatter, &tree), "<0>\n  ... (3 more children)\n");
+
+        let mut forest = PackedForest::new();
+        forest.add_single_node_tree(7);
+        forest.add_single_node_tree(8);
+        let mut output = String::new();
+        TreeFormatter::new().format_forest(&forest, &mut output).unwrap();
+        assert_eq!(output, "7\n8\n");
+    }
+
+    #[test]
+    fn test_write_tree() {
+        let tree = build_tree();
+        let mut expected = String::new();
+        TreeFormatter::new().format(&tree, &mut expected).unwrap();
+        let mut output = String::new();
+        tree.write_tree(&mut output, |val, w| write!(w, "{:?}", val)).unwrap();
+        assert_eq!(output, expected);
+
+        // The closure can keep state, and errors are passed on
+        let mut num_written = 0;
+        let result = tree.as_ref().write_tree(&mut String::new(), |val, w| {
+            num_written += 1;
+            if *val == 4 { Err(fmt::Error) } else { write!(w, "{}", val) }
+        });
+        assert!(result.is_err());
+        assert_eq!(num_written, 5);
+    }
+
+    #[test]
+    fn test_markdown_list() {
+        let tree = build_tree();
+        assert_eq!(tree.to_markdown_list(), "- 0\n  - 1\n    - 2\n      - 3\n    - 4\n  - 5\n  - 6\n");
+
+        // Truncated nodes are list items too
+        let mut output = String::new();
+        TreeFormatter::markdown_list().max_depth(1).max_children(2).format(&tree, &mut output).unwrap();
+        assert_eq!(output, "- 0\n  - 1\n    - ... (3 more descendants)\n  - 5\n  - ... (1 more child)\n");
+    }
+
+    #[test]
+    fn test_parse_indented() {
+        let tree = build_tree();
+        let mut text = String::new();
+        TreeFormatter::new().indent("\t").format(&tree, &mut text).unwrap();
+        let parsed = PackedTree::parse_indented(&text, "\t").unwrap();
+        assert_eq!(parsed.iter_flattened().map(|val| val.parse().unwrap()).collect::<Vec<u32>>(), tree.iter_flattened().copied().collect::<Vec<_>>());
+        assert!(parsed.raw_data().iter().zip(tree.raw_data()).all(|(a, b)| a.subtree_size() == b.subtree_size()));
+
+        // Empty lines are skipped, but count for the line numbers
+        let forest = PackedForest::parse_indented("a\n\n  b c\n   \nd\r\n", "  ").unwrap();
+        assert_eq!(forest.iter_flattened().collect::<Vec<_>>(), ["a", "b c", "d"]);
+        assert_eq!(forest.depths(), [0, 1, 0]);
+        assert_eq!(
+            PackedTree::parse_indented("a\n  b\n\nc\nd", "  "),
+            Err(ParseError::NotATree { error: TreeConvertError::MultipleRoots { count: 3 }, second_root_line: Some(4) })
+        );
+        assert_eq!(PackedTree::parse_indented("a\n\n   b", "  "), Err(ParseError::InvalidIndentation { line: 3 }));
+        assert_eq!(PackedTree::parse_indented("a\n\n    b", "  "), Err(ParseError::InvalidDepth { line: 3, depth: 2, max_depth: 1 }));
+        assert_eq!(PackedTree::parse_indented(" \n", "  "), Err(ParseError::NotATree { error: TreeConvertError::Empty, second_root_line: None }));
+    }
+}
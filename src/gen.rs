@@ -0,0 +1,194 @@
+//! Generators for random trees and forests, for use in tests and benchmarks.
+//!
+//! Requires the `test-util` feature.
+//!
+//! A tree is generated by a [`NodeCreator`], which decides the value of a node and creates the
+//! [`NodeCreator`]s of its children one by one. Two creators are provided: [`SimpleNodeCreator`],
+//! where every node at the same depth has the same number of children, and [`RandomNodeCreator`],
+//! where the number of children of a node is random.
+//!
+//! # Example
+//! ```
+//! use packed_tree::gen::{self, SimpleNodeCreator};
+//! use rand::SeedableRng;
+//!
+//! let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+//! let tree = gen::create_tree(SimpleNodeCreator::new(&[5, 5]), &mut rng);
+//! assert_eq!(tree.tot_num_nodes(), 1 + 5 + 5*5);
+//! ```
+
+use ::rand::distributions::{Distribution, Uniform};
+use ::rand::rngs::StdRng;
+use ::rand::{Rng, SeedableRng};
+
+use crate::*;
+
+/// Creates a node of a randomly generated tree, see the [module-level documentation](index.html).
+pub trait NodeCreator: Sized {
+    /// The type of the values of the created nodes.
+    type ValType;
+
+    /// The value of the node being created.
+    fn val(&self) -> Self::ValType;
+
+    /// Returns a [`NodeCreator`] for the next child of the node being created, or `None` if it has no more children.
+    fn next_child<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Option<Self>;
+}
+
+/// A [`NodeCreator`] for trees where at each depth, every node has the same number of children.
+/// The values of the nodes are random `u32`s, except for the root, which has value 1.
+#[derive(Clone, Debug)]
+pub struct SimpleNodeCreator<'a> {
+    val: u32,
+    depth: usize,
+    num_children_created: usize,
+    children_per_node_per_level: &'a [usize],
+}
+
+impl<'a> SimpleNodeCreator<'a> {
+    /// Create the [`NodeCreator`] of the root of a tree where every node at depth `i` has
+    /// `children_per_node_per_level[i]` children, and nodes at depth `children_per_node_per_level.len()` are leaves.
+    #[inline]
+    pub fn new(children_per_node_per_level: &'a [usize]) -> SimpleNodeCreator<'a> {
+        SimpleNodeCreator {
+            val: 1,
+            depth: 0,
+            num_children_created: 0,
+            children_per_node_per_level,
+        }
+    }
+}
+
+impl<'a> NodeCreator for SimpleNodeCreator<'a> {
+    type ValType = u32;
+
+    fn val(&self) -> Self::ValType {
+        self.val
+    }
+
+    fn next_child<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Option<Self> {
+        self.children_per_node_per_level.get(self.depth).and_then(|max_children| {
+            if self.num_children_created < *max_children {
+                self.num_children_created += 1;
+                Some(SimpleNodeCreator {
+                    val: rng.next_u32(),
+                    depth: self.depth + 1,
+                    num_children_created: 0,
+                    children_per_node_per_level: self.children_per_node_per_level,
+                })
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// A [`NodeCreator`] for trees where the number of children of each node is random.
+/// The values of the nodes are random `u32`s, except for the root, which has value 1.
+///
+/// A node at depth `i` keeps getting another child with probability `child_chance_per_level[i]`,
+/// so the number of children follows a geometric distribution.
+/// Nodes at depth `child_chance_per_level.len()` are leaves.
+#[derive(Clone, Debug)]
+pub struct RandomNodeCreator<'a> {
+    val: u32,
+    depth: usize,
+    child_chance_per_level: &'a [f64],
+}
+
+impl<'a> RandomNodeCreator<'a> {
+    /// Create the [`NodeCreator`] of the root of a random tree.
+    /// See [`RandomNodeCreator`] for the meaning of `child_chance_per_level`.
+    #[inline]
+    pub fn new(child_chance_per_level: &'a [f64]) -> RandomNodeCreator<'a> {
+        RandomNodeCreator {
+            val: 1,
+            depth: 0,
+            child_chance_per_level,
+        }
+    }
+}
+
+impl<'a> NodeCreator for RandomNodeCreator<'a> {
+    type ValType = u32;
+
+    fn val(&self) -> Self::ValType {
+        self.val
+    }
+
+    fn next_child<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Option<Self> {
+        self.child_chance_per_level.get(self.depth).and_then(|child_chance| {
+            let range = Uniform::new(0.0f64, 1.0);
+            if range.sample(rng) < *child_chance {
+                Some(RandomNodeCreator {
+                    val: rng.next_u32(),
+                    depth: self.depth + 1,
+                    child_chance_per_level: self.child_chance_per_level,
+                })
+            } else {
+                None
+            }
+        })
+    }
+}
+
+fn create_tree_rec<C: NodeCreator, R: Rng + ?Sized>(creator: &mut C, rng: &mut R, node_builder: &mut NodeBuilder<C::ValType>) {
+    while let Some(mut child_creator) = creator.next_child(rng) {
+        node_builder.build_child(child_creator.val(), |child_builder| {
+            create_tree_rec(&mut child_creator, rng, child_builder);
+        });
+    }
+}
+
+/// Create a tree using the given [`NodeCreator`] for its root.
+pub fn create_tree<C: NodeCreator, R: Rng + ?Sized>(mut creator: C, rng: &mut R) -> PackedTree<C::ValType> {
+    PackedTree::new(creator.val(), |node_builder| {
+        create_tree_rec(&mut creator, rng, node_builder);
+    })
+}
+
+/// Create a forest with one tree per given [`NodeCreator`].
+pub fn create_forest<C: NodeCreator, R: Rng + ?Sized>(creators: impl IntoIterator<Item = C>, rng: &mut R) -> PackedForest<C::ValType> {
+    let mut forest = PackedForest::new();
+    for mut creator in creators {
+        forest.build_tree(creator.val(), |node_builder| {
+            create_tree_rec(&mut creator, rng, node_builder);
+        });
+    }
+    forest
+}
+
+/// Create a tree using the given [`NodeCreator`] for its root, using a random number generator seeded with `seed`.
+/// The same seed always results in the same tree.
+pub fn create_tree_seeded<C: NodeCreator>(creator: C, seed: u64) -> PackedTree<C::ValType> {
+    create_tree(creator, &mut StdRng::seed_from_u64(seed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_node_creator() {
+        let tree = create_tree_seeded(SimpleNodeCreator::new(&[2, 3]), 1);
+        assert_eq!(tree.tot_num_nodes(), 1 + 2 + 2 * 3);
+        assert_eq!(tree.root().children().count(), 2);
+        assert!(tree.root().children().all(|child| child.children().count() == 3));
+    }
+
+    #[test]
+    fn test_random_node_creator() {
+        let creator = RandomNodeCreator::new(&[0.9, 0.5, 0.5]);
+        let tree = create_tree_seeded(creator.clone(), 7);
+        assert_eq!(tree, create_tree_seeded(creator, 7));
+        assert!(tree.depths().iter().all(|depth| *depth <= 3));
+    }
+
+    #[test]
+    fn test_create_forest() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let forest = create_forest(vec![SimpleNodeCreator::new(&[1]); 4], &mut rng);
+        assert_eq!(forest.iter_trees().count(), 4);
+        assert_eq!(forest.tot_num_nodes(), 8);
+    }
+}
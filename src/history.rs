@@ -0,0 +1,200 @@
+// This file contains ForestHistory, which keeps snapshots of a PackedForest for undo and redo.
+
+use crate::*;
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// A [`PackedForest`] together with a history of earlier versions of it, to implement undo and redo,
+/// e.g. in an editor that rebuilds its trees after every change.
+///
+/// [`checkpoint`](ForestHistory::checkpoint) records the current version, [`undo`](ForestHistory::undo) goes back
+/// to the last recorded version, and [`redo`](ForestHistory::redo) goes forward again. Recording a version doesn't
+/// copy the forest: the versions are shared through an [`Arc`], and the forest is only copied when it is changed
+/// in place with [`current_mut`](ForestHistory::current_mut) while an older version still refers to it.
+/// Replacing the forest with [`set`](ForestHistory::set) never copies it.
+///
+/// At most [`max_history`](ForestHistory::max_history) versions are kept for undo.
+/// When a new checkpoint would exceed that, the oldest version is discarded.
+///
+/// # Example
+/// ```
+/// use packed_tree::{ForestHistory, PackedForest};
+///
+/// let mut history = ForestHistory::new(PackedForest::new(), 10);
+///
+/// history.checkpoint();
+/// history.current_mut().add_single_node_tree(1);
+/// history.checkpoint();
+/// history.current_mut().add_single_node_tree(2);
+/// assert_eq!(history.current().iter_flattened().copied().collect::<Vec<_>>(), [1, 2]);
+///
+/// assert!(history.undo());
+/// assert_eq!(history.current().iter_flattened().copied().collect::<Vec<_>>(), [1]);
+/// assert!(history.undo());
+/// assert_eq!(history.current().tot_num_nodes(), 0);
+/// assert!(!history.undo());
+///
+/// assert!(history.redo());
+/// assert_eq!(history.current().iter_flattened().copied().collect::<Vec<_>>(), [1]);
+/// ```
+pub struct ForestHistory<T> {
+    current: Arc<PackedForest<T>>,
+    // The versions that undo goes back to, from the oldest to the newest.
+    undo_versions: VecDeque<Arc<PackedForest<T>>>,
+    // The versions that redo goes forward to, from the furthest to the nearest.
+    redo_versions: Vec<Arc<PackedForest<T>>>,
+    max_history: usize,
+}
+
+impl<T> ForestHistory<T> {
+    /// Create a new [`ForestHistory`] with the given forest as its current version and no history,
+    /// which keeps at most `max_history` versions for undo.
+    #[inline]
+    pub fn new(forest: PackedForest<T>, max_history: usize) -> ForestHistory<T> {
+        ForestHistory {
+            current: Arc::new(forest),
+            undo_versions: VecDeque::new(),
+            redo_versions: Vec::new(),
+            max_history,
+        }
+    }
+
+    /// Returns the current version of the forest.
+    #[inline(always)]
+    pub fn current(&self) -> &PackedForest<T> {
+        &self.current
+    }
+
+    /// Returns the current version of the forest, to change it in place.
+    ///
+    /// If a recorded version still shares the forest (e.g. right after [`checkpoint`](ForestHistory::checkpoint)),
+    /// the forest is copied first, so that the recorded version doesn't change.
+    #[inline]
+    pub fn current_mut(&mut self) -> &mut PackedForest<T> where T: Clone {
+        Arc::make_mut(&mut self.current)
+    }
+
+    /// Replaces the current version of the forest with the given forest, without recording the old one.
+    /// Call [`checkpoint`](ForestHistory::checkpoint) first to be able to undo this.
+    #[inline]
+    pub fn set(&mut self, forest: PackedForest<T>) {
+        self.current = Arc::new(forest);
+    }
+
+    /// Records the current version of the forest, so that [`undo`](ForestHistory::undo) can go back to it.
+    ///
+    /// This discards the versions that [`redo`](ForestHistory::redo) could go forward to, and the oldest recorded
+    /// version if more than [`max_history`](ForestHistory::max_history) versions would be kept.
+    pub fn checkpoint(&mut self) {
+        self.redo_versions.clear();
+        self.undo_versions.push_back(self.current.clone());
+        self.trim();
+    }
+
+    /// Goes back to the last recorded version. The current version can be restored with [`redo`](ForestHistory::redo).
+    ///
+    /// Returns `false` (and does nothing) if there is no recorded version.
+    pub fn undo(&mut self) -> bool {
+        match self.undo_versions.pop_back() {
+            Some(version) => {
+                self.redo_versions.push(std::mem::replace(&mut self.current, version));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Goes forward to the version that was current before the last [`undo`](ForestHistory::undo).
+    ///
+    /// Returns `false` (and does nothing) if there is no such version, because nothing was undone
+    /// or because a checkpoint was recorded since.
+    pub fn redo(&mut self) -> bool {
+        match self.redo_versions.pop() {
+            Some(version) => {
+                self.undo_versions.push_back(std::mem::replace(&mut self.current, version));
+                self.trim();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the number of times that [`undo`](ForestHistory::undo) can be called.
+    #[inline(always)]
+    pub fn num_undo_versions(&self) -> usize {
+        self.undo_versions.len()
+    }
+
+    /// Returns the number of times that [`redo`](ForestHistory::redo) can be called.
+    #[inline(always)]
+    pub fn num_redo_versions(&self) -> usize {
+        self.redo_versions.len()
+    }
+
+    /// Returns the maximum number of versions that are kept for undo.
+    #[inline(always)]
+    pub fn max_history(&self) -> usize {
+        self.max_history
+    }
+
+    /// Sets the maximum number of versions that are kept for undo, discarding the oldest ones if there are more.
+    #[inline]
+    pub fn set_max_history(&mut self, max_history: usize) {
+        self.max_history = max_history;
+        self.trim();
+    }
+
+    /// Discards all recorded versions.
+    #[inline]
+    pub fn clear_history(&mut self) {
+        self.undo_versions.clear();
+        self.redo_versions.clear();
+    }
+
+    /// Discards all recorded versions, and returns the current version of the forest.
+    pub fn into_current(mut self) -> PackedForest<T> {
+        self.clear_history();
+        // Only the recorded versions can share the forest, so it isn't shared anymore.
+        Arc::try_unwrap(self.current).unwrap_or_else(|_| unreachable!())
+    }
+
+    fn trim(&mut self) {
+        while self.undo_versions.len() > self.max_history {
+            self.undo_versions.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forest_history() {
+        let mut history = ForestHistory::new(PackedForest::new(), 2);
+        for i in 0..4 {
+            history.checkpoint();
+            history.current_mut().add_single_node_tree(i);
+        }
+        // Only the last 2 versions are kept
+        assert_eq!(history.num_undo_versions(), 2);
+        assert!(history.undo() && history.undo() && !history.undo());
+        assert_eq!(history.current().iter_flattened().copied().collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(history.num_redo_versions(), 2);
+
+        // Recording a version shares the forest until it is changed
+        history.checkpoint();
+        assert!(Arc::ptr_eq(&history.current, history.undo_versions.back().unwrap()));
+        assert_eq!(history.num_redo_versions(), 0);
+        history.set(PackedForest::new());
+        assert!(history.undo() && history.redo());
+        assert_eq!(history.current().tot_num_nodes(), 0);
+
+        history.set_max_history(0);
+        assert_eq!(history.num_undo_versions(), 0);
+        history.checkpoint();
+        assert!(!history.undo());
+        assert_eq!(history.into_current().tot_num_nodes(), 0);
+    }
+}
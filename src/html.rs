@@ -0,0 +1,190 @@
+// This file contains functions that write trees as nested HTML lists (<ul> and <li> elements),
+// e.g. to show a hierarchy on a web page.
+
+use crate::*;
+
+use std::fmt::{self, Display, Write};
+
+/// The label and the attributes of the `<li>` element of a node, which are set by the closure passed to
+/// [`PackedForest::write_html_list`]. Both are escaped when they are written, so they can contain any text.
+///
+/// The same [`HtmlItem`] is reused for every node, after it is cleared.
+#[derive(Clone, Debug, Default)]
+pub struct HtmlItem {
+    label: String,
+    attributes: Vec<(String, String)>,
+}
+
+impl HtmlItem {
+    /// Set the text of the element.
+    #[inline]
+    pub fn set_label(&mut self, label: impl Display) {
+        self.label.clear();
+        write!(self.label, "{}", label).unwrap();
+    }
+
+    /// Add an attribute to the element. The value is escaped, but the name is written as is.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid attribute name: it must not be empty, and must not contain whitespace,
+    /// control characters or any of the characters `"'<>/=`, which would let it add markup to the output.
+    #[inline]
+    pub fn add_attribute(&mut self, name: &str, value: impl Display) {
+        assert!(is_valid_attribute_name(name), "invalid HTML attribute name {:?}", name);
+        self.attributes.push((name.to_string(), value.to_string()));
+    }
+
+    fn clear(&mut self) {
+        self.label.clear();
+        self.attributes.clear();
+    }
+}
+
+// Whether the name can be written as the name of an attribute as is, see HtmlItem::add_attribute.
+fn is_valid_attribute_name(name: &str) -> bool {
+    !name.is_empty() && !name.chars().any(|c| c.is_whitespace() || c.is_control() || "\"'<>/=".contains(c))
+}
+
+// Writes the text with the characters that have a special meaning in HTML escaped,
+// so it can be used both as the content of an element and as the value of a quoted attribute.
+fn write_escaped(w: &mut dyn Write, text: &str) -> fmt::Result {
+    let mut rest = text;
+    while let Some(index) = rest.find(['&', '<', '>', '"', '\'']) {
+        w.write_str(&rest[..index])?;
+        w.write_str(match rest.as_bytes()[index] {
+            b'&' => "&amp;",
+            b'<' => "&lt;",
+            b'>' => "&gt;",
+            b'"' => "&quot;",
+            _ => "&#39;",
+        })?;
+        rest = &rest[index + 1..];
+    }
+    w.write_str(rest)
+}
+
+// Writes a list of nodes as a <ul> element, or nothing if there are no nodes.
+fn write_html_nodes<T>(
+    nodes: NodeIter<'_, T>,
+    w: &mut dyn Write,
+    item: &mut HtmlItem,
+    f: &mut dyn FnMut(&T, &mut HtmlItem),
+) -> fmt::Result {
+    if nodes.num_remaining_nodes_incl_descendants() == 0 {
+        return Ok(());
+    }
+    w.write_str("<ul>")?;
+    for node in nodes {
+        item.clear();
+        f(node.val(), item);
+        w.write_str("<li")?;
+        for (name, value) in &item.attributes {
+            write!(w, " {}=\"", name)?;
+            write_escaped(w, value)?;
+            w.write_char('"')?;
+        }
+        w.write_char('>')?;
+        write_escaped(w, &item.label)?;
+        write_html_nodes(node.children(), w, item, f)?;
+        w.write_str("</li>")?;
+    }
+    w.write_str("</ul>")
+}
+
+impl<T> PackedForest<T> {
+    /// Write the trees of this forest to `w` as a nested HTML list: a `<ul>` element with an `<li>` element for every root,
+    /// which contains the label of the node, followed by a `<ul>` element with the children of the node (if it has any).
+    /// Nothing is written if the forest is empty.
+    ///
+    /// The closure `f` is called for every node with its value, and sets the label and the attributes of its `<li>` element
+    /// (see [`HtmlItem`]), which are escaped, so they can safely contain any text.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let tree = PackedTree::new(("Services", true), |node_builder| {
+    ///     node_builder.add_child(("<web>", true));
+    ///     node_builder.add_child(("db & cache", false));
+    /// });
+    ///
+    /// let html = tree.to_html_list(|(name, healthy), item| {
+    ///     item.set_label(name);
+    ///     item.add_attribute("class", if *healthy { "ok" } else { "error" });
+    /// });
+    /// assert_eq!(html, concat!(
+    ///     r#"<ul><li class="ok">Services<ul>"#,
+    ///     r#"<li class="ok">&lt;web&gt;</li>"#,
+    ///     r#"<li class="error">db &amp; cache</li>"#,
+    ///     r#"</ul></li></ul>"#,
+    /// ));
+    /// ```
+    pub fn write_html_list(&self, w: &mut impl Write, mut f: impl FnMut(&T, &mut HtmlItem)) -> fmt::Result {
+        write_html_nodes(self.iter_trees(), w, &mut HtmlItem::default(), &mut f)
+    }
+
+    /// Returns the trees of this forest as a nested HTML list, see [`write_html_list`](PackedForest::write_html_list).
+    #[inline]
+    pub fn to_html_list(&self, f: impl FnMut(&T, &mut HtmlItem)) -> String {
+        let mut html = String::new();
+        self.write_html_list(&mut html, f).unwrap();
+        html
+    }
+}
+
+impl<T> PackedTree<T> {
+    /// Write this tree to `w` as a nested HTML list, see [`PackedForest::write_html_list`].
+    #[inline]
+    pub fn write_html_list(&self, w: &mut impl Write, f: impl FnMut(&T, &mut HtmlItem)) -> fmt::Result {
+        self.as_ref().write_html_list(w, f)
+    }
+
+    /// Returns this tree as a nested HTML list, see [`PackedForest::write_html_list`].
+    #[inline]
+    pub fn to_html_list(&self, f: impl FnMut(&T, &mut HtmlItem)) -> String {
+        self.as_ref().to_html_list(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_list() {
+        let mut forest = PackedForest::new();
+        assert_eq!(forest.to_html_list(|val: &&str, item| item.set_label(val)), "");
+
+        forest.build_tree("a", |node_builder| {
+            node_builder.build_child("b", |node_builder| {
+                node_builder.add_child("c");
+            });
+        });
+        forest.build_tree("'d\"", |_| {});
+        let html = forest.to_html_list(|val, item| {
+            item.set_label(val);
+            if val.len() > 1 {
+                item.add_attribute("title", val);
+                item.add_attribute("data-len", val.len());
+            }
+        });
+        assert_eq!(html, concat!(
+            "<ul><li>a<ul><li>b<ul><li>c</li></ul></li></ul></li>",
+            "<li title=\"&#39;d&quot;\" data-len=\"3\">&#39;d&quot;</li></ul>",
+        ));
+    }
+
+    #[test]
+    fn test_attribute_names() {
+        assert!(is_valid_attribute_name("data-len"));
+        assert!(is_valid_attribute_name("aria-label"));
+        for name in ["", "x onmouseover=alert(1)", "a\"b", "a>b", "a'b", "a<b", "a/b", "a=b", "a\tb", "a\u{0}b"] {
+            assert!(!is_valid_attribute_name(name), "{:?}", name);
+        }
+        let result = std::panic::catch_unwind(|| {
+            HtmlItem::default().add_attribute("x onmouseover=alert(1)", "");
+        });
+        assert!(result.is_err());
+    }
+}
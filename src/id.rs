@@ -0,0 +1,65 @@
+// This file contains NodeId, a typed pre-order index of a node, so that node indices
+// can't be mixed up with other indices.
+
+use std::fmt;
+
+/// The index of a node in a [`PackedForest`](crate::PackedForest) or [`PackedTree`](crate::PackedTree),
+/// in pre-order ordering (see [`PackedForest::get`](crate::PackedForest::get)).
+///
+/// Methods like [`get`](crate::PackedForest::get) and [`get_mut`](crate::PackedForest::get_mut) accept either a
+/// [`NodeId`] or a plain `usize`. Storing indices as [`NodeId`]s in your own data structures makes it impossible
+/// to mix them up with other indices by accident.
+///
+/// A [`NodeId`] is only meaningful for the forest it was created for: changing the structure of a forest changes
+/// the indices of its nodes.
+///
+/// # Example
+/// ```
+/// use packed_tree::{NodeId, PackedTree};
+///
+/// let tree = PackedTree::new("root", |node_builder| {
+///     node_builder.add_child("a");
+///     node_builder.add_child("b");
+/// });
+///
+/// let id = NodeId::new(2);
+/// assert_eq!(*tree.get(id).unwrap().val(), "b");
+/// assert_eq!(*tree.get(2).unwrap().val(), "b");
+/// assert_eq!(id.to_string(), "2");
+/// ```
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// Create a new [`NodeId`] for the node with the given pre-order index.
+    #[inline(always)]
+    pub const fn new(index: usize) -> NodeId {
+        NodeId(index)
+    }
+
+    /// Returns the pre-order index of the node.
+    #[inline(always)]
+    pub const fn index(self) -> usize {
+        self.0
+    }
+}
+
+impl From<usize> for NodeId {
+    #[inline(always)]
+    fn from(index: usize) -> NodeId {
+        NodeId(index)
+    }
+}
+
+impl From<NodeId> for usize {
+    #[inline(always)]
+    fn from(id: NodeId) -> usize {
+        id.0
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
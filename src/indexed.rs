@@ -0,0 +1,127 @@
+// This file contains WithIndices, an iterator adapter for lists of nodes that yields the index of every node
+// and knows how many nodes are remaining, e.g. to match the trees of a forest with data stored elsewhere.
+
+use crate::*;
+
+/// An iterator over a list of nodes that yields the index of every node together with the node,
+/// like [`Iterator::enumerate`], but which also knows how many nodes are remaining, so it implements [`ExactSizeIterator`].
+///
+/// See [`NodeIterMut::with_indices`], [`NodeListDrain::with_indices`] and [`NodeIter::with_indices`].
+#[derive(Clone, Debug)]
+pub struct WithIndices<I> {
+    iter: I,
+    next_index: usize,
+    num_remaining: usize,
+}
+
+impl<I> WithIndices<I> {
+    // `num_remaining` must be the number of items of `iter`.
+    #[inline(always)]
+    fn new(iter: I, num_remaining: usize) -> WithIndices<I> {
+        WithIndices { iter, next_index: 0, num_remaining }
+    }
+
+    /// Returns the index of the node that will be returned next, which is also the number of nodes returned so far.
+    #[inline(always)]
+    pub fn next_index(&self) -> usize {
+        self.next_index
+    }
+
+    /// Returns the number of nodes in the list, including the ones that have already been returned.
+    #[inline(always)]
+    pub fn num_nodes(&self) -> usize {
+        self.next_index + self.num_remaining
+    }
+
+    /// Returns the underlying iterator, e.g. to get [`NodeListDrain::num_remaining_nodes_incl_descendants`].
+    #[inline(always)]
+    pub fn get_ref(&self) -> &I {
+        &self.iter
+    }
+
+    /// Returns the underlying iterator.
+    #[inline(always)]
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+}
+
+impl<I: Iterator> Iterator for WithIndices<I> {
+    type Item = (usize, I::Item);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.iter.next()?;
+        let index = self.next_index;
+        self.next_index += 1;
+        self.num_remaining -= 1;
+        Some((index, item))
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.num_remaining, Some(self.num_remaining))
+    }
+}
+
+impl<I: Iterator> ExactSizeIterator for WithIndices<I> {}
+
+impl<'t, T> NodeIter<'t, T> {
+    /// Returns an iterator that yields the index of every node together with the node, and knows how many nodes are remaining.
+    ///
+    /// This counts the remaining nodes first, which takes O(n) time, where n is the number of remaining nodes
+    /// (not counting their descendants). See [`NodeIterMut::with_indices`] for an example.
+    #[inline]
+    pub fn with_indices(self) -> WithIndices<NodeIter<'t, T>> {
+        let num_remaining = self.num_remaining_nodes();
+        WithIndices::new(self, num_remaining)
+    }
+}
+
+impl<'t, T> NodeIterMut<'t, T> {
+    /// Returns an iterator that yields the index of every node together with the node, and knows how many nodes are remaining.
+    ///
+    /// This counts the remaining nodes first, which takes O(n) time, where n is the number of remaining nodes
+    /// (not counting their descendants).
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree(1, |node_builder| {
+    ///     node_builder.add_child(2);
+    /// });
+    /// forest.build_tree(3, |_| {});
+    ///
+    /// // Data about the trees that is stored outside of the forest
+    /// let offsets = [10, 20];
+    ///
+    /// let mut trees = forest.iter_trees_mut().with_indices();
+    /// assert_eq!(trees.len(), 2);
+    /// for (index, mut tree) in &mut trees {
+    ///     *tree.val_mut() += offsets[index];
+    /// }
+    /// assert_eq!(trees.num_nodes(), 2);
+    /// assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [11, 2, 23]);
+    /// ```
+    #[inline]
+    pub fn with_indices(self) -> WithIndices<NodeIterMut<'t, T>> {
+        let num_remaining = self.num_remaining_nodes();
+        WithIndices::new(self, num_remaining)
+    }
+}
+
+impl<'t, T> NodeListDrain<'t, T> {
+    /// Returns an iterator that yields the index of every node together with the node, and knows how many nodes are remaining.
+    ///
+    /// This counts the remaining nodes first, which takes O(n) time, where n is the number of remaining nodes
+    /// (not counting their descendants). See [`NodeIterMut::with_indices`] for an example.
+    ///
+    /// Like the [`NodeListDrain`] itself, the nodes that haven't been returned are dropped when the iterator is dropped.
+    #[inline]
+    pub fn with_indices(self) -> WithIndices<NodeListDrain<'t, T>> {
+        let num_remaining = self.num_remaining_nodes();
+        WithIndices::new(self, num_remaining)
+    }
+}
@@ -0,0 +1,325 @@
+// This file contains InternedPackedForest, a PackedForest that stores every distinct value only once.
+// The nodes store small indices into a table of values, which is deduplicated while the trees are built.
+
+use crate::*;
+
+use hashbrown::HashTable;
+use std::collections::hash_map::RandomState;
+use std::convert::TryFrom;
+use std::hash::{BuildHasher, Hash};
+
+#[derive(Default, Clone)]
+struct Interner<T> {
+    values: Vec<T>,
+    // The ids of the values. The values themselves are only stored in `values`, and are hashed with `hasher`.
+    ids: HashTable<u32>,
+    hasher: RandomState,
+}
+
+impl<T: Hash + Eq> Interner<T> {
+    fn intern(&mut self, val: T) -> u32 {
+        let hash = self.hasher.hash_one(&val);
+        let values = &self.values;
+        if let Some(id) = self.ids.find(hash, |id| values[*id as usize] == val) {
+            return *id;
+        }
+        let id = u32::try_from(values.len()).expect("more than u32::MAX distinct values");
+        let (values, hasher) = (&self.values, &self.hasher);
+        self.ids.insert_unique(hash, id, |id| hasher.hash_one(&values[*id as usize]));
+        self.values.push(val);
+        id
+    }
+}
+
+/// A variant of [`PackedForest`] that stores every distinct value only once.
+///
+/// Each node stores a `u32` id, which is an index into a table of distinct values (see [`values`](InternedPackedForest::values)).
+/// Values are deduplicated with their [`Hash`] and [`Eq`] implementations while the trees are built.
+/// A value gets the next free id when a node with that value is finished for the first time,
+/// so children get their ids before their parents.
+/// This saves a lot of memory for trees with many repeated values, like tag names or tokens.
+///
+/// # Example
+/// ```
+/// use packed_tree::InternedPackedForest;
+///
+/// let mut forest = InternedPackedForest::new();
+/// forest.build_tree("div".to_string(), |node_builder| {
+///     node_builder.add_child("span".to_string());
+///     node_builder.add_child("span".to_string());
+///     node_builder.add_child("div".to_string());
+/// });
+///
+/// assert_eq!(forest.tot_num_nodes(), 4);
+/// assert_eq!(forest.values(), ["span", "div"]);
+///
+/// let root = forest.iter_trees().next().unwrap();
+/// let children: Vec<_> = root.children().map(|child| (child.id(), child.val().as_str())).collect();
+/// assert_eq!(children, [(0, "span"), (0, "span"), (1, "div")]);
+/// ```
+#[derive(Default, Clone)]
+pub struct InternedPackedForest<T> {
+    forest: PackedForest<u32>,
+    interner: Interner<T>,
+}
+
+impl<T: Hash + Eq> InternedPackedForest<T> {
+    /// Create a new, empty [`InternedPackedForest`].
+    #[inline]
+    pub fn new() -> InternedPackedForest<T> {
+        InternedPackedForest {
+            forest: PackedForest::new(),
+            interner: Interner {
+                values: Vec::new(),
+                ids: HashTable::new(),
+                hasher: RandomState::new(),
+            },
+        }
+    }
+
+    /// Build a tree with the given root value, and add it to the forest.
+    ///
+    /// See [`PackedForest::build_tree`].
+    #[inline]
+    pub fn build_tree<R>(&mut self, root_val: T, node_builder_cb: impl FnOnce(&mut InternedNodeBuilder<T>) -> R) -> R {
+        let mut builder = self.get_tree_builder();
+        let ret = node_builder_cb(&mut builder);
+        builder.finish(root_val);
+        ret
+    }
+
+    /// Add a tree with only a single node to the forest. The parameter `val` is the value of that single node.
+    #[inline]
+    pub fn add_single_node_tree(&mut self, val: T) {
+        self.get_tree_builder().finish(val);
+    }
+
+    /// Get an [`InternedNodeBuilder`] that can be used to build a tree that will be added to this forest.
+    ///
+    /// See [`PackedForest::get_tree_builder`].
+    #[inline]
+    pub fn get_tree_builder(&mut self) -> InternedNodeBuilder<'_, T> {
+        InternedNodeBuilder {
+            sub_node_builder: self.forest.get_tree_builder(),
+            interner: &mut self.interner,
+        }
+    }
+
+    /// Returns the id of the given value, adding it to the table of values if it isn't in there yet.
+    ///
+    /// This is useful to compare values of nodes by their id (see [`InternedNodeRef::id`]).
+    #[inline]
+    pub fn intern(&mut self, val: T) -> u32 {
+        self.interner.intern(val)
+    }
+}
+
+impl<T> InternedPackedForest<T> {
+    /// Returns an iterator that iterates over all the trees in this forest.
+    #[inline(always)]
+    pub fn iter_trees(&self) -> InternedNodeIter<'_, T> {
+        InternedNodeIter {
+            sub_iter: self.forest.iter_trees(),
+            values: &self.interner.values,
+        }
+    }
+
+    /// Get an [`InternedNodeRef`] to the node with the given index, or `None` if the index is out of bounds.
+    ///
+    /// See [`PackedForest::get`].
+    #[inline(always)]
+    pub fn get(&self, index: usize) -> Option<InternedNodeRef<'_, T>> {
+        self.forest.get(index).map(|sub_ref| InternedNodeRef {
+            sub_ref,
+            values: &self.interner.values,
+        })
+    }
+
+    /// Returns the table of distinct values. The id of a value is its index in this slice.
+    #[inline(always)]
+    pub fn values(&self) -> &[T] {
+        &self.interner.values
+    }
+
+    /// Returns the [`PackedForest`] of ids that this forest stores internally.
+    #[inline(always)]
+    pub fn ids(&self) -> &PackedForest<u32> {
+        &self.forest
+    }
+
+    /// Returns how many nodes are currently in all the trees in this forest in O(1) time.
+    #[inline(always)]
+    pub fn tot_num_nodes(&self) -> usize {
+        self.forest.tot_num_nodes()
+    }
+}
+
+/// A struct that lets you add children to a node that is currently being added to an [`InternedPackedForest`].
+///
+/// See [`NodeBuilder`] for more information.
+pub struct InternedNodeBuilder<'a, T> {
+    sub_node_builder: NodeBuilder<'a, u32>,
+    interner: &'a mut Interner<T>,
+}
+
+impl<'a, T: Hash + Eq> InternedNodeBuilder<'a, T> {
+    /// Returns the index of the node that is being built.
+    #[inline(always)]
+    pub fn index(&self) -> usize {
+        self.sub_node_builder.index()
+    }
+
+    /// Build a child node with the given value, and add it to the tree as a child of the node
+    /// that is being built by the current [`InternedNodeBuilder`].
+    ///
+    /// See [`NodeBuilder::build_child`].
+    #[inline]
+    pub fn build_child<R>(&mut self, val: T, child_builder_cb: impl FnOnce(&mut InternedNodeBuilder<T>) -> R) -> R {
+        let mut builder = self.get_child_builder();
+        let ret = child_builder_cb(&mut builder);
+        builder.finish(val);
+        ret
+    }
+
+    /// Add a child node with the given value to the tree as a child of the node that is being built by the current [`InternedNodeBuilder`].
+    /// Returns the id of the value.
+    #[inline]
+    pub fn add_child(&mut self, val: T) -> u32 {
+        self.get_child_builder().finish(val)
+    }
+
+    /// Get an [`InternedNodeBuilder`] that builds a child that will be added as a child of the node
+    /// that is being built by the current [`InternedNodeBuilder`].
+    ///
+    /// See [`NodeBuilder::get_child_builder`].
+    #[inline]
+    pub fn get_child_builder<'b>(&'b mut self) -> InternedNodeBuilder<'b, T> {
+        InternedNodeBuilder {
+            sub_node_builder: self.sub_node_builder.get_child_builder(),
+            interner: self.interner,
+        }
+    }
+
+    /// Finish building the node that this [`InternedNodeBuilder`] was building, giving it its value
+    /// and adding its nodes to the forest or the parent [`InternedNodeBuilder`]. Returns the id of the value.
+    ///
+    /// See [`NodeBuilder::finish`].
+    #[inline]
+    pub fn finish(self, val: T) -> u32 {
+        let id = self.interner.intern(val);
+        self.sub_node_builder.finish(id);
+        id
+    }
+}
+
+/// Iterates a list of nodes in an [`InternedPackedForest`].
+///
+/// See [`NodeIter`].
+pub struct InternedNodeIter<'t, T> {
+    sub_iter: NodeIter<'t, u32>,
+    values: &'t [T],
+}
+
+// Not using #[derive(Copy)] because it adds the T:Copy bound, which is unnecessary
+impl<'t, T> Copy for InternedNodeIter<'t, T> {}
+
+// Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
+impl<'t, T> Clone for InternedNodeIter<'t, T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'t, T> Iterator for InternedNodeIter<'t, T> {
+    type Item = InternedNodeRef<'t, T>;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let values = self.values;
+        self.sub_iter.next().map(|sub_ref| InternedNodeRef { sub_ref, values })
+    }
+}
+
+/// A shared reference to a node in an [`InternedPackedForest`].
+pub struct InternedNodeRef<'t, T> {
+    sub_ref: NodeRef<'t, u32>,
+    values: &'t [T],
+}
+
+// Not using #[derive(Copy)] because it adds the T:Copy bound, which is unnecessary
+impl<'t, T> Copy for InternedNodeRef<'t, T> {}
+
+// Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
+impl<'t, T> Clone for InternedNodeRef<'t, T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'t, T> InternedNodeRef<'t, T> {
+    /// Returns an iterator to the children of this node.
+    #[inline(always)]
+    pub fn children(&self) -> InternedNodeIter<'t, T> {
+        InternedNodeIter {
+            sub_iter: self.sub_ref.children(),
+            values: self.values,
+        }
+    }
+
+    /// Returns a reference to the value of this node.
+    #[inline(always)]
+    pub fn val(&self) -> &'t T {
+        &self.values[self.id() as usize]
+    }
+
+    /// Returns the id of the value of this node. Nodes have the same id if and only if they have equal values.
+    #[inline(always)]
+    pub fn id(&self) -> u32 {
+        *self.sub_ref.val()
+    }
+
+    /// Counts the number of descendants of this node (also counting the node itself) in O(1) time.
+    #[inline(always)]
+    pub fn num_descendants_incl_self(&self) -> usize {
+        self.sub_ref.num_descendants_incl_self()
+    }
+
+    /// Counts the number of descendants of this node (not counting the node itself) in O(1) time.
+    #[inline(always)]
+    pub fn num_descendants_excl_self(&self) -> usize {
+        self.sub_ref.num_descendants_excl_self()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interned_forest() {
+        let mut forest = InternedPackedForest::new();
+        forest.build_tree("a", |node_builder| {
+            node_builder.build_child("b", |node_builder| {
+                assert_eq!(node_builder.add_child("a"), 0);
+                node_builder.add_child("c");
+            });
+            assert_eq!(node_builder.add_child("b"), 2);
+        });
+        forest.add_single_node_tree("c");
+        assert_eq!(forest.intern("d"), 3);
+        assert_eq!(forest.intern("b"), 2);
+
+        assert_eq!(forest.values(), ["a", "c", "b", "d"]);
+        assert_eq!(forest.tot_num_nodes(), 6);
+        assert_eq!(forest.ids().iter_flattened().copied().collect::<Vec<_>>(), [0, 2, 0, 1, 2, 1]);
+
+        let roots: Vec<_> = forest.iter_trees().map(|tree| *tree.val()).collect();
+        assert_eq!(roots, ["a", "c"]);
+        let node = forest.get(1).unwrap();
+        assert_eq!(node.children().map(|child| *child.val()).collect::<Vec<_>>(), ["a", "c"]);
+        assert_eq!(node.num_descendants_excl_self(), 2);
+        assert!(forest.get(6).is_none());
+    }
+}
@@ -0,0 +1,208 @@
+// This file contains LcaIndex, which answers lowest common ancestor queries on a PackedForest.
+
+use crate::*;
+
+/// An index over the nodes of a [`PackedForest`] (or [`PackedTree`]) that answers
+/// lowest common ancestor queries in O(1) time.
+///
+/// Building the index takes O(n log n) time and memory, where n is the number of nodes in the forest.
+/// It is implemented using an Euler tour of the forest and a sparse table for range minimum queries.
+///
+/// The index doesn't borrow the forest, and refers to nodes by their pre-order index (see [`PackedForest::get`]).
+/// It is only valid for the forest it was built from.
+///
+/// # Example
+/// ```
+/// use packed_tree::{PackedTree, LcaIndex};
+///
+/// let tree = PackedTree::new("root", |node_builder| {
+///     node_builder.build_child("a", |node_builder| {
+///         node_builder.add_child("a.1"); // index 2
+///         node_builder.add_child("a.2"); // index 3
+///     });
+///     node_builder.add_child("b"); // index 4
+/// });
+///
+/// let lca_index = LcaIndex::new(tree.as_ref());
+/// assert_eq!(lca_index.lca(2, 3), Some(1));
+/// assert_eq!(lca_index.lca(2, 4), Some(0));
+/// assert_eq!(lca_index.lca(1, 3), Some(1));
+/// ```
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct LcaIndex {
+    // The pre-order indices of the nodes in the order in which the Euler tour visits them.
+    euler_tour: Vec<usize>,
+    // For each node, the position of its first occurrence in euler_tour.
+    first_occurrence: Vec<usize>,
+    // For each node, its depth (0 for roots).
+    depths: Vec<usize>,
+    // For each node, the index of the root of the tree that it's in.
+    roots: Vec<usize>,
+    // sparse_table[k][i] is the node with the lowest depth in euler_tour[i .. i+2^k]
+    sparse_table: Vec<Vec<usize>>,
+}
+
+impl LcaIndex {
+    /// Build an [`LcaIndex`] for the given forest.
+    pub fn new<T>(forest: &PackedForest<T>) -> LcaIndex {
+        let num_nodes = forest.tot_num_nodes();
+        let mut euler_tour = Vec::with_capacity(2 * num_nodes);
+        let mut first_occurrence = vec![0; num_nodes];
+        let mut depths = vec![0; num_nodes];
+        let mut roots = vec![0; num_nodes];
+
+        // Stack of (node index, remaining children, index of the next child)
+        let mut stack = Vec::new();
+        let mut root_index = 0;
+        for root in forest.iter_trees() {
+            first_occurrence[root_index] = euler_tour.len();
+            euler_tour.push(root_index);
+            roots[root_index] = root_index;
+            stack.push((root_index, root.children(), root_index + 1));
+
+            while let Some((_, children, next_child_index)) = stack.last_mut() {
+                if let Some(child) = children.next() {
+                    let child_index = *next_child_index;
+                    *next_child_index += child.num_descendants_incl_self();
+
+                    first_occurrence[child_index] = euler_tour.len();
+                    euler_tour.push(child_index);
+                    depths[child_index] = stack.len();
+                    roots[child_index] = root_index;
+                    stack.push((child_index, child.children(), child_index + 1));
+                } else {
+                    stack.pop();
+                    if let Some((parent_index, _, _)) = stack.last() {
+                        euler_tour.push(*parent_index);
+                    }
+                }
+            }
+
+            root_index += root.num_descendants_incl_self();
+        }
+
+        let mut sparse_table = vec![euler_tour.clone()];
+        let mut width = 1;
+        while 2 * width <= euler_tour.len() {
+            let prev = sparse_table.last().unwrap();
+            let level = (0..(euler_tour.len() + 1 - 2 * width)).map(|i| {
+                let (a, b) = (prev[i], prev[i + width]);
+                if depths[a] <= depths[b] { a } else { b }
+            }).collect();
+            sparse_table.push(level);
+            width *= 2;
+        }
+
+        LcaIndex {
+            euler_tour,
+            first_occurrence,
+            depths,
+            roots,
+            sparse_table,
+        }
+    }
+
+    /// Returns the index of the lowest common ancestor of the nodes with indices `a` and `b` in O(1) time.
+    ///
+    /// A node counts as an ancestor of itself, so if `a` is an ancestor of `b`, the result is `a`.
+    ///
+    /// Returns `None` if one of the indices is out of bounds or if the nodes are in different trees.
+    pub fn lca(&self, a: usize, b: usize) -> Option<usize> {
+        if a >= self.num_nodes() || b >= self.num_nodes() || self.roots[a] != self.roots[b] {
+            return None;
+        }
+        let (a_pos, b_pos) = (self.first_occurrence[a], self.first_occurrence[b]);
+        let (start, end) = if a_pos <= b_pos { (a_pos, b_pos + 1) } else { (b_pos, a_pos + 1) };
+
+        // floor(log2(end - start))
+        let level = (usize::BITS - 1 - (end - start).leading_zeros()) as usize;
+        let width = 1 << level;
+        let left = self.sparse_table[level][start];
+        let right = self.sparse_table[level][end - width];
+        Some(if self.depths[left] <= self.depths[right] { left } else { right })
+    }
+
+    /// Returns the depth of the node with the given index (0 for the roots of the trees),
+    /// or `None` if the index is out of bounds.
+    #[inline]
+    pub fn depth(&self, index: usize) -> Option<usize> {
+        self.depths.get(index).copied()
+    }
+
+    /// Returns the number of nodes in the forest this index was built from.
+    #[inline(always)]
+    pub fn num_nodes(&self) -> usize {
+        self.depths.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Naive LCA by walking up parent indices.
+    fn naive_lca(parents: &[Option<usize>], a: usize, b: usize) -> Option<usize> {
+        let mut ancestors_of_a = vec![a];
+        while let Some(parent) = parents[*ancestors_of_a.last().unwrap()] {
+            ancestors_of_a.push(parent);
+        }
+        let mut cur = Some(b);
+        while let Some(node) = cur {
+            if ancestors_of_a.contains(&node) {
+                return Some(node);
+            }
+            cur = parents[node];
+        }
+        None
+    }
+
+    fn collect_parents(node: NodeRef<i32>, index: usize, parent: Option<usize>, parents: &mut Vec<Option<usize>>) {
+        parents[index] = parent;
+        let mut child_index = index + 1;
+        for child in node.children() {
+            collect_parents(child, child_index, Some(index), parents);
+            child_index += child.num_descendants_incl_self();
+        }
+    }
+
+    #[test]
+    fn test_lca_matches_naive() {
+        let mut forest = PackedForest::new();
+        for tree in 0..3 {
+            forest.build_tree(tree, |node_builder| {
+                node_builder.build_child(1, |node_builder| {
+                    node_builder.add_child(2);
+                    node_builder.build_child(3, |node_builder| {
+                        node_builder.add_child(4);
+                    });
+                });
+                node_builder.add_child(5);
+                node_builder.build_child(6, |node_builder| {
+                    node_builder.add_child(7);
+                });
+            });
+        }
+        forest.add_single_node_tree(8);
+
+        let mut parents = vec![None; forest.tot_num_nodes()];
+        let mut root_index = 0;
+        for root in forest.iter_trees() {
+            collect_parents(root, root_index, None, &mut parents);
+            root_index += root.num_descendants_incl_self();
+        }
+
+        let lca_index = LcaIndex::new(&forest);
+        for a in 0..forest.tot_num_nodes() {
+            for b in 0..forest.tot_num_nodes() {
+                assert_eq!(lca_index.lca(a, b), naive_lca(&parents, a, b), "lca({}, {})", a, b);
+            }
+        }
+        assert_eq!(lca_index.lca(0, forest.tot_num_nodes()), None);
+    }
+
+    #[test]
+    fn test_lca_empty() {
+        let lca_index = LcaIndex::new(&PackedForest::<i32>::new());
+        assert_eq!(lca_index.lca(0, 0), None);
+    }
+}
@@ -15,20 +15,20 @@
 //!
 //! // Create the tree
 //! let tree = PackedTree::new("the root node", |node_builder| {
-//! 	node_builder.add_child("a node without children");
-//! 	node_builder.build_child("a node with children", |node_builder| {
-//! 		node_builder.add_child("another node without children");
-//! 	});
+//!     node_builder.add_child("a node without children");
+//!     node_builder.build_child("a node with children", |node_builder| {
+//!         node_builder.add_child("another node without children");
+//!     });
 //! });
 //!
 //! // Iterate it, printing the values and counting the number of nodes
 //! fn count_num_nodes(node: NodeRef<&'static str>) -> usize {
-//! 	println!("Counting node \"{}\"", node.val());
-//! 	let mut result = 1;
-//! 	for child in node.children() {
-//! 		result += count_num_nodes(child);
-//! 	}
-//! 	result
+//!     println!("Counting node \"{}\"", node.val());
+//!     let mut result = 1;
+//!     for child in node.children() {
+//!         result += count_num_nodes(child);
+//!     }
+//!     result
 //! }
 //!
 //! assert_eq!(count_num_nodes(tree.root()), 4);
@@ -39,11 +39,94 @@ extern crate derive_destructure;
 
 mod core;
 mod tree;
+mod policy;
 mod exactsize;
 mod serde;
 mod test;
 mod extra;
+mod diff;
+mod lca;
+mod path;
+mod query;
+mod edit;
+mod error;
+mod node;
+mod random;
+mod format;
+mod shared;
+mod interned;
+mod small;
+mod array;
+mod stream;
+mod pattern;
+mod common;
+mod nested;
+mod map;
+mod view;
+mod pool;
+mod changes;
+mod unfold;
+mod stack;
+mod owned;
+mod writer;
+mod html;
+mod compress;
+mod indexed;
+mod zipper;
+mod persistent;
+mod history;
+mod editlist;
+mod id;
+mod reconcile;
+mod mapped;
+mod parallel;
+mod depth;
+
+#[cfg(any(feature = "test-util", test))]
+pub mod gen;
+
+#[cfg(any(feature = "proptest", test))]
+pub mod strategy;
+
+#[cfg(any(feature = "ffi", test))]
+pub mod ffi;
 
 pub use crate::core::*;
+pub use crate::policy::*;
 pub use crate::exactsize::*;
 pub use crate::tree::*;
+pub use crate::diff::*;
+pub use crate::lca::*;
+pub use crate::path::*;
+pub use crate::query::*;
+pub use crate::edit::*;
+pub use crate::error::*;
+pub use crate::node::*;
+pub use crate::format::*;
+pub use crate::shared::*;
+pub use crate::interned::*;
+pub use crate::small::*;
+pub use crate::array::*;
+pub use crate::stream::*;
+pub use crate::pattern::*;
+pub use crate::common::*;
+pub use crate::nested::*;
+pub use crate::map::*;
+pub use crate::view::*;
+pub use crate::pool::*;
+pub use crate::changes::*;
+pub use crate::stack::*;
+pub use crate::owned::*;
+pub use crate::writer::*;
+pub use crate::html::*;
+#[cfg(any(feature = "zstd", feature = "lz4_flex", test))]
+pub use crate::compress::*;
+pub use crate::indexed::*;
+pub use crate::zipper::*;
+pub use crate::persistent::*;
+pub use crate::history::*;
+pub use crate::editlist::*;
+pub use crate::id::*;
+pub use crate::reconcile::*;
+pub use crate::mapped::*;
+pub use crate::depth::*;
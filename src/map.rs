@@ -0,0 +1,165 @@
+// This file contains NodeMap, which stores extra data for every node of a forest, next to the forest.
+
+use crate::*;
+
+use std::mem::size_of;
+
+/// Stores a value of type `V` for every node of a [`PackedForest`], indexed by the pre-order indices of the nodes
+/// (see [`PackedForest::get`]), and accessible with a [`NodeRef`] to the node.
+///
+/// This is useful to annotate the nodes of a forest with data that is computed later, without rebuilding the forest.
+/// To use this with a [`PackedTree`], pass `tree.as_ref()`.
+///
+/// A [`NodeMap`] remembers where the nodes of the forest that it was created for are stored,
+/// so it must only be used with that forest, and the forest must not be changed while the map is used.
+/// Passing a node that is not stored in the memory of that forest returns `None`, but if the forest was changed
+/// (e.g. cleared and rebuilt), this isn't detected and the values of the wrong nodes may be returned.
+///
+/// # Example
+/// ```
+/// use packed_tree::{PackedTree, NodeMap};
+///
+/// let tree = PackedTree::new("root", |node_builder| {
+///     node_builder.add_child("a");
+///     node_builder.add_child("b");
+/// });
+///
+/// let mut visits = NodeMap::new(tree.as_ref());
+/// for child in tree.root().children() {
+///     *visits.get_mut(child).unwrap() += 1;
+/// }
+/// assert_eq!(visits.get(tree.root()), Some(&0));
+/// assert_eq!(visits.as_slice(), [0, 1, 1]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct NodeMap<V> {
+    values: Vec<V>,
+    // The address of the first node of the forest, and the size of a node in bytes.
+    forest_address: usize,
+    node_size: usize,
+}
+
+impl<V> NodeMap<V> {
+    /// Create a new [`NodeMap`] for the given forest, with the default value for every node.
+    #[inline]
+    pub fn new<T>(forest: &PackedForest<T>) -> NodeMap<V> where V: Default {
+        NodeMap::from_fn(forest, |_| V::default())
+    }
+
+    /// Create a new [`NodeMap`] for the given forest, where the value of every node is computed by `f`.
+    /// The nodes are passed to `f` in pre-order.
+    pub fn from_fn<'t, T>(forest: &'t PackedForest<T>, mut f: impl FnMut(NodeRef<'t, T>) -> V) -> NodeMap<V> {
+        let values = (0..forest.tot_num_nodes()).map(|index| f(forest.get(index).unwrap())).collect();
+        NodeMap {
+            values,
+            forest_address: forest.raw_data().as_ptr() as usize,
+            node_size: size_of::<NodeData<T>>(),
+        }
+    }
+
+    fn index_of<T>(&self, node: NodeRef<'_, T>) -> Option<usize> {
+        debug_assert_eq!(self.node_size, size_of::<NodeData<T>>(), "the node is of a different type than the forest of this NodeMap");
+        let offset = (node.raw_slice().as_ptr() as usize).checked_sub(self.forest_address)?;
+        let index = offset / self.node_size;
+        if index + node.num_descendants_incl_self() <= self.values.len() {
+            debug_assert!(offset.is_multiple_of(self.node_size));
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a reference to the value of the given node, or `None` if the node is not part of the forest of this map.
+    #[inline]
+    pub fn get<T>(&self, node: NodeRef<'_, T>) -> Option<&V> {
+        self.index_of(node).map(|index| &self.values[index])
+    }
+
+    /// Returns a mutable reference to the value of the given node, or `None` if the node is not part of the forest of this map.
+    #[inline]
+    pub fn get_mut<T>(&mut self, node: NodeRef<'_, T>) -> Option<&mut V> {
+        self.index_of(node).map(move |index| &mut self.values[index])
+    }
+
+    /// Returns a reference to the value of the node with the given pre-order index, or `None` if the index is out of bounds.
+    #[inline(always)]
+    pub fn get_by_index(&self, index: usize) -> Option<&V> {
+        self.values.get(index)
+    }
+
+    /// Returns a mutable reference to the value of the node with the given pre-order index, or `None` if the index is out of bounds.
+    #[inline(always)]
+    pub fn get_by_index_mut(&mut self, index: usize) -> Option<&mut V> {
+        self.values.get_mut(index)
+    }
+
+    /// Returns the number of values in this map, which is the number of nodes in its forest.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if the forest of this map has no nodes.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the values of all nodes, in pre-order.
+    #[inline(always)]
+    pub fn as_slice(&self) -> &[V] {
+        &self.values
+    }
+
+    /// Returns the values of all nodes, in pre-order, mutably.
+    #[inline(always)]
+    pub fn as_mut_slice(&mut self) -> &mut [V] {
+        &mut self.values
+    }
+
+    /// Returns the values of all nodes, in pre-order.
+    #[inline(always)]
+    pub fn into_vec(self) -> Vec<V> {
+        self.values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_map() {
+        let mut forest = PackedForest::new();
+        forest.build_tree("a", |node_builder| {
+            node_builder.build_child("b", |node_builder| {
+                node_builder.add_child("c");
+            });
+            node_builder.add_child("d");
+        });
+        forest.add_single_node_tree("e");
+
+        let mut sizes = NodeMap::from_fn(&forest, |node| node.num_descendants_incl_self());
+        assert_eq!(sizes.as_slice(), [4, 2, 1, 1, 1]);
+        assert_eq!(sizes.len(), 5);
+        for tree in forest.iter_trees() {
+            for child in tree.children() {
+                *sizes.get_mut(child).unwrap() *= 10;
+            }
+        }
+        assert_eq!(sizes.get(forest.get(1).unwrap()), Some(&20));
+        assert_eq!(sizes.get(forest.get(4).unwrap()), Some(&1));
+        assert_eq!(sizes.get_by_index(3), Some(&10));
+        assert_eq!(sizes.get_by_index(5), None);
+
+        // Nodes outside of the forest
+        let other = PackedTree::new("x", |_| {});
+        assert_eq!(sizes.get(other.root()), None);
+
+        let empty = PackedForest::<&str>::new();
+        let map = NodeMap::<u8>::new(&empty);
+        assert!(map.is_empty());
+        assert_eq!(map.get(forest.get(0).unwrap()), None);
+        assert!(map.into_vec().is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+// This file contains MappedNodeRef, which presents a projection of the values of a tree
+// (e.g. a single field) without building a new forest.
+
+use crate::*;
+
+/// A [`NodeRef`] whose value (and the values of its descendants) is seen through a projection `f`,
+/// e.g. to a single field of a larger value. See [`NodeRef::map_view`].
+///
+/// Nothing is copied: the projection is applied whenever a value is accessed.
+/// [`MappedNodeRef`] implements [`TreeNode`], so it can be passed to algorithms that are written for [`TreeNode`]s.
+pub struct MappedNodeRef<'t, T, F> {
+    node: NodeRef<'t, T>,
+    f: F,
+}
+
+// Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
+impl<'t, T, F: Clone> Clone for MappedNodeRef<'t, T, F> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        MappedNodeRef {
+            node: self.node,
+            f: self.f.clone(),
+        }
+    }
+}
+
+impl<'t, T, F: Copy> Copy for MappedNodeRef<'t, T, F> {}
+
+impl<'t, T, U: 't, F: Fn(&'t T) -> &'t U> MappedNodeRef<'t, T, F> {
+    /// Returns the projected value of this node.
+    #[inline(always)]
+    pub fn val(&self) -> &'t U {
+        (self.f)(self.node.val())
+    }
+
+    /// Returns an iterator over the children of this node, which have the same projection.
+    #[inline(always)]
+    pub fn children(&self) -> MappedNodeIter<'t, T, F> where F: Clone {
+        MappedNodeIter {
+            iter: self.node.children(),
+            f: self.f.clone(),
+        }
+    }
+
+    /// Returns the underlying [`NodeRef`], whose value isn't projected.
+    #[inline(always)]
+    pub fn node(&self) -> NodeRef<'t, T> {
+        self.node
+    }
+
+    /// Counts the number of descendants of this node (also counting the node itself).
+    #[inline(always)]
+    pub fn num_descendants_incl_self(&self) -> usize {
+        self.node.num_descendants_incl_self()
+    }
+}
+
+/// An iterator over [`MappedNodeRef`]s, see [`MappedNodeRef::children`].
+pub struct MappedNodeIter<'t, T, F> {
+    iter: NodeIter<'t, T>,
+    f: F,
+}
+
+// Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
+impl<'t, T, F: Clone> Clone for MappedNodeIter<'t, T, F> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        MappedNodeIter {
+            iter: self.iter,
+            f: self.f.clone(),
+        }
+    }
+}
+
+impl<'t, T, F: Clone> Iterator for MappedNodeIter<'t, T, F> {
+    type Item = MappedNodeRef<'t, T, F>;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.iter.next()?;
+        Some(MappedNodeRef {
+            node,
+            f: self.f.clone(),
+        })
+    }
+}
+
+impl<'t, T, U: 't, F: Fn(&'t T) -> &'t U + Clone> TreeNode for MappedNodeRef<'t, T, F> {
+    type Val = U;
+    type Child<'a> = MappedNodeRef<'t, T, F> where Self: 'a;
+    type Children<'a> = MappedNodeIter<'t, T, F> where Self: 'a;
+
+    #[inline(always)]
+    fn val(&self) -> &U {
+        MappedNodeRef::val(self)
+    }
+
+    #[inline(always)]
+    fn children(&self) -> MappedNodeIter<'t, T, F> {
+        MappedNodeRef::children(self)
+    }
+
+    #[inline(always)]
+    fn num_descendants_incl_self(&self) -> usize {
+        MappedNodeRef::num_descendants_incl_self(self)
+    }
+}
+
+impl<'t, T> NodeRef<'t, T> {
+    /// Returns a view of this node and its descendants where every value is projected by `f`,
+    /// without building a new tree. This is useful when only a part of every value is needed,
+    /// e.g. to pass it to code that works with any [`TreeNode`].
+    ///
+    /// `f` is cloned for every child that is visited, so it should be cheap to clone,
+    /// like closures that only capture references.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// struct Item {
+    ///     name: String,
+    ///     size: u64,
+    /// }
+    ///
+    /// let tree = PackedTree::new(Item { name: "root".to_string(), size: 0 }, |node_builder| {
+    ///     node_builder.add_child(Item { name: "a".to_string(), size: 10 });
+    ///     node_builder.add_child(Item { name: "b".to_string(), size: 20 });
+    /// });
+    ///
+    /// let names = tree.root().map_view(|item: &Item| &item.name);
+    /// assert_eq!(names.val(), "root");
+    /// assert_eq!(names.children().map(|child| child.val().as_str()).collect::<Vec<_>>(), ["a", "b"]);
+    /// ```
+    #[inline(always)]
+    pub fn map_view<U: 't, F: Fn(&'t T) -> &'t U>(self, f: F) -> MappedNodeRef<'t, T, F> {
+        MappedNodeRef { node: self, f }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_leaves<N: TreeNode<Val = i32>>(node: &N) -> usize {
+        if node.num_children() == 0 {
+            1
+        } else {
+            node.children().map(|child| count_leaves(&child)).sum()
+        }
+    }
+
+    #[test]
+    fn test_map_view() {
+        let tree = PackedTree::new((0, "root"), |node_builder| {
+            node_builder.build_child((1, "a"), |node_builder| {
+                node_builder.add_child((2, "a1"));
+            });
+            node_builder.add_child((3, "b"));
+        });
+
+        let view = tree.root().map_view(|(id, _): &(i32, &str)| id);
+        assert_eq!(*view.val(), 0);
+        assert_eq!(view.num_descendants_incl_self(), 4);
+        assert_eq!(count_leaves(&view), 2);
+        let a = view.children().next().unwrap();
+        assert_eq!(*a.children().next().unwrap().val(), 2);
+        assert_eq!(*a.node().val(), (1, "a"));
+        assert_eq!(TreeNode::num_children(&view), 2);
+    }
+}
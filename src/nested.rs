@@ -0,0 +1,352 @@
+// This file contains conversions between PackedTree and other tree types, in particular "naive" tree types
+// where every node owns a Vec of its children.
+
+use crate::*;
+
+/// Accessors for a "naive" tree node type, that stores a value and owns a [`Vec`] of its children, like:
+/// ```
+/// struct MyNode {
+///     value: String,
+///     children: Vec<MyNode>,
+/// }
+/// ```
+///
+/// Implementing this trait for such a type makes it usable with [`PackedTree::from_nested`] and [`PackedTree::to_nested`],
+/// through the blanket implementations of [`IntoPackedTree`] and [`FromPackedTree`].
+pub trait NestedNodeAccess: Sized {
+    /// The type of the value stored in every node.
+    type Value;
+
+    /// Returns a reference to the value of this node.
+    fn val(&self) -> &Self::Value;
+
+    /// Returns the children of this node.
+    fn children(&self) -> &[Self];
+
+    /// Creates a node with the given value and children.
+    fn from_val_and_children(val: Self::Value, children: Vec<Self>) -> Self;
+}
+
+/// A type that can be converted into a [`PackedTree`], see [`PackedTree::from_nested`].
+///
+/// This is implemented for references to any type that implements [`NestedNodeAccess`], if its values can be cloned.
+pub trait IntoPackedTree<T> {
+    /// Converts this into a [`PackedTree`].
+    fn into_packed_tree(self) -> PackedTree<T>;
+}
+
+/// A type that can be created from a [`PackedTree`], see [`PackedTree::to_nested`].
+///
+/// This is implemented for any type that implements [`NestedNodeAccess`], if its values can be cloned.
+pub trait FromPackedTree<T>: Sized {
+    /// Creates a value from the given [`PackedTree`].
+    fn from_packed_tree(tree: &PackedTree<T>) -> Self;
+}
+
+impl<N: NestedNodeAccess> IntoPackedTree<N::Value> for &N where N::Value: Clone {
+    #[inline]
+    fn into_packed_tree(self) -> PackedTree<N::Value> {
+        PackedTree::from_visitor(self)
+    }
+}
+
+impl<N: NestedNodeAccess> FromPackedTree<N::Value> for N where N::Value: Clone {
+    fn from_packed_tree(tree: &PackedTree<N::Value>) -> Self {
+        // This is done iteratively instead of recursively, so that deep trees can't overflow the stack.
+        // In reverse pre-order, the children of a node come right before the node itself, with the first child last,
+        // so when a node is reached, its children are on top of the stack, in order.
+        let mut stack: Vec<(N, usize)> = Vec::new();
+        for node_data in tree.raw_data().iter().rev() {
+            let subtree_size = node_data.subtree_size().get();
+            let mut num_descendants_left = subtree_size - 1;
+            let mut children = Vec::new();
+            while num_descendants_left > 0 {
+                let (child, child_subtree_size) = stack.pop().unwrap();
+                num_descendants_left -= child_subtree_size;
+                children.push(child);
+            }
+            stack.push((N::from_val_and_children(node_data.val().clone(), children), subtree_size));
+        }
+        debug_assert_eq!(stack.len(), 1);
+        stack.pop().unwrap().0
+    }
+}
+
+/// A simple tree type where every node owns a [`Vec`] of its children, for interoperability with
+/// APIs that need a tree of separately allocated nodes.
+///
+/// Use [`PackedTree::to_nested`] to convert a [`PackedTree`] into a [`NestedNode`], and [`PackedTree::from_nested`] to convert it back.
+///
+/// # Example
+/// ```
+/// use packed_tree::{PackedTree, NestedNode};
+///
+/// let tree = PackedTree::new(1, |node_builder| {
+///     node_builder.build_child(2, |node_builder| {
+///         node_builder.add_child(3);
+///     });
+///     node_builder.add_child(4);
+/// });
+///
+/// let nested: NestedNode<i32> = tree.to_nested();
+/// assert_eq!(nested.val, 1);
+/// assert_eq!(nested.children[0].children[0].val, 3);
+/// assert!(PackedTree::from_nested(&nested) == tree);
+/// ```
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct NestedNode<T> {
+    /// The value of this node.
+    pub val: T,
+    /// The children of this node.
+    pub children: Vec<NestedNode<T>>,
+}
+
+impl<T> NestedNode<T> {
+    /// Creates a node without children.
+    #[inline]
+    pub fn leaf(val: T) -> NestedNode<T> {
+        NestedNode { val, children: Vec::new() }
+    }
+}
+
+impl<T> NestedNodeAccess for NestedNode<T> {
+    type Value = T;
+
+    #[inline(always)]
+    fn val(&self) -> &T {
+        &self.val
+    }
+
+    #[inline(always)]
+    fn children(&self) -> &[NestedNode<T>] {
+        &self.children
+    }
+
+    #[inline(always)]
+    fn from_val_and_children(val: T, children: Vec<NestedNode<T>>) -> NestedNode<T> {
+        NestedNode { val, children }
+    }
+}
+
+/// A minimal interface to read a tree of any type: a node has a value and can visit its children.
+/// This is used by [`PackedTree::from_visitor`] to build a [`PackedTree`] from any other tree type.
+///
+/// The trait is implemented for [`NodeRef`] (with references to the values as values), and for
+/// references to types that implement [`NestedNodeAccess`] (with clones of the values as values).
+pub trait TreeLike: Sized {
+    /// The type of the values that the nodes of the resulting [`PackedTree`] will have.
+    type Val;
+
+    /// Calls `visitor` on each of the children of this node, in order.
+    fn visit_children(&self, visitor: impl FnMut(Self));
+
+    /// Returns the value of this node. This is called after the children of this node have been visited.
+    fn into_val(self) -> Self::Val;
+}
+
+impl<'t, T> TreeLike for NodeRef<'t, T> {
+    type Val = &'t T;
+
+    #[inline]
+    fn visit_children(&self, visitor: impl FnMut(Self)) {
+        self.children().for_each(visitor)
+    }
+
+    #[inline(always)]
+    fn into_val(self) -> &'t T {
+        self.val()
+    }
+}
+
+impl<N: NestedNodeAccess> TreeLike for &N where N::Value: Clone {
+    type Val = N::Value;
+
+    #[inline]
+    fn visit_children(&self, visitor: impl FnMut(Self)) {
+        self.children().iter().for_each(visitor)
+    }
+
+    #[inline(always)]
+    fn into_val(self) -> N::Value {
+        self.val().clone()
+    }
+}
+
+fn add_visited_children<N: TreeLike>(node: &N, node_builder: &mut NodeBuilder<N::Val>) {
+    node.visit_children(|child| {
+        node_builder.build_child_by_ret_val(|node_builder| {
+            add_visited_children(&child, node_builder);
+            child.into_val()
+        });
+    });
+}
+
+impl<T> PackedTree<T> {
+    /// Creates a [`PackedTree`] from a tree of another type, see [`IntoPackedTree`].
+    ///
+    /// This is the easiest way to convert a "naive" tree type, where every node owns a [`Vec`] of its children,
+    /// into a [`PackedTree`]: implement [`NestedNodeAccess`] for the node type and pass a reference to the root.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedTree, NestedNodeAccess};
+    ///
+    /// #[derive(PartialEq, Debug)]
+    /// struct MyNode {
+    ///     value: u32,
+    ///     children: Vec<MyNode>,
+    /// }
+    ///
+    /// impl NestedNodeAccess for MyNode {
+    ///     type Value = u32;
+    ///     fn val(&self) -> &u32 {
+    ///         &self.value
+    ///     }
+    ///     fn children(&self) -> &[MyNode] {
+    ///         &self.children
+    ///     }
+    ///     fn from_val_and_children(value: u32, children: Vec<MyNode>) -> MyNode {
+    ///         MyNode { value, children }
+    ///     }
+    /// }
+    ///
+    /// let my_root = MyNode::from_val_and_children(1, vec![
+    ///     MyNode::from_val_and_children(2, vec![MyNode::from_val_and_children(3, vec![])]),
+    ///     MyNode::from_val_and_children(4, vec![]),
+    /// ]);
+    ///
+    /// let tree = PackedTree::from_nested(&my_root);
+    /// assert_eq!(tree.iter_flattened().copied().collect::<Vec<_>>(), [1, 2, 3, 4]);
+    /// assert_eq!(tree.to_nested::<MyNode>(), my_root);
+    /// ```
+    #[inline]
+    pub fn from_nested(nested: impl IntoPackedTree<T>) -> PackedTree<T> {
+        nested.into_packed_tree()
+    }
+
+    /// Creates a [`PackedTree`] from any tree type that implements [`TreeLike`], starting from the given root node.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedTree, TreeLike};
+    ///
+    /// // A tree that is computed on the fly: every number n has the children n/2 and n/3, until 1
+    /// #[derive(Copy, Clone)]
+    /// struct Divisions(u32);
+    ///
+    /// impl TreeLike for Divisions {
+    ///     type Val = u32;
+    ///     fn visit_children(&self, mut visitor: impl FnMut(Divisions)) {
+    ///         if self.0 > 1 {
+    ///             visitor(Divisions(self.0 / 2));
+    ///             visitor(Divisions(self.0 / 3));
+    ///         }
+    ///     }
+    ///     fn into_val(self) -> u32 {
+    ///         self.0
+    ///     }
+    /// }
+    ///
+    /// let tree = PackedTree::from_visitor(Divisions(6));
+    /// assert_eq!(tree.iter_flattened().copied().collect::<Vec<_>>(), [6, 3, 1, 1, 2, 1, 0]);
+    /// ```
+    #[inline]
+    pub fn from_visitor<N: TreeLike<Val = T>>(root: N) -> PackedTree<T> {
+        PackedTree::new_by_ret_val(|node_builder| {
+            add_visited_children(&root, node_builder);
+            root.into_val()
+        })
+    }
+
+    /// Converts this tree into a tree of another type, see [`FromPackedTree`] and [`PackedTree::from_nested`].
+    ///
+    /// To convert into the [`NestedNode`] type that is provided by this crate, use `tree.to_nested::<NestedNode<_>>()`.
+    /// This doesn't use recursion, so it works for trees of any depth.
+    #[inline]
+    pub fn to_nested<N: FromPackedTree<T>>(&self) -> N {
+        N::from_packed_tree(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(PartialEq, Debug)]
+    struct Node {
+        value: String,
+        children: Vec<Node>,
+    }
+
+    impl NestedNodeAccess for Node {
+        type Value = String;
+
+        fn val(&self) -> &String {
+            &self.value
+        }
+
+        fn children(&self) -> &[Node] {
+            &self.children
+        }
+
+        fn from_val_and_children(value: String, children: Vec<Node>) -> Node {
+            Node { value, children }
+        }
+    }
+
+    fn node(value: &str, children: Vec<Node>) -> Node {
+        Node { value: value.to_string(), children }
+    }
+
+    #[test]
+    fn test_nested() {
+        let nested = node("a", vec![
+            node("b", vec![node("c", vec![]), node("d", vec![])]),
+            node("e", vec![]),
+            node("f", vec![node("g", vec![node("h", vec![])])]),
+        ]);
+        let tree = PackedTree::from_nested(&nested);
+        assert_eq!(tree.iter_flattened().map(|val| val.as_str()).collect::<Vec<_>>(), ["a", "b", "c", "d", "e", "f", "g", "h"]);
+        assert_eq!(tree.root().children().count(), 3);
+        assert_eq!(tree.to_nested::<Node>(), nested);
+
+        let leaf = node("x", vec![]);
+        let tree = PackedTree::from_nested(&leaf);
+        assert_eq!(tree.tot_num_nodes(), 1);
+        assert_eq!(tree.to_nested::<Node>(), leaf);
+    }
+
+    #[test]
+    fn test_nested_node() {
+        let tree = PackedTree::new(0, |node_builder| {
+            node_builder.build_child(1, |node_builder| {
+                node_builder.add_leaf_children(2..5);
+            });
+            node_builder.build_child(5, |node_builder| {
+                node_builder.build_child(6, |node_builder| {
+                    node_builder.add_child(7);
+                });
+            });
+        });
+        let nested = tree.to_nested::<NestedNode<_>>();
+        assert_eq!(nested, NestedNode {
+            val: 0,
+            children: vec![
+                NestedNode { val: 1, children: vec![NestedNode::leaf(2), NestedNode::leaf(3), NestedNode::leaf(4)] },
+                NestedNode { val: 5, children: vec![NestedNode { val: 6, children: vec![NestedNode::leaf(7)] }] },
+            ],
+        });
+        assert!(PackedTree::from_nested(&nested) == tree);
+        assert_eq!(PackedTree::new(8, |_| {}).to_nested::<NestedNode<_>>(), NestedNode::leaf(8));
+    }
+
+    #[test]
+    fn test_from_visitor() {
+        let nested = node("a", vec![node("b", vec![node("c", vec![])]), node("d", vec![])]);
+        let tree = PackedTree::from_visitor(&nested);
+        assert!(tree == PackedTree::from_nested(&nested));
+
+        let tree_of_refs = PackedTree::from_visitor(tree.root().children().next().unwrap());
+        assert_eq!(tree_of_refs.iter_flattened().map(|val| val.as_str()).collect::<Vec<_>>(), ["b", "c"]);
+    }
+}
@@ -0,0 +1,156 @@
+// This file contains the TreeNode trait, which lets generic code read trees
+// regardless of whether they're stored in a PackedForest or a MetaPackedForest.
+
+use crate::*;
+
+/// Read-only access to a node of a tree, implemented by [`NodeRef`] and [`MetaNodeRef`] (such as [`ExactSizeNodeRef`]),
+/// as well as shared references to [`NodeRefMut`] and [`MetaNodeRefMut`].
+///
+/// This makes it possible to write algorithms (printers, hashers, visitors, ...) once for all of these types.
+///
+/// The trait is implemented for `&NodeRefMut` rather than `NodeRefMut` itself, so that it doesn't shadow
+/// [`NodeRefMut::children`], which returns an iterator of mutable references.
+///
+/// # Example
+/// ```
+/// use packed_tree::{PackedTree, ExactSizePackedTree, TreeNode};
+///
+/// fn sum<N: TreeNode<Val = i32>>(node: &N) -> i32 {
+///     *node.val() + node.children().map(|child| sum(&child)).sum::<i32>()
+/// }
+///
+/// let tree = PackedTree::new(1, |node_builder| {
+///     node_builder.add_child(2);
+///     node_builder.add_child(3);
+/// });
+/// let exact_size_tree = ExactSizePackedTree::new(1, |node_builder| {
+///     node_builder.add_child(2);
+///     node_builder.add_child(3);
+/// });
+///
+/// assert_eq!(sum(&tree.root()), 6);
+/// assert_eq!(sum(&exact_size_tree.root()), 6);
+/// ```
+pub trait TreeNode {
+    /// The type of the values of the nodes.
+    type Val;
+
+    /// The type of the children of this node.
+    type Child<'a>: TreeNode<Val = Self::Val> where Self: 'a;
+
+    /// The type of the iterator over the children of this node.
+    type Children<'a>: Iterator<Item = Self::Child<'a>> where Self: 'a;
+
+    /// Returns a reference to the value of this node.
+    fn val(&self) -> &Self::Val;
+
+    /// Returns an iterator over the children of this node.
+    fn children(&self) -> Self::Children<'_>;
+
+    /// Counts the number of descendants of this node (also counting the node itself).
+    fn num_descendants_incl_self(&self) -> usize;
+
+    /// Counts the number of descendants of this node (not counting the node itself).
+    #[inline]
+    fn num_descendants_excl_self(&self) -> usize {
+        self.num_descendants_incl_self() - 1
+    }
+
+    /// Returns the number of children of this node.
+    #[inline]
+    fn num_children(&self) -> usize {
+        self.children().count()
+    }
+}
+
+impl<'t, T, P: NodePolicy> TreeNode for MetaNodeRef<'t, T, P> {
+    type Val = T;
+    type Child<'a> = MetaNodeRef<'t, T, P> where Self: 'a;
+    type Children<'a> = MetaNodeIter<'t, T, P> where Self: 'a;
+
+    #[inline(always)]
+    fn val(&self) -> &T {
+        MetaNodeRef::val(self)
+    }
+
+    #[inline(always)]
+    fn children(&self) -> MetaNodeIter<'t, T, P> {
+        MetaNodeRef::children(self)
+    }
+
+    #[inline(always)]
+    fn num_descendants_incl_self(&self) -> usize {
+        MetaNodeRef::num_descendants_incl_self(self)
+    }
+
+    #[inline(always)]
+    fn num_children(&self) -> usize {
+        MetaNodeRef::num_children(self)
+    }
+}
+
+impl<'b, 't, T, P: NodePolicy> TreeNode for &'b MetaNodeRefMut<'t, T, P> {
+    type Val = T;
+    type Child<'a> = MetaNodeRef<'b, T, P> where Self: 'a;
+    type Children<'a> = MetaNodeIter<'b, T, P> where Self: 'a;
+
+    #[inline(always)]
+    fn val(&self) -> &T {
+        MetaNodeRefMut::val(self)
+    }
+
+    #[inline(always)]
+    fn children(&self) -> MetaNodeIter<'b, T, P> {
+        self.reborrow_shared().children()
+    }
+
+    #[inline(always)]
+    fn num_descendants_incl_self(&self) -> usize {
+        MetaNodeRefMut::num_descendants_incl_self(self)
+    }
+
+    #[inline(always)]
+    fn num_children(&self) -> usize {
+        MetaNodeRefMut::num_children(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect_vals<N: TreeNode<Val = u32>>(node: &N, vals: &mut Vec<(u32, usize, usize)>) {
+        vals.push((*node.val(), node.num_children(), node.num_descendants_excl_self()));
+        for child in node.children() {
+            collect_vals(&child, vals);
+        }
+    }
+
+    fn all_vals<N: TreeNode<Val = u32>>(node: &N) -> Vec<(u32, usize, usize)> {
+        let mut vals = Vec::new();
+        collect_vals(node, &mut vals);
+        vals
+    }
+
+    #[test]
+    fn test_tree_node() {
+        let mut tree = PackedTree::new(1, |node_builder| {
+            node_builder.build_child(2, |node_builder| {
+                node_builder.add_child(3);
+            });
+            node_builder.add_child(4);
+        });
+        let mut exact_size_tree = ExactSizePackedTree::new(1, |node_builder| {
+            node_builder.build_child(2, |node_builder| {
+                node_builder.add_child(3);
+            });
+            node_builder.add_child(4);
+        });
+
+        let expected = [(1, 2, 3), (2, 1, 1), (3, 0, 0), (4, 0, 0)];
+        assert_eq!(all_vals(&tree.root()), expected);
+        assert_eq!(all_vals(&&tree.root_mut()), expected);
+        assert_eq!(all_vals(&exact_size_tree.root()), expected);
+        assert_eq!(all_vals(&&exact_size_tree.root_mut()), expected);
+    }
+}
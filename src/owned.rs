@@ -0,0 +1,167 @@
+// This file contains OwnedTreeBuilder, a builder for a single tree that owns its forest instead of borrowing it.
+
+use crate::*;
+
+/// Builds a tree like a [`NodeBuilder`], but owns the [`PackedForest`] it builds the tree in,
+/// and gives it back from [`finish`](OwnedTreeBuilder::finish).
+///
+/// A [`NodeBuilder`] borrows its forest (and the [`NodeBuilder`] of its parent), so it can't be stored in a struct
+/// or returned from a function. An [`OwnedTreeBuilder`] has no lifetime, so it can be kept in a state machine, held across
+/// an `.await`, or sent to another thread (if `T` is [`Send`]) while the tree is built bit by bit.
+///
+/// It is a thin wrapper around a [`TreeBuilderStack`] that always has the root of the tree open, so it builds exactly one tree.
+/// Use a [`TreeBuilderStack`] directly to build several trees at once.
+///
+/// The builder has a current node, which starts out as the root of the tree. [`begin_child`](OwnedTreeBuilder::begin_child)
+/// adds a child to the current node and makes it the current node, and [`end_child`](OwnedTreeBuilder::end_child)
+/// makes its parent the current node again.
+///
+/// # Example
+/// ```
+/// use packed_tree::{OwnedTreeBuilder, PackedTree};
+///
+/// fn start_document(title: &str) -> OwnedTreeBuilder<String> {
+///     let mut builder = OwnedTreeBuilder::new("document".to_string());
+///     builder.begin_child("header".to_string());
+///     builder.add_child(title.to_string());
+///     builder.end_child();
+///     builder
+/// }
+///
+/// let mut builder = start_document("Hello");
+/// builder.build_child("body".to_string(), |builder| {
+///     builder.add_child("text".to_string());
+/// });
+///
+/// let tree = PackedTree::try_from_forest(builder.finish()).unwrap();
+/// assert_eq!(tree.iter_flattened().collect::<Vec<_>>(), ["document", "header", "Hello", "body", "text"]);
+/// ```
+pub struct OwnedTreeBuilder<T> {
+    // The root of the tree is always open.
+    stack: TreeBuilderStack<T>,
+}
+
+impl<T> OwnedTreeBuilder<T> {
+    /// Create a new [`OwnedTreeBuilder`] that builds a tree with the given root value in a new, empty forest.
+    #[inline]
+    pub fn new(root_val: T) -> OwnedTreeBuilder<T> {
+        OwnedTreeBuilder::from_forest(PackedForest::new(), root_val)
+    }
+
+    /// Create a new [`OwnedTreeBuilder`] that builds a tree with the given root value,
+    /// which will be added to the end of the given forest.
+    #[inline]
+    pub fn from_forest(forest: PackedForest<T>, root_val: T) -> OwnedTreeBuilder<T> {
+        let mut stack = TreeBuilderStack::from_forest(forest);
+        stack.push(root_val);
+        OwnedTreeBuilder { stack }
+    }
+
+    /// Returns the depth of the current node, i.e. its distance from the root of the tree. The root itself has depth 0.
+    #[inline(always)]
+    pub fn depth(&self) -> usize {
+        self.stack.depth() - 1
+    }
+
+    /// Add a child node without children, with the given value, to the current node.
+    #[inline]
+    pub fn add_child(&mut self, val: T) {
+        self.stack.leaf(val);
+    }
+
+    /// Add a child node with the given value to the current node, and make it the current node,
+    /// so that the next nodes are added as its children until [`end_child`](OwnedTreeBuilder::end_child) is called.
+    #[inline]
+    pub fn begin_child(&mut self, val: T) {
+        self.stack.push(val);
+    }
+
+    /// Finish the current node, making its parent the current node again, and return a [`NodeRefMut`] to it.
+    /// Returns `None` (and does nothing) if the current node is the root.
+    #[inline]
+    pub fn end_child(&mut self) -> Option<NodeRefMut<'_, T>> {
+        if self.depth() == 0 {
+            return None;
+        }
+        self.stack.pop()
+    }
+
+    /// Add a child node with the given value to the current node, and call `child_builder_cb` to add its children,
+    /// like [`NodeBuilder::build_child`]. The value returned by `child_builder_cb` becomes the return value of this function.
+    ///
+    /// Children that `child_builder_cb` begins but doesn't end are ended when it returns.
+    #[inline]
+    pub fn build_child<R>(&mut self, val: T, child_builder_cb: impl FnOnce(&mut OwnedTreeBuilder<T>) -> R) -> R {
+        let depth = self.depth();
+        self.begin_child(val);
+        let ret = child_builder_cb(self);
+        while self.depth() > depth {
+            self.end_child();
+        }
+        ret
+    }
+
+    /// Finish all nodes, including the root, and return the forest with the new tree at the end.
+    #[inline]
+    pub fn finish(self) -> PackedForest<T> {
+        self.stack.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owned_tree_builder() {
+        let mut forest = PackedForest::new();
+        forest.add_single_node_tree(0);
+        let mut builder = OwnedTreeBuilder::from_forest(forest, 1);
+        assert!(builder.end_child().is_none());
+        builder.begin_child(2);
+        builder.add_child(3);
+        assert_eq!(builder.depth(), 1);
+
+        // The builder can be sent to another thread halfway through
+        let mut builder = std::thread::spawn(move || {
+            let node = builder.end_child().unwrap();
+            assert_eq!(node.num_descendants_incl_self(), 2);
+            builder
+        }).join().unwrap();
+
+        let ret = builder.build_child(4, |builder| {
+            builder.begin_child(5);
+            builder.add_child(6);
+            builder.depth()
+        });
+        assert_eq!(ret, 2);
+        builder.add_child(7);
+        assert_eq!(builder.depth(), 0);
+
+        let forest = builder.finish();
+        assert_eq!(forest.iter_trees().map(|tree| tree.num_descendants_incl_self()).collect::<Vec<_>>(), [1, 7]);
+        assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(forest.get(4).unwrap().children().count(), 1);
+    }
+
+    #[test]
+    fn test_owned_tree_builder_in_struct() {
+        fn assert_send<T: Send>(_: &T) {}
+
+        // The builder can be stored in a struct and returned from a function
+        struct Document {
+            builder: OwnedTreeBuilder<String>,
+        }
+        fn start_document() -> Document {
+            let mut builder = OwnedTreeBuilder::new("document".to_string());
+            builder.begin_child("body".to_string());
+            Document { builder }
+        }
+
+        let mut document = start_document();
+        assert_send(&document.builder);
+        document.builder.add_child("text".to_string());
+        let tree = PackedTree::try_from_forest(document.builder.finish()).unwrap();
+        assert_eq!(tree.iter_flattened().collect::<Vec<_>>(), ["document", "body", "text"]);
+    }
+}
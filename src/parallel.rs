@@ -0,0 +1,95 @@
+#![cfg(any(feature = "rayon", test))]
+
+// This file contains par_find and par_any, which search the nodes of a forest on multiple threads using rayon.
+// They require the `rayon` cargo feature.
+
+use crate::*;
+
+use rayon::prelude::*;
+
+impl<T: Sync> PackedForest<T> {
+    /// Returns the first node, in pre-order, for which `predicate` returns `true`, or `None` if there is no such node,
+    /// like [`find`](PackedForest::find), but calls `predicate` on multiple threads. Requires the `rayon` feature.
+    ///
+    /// The nodes are split into ranges of pre-order indices that are searched in parallel. Once a match is found,
+    /// the ranges after it are abandoned, but the ranges before it still have to be searched, since they may
+    /// contain an earlier match. Use [`par_any`](PackedForest::par_any) if any match will do.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "rayon")] {
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// for i in 0..1000 {
+    ///     forest.build_tree(i, |node_builder| {
+    ///         node_builder.add_child(i * 2);
+    ///     });
+    /// }
+    ///
+    /// let node = forest.par_find(|node| *node.val() > 10 && node.num_descendants_incl_self() == 1).unwrap();
+    /// assert_eq!(*node.val(), 12);
+    /// assert!(forest.par_any(|node| *node.val() == 1998));
+    /// # }
+    /// ```
+    pub fn par_find<'t>(&'t self, predicate: impl Fn(NodeRef<'t, T>) -> bool + Sync) -> Option<NodeRef<'t, T>> {
+        (0..self.tot_num_nodes())
+            .into_par_iter()
+            .map(|index| self.get(index).unwrap())
+            .find_first(|node| predicate(*node))
+    }
+
+    /// Returns `true` if `predicate` returns `true` for any node, calling `predicate` on multiple threads.
+    /// Requires the `rayon` feature.
+    ///
+    /// The search stops as soon as any thread finds a match, so unlike [`par_find`](PackedForest::par_find),
+    /// nodes before the match may be skipped. See [`par_find`](PackedForest::par_find) for an example.
+    pub fn par_any<'t>(&'t self, predicate: impl Fn(NodeRef<'t, T>) -> bool + Sync) -> bool {
+        (0..self.tot_num_nodes())
+            .into_par_iter()
+            .any(|index| predicate(self.get(index).unwrap()))
+    }
+}
+
+impl<T: Sync> PackedTree<T> {
+    /// Returns the first node, in pre-order, for which `predicate` returns `true`, or `None` if there is no such node,
+    /// calling `predicate` on multiple threads. Requires the `rayon` feature.
+    ///
+    /// See [`PackedForest::par_find`].
+    #[inline]
+    pub fn par_find<'t>(&'t self, predicate: impl Fn(NodeRef<'t, T>) -> bool + Sync) -> Option<NodeRef<'t, T>> {
+        self.as_ref().par_find(predicate)
+    }
+
+    /// Returns `true` if `predicate` returns `true` for any node, calling `predicate` on multiple threads.
+    /// Requires the `rayon` feature.
+    ///
+    /// See [`PackedForest::par_any`].
+    #[inline]
+    pub fn par_any<'t>(&'t self, predicate: impl Fn(NodeRef<'t, T>) -> bool + Sync) -> bool {
+        self.as_ref().par_any(predicate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_par_find() {
+        let forest = PackedForest::unfold(0..200, |n: &u32| (*n, if *n < 100_000 { vec![n * 10 + 1, n * 10 + 2] } else { vec![] }));
+        for target in [0, 5, 199, 12_345, 1_999_999, 5_000_000] {
+            let expected = forest.find(|node| *node.val() % 1000 == target % 1000 && *node.val() >= target);
+            let found = forest.par_find(|node| *node.val() % 1000 == target % 1000 && *node.val() >= target);
+            assert_eq!(found.map(|node| *node.val()), expected.map(|node| *node.val()));
+            assert_eq!(forest.par_any(|node| *node.val() == target), forest.find(|node| *node.val() == target).is_some());
+        }
+
+        let tree = PackedTree::new(1, |node_builder| {
+            node_builder.add_child(2);
+        });
+        assert_eq!(*tree.par_find(|node| *node.val() > 1).unwrap().val(), 2);
+        assert!(!tree.par_any(|node| *node.val() > 2));
+        assert!(PackedForest::<u32>::new().par_find(|_| true).is_none());
+    }
+}
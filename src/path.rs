@@ -0,0 +1,606 @@
+// This file contains functions for looking up nodes by a path of keys, like "a/b/c",
+// where each key identifies a child of the previous node.
+
+use crate::*;
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+
+use smallvec::SmallVec;
+
+/// The separator between the keys in the paths used by [`NodeRef::lookup_path`], [`PathIndex`], etc.
+pub const PATH_SEPARATOR: char = '/';
+
+fn path_keys(path: &str) -> impl Iterator<Item = &str> {
+    path.split(PATH_SEPARATOR).filter(|key| !key.is_empty())
+}
+
+impl<'t, T> NodeRef<'t, T> {
+    /// Returns the descendant of this node that is reached by following the given path of keys,
+    /// where `key_fn` returns the key of a node. At each level, the first child with the right key is chosen.
+    ///
+    /// Keys in the path are separated by [`PATH_SEPARATOR`] (`'/'`). Empty keys are ignored,
+    /// so the paths `"a/b"`, `"/a/b/"` and `"a//b"` are equivalent, and the empty path refers to this node.
+    pub fn lookup_path_by(&self, path: &str, key_fn: impl Fn(&T) -> &str) -> Option<NodeRef<'t, T>> {
+        let mut node = *self;
+        for key in path_keys(path) {
+            node = node.children().find(|child| key_fn(child.val()) == key)?;
+        }
+        Some(node)
+    }
+
+    /// Returns the descendant of this node that is reached by following the given path of keys, where the key of a node is its value.
+    ///
+    /// See [`lookup_path_by`](NodeRef::lookup_path_by).
+    #[inline]
+    pub fn lookup_path(&self, path: &str) -> Option<NodeRef<'t, T>> where T: Borrow<str> {
+        self.lookup_path_by(path, |val| val.borrow())
+    }
+}
+
+impl<T> PackedTree<T> {
+    /// Returns the node that is reached from the root by following the given path of keys,
+    /// where `key_fn` returns the key of a node. The key of the root itself is not part of the path.
+    ///
+    /// See [`NodeRef::lookup_path_by`].
+    #[inline]
+    pub fn lookup_path_by(&self, path: &str, key_fn: impl Fn(&T) -> &str) -> Option<NodeRef<'_, T>> {
+        self.root().lookup_path_by(path, key_fn)
+    }
+
+    /// Returns the node that is reached from the root by following the given path of keys, where the key of a node is its value.
+    /// The key of the root itself is not part of the path.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let tree = PackedTree::new("", |node_builder| {
+    ///     node_builder.build_child("etc", |node_builder| {
+    ///         node_builder.add_child("hosts");
+    ///     });
+    /// });
+    ///
+    /// assert_eq!(tree.lookup_path("etc/hosts").map(|node| *node.val()), Some("hosts"));
+    /// assert!(tree.lookup_path("etc/passwd").is_none());
+    /// ```
+    #[inline]
+    pub fn lookup_path(&self, path: &str) -> Option<NodeRef<'_, T>> where T: Borrow<str> {
+        self.root().lookup_path(path)
+    }
+}
+
+/// A map from the paths of the nodes in a [`PackedTree`] to their indices, for repeated path lookups in O(1) time
+/// (not counting hashing the path).
+///
+/// The paths are the same as those of [`PackedTree::lookup_path`], with the keys joined by [`PATH_SEPARATOR`].
+/// When multiple children of a node have the same key, the path refers to the first one, just like for [`PackedTree::lookup_path`].
+///
+/// Nodes whose key is empty or contains [`PATH_SEPARATOR`] can't be reached by [`PackedTree::lookup_path`], so they
+/// aren't in the index either, and neither are their descendants.
+///
+/// The index doesn't borrow the tree, and is only valid for the tree it was built from.
+#[derive(Eq, PartialEq, Clone, Debug, Default)]
+pub struct PathIndex {
+    indices: HashMap<String, usize>,
+}
+
+impl PathIndex {
+    /// Build a [`PathIndex`] of the given tree, where `key_fn` returns the key of a node.
+    pub fn new_by<T>(tree: &PackedTree<T>, key_fn: impl Fn(&T) -> &str) -> PathIndex {
+        fn add_children<T>(node: NodeRef<T>, index: usize, path: &mut String, indices: &mut HashMap<String, usize>, key_fn: &impl Fn(&T) -> &str) {
+            let mut child_index = index + 1;
+            for child in node.children() {
+                let key = key_fn(child.val());
+                if !key.is_empty() && !key.contains(PATH_SEPARATOR) {
+                    let path_len = path.len();
+                    if path_len > 0 {
+                        path.push(PATH_SEPARATOR);
+                    }
+                    path.push_str(key);
+                    indices.entry(path.clone()).or_insert(child_index);
+                    add_children(child, child_index, path, indices, key_fn);
+                    path.truncate(path_len);
+                }
+                child_index += child.num_descendants_incl_self();
+            }
+        }
+
+        let mut indices = HashMap::with_capacity(tree.tot_num_nodes());
+        indices.insert(String::new(), 0);
+        add_children(tree.root(), 0, &mut String::new(), &mut indices, &key_fn);
+        PathIndex { indices }
+    }
+
+    /// Build a [`PathIndex`] of the given tree, where the key of a node is its value.
+    #[inline]
+    pub fn new<T: Borrow<str>>(tree: &PackedTree<T>) -> PathIndex {
+        PathIndex::new_by(tree, |val| val.borrow())
+    }
+
+    /// Returns the index of the node with the given path, or `None` if there is no such node.
+    ///
+    /// The path is normalized like in [`NodeRef::lookup_path_by`] before looking it up.
+    pub fn get(&self, path: &str) -> Option<usize> {
+        let mut normalized = String::with_capacity(path.len());
+        for key in path_keys(path) {
+            if !normalized.is_empty() {
+                normalized.push(PATH_SEPARATOR);
+            }
+            normalized.push_str(key);
+        }
+        self.indices.get(&normalized).copied()
+    }
+
+    /// Returns the number of distinct paths in this index.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Returns `true` if this index contains no paths, which only happens for a default-constructed [`PathIndex`].
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
+
+/// Iterates over the values of the nodes on the path from the root of a tree to a given node,
+/// see [`PackedForest::path_to`].
+pub struct PathTo<'t, T> {
+    // The nodes that may contain the target node, and the index of the first of them
+    candidates: NodeIter<'t, T>,
+    candidates_index: usize,
+    target_index: usize,
+}
+
+// Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
+impl<'t, T> Clone for PathTo<'t, T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        PathTo {
+            candidates: self.candidates,
+            candidates_index: self.candidates_index,
+            target_index: self.target_index,
+        }
+    }
+}
+
+impl<'t, T> Iterator for PathTo<'t, T> {
+    type Item = &'t T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for node in self.candidates.by_ref() {
+            if self.target_index < self.candidates_index {
+                // The target was the previous node on the path, so the path has ended.
+                break;
+            }
+            let subtree_end = self.candidates_index + node.num_descendants_incl_self();
+            if self.target_index < subtree_end {
+                // The target is this node or one of its descendants, so the path continues with its children.
+                self.candidates = node.children();
+                self.candidates_index += 1;
+                return Some(node.val());
+            }
+            self.candidates_index = subtree_end;
+        }
+        None
+    }
+}
+
+/// Iterates over the paths from a node to each of the leaves below it, see [`NodeRef::paths_to_leaves`].
+///
+/// Use [`next_path`](PathsToLeaves::next_path) to get the paths without allocating memory for each of them.
+pub struct PathsToLeaves<'t, T> {
+    root: Option<NodeRef<'t, T>>,
+    // The values of the nodes on the current path, and the remaining children of each of those nodes
+    path: Vec<&'t T>,
+    remaining_children: Vec<NodeIter<'t, T>>,
+}
+
+impl<'t, T> PathsToLeaves<'t, T> {
+    /// Returns the values of the nodes on the path to the next leaf, or `None` if there are no more leaves.
+    ///
+    /// Unlike [`next`](Iterator::next), this doesn't allocate a [`Vec`] for every path,
+    /// since the returned slice borrows from the iterator.
+    pub fn next_path(&mut self) -> Option<&[&'t T]> {
+        if let Some(root) = self.root.take() {
+            self.path.push(root.val());
+            self.remaining_children.push(root.children());
+            if root.num_descendants_excl_self() == 0 {
+                return Some(&self.path);
+            }
+        }
+        while let Some(children) = self.remaining_children.last_mut() {
+            match children.next() {
+                Some(child) => {
+                    self.path.push(child.val());
+                    self.remaining_children.push(child.children());
+                    if child.num_descendants_excl_self() == 0 {
+                        return Some(&self.path);
+                    }
+                }
+                None => {
+                    self.path.pop();
+                    self.remaining_children.pop();
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<'t, T> Iterator for PathsToLeaves<'t, T> {
+    type Item = Vec<&'t T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_path().map(|path| path.to_vec())
+    }
+}
+
+impl<'t, T> NodeRef<'t, T> {
+    /// Returns an iterator over the paths from this node to each of the leaves below it, in pre-order.
+    /// Each path consists of the values of the nodes on it, starting with the value of this node and ending with the value of the leaf.
+    /// If this node is a leaf, there is a single path containing only this node.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let tree = PackedTree::new("config", |node_builder| {
+    ///     node_builder.build_child("server", |node_builder| {
+    ///         node_builder.add_child("host");
+    ///         node_builder.add_child("port");
+    ///     });
+    ///     node_builder.add_child("debug");
+    /// });
+    ///
+    /// let mut paths = tree.root().paths_to_leaves();
+    /// let mut keys = Vec::new();
+    /// while let Some(path) = paths.next_path() {
+    ///     keys.push(path.iter().map(|key| **key).collect::<Vec<_>>().join("."));
+    /// }
+    /// assert_eq!(keys, ["config.server.host", "config.server.port", "config.debug"]);
+    /// ```
+    #[inline]
+    pub fn paths_to_leaves(&self) -> PathsToLeaves<'t, T> {
+        PathsToLeaves {
+            root: Some(*self),
+            path: Vec::new(),
+            remaining_children: Vec::new(),
+        }
+    }
+}
+
+/// The indices of the children that are followed to get from a node to one of its descendants,
+/// see [`NodeRef::iter_with_paths`].
+pub type ChildIndexPath = SmallVec<[usize; 8]>;
+
+/// Iterates over a node and all its descendants in pre-order, together with their paths, see [`NodeRef::iter_with_paths`].
+pub struct IterWithPaths<'t, T> {
+    root: Option<NodeRef<'t, T>>,
+    // The remaining children of the ancestors of the next node, and how many children of each have been visited
+    stack: Vec<(NodeIter<'t, T>, usize)>,
+}
+
+impl<'t, T> Iterator for IterWithPaths<'t, T> {
+    type Item = (ChildIndexPath, NodeRef<'t, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            self.stack.push((root.children(), 0));
+            return Some((ChildIndexPath::new(), root));
+        }
+        loop {
+            let (children, num_visited) = self.stack.last_mut()?;
+            match children.next() {
+                Some(child) => {
+                    *num_visited += 1;
+                    let path = self.stack.iter().map(|(_, num_visited)| num_visited - 1).collect();
+                    self.stack.push((child.children(), 0));
+                    return Some((path, child));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Iterates over nodes in pre-order, together with their depth, see [`NodeRef::iter_with_depth`] and [`PackedForest::iter_with_depth`].
+pub struct IterWithDepth<'t, T> {
+    // The remaining nodes at every level, where the first element contains the nodes at depth 0
+    stack: Vec<NodeIter<'t, T>>,
+}
+
+impl<'t, T> Iterator for IterWithDepth<'t, T> {
+    type Item = (usize, NodeRef<'t, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let nodes = self.stack.last_mut()?;
+            match nodes.next() {
+                Some(node) => {
+                    let depth = self.stack.len() - 1;
+                    self.stack.push(node.children());
+                    return Some((depth, node));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+impl<'t, T> NodeRef<'t, T> {
+    /// Returns an iterator over this node and all its descendants in pre-order, together with their paths:
+    /// the indices of the children that are followed to get from this node to the descendant.
+    /// The path of this node itself is empty, the path of its first child is `[0]`, etc.
+    ///
+    /// This is useful to export trees to formats that address nodes by their path, like JSON Pointers or file paths.
+    /// A node's path takes O(depth) time to create, and doesn't allocate for nodes that are at most 8 levels deep.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let tree = PackedTree::new("root", |node_builder| {
+    ///     node_builder.add_child("a");
+    ///     node_builder.build_child("b", |node_builder| {
+    ///         node_builder.add_child("c");
+    ///     });
+    /// });
+    ///
+    /// let pointers: Vec<String> = tree.iter_with_paths()
+    ///     .map(|(path, _)| path.iter().map(|index| format!("/{}", index)).collect())
+    ///     .collect();
+    /// assert_eq!(pointers, ["", "/0", "/1", "/1/0"]);
+    /// ```
+    #[inline]
+    pub fn iter_with_paths(&self) -> IterWithPaths<'t, T> {
+        IterWithPaths {
+            root: Some(*self),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Returns an iterator over this node and all its descendants in pre-order, together with their depth
+    /// relative to this node, which has depth 0.
+    ///
+    /// Unlike [`PackedForest::depths`], this gives access to the nodes themselves, e.g. to look at their children.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let tree = PackedTree::new("src", |node_builder| {
+    ///     node_builder.build_child("bin", |node_builder| {
+    ///         node_builder.add_child("main.rs");
+    ///     });
+    ///     node_builder.add_child("lib.rs");
+    /// });
+    ///
+    /// let lines: Vec<String> = tree.iter_with_depth()
+    ///     .map(|(depth, node)| {
+    ///         let suffix = if node.children().next().is_some() { "/" } else { "" };
+    ///         format!("{}{}{}", "  ".repeat(depth), node.val(), suffix)
+    ///     })
+    ///     .collect();
+    /// assert_eq!(lines, ["src/", "  bin/", "    main.rs", "  lib.rs"]);
+    /// ```
+    #[inline]
+    pub fn iter_with_depth(&self) -> IterWithDepth<'t, T> {
+        IterWithDepth {
+            stack: vec![self.as_node_iter()],
+        }
+    }
+}
+
+impl<T> PackedForest<T> {
+    /// Returns an iterator over all nodes of all trees in this forest in pre-order, together with their depth.
+    /// The roots of the trees have depth 0.
+    ///
+    /// See [`NodeRef::iter_with_depth`].
+    #[inline]
+    pub fn iter_with_depth(&self) -> IterWithDepth<'_, T> {
+        IterWithDepth {
+            stack: vec![self.iter_trees()],
+        }
+    }
+
+    /// Returns an iterator over the values of the nodes on the path from the root of the tree containing the node
+    /// with the given pre-order index (see [`get`](PackedForest::get)) down to that node, including both.
+    /// If the index is out of bounds, the iterator is empty.
+    ///
+    /// This is useful for error messages that show where in a tree something went wrong.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree("root", |node_builder| {
+    ///     node_builder.add_child("version");
+    ///     node_builder.build_child("config", |node_builder| {
+    ///         node_builder.build_child("servers", |node_builder| {
+    ///             node_builder.add_child("a");
+    ///             node_builder.add_child("b");
+    ///         });
+    ///     });
+    /// });
+    ///
+    /// let path: Vec<_> = forest.path_to(5).copied().collect();
+    /// assert_eq!(path.join(" > "), "root > config > servers > b");
+    /// ```
+    #[inline]
+    pub fn path_to(&self, index: usize) -> PathTo<'_, T> {
+        PathTo {
+            candidates: self.iter_trees(),
+            candidates_index: 0,
+            target_index: index,
+        }
+    }
+}
+
+impl<T> PackedTree<T> {
+    /// Returns an iterator over the values of the nodes on the path from the root down to the node with the given pre-order index.
+    ///
+    /// See [`PackedForest::path_to`].
+    #[inline(always)]
+    pub fn path_to(&self, index: usize) -> PathTo<'_, T> {
+        self.as_ref().path_to(index)
+    }
+
+    /// Returns an iterator over all nodes of this tree in pre-order, together with the indices of the children
+    /// that are followed to get from the root to the node.
+    ///
+    /// See [`NodeRef::iter_with_paths`].
+    #[inline(always)]
+    pub fn iter_with_paths(&self) -> IterWithPaths<'_, T> {
+        self.root().iter_with_paths()
+    }
+
+    /// Returns an iterator over all nodes of this tree in pre-order, together with their depth. The root has depth 0.
+    ///
+    /// See [`NodeRef::iter_with_depth`].
+    #[inline(always)]
+    pub fn iter_with_depth(&self) -> IterWithDepth<'_, T> {
+        self.root().iter_with_depth()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_tree() -> PackedTree<String> {
+        PackedTree::new("root".to_string(), |node_builder| {
+            node_builder.build_child("a".to_string(), |node_builder| {
+                node_builder.add_child("x".to_string());
+                node_builder.add_child("y".to_string());
+            });
+            node_builder.build_child("b".to_string(), |node_builder| {
+                node_builder.add_child("x".to_string());
+            });
+            node_builder.add_child("a".to_string());
+        })
+    }
+
+    #[test]
+    fn test_lookup_path() {
+        let tree = build_tree();
+        assert_eq!(tree.lookup_path("").unwrap().val(), "root");
+        assert_eq!(tree.lookup_path("a/y").unwrap().val(), "y");
+        assert_eq!(tree.lookup_path("/b//x/").unwrap().val(), "x");
+        assert_eq!(tree.lookup_path("a").unwrap().num_descendants_incl_self(), 3);
+        assert!(tree.lookup_path("b/y").is_none());
+        assert_eq!(tree.lookup_path_by("B/X", |val| if val == "b" { "B" } else if val == "x" { "X" } else { val }).unwrap().val(), "x");
+    }
+
+    #[test]
+    fn test_path_index() {
+        let tree = build_tree();
+        let index = PathIndex::new(&tree);
+        assert_eq!(index.len(), 6);
+        for path in &["", "a", "a/x", "a/y", "b", "b/x", "/a/y", "c", "a/x/z"] {
+            let expected = tree.lookup_path(path).map(|node| {
+                tree.position(|other| std::ptr::eq(node.val(), other.val())).unwrap()
+            });
+            assert_eq!(index.get(path), expected, "path {:?}", path);
+        }
+
+        // Keys that lookup_path can't follow aren't indexed
+        let tree = PackedTree::new("", |node_builder| {
+            node_builder.build_child("", |node_builder| {
+                node_builder.add_child("x");
+            });
+            node_builder.build_child("a/b", |node_builder| {
+                node_builder.add_child("y");
+            });
+            node_builder.add_child("a");
+        });
+        let index = PathIndex::new(&tree);
+        assert_eq!(index.len(), 2);
+        for path in &["", "/", "x", "a/b", "a/b/y", "a", "//a/"] {
+            let expected = tree.lookup_path(path).map(|node| {
+                tree.position(|other| std::ptr::eq(node.val(), other.val())).unwrap()
+            });
+            assert_eq!(index.get(path), expected, "path {:?}", path);
+        }
+    }
+
+    #[test]
+    fn test_path_to() {
+        let tree = build_tree();
+        assert_eq!(tree.path_to(0).collect::<Vec<_>>(), ["root"]);
+        assert_eq!(tree.path_to(3).collect::<Vec<_>>(), ["root", "a", "y"]);
+        assert_eq!(tree.path_to(5).collect::<Vec<_>>(), ["root", "b", "x"]);
+        assert_eq!(tree.path_to(6).collect::<Vec<_>>(), ["root", "a"]);
+        assert_eq!(tree.path_to(7).count(), 0);
+
+        let mut forest = PackedForest::from(tree);
+        forest.build_tree("second".to_string(), |node_builder| {
+            node_builder.add_child("c".to_string());
+        });
+        assert_eq!(forest.path_to(8).collect::<Vec<_>>(), ["second", "c"]);
+        assert_eq!(forest.path_to(9).count(), 0);
+    }
+
+    #[test]
+    fn test_paths_to_leaves() {
+        let tree = build_tree();
+        let paths: Vec<_> = tree.root().paths_to_leaves().collect();
+        assert_eq!(paths, [vec!["root", "a", "x"], vec!["root", "a", "y"], vec!["root", "b", "x"], vec!["root", "a"]]);
+        let leaf = tree.get(5).unwrap();
+        assert_eq!(leaf.paths_to_leaves().collect::<Vec<_>>(), [vec!["x"]]);
+
+        let mut paths = tree.get(1).unwrap().paths_to_leaves();
+        assert_eq!(paths.next_path().unwrap(), ["a", "x"]);
+        assert_eq!(paths.next_path().unwrap(), ["a", "y"]);
+        assert!(paths.next_path().is_none());
+        assert!(paths.next().is_none());
+    }
+
+    #[test]
+    fn test_iter_with_paths() {
+        let tree = build_tree();
+        let paths: Vec<(Vec<usize>, &str)> = tree.iter_with_paths()
+            .map(|(path, node)| (path.to_vec(), node.val().as_str()))
+            .collect();
+        assert_eq!(paths, [
+            (vec![], "root"),
+            (vec![0], "a"),
+            (vec![0, 0], "x"),
+            (vec![0, 1], "y"),
+            (vec![1], "b"),
+            (vec![1, 0], "x"),
+            (vec![2], "a"),
+        ]);
+        for (path, node) in tree.iter_with_paths() {
+            assert!(tree.root().descendant_at_path(&path) == Some(node));
+        }
+        let leaf = tree.get(6).unwrap();
+        assert_eq!(leaf.iter_with_paths().map(|(path, _)| path.len()).collect::<Vec<_>>(), [0]);
+    }
+
+    #[test]
+    fn test_iter_with_depth() {
+        let tree = build_tree();
+        let depths: Vec<(usize, &str)> = tree.iter_with_depth().map(|(depth, node)| (depth, node.val().as_str())).collect();
+        assert_eq!(depths, [(0, "root"), (1, "a"), (2, "x"), (2, "y"), (1, "b"), (2, "x"), (1, "a")]);
+
+        let subtree = tree.get(1).unwrap();
+        assert_eq!(subtree.iter_with_depth().map(|(depth, _)| depth).collect::<Vec<_>>(), [0, 1, 1]);
+
+        let mut forest = PackedForest::new();
+        forest.build_tree(0, |node_builder| {
+            node_builder.add_child(1);
+        });
+        forest.build_tree(2, |_| {});
+        assert_eq!(forest.iter_with_depth().map(|(depth, _)| depth).collect::<Vec<_>>(), forest.depths());
+        assert!(PackedForest::<u8>::new().iter_with_depth().next().is_none());
+    }
+}
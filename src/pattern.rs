@@ -0,0 +1,165 @@
+// This file contains functions to find the places where a pattern tree occurs in a tree.
+
+use crate::*;
+
+/// The result of matching the value of a node in a pattern against a node in a tree,
+/// see [`PackedTree::find_subtree_matches_by`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum PatternMatch {
+    /// The node doesn't match.
+    No,
+    /// The value matches, and the node matches if its children match the children of the pattern node
+    /// (in the same order, and with the same number of children).
+    Node,
+    /// The node and all of its descendants match, regardless of the children of the pattern node.
+    /// This can be used to implement wildcards.
+    Subtree,
+}
+
+fn matches_pattern<T, P>(
+    node: NodeRef<'_, T>,
+    pattern: NodeRef<'_, P>,
+    matcher: &mut impl FnMut(&P, NodeRef<'_, T>) -> PatternMatch,
+) -> bool {
+    match matcher(pattern.val(), node) {
+        PatternMatch::No => false,
+        PatternMatch::Subtree => true,
+        PatternMatch::Node => {
+            let mut children = node.children();
+            let mut pattern_children = pattern.children();
+            loop {
+                match (children.next(), pattern_children.next()) {
+                    (Some(child), Some(pattern_child)) => {
+                        if !matches_pattern(child, pattern_child, matcher) {
+                            return false;
+                        }
+                    }
+                    (None, None) => return true,
+                    _ => return false,
+                }
+            }
+        }
+    }
+}
+
+impl<T> PackedTree<T> {
+    /// Returns an iterator over the pre-order indices (see [`get`](PackedTree::get)) of the nodes where `pattern` occurs,
+    /// i.e. the nodes that have the same value as the root of `pattern`, whose children match the children of the root of `pattern`, and so on.
+    ///
+    /// See [`find_subtree_matches_by`](PackedTree::find_subtree_matches_by) to compare values in a custom way or to use wildcards.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// let tree = PackedTree::new("+", |node_builder| {
+    ///     node_builder.build_child("*", |node_builder| {
+    ///         node_builder.add_child("x");
+    ///         node_builder.add_child("0");
+    ///     });
+    ///     node_builder.build_child("*", |node_builder| {
+    ///         node_builder.add_child("y");
+    ///         node_builder.add_child("0");
+    ///     });
+    /// });
+    /// let pattern = PackedTree::new("*", |node_builder| {
+    ///     node_builder.add_child("x");
+    ///     node_builder.add_child("0");
+    /// });
+    ///
+    /// assert_eq!(tree.find_subtree_matches(&pattern).collect::<Vec<_>>(), [1]);
+    /// ```
+    #[inline]
+    pub fn find_subtree_matches<'a>(&'a self, pattern: &'a PackedTree<T>) -> impl Iterator<Item = usize> + 'a where T: Eq {
+        self.find_subtree_matches_by(pattern, |pattern_val, node| {
+            if pattern_val == node.val() {
+                PatternMatch::Node
+            } else {
+                PatternMatch::No
+            }
+        })
+    }
+
+    /// Returns an iterator over the pre-order indices of the nodes where `pattern` occurs, where `matcher` decides
+    /// whether a node in this tree matches a node in the pattern (see [`PatternMatch`]).
+    ///
+    /// This takes O(n*m) time in the worst case, where n is the number of nodes in this tree and m the number of nodes in the pattern.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedTree, PatternMatch};
+    ///
+    /// let tree = PackedTree::new("+", |node_builder| {
+    ///     node_builder.build_child("*", |node_builder| {
+    ///         node_builder.add_child("x");
+    ///         node_builder.add_child("0");
+    ///     });
+    ///     node_builder.build_child("*", |node_builder| {
+    ///         node_builder.build_child("-", |node_builder| {
+    ///             node_builder.add_child("y");
+    ///         });
+    ///         node_builder.add_child("0");
+    ///     });
+    /// });
+    ///
+    /// // None matches any subtree
+    /// let pattern = PackedTree::new(Some("*"), |node_builder| {
+    ///     node_builder.add_child(None);
+    ///     node_builder.add_child(Some("0"));
+    /// });
+    /// let matches = tree.find_subtree_matches_by(&pattern, |pattern_val, node| match pattern_val {
+    ///     None => PatternMatch::Subtree,
+    ///     Some(val) if val == node.val() => PatternMatch::Node,
+    ///     Some(_) => PatternMatch::No,
+    /// });
+    /// assert_eq!(matches.collect::<Vec<_>>(), [1, 4]);
+    /// ```
+    pub fn find_subtree_matches_by<'a, P>(
+        &'a self,
+        pattern: &'a PackedTree<P>,
+        mut matcher: impl FnMut(&P, NodeRef<'_, T>) -> PatternMatch + 'a,
+    ) -> impl Iterator<Item = usize> + 'a {
+        (0..self.tot_num_nodes()).filter(move |index| matches_pattern(self.get(*index).unwrap(), pattern.root(), &mut matcher))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_subtree_matches() {
+        let tree = PackedTree::new(1, |node_builder| {
+            node_builder.build_child(2, |node_builder| {
+                node_builder.add_child(3);
+            });
+            node_builder.build_child(2, |node_builder| {
+                node_builder.add_child(3);
+                node_builder.add_child(4);
+            });
+            node_builder.add_child(2);
+        });
+
+        let pattern = PackedTree::new(2, |node_builder| {
+            node_builder.add_child(3);
+        });
+        assert_eq!(tree.find_subtree_matches(&pattern).collect::<Vec<_>>(), [1]);
+        let pattern = PackedTree::new(2, |_| {});
+        assert_eq!(tree.find_subtree_matches(&pattern).collect::<Vec<_>>(), [6]);
+        let pattern = PackedTree::new(5, |_| {});
+        assert_eq!(tree.find_subtree_matches(&pattern).count(), 0);
+        assert_eq!(tree.find_subtree_matches(&tree).collect::<Vec<_>>(), [0]);
+
+        // 0 is a wildcard
+        let pattern = PackedTree::new(2, |node_builder| {
+            node_builder.add_child(0);
+            node_builder.add_child(0);
+        });
+        let matches = tree.find_subtree_matches_by(&pattern, |pattern_val, node| match *pattern_val {
+            0 => PatternMatch::Subtree,
+            val if val == *node.val() => PatternMatch::Node,
+            _ => PatternMatch::No,
+        });
+        assert_eq!(matches.collect::<Vec<_>>(), [3]);
+    }
+}
@@ -0,0 +1,424 @@
+// This file contains PersistentPackedTree, an immutable tree whose modified copies share the unchanged parts
+// of the tree with the original, e.g. for undo stacks.
+
+use crate::*;
+
+use std::sync::Arc;
+
+// Subtrees with at most this many nodes are stored as a single packed chunk.
+const MAX_CHUNK_SIZE: usize = 64;
+
+enum Subtree<T> {
+    // A small subtree, stored in the packed layout.
+    Chunk(Arc<PackedTree<T>>),
+    // A large subtree, whose children are shared separately.
+    Branch(Arc<Branch<T>>),
+}
+
+struct Branch<T> {
+    val: T,
+    children: Vec<Subtree<T>>,
+    // The number of nodes in this subtree, including the node itself.
+    num_nodes: usize,
+}
+
+// Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
+impl<T> Clone for Subtree<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        match self {
+            Subtree::Chunk(chunk) => Subtree::Chunk(chunk.clone()),
+            Subtree::Branch(branch) => Subtree::Branch(branch.clone()),
+        }
+    }
+}
+
+impl<T> Subtree<T> {
+    fn from_node(node: NodeRef<'_, T>) -> Subtree<T> where T: Clone {
+        // The branches whose children are still being converted, with their remaining children
+        let mut stack: Vec<(Branch<T>, NodeIter<'_, T>)> = Vec::new();
+        let mut next = Some(node);
+        loop {
+            let finished = match next {
+                Some(node) if node.num_descendants_incl_self() <= MAX_CHUNK_SIZE => {
+                    Some(Subtree::Chunk(Arc::new(PackedTree::new(node.val().clone(), |node_builder| {
+                        for child in node.children() {
+                            node_builder.add_cloned_subtree(child);
+                        }
+                    }))))
+                }
+                Some(node) => {
+                    let branch = Branch {
+                        val: node.val().clone(),
+                        children: Vec::with_capacity(node.children().count()),
+                        num_nodes: node.num_descendants_incl_self(),
+                    };
+                    stack.push((branch, node.children()));
+                    None
+                }
+                None => stack.pop().map(|(branch, _)| Subtree::Branch(Arc::new(branch))),
+            };
+            if let Some(subtree) = finished {
+                match stack.last_mut() {
+                    Some((parent, _)) => parent.children.push(subtree),
+                    None => return subtree,
+                }
+            }
+            next = stack.last_mut().and_then(|(_, children)| children.next());
+        }
+    }
+
+    #[inline]
+    fn num_nodes(&self) -> usize {
+        match self {
+            Subtree::Chunk(chunk) => chunk.tot_num_nodes(),
+            Subtree::Branch(branch) => branch.num_nodes,
+        }
+    }
+
+    #[inline]
+    fn val(&self) -> &T {
+        match self {
+            Subtree::Chunk(chunk) => chunk.root().val(),
+            Subtree::Branch(branch) => &branch.val,
+        }
+    }
+
+    // Returns the child that contains the node with the given index (relative to this subtree, and not 0),
+    // together with the index of the child and the index of the node relative to the child.
+    fn find_child(children: &[Subtree<T>], mut index: usize) -> Option<(usize, usize)> {
+        index -= 1;
+        for (child_index, child) in children.iter().enumerate() {
+            if index < child.num_nodes() {
+                return Some((child_index, index));
+            }
+            index -= child.num_nodes();
+        }
+        None
+    }
+
+    fn get(&self, mut index: usize) -> Option<&T> {
+        let mut subtree = self;
+        loop {
+            match subtree {
+                Subtree::Chunk(chunk) => return chunk.get(index).map(|node| node.val()),
+                Subtree::Branch(branch) if index == 0 => return Some(&branch.val),
+                Subtree::Branch(branch) => {
+                    let (child_index, child_node_index) = Subtree::find_child(&branch.children, index)?;
+                    subtree = &branch.children[child_index];
+                    index = child_node_index;
+                }
+            }
+        }
+    }
+
+    // Splits the node at the root of a chunk off from its children, which become separate chunks.
+    fn to_branch(chunk: &PackedTree<T>) -> Branch<T> where T: Clone {
+        Branch {
+            val: chunk.root().val().clone(),
+            children: chunk.root().children().map(Subtree::from_node).collect(),
+            num_nodes: chunk.tot_num_nodes(),
+        }
+    }
+
+    // Puts `subtree` in place of the child with the given index of the last branch of `path`, then puts that branch
+    // in place of the given child of the branch before it, and so on. Returns the new version of the first branch.
+    fn replace_along_path(path: Vec<(Branch<T>, usize)>, mut subtree: Subtree<T>) -> Subtree<T> {
+        for (mut branch, child_index) in path.into_iter().rev() {
+            branch.num_nodes = branch.num_nodes - branch.children[child_index].num_nodes() + subtree.num_nodes();
+            branch.children[child_index] = subtree;
+            subtree = Subtree::Branch(Arc::new(branch));
+        }
+        subtree
+    }
+
+    fn with_val(&self, mut index: usize, val: T) -> Option<Subtree<T>> where T: Clone {
+        // Copies of the branches on the path to the node, with the index of the child to follow
+        let mut path = Vec::new();
+        let mut subtree = self;
+        let changed = loop {
+            match subtree {
+                Subtree::Chunk(chunk) => {
+                    // Chunks are small, so they're copied as a whole
+                    let mut chunk = PackedTree::clone(chunk);
+                    *chunk.get_mut(index)?.val_mut() = val;
+                    break Subtree::Chunk(Arc::new(chunk));
+                }
+                Subtree::Branch(branch) if index == 0 => break Subtree::Branch(Arc::new(Branch {
+                    val,
+                    children: branch.children.clone(),
+                    num_nodes: branch.num_nodes,
+                })),
+                Subtree::Branch(branch) => {
+                    let (child_index, child_node_index) = Subtree::find_child(&branch.children, index)?;
+                    path.push((branch.shallow_clone(), child_index));
+                    subtree = &branch.children[child_index];
+                    index = child_node_index;
+                }
+            }
+        };
+        Some(Subtree::replace_along_path(path, changed))
+    }
+
+    fn with_subtree(&self, mut index: usize, subtree: &Subtree<T>) -> Option<Subtree<T>> where T: Clone {
+        // Copies of the branches on the path to the node, with the index of the child to follow
+        let mut path = Vec::new();
+        let mut current = self.clone();
+        while index != 0 {
+            let branch = match &current {
+                Subtree::Chunk(chunk) if index < chunk.tot_num_nodes() => Subtree::to_branch(chunk),
+                Subtree::Chunk(_) => return None,
+                Subtree::Branch(branch) => branch.shallow_clone(),
+            };
+            let (child_index, child_node_index) = Subtree::find_child(&branch.children, index)?;
+            current = branch.children[child_index].clone();
+            index = child_node_index;
+            path.push((branch, child_index));
+        }
+        Some(Subtree::replace_along_path(path, subtree.clone()))
+    }
+
+    #[inline]
+    fn ptr_eq(&self, other: &Subtree<T>) -> bool {
+        match (self, other) {
+            (Subtree::Chunk(a), Subtree::Chunk(b)) => Arc::ptr_eq(a, b),
+            (Subtree::Branch(a), Subtree::Branch(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl<T: Clone> Branch<T> {
+    // Copies the value of this branch, and shares its children.
+    #[inline]
+    fn shallow_clone(&self) -> Branch<T> {
+        Branch {
+            val: self.val.clone(),
+            children: self.children.clone(),
+            num_nodes: self.num_nodes,
+        }
+    }
+}
+
+impl<T> Drop for Branch<T> {
+    // Without this, dropping a deep tree would recurse once per level.
+    fn drop(&mut self) {
+        let mut children = std::mem::take(&mut self.children);
+        while let Some(child) = children.pop() {
+            if let Subtree::Branch(branch) = child {
+                // Only the last owner of a branch drops its children
+                if let Ok(mut branch) = Arc::try_unwrap(branch) {
+                    children.append(&mut branch.children);
+                }
+            }
+        }
+    }
+}
+
+/// An immutable tree, where "modifying" the tree returns a modified copy that shares all the unchanged parts
+/// of the tree with the original. This makes it cheap to keep many versions of a tree around, e.g. for an undo stack
+/// or to try out changes speculatively, without cloning the whole tree every time.
+///
+/// The tree is split into small chunks, which are stored in the packed layout of a [`PackedTree`] and shared
+/// through an [`Arc`]. The nodes with large subtrees are stored separately, with their children shared
+/// through an [`Arc`] as well. A modification only copies the chunk that contains the modified node
+/// and the nodes on the path from the root to it, so it takes O(depth + chunk size) time, if every node has
+/// a bounded number of children.
+///
+/// Nodes are identified by their pre-order index, like in [`PackedTree::get`].
+/// Cloning a [`PersistentPackedTree`] takes O(1) time.
+///
+/// # Example
+/// ```
+/// use packed_tree::{PackedTree, PersistentPackedTree};
+///
+/// let tree = PackedTree::new(0, |node_builder| {
+///     node_builder.build_child(1, |node_builder| {
+///         node_builder.add_child(2);
+///     });
+///     node_builder.add_child(3);
+/// });
+/// let original = PersistentPackedTree::from_tree(&tree);
+///
+/// let changed = original.with_val(2, 20).unwrap();
+/// let replaced = changed.with_subtree(1, &PersistentPackedTree::from_tree(&PackedTree::new(4, |_| {}))).unwrap();
+///
+/// // All versions are still available
+/// assert_eq!(original.to_tree().iter_flattened().copied().collect::<Vec<_>>(), [0, 1, 2, 3]);
+/// assert_eq!(changed.to_tree().iter_flattened().copied().collect::<Vec<_>>(), [0, 1, 20, 3]);
+/// assert_eq!(replaced.to_tree().iter_flattened().copied().collect::<Vec<_>>(), [0, 4, 3]);
+/// assert_eq!(replaced.get(2), Some(&3));
+/// ```
+pub struct PersistentPackedTree<T> {
+    root: Subtree<T>,
+}
+
+// Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
+impl<T> Clone for PersistentPackedTree<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        PersistentPackedTree { root: self.root.clone() }
+    }
+}
+
+impl<T: Clone> PersistentPackedTree<T> {
+    /// Create a new [`PersistentPackedTree`] with copies of the values of the given tree.
+    pub fn from_tree(tree: &PackedTree<T>) -> PersistentPackedTree<T> {
+        PersistentPackedTree { root: Subtree::from_node(tree.root()) }
+    }
+
+    /// Returns a [`PackedTree`] with copies of the values of this tree.
+    pub fn to_tree(&self) -> PackedTree<T> {
+        let mut forest = PackedForest::with_capacity(self.tot_num_nodes());
+        // The branches whose children are still being added, with the index of their node and their remaining children
+        let mut stack = Vec::new();
+        let mut next = Some(&self.root);
+        loop {
+            match next {
+                Some(Subtree::Chunk(chunk)) => forest.push_cloned_subtree(chunk.raw_data()),
+                Some(Subtree::Branch(branch)) => {
+                    stack.push((forest.push_open_node(branch.val.clone()), branch.children.iter()));
+                }
+                None => match stack.pop() {
+                    Some((index, _)) => forest.close_node(index),
+                    None => break,
+                },
+            }
+            next = stack.last_mut().and_then(|(_, children)| children.next());
+        }
+        PackedTree::try_from_forest(forest).unwrap()
+    }
+
+    /// Returns a copy of this tree where the node with the given pre-order index has the value `val`,
+    /// or `None` if there is no node with that index. This tree itself is not changed.
+    #[inline]
+    pub fn with_val(&self, index: usize, val: T) -> Option<PersistentPackedTree<T>> {
+        Some(PersistentPackedTree { root: self.root.with_val(index, val)? })
+    }
+
+    /// Returns a copy of this tree where the subtree of the node with the given pre-order index is replaced by
+    /// `subtree`, or `None` if there is no node with that index. This tree itself is not changed.
+    ///
+    /// The result shares the nodes of `subtree` instead of copying them.
+    #[inline]
+    pub fn with_subtree(&self, index: usize, subtree: &PersistentPackedTree<T>) -> Option<PersistentPackedTree<T>> {
+        Some(PersistentPackedTree { root: self.root.with_subtree(index, &subtree.root)? })
+    }
+}
+
+impl<T> PersistentPackedTree<T> {
+    /// Returns the value of the root of this tree.
+    #[inline]
+    pub fn root_val(&self) -> &T {
+        self.root.val()
+    }
+
+    /// Returns the value of the node with the given pre-order index, or `None` if there is no such node.
+    ///
+    /// This takes O(depth + number of siblings of the nodes on the path) time.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.root.get(index)
+    }
+
+    /// Returns the number of nodes in this tree in O(1) time.
+    #[inline]
+    pub fn tot_num_nodes(&self) -> usize {
+        self.root.num_nodes()
+    }
+
+    /// Returns `true` if both trees share all their nodes, e.g. because one is a clone of the other.
+    /// This takes O(1) time.
+    ///
+    /// If this returns `false`, the trees can still be equal.
+    #[inline]
+    pub fn ptr_eq(&self, other: &PersistentPackedTree<T>) -> bool {
+        self.root.ptr_eq(&other.root)
+    }
+}
+
+impl<T: Clone> From<&PackedTree<T>> for PersistentPackedTree<T> {
+    #[inline]
+    fn from(tree: &PackedTree<T>) -> Self {
+        PersistentPackedTree::from_tree(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn children(subtree: &Subtree<u32>) -> &[Subtree<u32>] {
+        match subtree {
+            Subtree::Branch(branch) => &branch.children,
+            Subtree::Chunk(_) => &[],
+        }
+    }
+
+    #[test]
+    fn test_persistent_tree() {
+        // A root with 3 children that each have 100 leaves
+        let tree = PackedTree::new(0, |node_builder| {
+            for i in 0..3 {
+                node_builder.build_child(i * 1000, |node_builder| {
+                    node_builder.add_leaf_children((1..=100).map(|j| i * 1000 + j));
+                });
+            }
+        });
+        let original = PersistentPackedTree::from_tree(&tree);
+        assert_eq!(original.tot_num_nodes(), 304);
+        assert!(original.to_tree() == tree);
+        assert!(original.clone().ptr_eq(&original));
+
+        // Changing a leaf of the second child shares the first and the last child
+        let changed = original.with_val(103, 7).unwrap();
+        assert_eq!((changed.get(103), original.get(103)), (Some(&7), Some(&1001)));
+        let (old, new) = (children(&original.root), children(&changed.root));
+        assert!(old[0].ptr_eq(&new[0]) && old[2].ptr_eq(&new[2]) && !old[1].ptr_eq(&new[1]));
+        assert!(original.with_val(304, 7).is_none());
+
+        assert!(children(&old[1])[1].ptr_eq(&children(&new[1])[1]));
+
+        let leaf = PersistentPackedTree::from_tree(&PackedTree::new(5, |_| {}));
+        let replaced = changed.with_subtree(203, &original).unwrap();
+        assert_eq!(replaced.tot_num_nodes(), 304 - 101 + 304);
+        assert_eq!(replaced.get(203 + 103), Some(&1001));
+        let replaced = replaced.with_subtree(1, &leaf).unwrap();
+        assert_eq!(replaced.tot_num_nodes(), 2 + 101 + 304);
+        let mut expected = vec![0, 5];
+        expected.extend(changed.to_tree().iter_flattened().skip(102).take(101));
+        expected.extend(tree.iter_flattened());
+        assert_eq!(replaced.to_tree().iter_flattened().copied().collect::<Vec<_>>(), expected);
+        assert_eq!(expected[3], 7);
+
+        // Replacing a subtree inside a chunk splits the chunk
+        let small = PersistentPackedTree::from_tree(&PackedTree::new(1, |node_builder| {
+            node_builder.build_child(2, |node_builder| {
+                node_builder.add_child(3);
+            });
+            node_builder.add_child(4);
+        }));
+        let small_replaced = small.with_subtree(2, &leaf).unwrap();
+        assert_eq!(small_replaced.to_tree().iter_flattened().copied().collect::<Vec<_>>(), [1, 2, 5, 4]);
+        assert!(matches!(small_replaced.root, Subtree::Branch(_)));
+        assert_eq!(small.to_tree().iter_flattened().copied().collect::<Vec<_>>(), [1, 2, 3, 4]);
+        assert!(leaf.with_subtree(1, &leaf).is_none());
+        assert!(leaf.with_subtree(0, &original).unwrap().ptr_eq(&original));
+    }
+
+    #[test]
+    fn test_deep_chain() {
+        let tree = PackedTree::unfold_iterative(0, |n| (*n, if *n < 999_999 { vec![n + 1] } else { vec![] }));
+        let original = PersistentPackedTree::from_tree(&tree);
+        assert_eq!(original.tot_num_nodes(), 1_000_000);
+        assert_eq!(original.get(999_999), Some(&999_999));
+        assert!(original.to_tree() == tree);
+
+        let changed = original.with_val(999_999, 7).unwrap();
+        let replaced = changed.with_subtree(999_990, &PersistentPackedTree::from_tree(&PackedTree::new(8, |_| {})));
+        let replaced = replaced.unwrap();
+        assert_eq!((changed.get(999_999), original.get(999_999)), (Some(&7), Some(&999_999)));
+        assert_eq!(replaced.tot_num_nodes(), 999_991);
+        assert_eq!(replaced.to_tree().iter_flattened().next_back(), Some(&8));
+    }
+}
@@ -0,0 +1,351 @@
+// This file contains the NodePolicy trait, which decides what extra data a PackedForest
+// stores per node. The trait is sealed, so new policies can only be added in this crate.
+
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Information about a node that is known when it's finished being built, see [`NodePolicy::meta`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct NodeInfo {
+    pub(crate) index: usize,
+    pub(crate) depth: usize,
+    pub(crate) num_children: usize,
+    pub(crate) subtree_size: usize,
+}
+
+impl NodeInfo {
+    /// Returns the index of the node. See [`NodeBuilder::index`](crate::NodeBuilder::index).
+    #[inline(always)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the depth of the node. See [`NodeBuilder::depth`](crate::NodeBuilder::depth).
+    #[inline(always)]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the number of children of the node.
+    #[inline(always)]
+    pub fn num_children(&self) -> usize {
+        self.num_children
+    }
+
+    /// Returns the number of nodes in the subtree of the node, including the node itself.
+    #[inline(always)]
+    pub fn subtree_size(&self) -> usize {
+        self.subtree_size
+    }
+}
+
+/// Decides which extra data a [`PackedForest`](crate::PackedForest) stores for every node, next to its value.
+/// A forest with a policy other than the default [`NoMeta`] is also called a [`MetaPackedForest`](crate::MetaPackedForest).
+///
+/// This trait is sealed: it can't be implemented outside of this crate.
+/// The available policies are:
+/// - [`NoMeta`], which doesn't store anything. This is the default.
+/// - [`ChildCount`], which stores the number of children of each node, see [`ExactSizePackedForest`](crate::ExactSizePackedForest).
+///   The number is stored as a `usize` by default, or as a smaller integer type to save memory (see [`ChildCounter`]).
+pub trait NodePolicy: sealed::Sealed {
+    /// The data that is stored for every node.
+    type Meta: Copy + Default + Eq + Hash + Debug;
+
+    /// What the iterators over the children of a node (and over the trees of a forest) use to keep track of
+    /// how many nodes they have left: a `usize` for a [`ChildCountPolicy`], and `()` for the other policies.
+    type Count: NodeCount;
+
+    /// Computes the data to store for a node, when the node is finished being built.
+    fn meta(info: &NodeInfo) -> Self::Meta;
+
+    /// Returns the number of children recorded in `meta`, or `None` if this policy doesn't record it.
+    #[inline(always)]
+    fn num_children(_meta: &Self::Meta) -> Option<usize> {
+        None
+    }
+
+    // The prefix of the names of the forests and trees with this policy in their Debug output.
+    #[doc(hidden)]
+    const NAME_PREFIX: &'static str = "Meta";
+}
+
+/// A [`NodePolicy`] that records the number of children of every node,
+/// which makes iterators over the children of a node [`ExactSizeIterator`]s.
+pub trait ChildCountPolicy: NodePolicy<Count = usize> {}
+
+/// The number of nodes that an iterator has left, if its [`NodePolicy`] keeps track of it. See [`NodePolicy::Count`].
+///
+/// This trait is sealed: it is implemented for `usize` and `()`.
+pub trait NodeCount: sealed::Sealed + Copy + Default + Eq + Hash + Debug {
+    /// A count of zero nodes.
+    const ZERO: Self;
+
+    /// Creates a count of `count` nodes.
+    fn new(count: usize) -> Self;
+
+    /// Returns the number of nodes, or `None` if they aren't counted.
+    fn get(self) -> Option<usize>;
+
+    /// Returns the count with `count` more nodes.
+    fn add(self, count: usize) -> Self;
+
+    /// Returns the count with `count` fewer nodes.
+    fn sub(self, count: usize) -> Self;
+}
+
+impl NodeCount for usize {
+    const ZERO: usize = 0;
+
+    #[inline(always)]
+    fn new(count: usize) -> usize {
+        count
+    }
+
+    #[inline(always)]
+    fn get(self) -> Option<usize> {
+        Some(self)
+    }
+
+    #[inline(always)]
+    fn add(self, count: usize) -> usize {
+        self + count
+    }
+
+    #[inline(always)]
+    fn sub(self, count: usize) -> usize {
+        self - count
+    }
+}
+
+impl sealed::Sealed for () {}
+
+impl NodeCount for () {
+    const ZERO: () = ();
+
+    #[inline(always)]
+    fn new(_count: usize) {}
+
+    #[inline(always)]
+    fn get(self) -> Option<usize> {
+        None
+    }
+
+    #[inline(always)]
+    fn add(self, _count: usize) {}
+
+    #[inline(always)]
+    fn sub(self, _count: usize) {}
+}
+
+/// The default [`NodePolicy`] of [`PackedForest`](crate::PackedForest), which doesn't store any extra data,
+/// so every node only has the overhead of a single `usize`.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Hash, Debug)]
+pub struct NoMeta;
+
+impl sealed::Sealed for NoMeta {}
+
+impl NodePolicy for NoMeta {
+    type Meta = ();
+    type Count = ();
+
+    const NAME_PREFIX: &'static str = "";
+
+    #[inline(always)]
+    fn meta(_info: &NodeInfo) {}
+}
+
+/// An integer type that [`ChildCount`] can use to store the number of children of a node.
+///
+/// This trait is sealed: it is implemented for `u8`, `u16`, `u32`, `u64` and `usize`.
+pub trait ChildCounter: sealed::Sealed + Copy + Default + Eq + Hash + Debug {
+    /// Converts the number of children to this type, or returns `None` if it doesn't fit.
+    fn from_num_children(num_children: usize) -> Option<Self>;
+
+    /// Converts this back to the number of children.
+    fn to_num_children(self) -> usize;
+}
+
+macro_rules! impl_child_counter {
+    ($($t:ty),*) => {
+        $(
+            impl sealed::Sealed for $t {}
+
+            impl ChildCounter for $t {
+                #[inline(always)]
+                fn from_num_children(num_children: usize) -> Option<$t> {
+                    <$t>::try_from(num_children).ok()
+                }
+
+                #[inline(always)]
+                fn to_num_children(self) -> usize {
+                    // This was converted from a usize, so it fits in one.
+                    self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_child_counter!(u8, u16, u32, u64, usize);
+
+/// The [`NodePolicy`] of [`ExactSizePackedForest`](crate::ExactSizePackedForest), which stores the number of children
+/// of every node as a `C`, which is a `usize` by default.
+///
+/// Since no node realistically has more than 4 billion children, `ChildCount<u32>` is enough for most uses,
+/// and saves 4 bytes per node on 64-bit platforms (depending on the alignment of the values).
+/// Building a node with more children than fit in `C` panics.
+///
+/// # Example
+/// ```
+/// use packed_tree::{MetaPackedTree, ChildCount};
+///
+/// let tree = MetaPackedTree::<u32, ChildCount<u32>>::new(0, |node_builder| {
+///     node_builder.add_child(1);
+///     node_builder.add_child(2);
+/// });
+/// assert_eq!(tree.root().children().len(), 2);
+/// ```
+#[derive(Copy, Clone, Default, Eq, PartialEq, Hash, Debug)]
+pub struct ChildCount<C: ChildCounter = usize> {
+    _counter: PhantomData<C>,
+}
+
+impl<C: ChildCounter> sealed::Sealed for ChildCount<C> {}
+
+impl<C: ChildCounter> NodePolicy for ChildCount<C> {
+    type Meta = C;
+    type Count = usize;
+
+    const NAME_PREFIX: &'static str = "ExactSize";
+
+    #[inline(always)]
+    fn meta(info: &NodeInfo) -> C {
+        match C::from_num_children(info.num_children) {
+            Some(meta) => meta,
+            None => panic!("a node has {} children, which doesn't fit in a {}", info.num_children, std::any::type_name::<C>()),
+        }
+    }
+
+    #[inline(always)]
+    fn num_children(meta: &C) -> Option<usize> {
+        Some(meta.to_num_children())
+    }
+}
+
+impl<C: ChildCounter> ChildCountPolicy for ChildCount<C> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[derive(Copy, Clone, Default, Eq, PartialEq, Hash, Debug)]
+    struct Depth;
+
+    impl sealed::Sealed for Depth {}
+
+    impl NodePolicy for Depth {
+        type Meta = usize;
+        type Count = ();
+
+        fn meta(info: &NodeInfo) -> usize {
+            info.depth()
+        }
+    }
+
+    fn build<P: NodePolicy>(node_builder: &mut MetaNodeBuilder<u32, P>) {
+        node_builder.build_child(1, |node_builder| {
+            node_builder.add_child(2);
+        });
+        node_builder.add_child(3);
+    }
+
+    #[test]
+    fn test_policies() {
+        let mut depth_tree = MetaPackedTree::<u32, Depth>::new(0, build);
+        let exact_size_tree = MetaPackedTree::<u32, ChildCount>::new(0, build);
+
+        let depths: Vec<usize> = depth_tree.raw_data().iter().map(|node_data| *node_data.meta()).collect();
+        assert_eq!(depths, [0, 1, 2, 1]);
+        let num_children: Vec<usize> = exact_size_tree.raw_data().iter().map(|node_data| *node_data.meta()).collect();
+        assert_eq!(num_children, [2, 1, 0, 0]);
+
+        // Without a ChildCountPolicy, the children are counted.
+        assert_eq!(depth_tree.root().num_children(), 2);
+        assert_eq!(depth_tree.root_mut().num_children(), 2);
+        assert_eq!(depth_tree.root().children().size_hint(), (0, None));
+        assert_eq!(exact_size_tree.root().children().len(), 2);
+    }
+
+    #[test]
+    fn test_leaf_children_and_max_depth() {
+        let mut forest = MetaPackedForest::<u32, Depth>::new();
+        forest.build_tree(0, |node_builder| {
+            node_builder.set_max_depth(2);
+            node_builder.add_leaf_children([1, 2]);
+            node_builder.build_child(3, |node_builder| {
+                node_builder.build_child(4, |node_builder| {
+                    assert!(node_builder.try_get_child_builder().is_err());
+                });
+                node_builder.add_leaf_children_from_slice(&[5]);
+            });
+        });
+        let depths: Vec<usize> = forest.raw_data().iter().map(|node_data| *node_data.meta()).collect();
+        assert_eq!(depths, [0, 1, 1, 1, 2, 2]);
+
+        let tree = MetaPackedTree::<u32, ChildCount>::new(0, |node_builder| {
+            node_builder.add_leaf_children(1..4);
+            node_builder.build_child(4, |node_builder| {
+                node_builder.add_leaf_children_from_slice(&[5, 6]);
+            });
+        });
+        assert_eq!(tree.root().children().len(), 4);
+        assert_eq!(tree.root().children().map(|child| child.children().len()).collect::<Vec<_>>(), [0, 0, 0, 2]);
+
+        let mut forest = MetaPackedForest::<u32, ChildCount>::new();
+        forest.add_single_node_tree(0);
+        forest.build_tree(1, |node_builder| node_builder.add_leaf_children([2]));
+        assert_eq!(forest.iter_trees().len(), 2);
+        assert_eq!(forest.num_trees(), 2);
+        let (first, rest) = forest.iter_trees_mut().split_at(1);
+        assert_eq!((first.len(), rest.len()), (1, 1));
+        assert_eq!(forest.drain_trees().len(), 2);
+        assert_eq!(forest.num_trees(), 0);
+    }
+
+    #[test]
+    fn test_child_counter() {
+        let tree = MetaPackedTree::<u32, ChildCount<u8>>::new(0, build);
+        let num_children: Vec<u8> = tree.raw_data().iter().map(|node_data| *node_data.meta()).collect();
+        assert_eq!(num_children, [2, 1, 0, 0]);
+        assert_eq!(tree.root().children().len(), 2);
+
+        let result = std::panic::catch_unwind(|| {
+            MetaPackedTree::<u32, ChildCount<u8>>::new(0, |node_builder| {
+                for i in 0..256 {
+                    node_builder.add_child(i);
+                }
+            })
+        });
+        assert!(result.is_err());
+
+        // A panic while building a tree doesn't leave it counted
+        let mut forest = MetaPackedForest::<u32, ChildCount<u8>>::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            forest.build_tree(0, |node_builder| {
+                for i in 0..256 {
+                    node_builder.add_child(i);
+                }
+            });
+        }));
+        assert!(result.is_err());
+        assert_eq!(forest.tot_num_nodes(), 0);
+        assert_eq!(forest.iter_trees().len(), 0);
+    }
+}
@@ -0,0 +1,129 @@
+// This file contains ForestPool, which reuses the memory of two forests for code that rebuilds a forest over and over.
+
+use crate::*;
+
+use std::mem;
+
+/// Keeps two [`PackedForest`]s, the one that is currently being built or used, and the one of the previous frame,
+/// so that a forest can be rebuilt every frame (e.g. in a game or UI engine) without allocating memory every time.
+///
+/// Every call to [`begin_frame`](ForestPool::begin_frame) clears the oldest forest (keeping its capacity)
+/// and makes it the current forest. The forest of the previous frame stays available until the next call,
+/// e.g. to compare it with the new one.
+///
+/// # Example
+/// ```
+/// use packed_tree::ForestPool;
+///
+/// let mut pool = ForestPool::new();
+/// for frame in 0..3 {
+///     let forest = pool.begin_frame();
+///     forest.build_tree(frame, |node_builder| {
+///         node_builder.add_leaf_children(0..100);
+///     });
+/// }
+/// assert_eq!(*pool.current().iter_trees().next().unwrap().val(), 2);
+/// assert_eq!(*pool.previous().iter_trees().next().unwrap().val(), 1);
+/// ```
+pub struct ForestPool<T> {
+    current: PackedForest<T>,
+    previous: PackedForest<T>,
+}
+
+impl<T> ForestPool<T> {
+    /// Create a new [`ForestPool`] with two empty forests.
+    #[inline]
+    pub fn new() -> ForestPool<T> {
+        ForestPool {
+            current: PackedForest::new(),
+            previous: PackedForest::new(),
+        }
+    }
+
+    /// Create a new [`ForestPool`] where both forests have the given capacity (see [`PackedForest::with_capacity`]).
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> ForestPool<T> {
+        ForestPool {
+            current: PackedForest::with_capacity(capacity),
+            previous: PackedForest::with_capacity(capacity),
+        }
+    }
+
+    /// Starts a new frame: the current forest becomes the previous forest, and the forest of the previous frame
+    /// is cleared (keeping its capacity) and returned as the new current forest.
+    #[inline]
+    pub fn begin_frame(&mut self) -> &mut PackedForest<T> {
+        mem::swap(&mut self.current, &mut self.previous);
+        self.current.clear();
+        &mut self.current
+    }
+
+    /// Returns the current forest, i.e. the one that was returned by the last call to [`begin_frame`](ForestPool::begin_frame).
+    #[inline(always)]
+    pub fn current(&self) -> &PackedForest<T> {
+        &self.current
+    }
+
+    /// Returns the current forest mutably.
+    #[inline(always)]
+    pub fn current_mut(&mut self) -> &mut PackedForest<T> {
+        &mut self.current
+    }
+
+    /// Returns the forest of the previous frame, which is empty before the second call to [`begin_frame`](ForestPool::begin_frame).
+    #[inline(always)]
+    pub fn previous(&self) -> &PackedForest<T> {
+        &self.previous
+    }
+
+    /// Returns both forests, so that the current one can be built while the previous one is read.
+    #[inline(always)]
+    pub fn current_and_previous_mut(&mut self) -> (&mut PackedForest<T>, &PackedForest<T>) {
+        (&mut self.current, &self.previous)
+    }
+
+    /// Returns the two forests, the current one first.
+    #[inline]
+    pub fn into_forests(self) -> (PackedForest<T>, PackedForest<T>) {
+        (self.current, self.previous)
+    }
+}
+
+// Not using #[derive(Default)] because it adds the T:Default bound, which is unnecessary
+impl<T> Default for ForestPool<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        ForestPool::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forest_pool() {
+        let mut pool = ForestPool::with_capacity(10);
+        assert_eq!(pool.current().capacity(), 10);
+
+        pool.begin_frame().build_tree(1, |node_builder| {
+            node_builder.add_leaf_children(0..50);
+        });
+        let capacity = pool.current().capacity();
+        pool.begin_frame().add_single_node_tree(2);
+        assert_eq!(pool.previous().tot_num_nodes(), 51);
+
+        // The forest of the first frame is reused, without reallocating
+        let forest = pool.begin_frame();
+        assert_eq!(forest.tot_num_nodes(), 0);
+        assert_eq!(forest.capacity(), capacity);
+        forest.add_single_node_tree(3);
+
+        let (current, previous) = pool.current_and_previous_mut();
+        current.add_single_node_tree(*previous.iter_trees().next().unwrap().val());
+        assert_eq!(pool.current_mut().iter_flattened().copied().collect::<Vec<_>>(), [3, 2]);
+        let (current, previous) = pool.into_forests();
+        assert_eq!(current.tot_num_nodes(), 2);
+        assert_eq!(previous.tot_num_nodes(), 1);
+    }
+}
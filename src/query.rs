@@ -0,0 +1,287 @@
+// This file contains Selector, a small query language for selecting nodes in a tree,
+// loosely modeled after XPath location paths.
+
+use crate::*;
+
+use std::borrow::Borrow;
+
+/// The direction in which a step of a [`Selector`] moves from the nodes selected so far.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Axis {
+    /// Stay at the selected nodes themselves.
+    SelfNode,
+    /// Move to the children of the selected nodes.
+    Child,
+    /// Move to all descendants of the selected nodes, not including the nodes themselves.
+    Descendant,
+    /// Move to all descendants of the selected nodes, including the nodes themselves.
+    DescendantOrSelf,
+}
+
+type Predicate<'p, T> = Box<dyn Fn(NodeRef<'_, T>) -> bool + 'p>;
+
+struct Step<'p, T> {
+    axis: Axis,
+    predicate: Option<Predicate<'p, T>>,
+}
+
+/// A query that selects nodes in a tree, built from a list of steps.
+///
+/// Starting from a context node (e.g. the root of a tree), each step moves along an [`Axis`]
+/// from the nodes selected so far, optionally keeping only the nodes for which a predicate returns `true`.
+/// The nodes selected by the last step are returned in pre-order, without duplicates.
+///
+/// # Example
+/// ```
+/// use packed_tree::{PackedTree, Selector};
+///
+/// let tree = PackedTree::new(0, |node_builder| {
+///     node_builder.build_child(1, |node_builder| {
+///         node_builder.add_child(5);
+///         node_builder.add_child(2);
+///     });
+///     node_builder.build_child(6, |node_builder| {
+///         node_builder.add_child(7);
+///     });
+/// });
+///
+/// // Select all grandchildren of the root with a value greater than 3
+/// let selector = Selector::new().child().child_where(|node| *node.val() > 3);
+/// let vals: Vec<_> = tree.select(&selector).map(|node| *node.val()).collect();
+/// assert_eq!(vals, [5, 7]);
+/// ```
+pub struct Selector<'p, T> {
+    steps: Vec<Step<'p, T>>,
+}
+
+impl<'p, T> Default for Selector<'p, T> {
+    #[inline(always)]
+    fn default() -> Self {
+        Selector::new()
+    }
+}
+
+impl<'p, T> Selector<'p, T> {
+    /// Create a new [`Selector`] without any steps, which selects only the context node.
+    #[inline(always)]
+    pub fn new() -> Selector<'p, T> {
+        Selector {
+            steps: Vec::new(),
+        }
+    }
+
+    /// Add a step that moves along `axis`, keeping all nodes it reaches.
+    #[inline]
+    pub fn step(mut self, axis: Axis) -> Self {
+        self.steps.push(Step {
+            axis,
+            predicate: None,
+        });
+        self
+    }
+
+    /// Add a step that moves along `axis`, keeping only the nodes for which `predicate` returns `true`.
+    #[inline]
+    pub fn step_where(mut self, axis: Axis, predicate: impl Fn(NodeRef<'_, T>) -> bool + 'p) -> Self {
+        self.steps.push(Step {
+            axis,
+            predicate: Some(Box::new(predicate)),
+        });
+        self
+    }
+
+    /// Add a step that selects all children of the selected nodes.
+    #[inline]
+    pub fn child(self) -> Self {
+        self.step(Axis::Child)
+    }
+
+    /// Add a step that selects the children of the selected nodes for which `predicate` returns `true`.
+    #[inline]
+    pub fn child_where(self, predicate: impl Fn(NodeRef<'_, T>) -> bool + 'p) -> Self {
+        self.step_where(Axis::Child, predicate)
+    }
+
+    /// Add a step that selects all descendants of the selected nodes.
+    #[inline]
+    pub fn descendant(self) -> Self {
+        self.step(Axis::Descendant)
+    }
+
+    /// Add a step that selects the descendants of the selected nodes for which `predicate` returns `true`.
+    #[inline]
+    pub fn descendant_where(self, predicate: impl Fn(NodeRef<'_, T>) -> bool + 'p) -> Self {
+        self.step_where(Axis::Descendant, predicate)
+    }
+
+    /// Add a step that keeps only the selected nodes for which `predicate` returns `true`.
+    #[inline]
+    pub fn filter(self, predicate: impl Fn(NodeRef<'_, T>) -> bool + 'p) -> Self {
+        self.step_where(Axis::SelfNode, predicate)
+    }
+
+    /// Returns the nodes selected by this [`Selector`], starting from the context node `node`, in pre-order.
+    pub fn select<'t>(&self, node: NodeRef<'t, T>) -> std::vec::IntoIter<NodeRef<'t, T>> {
+        // The selected nodes, with their pre-order offsets relative to the context node.
+        let mut selected = vec![(0, node)];
+        for step in &self.steps {
+            let mut next = Vec::new();
+            // The offset one past the end of the last subtree whose descendants were added. The selected nodes are
+            // in pre-order, so a node before this offset is a descendant of that subtree, and its descendants are already added.
+            let mut expanded_end = 0;
+            for (offset, node) in selected {
+                match step.axis {
+                    Axis::SelfNode => next.push((offset, node)),
+                    Axis::Child => {
+                        let mut child_offset = offset + 1;
+                        for child in node.children() {
+                            next.push((child_offset, child));
+                            child_offset += child.num_descendants_incl_self();
+                        }
+                    }
+                    Axis::Descendant | Axis::DescendantOrSelf => {
+                        if offset < expanded_end {
+                            continue;
+                        }
+                        expanded_end = offset + node.num_descendants_incl_self();
+                        let first = if step.axis == Axis::Descendant { 1 } else { 0 };
+                        for descendant_offset in first..node.num_descendants_incl_self() {
+                            next.push((offset + descendant_offset, node.descendant_at_offset(descendant_offset).unwrap()));
+                        }
+                    }
+                }
+            }
+            if let Some(predicate) = &step.predicate {
+                next.retain(|(_, node)| predicate(*node));
+            }
+            // Every node is reached at most once, but the children of a selected node can come after the children
+            // of its selected descendants, so restore pre-order.
+            next.sort_by_key(|(offset, _)| *offset);
+            selected = next;
+        }
+        selected.into_iter().map(|(_, node)| node).collect::<Vec<_>>().into_iter()
+    }
+}
+
+impl<'p, T: Borrow<str> + 'p> Selector<'p, T> {
+    /// Create a [`Selector`] from a glob-like path, where the key of each node is its value.
+    ///
+    /// The path consists of segments separated by [`PATH_SEPARATOR`] (`'/'`), each of which is a step:
+    ///   * `*` selects all children,
+    ///   * `**` selects all descendants, including the nodes themselves,
+    ///   * any other segment selects the children whose value is equal to the segment.
+    ///
+    /// Empty segments are ignored. Like [`PackedTree::lookup_path`], the path doesn't include the context node itself.
+    /// Further steps, e.g. with predicates, can be added to the returned [`Selector`].
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedTree, Selector};
+    ///
+    /// let tree = PackedTree::new("", |node_builder| {
+    ///     node_builder.build_child("src", |node_builder| {
+    ///         node_builder.add_child("lib.rs");
+    ///         node_builder.build_child("bin", |node_builder| {
+    ///             node_builder.add_child("main.rs");
+    ///         });
+    ///     });
+    ///     node_builder.build_child("tests", |node_builder| {
+    ///         node_builder.add_child("test.rs");
+    ///     });
+    /// });
+    ///
+    /// let selector = Selector::<&str>::parse("**").filter(|node| node.val().ends_with(".rs"));
+    /// let rust_files: Vec<_> = tree.select(&selector).map(|node| *node.val()).collect();
+    /// assert_eq!(rust_files, ["lib.rs", "main.rs", "test.rs"]);
+    ///
+    /// let in_src: Vec<_> = tree.select(&Selector::parse("src/*")).map(|node| *node.val()).collect();
+    /// assert_eq!(in_src, ["lib.rs", "bin"]);
+    /// ```
+    pub fn parse(path: &str) -> Selector<'p, T> {
+        let mut selector = Selector::<'p, T>::new();
+        for segment in path.split(PATH_SEPARATOR).filter(|segment| !segment.is_empty()) {
+            selector = match segment {
+                "*" => selector.child(),
+                "**" => selector.step(Axis::DescendantOrSelf),
+                key => {
+                    let key = key.to_string();
+                    selector.child_where(move |node| node.val().borrow() == key)
+                }
+            };
+        }
+        selector
+    }
+}
+
+impl<'t, T> NodeRef<'t, T> {
+    /// Returns the nodes selected by `selector` with this node as context node, in pre-order.
+    ///
+    /// See [`Selector`].
+    #[inline]
+    pub fn select(&self, selector: &Selector<'_, T>) -> std::vec::IntoIter<NodeRef<'t, T>> {
+        selector.select(*self)
+    }
+}
+
+impl<T> PackedTree<T> {
+    /// Returns the nodes selected by `selector` with the root as context node, in pre-order.
+    ///
+    /// See [`Selector`].
+    #[inline]
+    pub fn select(&self, selector: &Selector<'_, T>) -> std::vec::IntoIter<NodeRef<'_, T>> {
+        selector.select(self.root())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_tree() -> PackedTree<&'static str> {
+        PackedTree::new("root", |node_builder| {
+            node_builder.build_child("a", |node_builder| {
+                node_builder.build_child("b", |node_builder| {
+                    node_builder.add_child("c");
+                });
+                node_builder.add_child("c");
+            });
+            node_builder.build_child("b", |node_builder| {
+                node_builder.add_child("c");
+            });
+        })
+    }
+
+    fn select_vals(tree: &PackedTree<&'static str>, selector: &Selector<&'static str>) -> Vec<&'static str> {
+        tree.select(selector).map(|node| *node.val()).collect()
+    }
+
+    #[test]
+    fn test_select_steps() {
+        let tree = build_tree();
+        assert_eq!(select_vals(&tree, &Selector::new()), ["root"]);
+        assert_eq!(select_vals(&tree, &Selector::new().child()), ["a", "b"]);
+        assert_eq!(select_vals(&tree, &Selector::new().descendant().filter(|node| *node.val() == "c")).len(), 3);
+        // overlapping descendant steps don't produce duplicates
+        assert_eq!(tree.select(&Selector::new().descendant().descendant()).count(), 4);
+        assert_eq!(select_vals(&tree, &Selector::new().descendant_where(|node| node.num_descendants_excl_self() > 0)), ["a", "b", "b"]);
+    }
+
+    #[test]
+    fn test_select_parse() {
+        let tree = build_tree();
+        assert_eq!(select_vals(&tree, &Selector::parse("a/*")), ["b", "c"]);
+        assert_eq!(tree.select(&Selector::parse("**/c")).count(), 3);
+        assert_eq!(tree.select(&Selector::parse("**/b/c")).count(), 2);
+        assert_eq!(select_vals(&tree, &Selector::parse("")), ["root"]);
+        assert_eq!(tree.select(&Selector::parse("x/*")).count(), 0);
+    }
+
+    #[test]
+    fn test_select_nested_descendants() {
+        // A long chain, where every descendant step reaches almost every node from almost every selected node
+        let chain = PackedTree::try_from_forest(PackedForest::from_depth_values((0..100_000).map(|i| (i, i))).unwrap()).unwrap();
+        let selector = Selector::new().descendant().descendant().step(Axis::DescendantOrSelf);
+        let selected: Vec<_> = chain.select(&selector).map(|node| *node.val()).collect();
+        assert_eq!(selected, (2..100_000).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,142 @@
+#![cfg(any(feature = "rand", test))]
+
+// This file contains functions for randomly sampling nodes of a PackedForest.
+// Since nodes are stored in pre-order, uniform sampling is just sampling an index.
+
+use ::rand::Rng;
+
+use crate::*;
+
+impl<T> PackedForest<T> {
+    /// Returns a uniformly random node of the forest in O(1) time, or `None` if the forest is empty.
+    ///
+    /// Requires the `rand` feature.
+    #[inline]
+    pub fn sample_node<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<NodeRef<'_, T>> {
+        if self.tot_num_nodes() == 0 {
+            None
+        } else {
+            self.get(rng.gen_range(0, self.tot_num_nodes()))
+        }
+    }
+}
+
+impl<T> PackedTree<T> {
+    /// Returns a uniformly random node of the tree in O(1) time.
+    ///
+    /// Requires the `rand` feature.
+    #[inline]
+    pub fn sample_node<R: Rng + ?Sized>(&self, rng: &mut R) -> NodeRef<'_, T> {
+        self.root().sample_descendant(rng)
+    }
+}
+
+impl<'t, T> NodeRef<'t, T> {
+    /// Returns a uniformly random node among the descendants of this node (including the node itself) in O(1) time.
+    ///
+    /// Requires the `rand` feature.
+    #[inline]
+    pub fn sample_descendant<R: Rng + ?Sized>(&self, rng: &mut R) -> NodeRef<'t, T> {
+        let offset = rng.gen_range(0, self.num_descendants_incl_self());
+        self.descendant_at_offset(offset).unwrap()
+    }
+
+    /// Returns a random child of this node, where the probability of each child being chosen is proportional
+    /// to the number of nodes in its subtree. Returns `None` if this node has no children.
+    ///
+    /// Repeatedly calling this method starting from a root, until a node is chosen
+    /// (see [`sample_child_or_self_by_subtree_size`](NodeRef::sample_child_or_self_by_subtree_size)),
+    /// performs a random walk that ends in a uniformly random node.
+    ///
+    /// This takes time linear in the number of children of this node.
+    ///
+    /// Requires the `rand` feature.
+    pub fn sample_child_by_subtree_size<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<NodeRef<'t, T>> {
+        if self.num_descendants_excl_self() == 0 {
+            return None;
+        }
+        let mut remaining = rng.gen_range(0, self.num_descendants_excl_self());
+        for child in self.children() {
+            if remaining < child.num_descendants_incl_self() {
+                return Some(child);
+            }
+            remaining -= child.num_descendants_incl_self();
+        }
+        unreachable!()
+    }
+
+    /// Chooses either this node or one of its children at random, where the probability of choosing this node is 1 over
+    /// the number of nodes in its subtree, and the probability of each child is proportional to the number of nodes in the child's subtree.
+    /// Returns `None` if this node was chosen.
+    ///
+    /// Starting at a root and repeatedly calling this method on the chosen child until `None` is returned
+    /// ends at a uniformly random node of the tree.
+    ///
+    /// Requires the `rand` feature.
+    pub fn sample_child_or_self_by_subtree_size<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<NodeRef<'t, T>> {
+        if rng.gen_range(0, self.num_descendants_incl_self()) == 0 {
+            None
+        } else {
+            self.sample_child_by_subtree_size(rng)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::rand::SeedableRng;
+    use ::rand_xorshift::XorShiftRng;
+
+    fn build_tree() -> PackedTree<usize> {
+        PackedTree::new(0, |node_builder| {
+            node_builder.build_child(1, |node_builder| {
+                node_builder.add_child(2);
+                node_builder.add_child(3);
+                node_builder.add_child(4);
+            });
+            node_builder.add_child(5);
+        })
+    }
+
+    #[test]
+    fn test_sample_node() {
+        let tree = build_tree();
+        let mut rng = XorShiftRng::seed_from_u64(123);
+        let mut counts = [0; 6];
+        for _ in 0..6000 {
+            counts[*tree.sample_node(&mut rng).val()] += 1;
+        }
+        assert!(counts.iter().all(|count| *count > 800 && *count < 1200), "{:?}", counts);
+
+        assert!(PackedForest::<usize>::new().sample_node(&mut rng).is_none());
+    }
+
+    #[test]
+    fn test_sample_descendant() {
+        let tree = build_tree();
+        let mut rng = XorShiftRng::seed_from_u64(123);
+        let node = tree.get(1).unwrap();
+        for _ in 0..100 {
+            let val = *node.sample_descendant(&mut rng).val();
+            assert!((1..=4).contains(&val));
+        }
+    }
+
+    #[test]
+    fn test_random_walk() {
+        let tree = build_tree();
+        let mut rng = XorShiftRng::seed_from_u64(123);
+        let mut counts = [0; 6];
+        for _ in 0..6000 {
+            let mut node = tree.root();
+            while let Some(child) = node.sample_child_or_self_by_subtree_size(&mut rng) {
+                node = child;
+            }
+            counts[*node.val()] += 1;
+        }
+        assert!(counts.iter().all(|count| *count > 800 && *count < 1200), "{:?}", counts);
+
+        assert!(tree.get(5).unwrap().sample_child_by_subtree_size(&mut rng).is_none());
+    }
+}
@@ -0,0 +1,500 @@
+// This file contains Reconciliation, which matches the nodes of an old and a new version of a forest by key,
+// e.g. to move state that is stored outside of the forest over to a forest that was rebuilt,
+// and diff_keyed, which turns such a matching into a list of operations, like a virtual DOM.
+
+use crate::*;
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// A matching between the nodes of an old and a new version of a [`PackedForest`], based on keys that are
+/// computed from the values of the nodes.
+///
+/// Nodes are matched the way UI frameworks match the elements of a rebuilt user interface: the trees of the
+/// forests are matched by key, and then the children of every pair of matched nodes are matched by key,
+/// so a node can only be matched if its parent is matched too. Keys only need to be unique among siblings.
+/// If multiple siblings have the same key, they are matched in order.
+///
+/// # Example
+/// ```
+/// use packed_tree::{PackedForest, Reconciliation};
+///
+/// let mut old = PackedForest::new();
+/// old.build_tree("list", |node_builder| {
+///     node_builder.add_child("a");
+///     node_builder.add_child("b");
+/// });
+/// let mut new = PackedForest::new();
+/// new.build_tree("list", |node_builder| {
+///     node_builder.add_child("c");
+///     node_builder.add_child("a");
+/// });
+///
+/// let reconciliation = Reconciliation::new(&old, &new, |val| *val);
+/// assert_eq!(reconciliation.old_to_new(1), Some(2)); // "a"
+/// assert_eq!(reconciliation.old_to_new(2), None); // "b"
+/// assert_eq!(reconciliation.added_keys(), ["c"]);
+/// assert_eq!(reconciliation.removed_keys(), ["b"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Reconciliation<K> {
+    old_to_new: Vec<Option<usize>>,
+    new_to_old: Vec<Option<usize>>,
+    added_keys: Vec<K>,
+    removed_keys: Vec<K>,
+}
+
+// Returns the indices of the nodes in `range` that aren't descendants of other nodes in `range`,
+// i.e. of the children of a node if `range` is the range of its descendants.
+fn sibling_indices<T>(forest: &PackedForest<T>, range: std::ops::Range<usize>) -> Vec<usize> {
+    let mut indices = Vec::new();
+    let mut index = range.start;
+    while index < range.end {
+        indices.push(index);
+        index += forest.raw_data()[index].subtree_size().get();
+    }
+    indices
+}
+
+// Returns the range of the descendants (not including itself) of the node with the given index.
+#[inline]
+fn descendant_range<T>(forest: &PackedForest<T>, index: usize) -> std::ops::Range<usize> {
+    (index + 1)..(index + forest.raw_data()[index].subtree_size().get())
+}
+
+// Matches `old_siblings` with `new_siblings` by key, and returns the matched pairs in the order of `new_siblings`.
+fn match_siblings<K: Hash + Eq>(
+    old_siblings: &[usize],
+    old_keys: &[K],
+    new_siblings: &[usize],
+    new_keys: &[K],
+) -> Vec<(usize, usize)> {
+    let mut old_by_key: HashMap<&K, VecDeque<usize>> = HashMap::new();
+    for &old_index in old_siblings {
+        old_by_key.entry(&old_keys[old_index]).or_default().push_back(old_index);
+    }
+    new_siblings
+        .iter()
+        .filter_map(|&new_index| {
+            let old_index = old_by_key.get_mut(&new_keys[new_index])?.pop_front()?;
+            Some((old_index, new_index))
+        })
+        .collect()
+}
+
+impl<K: Hash + Eq> Reconciliation<K> {
+    /// Matches the nodes of `old` with the nodes of `new`, using the keys that `key_fn` returns for their values.
+    ///
+    /// `key_fn` is called once for every node of both forests. Apart from computing the keys,
+    /// this takes O(n) time, where n is the total number of nodes, and doesn't use recursion.
+    /// To match the nodes of [`PackedTree`]s, pass their [`as_ref`](AsRef::as_ref).
+    pub fn new<T>(old: &PackedForest<T>, new: &PackedForest<T>, mut key_fn: impl FnMut(&T) -> K) -> Reconciliation<K> {
+        let old_keys: Vec<K> = old.iter_flattened().map(&mut key_fn).collect();
+        let new_keys: Vec<K> = new.iter_flattened().map(&mut key_fn).collect();
+        let mut old_to_new = vec![None; old_keys.len()];
+        let mut new_to_old = vec![None; new_keys.len()];
+
+        // The lists of siblings that still have to be matched
+        let mut stack = vec![(sibling_indices(old, 0..old_keys.len()), sibling_indices(new, 0..new_keys.len()))];
+        while let Some((old_siblings, new_siblings)) = stack.pop() {
+            for (old_index, new_index) in match_siblings(&old_siblings, &old_keys, &new_siblings, &new_keys) {
+                old_to_new[old_index] = Some(new_index);
+                new_to_old[new_index] = Some(old_index);
+                stack.push((
+                    sibling_indices(old, descendant_range(old, old_index)),
+                    sibling_indices(new, descendant_range(new, new_index)),
+                ));
+            }
+        }
+
+        let added_keys = new_keys.into_iter().zip(&new_to_old).filter(|(_, old_index)| old_index.is_none()).map(|(key, _)| key).collect();
+        let removed_keys = old_keys.into_iter().zip(&old_to_new).filter(|(_, new_index)| new_index.is_none()).map(|(key, _)| key).collect();
+        Reconciliation { old_to_new, new_to_old, added_keys, removed_keys }
+    }
+}
+
+impl<K> Reconciliation<K> {
+    /// Returns the index in the new forest of the node that is matched with the node with index `old_index`
+    /// in the old forest, or `None` if it isn't matched (or if `old_index` is out of bounds).
+    #[inline]
+    pub fn old_to_new(&self, old_index: usize) -> Option<usize> {
+        self.old_to_new.get(old_index).copied().flatten()
+    }
+
+    /// Returns the index in the old forest of the node that is matched with the node with index `new_index`
+    /// in the new forest, or `None` if it isn't matched (or if `new_index` is out of bounds).
+    #[inline]
+    pub fn new_to_old(&self, new_index: usize) -> Option<usize> {
+        self.new_to_old.get(new_index).copied().flatten()
+    }
+
+    /// Returns, for every node of the old forest in pre-order, the index of the matched node in the new forest.
+    #[inline(always)]
+    pub fn old_to_new_indices(&self) -> &[Option<usize>] {
+        &self.old_to_new
+    }
+
+    /// Returns, for every node of the new forest in pre-order, the index of the matched node in the old forest.
+    #[inline(always)]
+    pub fn new_to_old_indices(&self) -> &[Option<usize>] {
+        &self.new_to_old
+    }
+
+    /// Returns the keys of the nodes of the new forest that aren't matched, in pre-order.
+    #[inline(always)]
+    pub fn added_keys(&self) -> &[K] {
+        &self.added_keys
+    }
+
+    /// Returns the keys of the nodes of the old forest that aren't matched, in pre-order.
+    #[inline(always)]
+    pub fn removed_keys(&self) -> &[K] {
+        &self.removed_keys
+    }
+}
+
+/// A single operation of a keyed diff, see [`diff_keyed`].
+///
+/// Nodes of the old forest are identified by their pre-order index in the old forest, and nodes of
+/// the new forest by their pre-order index in the new forest, so the indices don't change while the operations
+/// are applied. Only positions change: `position` is the position among the children of `parent` (or among the trees,
+/// if `parent` is `None`) right after the operation, given that all earlier operations have been applied.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum DiffOp {
+    /// Remove the node with index `old_index` in the old forest, together with all its descendants.
+    Remove { old_index: usize },
+    /// Insert a copy of the node with index `new_index` in the new forest, together with all its descendants,
+    /// so that it becomes child number `position` of the node with index `parent` in the old forest.
+    Insert { parent: Option<usize>, position: usize, new_index: usize },
+    /// Move the node with index `old_index` in the old forest, together with all its descendants,
+    /// so that it becomes child number `position` of its parent. It is matched with the node with index `new_index`
+    /// in the new forest.
+    Move { parent: Option<usize>, position: usize, old_index: usize, new_index: usize },
+    /// Replace the value of the node with index `old_index` in the old forest by the value of
+    /// the node with index `new_index` in the new forest.
+    Update { old_index: usize, new_index: usize },
+}
+
+// Returns, for every element of `seq`, whether it is part of a longest strictly increasing subsequence of `seq`.
+fn longest_increasing_subsequence(seq: &[usize]) -> Vec<bool> {
+    // tails[len - 1] is the index in `seq` of the smallest last element of an increasing subsequence of length `len`
+    let mut tails: Vec<usize> = Vec::new();
+    let mut predecessors = vec![None; seq.len()];
+    for (i, &val) in seq.iter().enumerate() {
+        let len = tails.partition_point(|&tail| seq[tail] < val);
+        predecessors[i] = if len > 0 { Some(tails[len - 1]) } else { None };
+        if len == tails.len() {
+            tails.push(i);
+        } else {
+            tails[len] = i;
+        }
+    }
+    let mut in_subsequence = vec![false; seq.len()];
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        in_subsequence[i] = true;
+        next = predecessors[i];
+    }
+    in_subsequence
+}
+
+// Appends the operations that turn the children `old_siblings` of the old node `parent` into the children `new_siblings`
+// of the matching new node, and returns the matched children.
+fn diff_siblings<K: Hash + Eq>(
+    parent: Option<usize>,
+    old_siblings: &[usize],
+    old_keys: &[K],
+    new_siblings: &[usize],
+    new_keys: &[K],
+    ops: &mut Vec<DiffOp>,
+) -> Vec<(usize, usize)> {
+    let matched = match_siblings(old_siblings, old_keys, new_siblings, new_keys);
+    let mut new_to_old = HashMap::new();
+    let mut old_is_matched = vec![false; old_siblings.len()];
+    let old_positions: HashMap<usize, usize> = old_siblings.iter().enumerate().map(|(position, &old_index)| (old_index, position)).collect();
+    for &(old_index, new_index) in &matched {
+        new_to_old.insert(new_index, old_index);
+        old_is_matched[old_positions[&old_index]] = true;
+    }
+
+    for (position, &old_index) in old_siblings.iter().enumerate() {
+        if !old_is_matched[position] {
+            ops.push(DiffOp::Remove { old_index });
+        }
+    }
+
+    // The matched nodes that are part of a longest increasing subsequence of old positions keep their place,
+    // which minimizes the number of moves.
+    let stays = longest_increasing_subsequence(&matched.iter().map(|(old_index, _)| old_positions[old_index]).collect::<Vec<_>>());
+    let mut matched_stays = stays.into_iter();
+    for (position, &new_index) in new_siblings.iter().enumerate() {
+        match new_to_old.get(&new_index) {
+            Some(&old_index) => {
+                if !matched_stays.next().unwrap() {
+                    ops.push(DiffOp::Move { parent, position, old_index, new_index });
+                }
+            }
+            None => ops.push(DiffOp::Insert { parent, position, new_index }),
+        }
+    }
+    matched
+}
+
+/// Computes the operations that turn `old` into `new`, matching their nodes by the keys that `key_fn` returns
+/// for their values, the way a virtual DOM updates a retained user interface.
+///
+/// Nodes are matched like in [`Reconciliation`]: first the trees, then the children of every pair of matched nodes.
+/// For every list of matched siblings, the operations are:
+/// 1. a [`DiffOp::Remove`] for every old node that isn't matched, and
+/// 2. in the order of the new siblings, a [`DiffOp::Insert`] for every new node that isn't matched, and a
+///    [`DiffOp::Move`] for every matched node that is out of place.
+///
+/// To move as few nodes as possible, the matched nodes that stay in place are those that form a
+/// longest increasing subsequence of their old positions, computed in O(m log m) time for m siblings.
+/// In addition, there is a [`DiffOp::Update`] for every pair of matched nodes whose values differ.
+///
+/// Applying the operations in order turns the old forest into the new one. `key_fn` is called once for every node
+/// of both forests. To diff [`PackedTree`]s, pass their [`as_ref`](AsRef::as_ref).
+///
+/// # Example
+/// ```
+/// use packed_tree::{diff_keyed, DiffOp, PackedForest};
+///
+/// let mut old = PackedForest::new();
+/// old.build_tree(("list", 0), |node_builder| {
+///     node_builder.add_child(("a", 1));
+///     node_builder.add_child(("b", 2));
+///     node_builder.add_child(("c", 3));
+/// });
+/// let mut new = PackedForest::new();
+/// new.build_tree(("list", 0), |node_builder| {
+///     node_builder.add_child(("c", 3));
+///     node_builder.add_child(("a", 10));
+///     node_builder.add_child(("d", 4));
+/// });
+///
+/// let ops = diff_keyed(&old, &new, |(key, _)| *key);
+/// assert_eq!(ops, [
+///     DiffOp::Remove { old_index: 2 },
+///     DiffOp::Move { parent: Some(0), position: 0, old_index: 3, new_index: 1 },
+///     DiffOp::Insert { parent: Some(0), position: 2, new_index: 3 },
+///     DiffOp::Update { old_index: 1, new_index: 2 },
+/// ]);
+/// ```
+pub fn diff_keyed<T: PartialEq, K: Hash + Eq>(old: &PackedForest<T>, new: &PackedForest<T>, mut key_fn: impl FnMut(&T) -> K) -> Vec<DiffOp> {
+    let old_keys: Vec<K> = old.iter_flattened().map(&mut key_fn).collect();
+    let new_keys: Vec<K> = new.iter_flattened().map(&mut key_fn).collect();
+    let mut ops = Vec::new();
+    let mut updates = Vec::new();
+
+    // The matched parents whose children still have to be diffed, and their children
+    let mut stack = vec![(None, sibling_indices(old, 0..old_keys.len()), sibling_indices(new, 0..new_keys.len()))];
+    while let Some((parent, old_siblings, new_siblings)) = stack.pop() {
+        let matched = diff_siblings(parent, &old_siblings, &old_keys, &new_siblings, &new_keys, &mut ops);
+        // Pushed in reverse, so that the children are diffed in order
+        for &(old_index, new_index) in matched.iter().rev() {
+            stack.push((
+                Some(old_index),
+                sibling_indices(old, descendant_range(old, old_index)),
+                sibling_indices(new, descendant_range(new, new_index)),
+            ));
+        }
+        for (old_index, new_index) in matched {
+            if old.raw_data()[old_index].val() != new.raw_data()[new_index].val() {
+                updates.push(DiffOp::Update { old_index, new_index });
+            }
+        }
+    }
+    ops.append(&mut updates);
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconciliation() {
+        let mut old = PackedForest::new();
+        old.build_tree("a", |node_builder| {
+            node_builder.build_child("x", |node_builder| {
+                node_builder.add_child("y");
+            });
+            node_builder.add_child("z");
+            node_builder.add_child("z");
+        });
+        old.build_tree("b", |node_builder| {
+            node_builder.add_child("x");
+        });
+
+        let mut new = PackedForest::new();
+        new.build_tree("b", |node_builder| {
+            node_builder.add_child("y");
+        });
+        new.build_tree("a", |node_builder| {
+            node_builder.add_child("z");
+            node_builder.build_child("x", |node_builder| {
+                node_builder.add_child("y");
+                node_builder.add_child("w");
+            });
+        });
+        new.add_single_node_tree("c");
+
+        let mut num_calls = 0;
+        let reconciliation = Reconciliation::new(&old, &new, |val| {
+            num_calls += 1;
+            val.to_string()
+        });
+        assert_eq!(num_calls, old.tot_num_nodes() + new.tot_num_nodes());
+        assert_eq!(reconciliation.old_to_new_indices(), [Some(2), Some(4), Some(5), Some(3), None, Some(0), None]);
+        assert_eq!(reconciliation.new_to_old_indices(), [Some(5), None, Some(0), Some(3), Some(1), Some(2), None, None]);
+        assert_eq!(reconciliation.added_keys(), ["y", "w", "c"]);
+        assert_eq!(reconciliation.removed_keys(), ["z", "x"]);
+        assert_eq!((reconciliation.old_to_new(100), reconciliation.new_to_old(1)), (None, None));
+
+        let same = Reconciliation::new(&old, &old, |val| *val);
+        assert!(same.old_to_new_indices().iter().enumerate().all(|(index, new_index)| *new_index == Some(index)));
+        assert!(same.added_keys().is_empty() && same.removed_keys().is_empty());
+    }
+
+    // Applies the operations of a keyed diff to a nested copy of `old`, to check that they give `new`.
+    fn apply_ops(old: &PackedForest<(char, i32)>, new: &PackedForest<(char, i32)>, ops: &[DiffOp]) -> PackedForest<(char, i32)> {
+        #[derive(Clone)]
+        struct Node {
+            old_index: Option<usize>,
+            val: (char, i32),
+            children: Vec<Node>,
+        }
+        fn from_node(node: NodeRef<(char, i32)>, index: Option<usize>) -> Node {
+            let mut child_index = index.map(|index| index + 1);
+            let children = node.children().map(|child| {
+                let node = from_node(child, child_index);
+                child_index = child_index.map(|index| index + child.num_descendants_incl_self());
+                node
+            }).collect();
+            Node { old_index: index, val: *node.val(), children }
+        }
+        fn find_list(nodes: &mut Vec<Node>, parent: usize) -> Option<&mut Vec<Node>> {
+            for node in nodes {
+                if node.old_index == Some(parent) {
+                    return Some(&mut node.children);
+                }
+                if let Some(list) = find_list(&mut node.children, parent) {
+                    return Some(list);
+                }
+            }
+            None
+        }
+        fn remove(nodes: &mut Vec<Node>, old_index: usize) -> Option<Node> {
+            if let Some(position) = nodes.iter().position(|node| node.old_index == Some(old_index)) {
+                return Some(nodes.remove(position));
+            }
+            nodes.iter_mut().find_map(|node| remove(&mut node.children, old_index))
+        }
+        fn update(nodes: &mut Vec<Node>, old_index: usize, val: (char, i32)) {
+            for node in nodes {
+                if node.old_index == Some(old_index) {
+                    node.val = val;
+                }
+                update(&mut node.children, old_index, val);
+            }
+        }
+        fn add_node(node: &Node, node_builder: &mut NodeBuilder<(char, i32)>) {
+            node_builder.build_child(node.val, |node_builder| {
+                for child in &node.children {
+                    add_node(child, node_builder);
+                }
+            });
+        }
+
+        let mut roots = Vec::new();
+        let mut index = 0;
+        for tree in old.iter_trees() {
+            roots.push(from_node(tree, Some(index)));
+            index += tree.num_descendants_incl_self();
+        }
+        for op in ops {
+            match *op {
+                DiffOp::Remove { old_index } => {
+                    remove(&mut roots, old_index).unwrap();
+                }
+                DiffOp::Insert { parent, position, new_index } => {
+                    let node = from_node(new.get(new_index).unwrap(), None);
+                    let list = match parent {
+                        Some(parent) => find_list(&mut roots, parent).unwrap(),
+                        None => &mut roots,
+                    };
+                    list.insert(position, node);
+                }
+                DiffOp::Move { parent, position, old_index, .. } => {
+                    let list = match parent {
+                        Some(parent) => find_list(&mut roots, parent).unwrap(),
+                        None => &mut roots,
+                    };
+                    let from = list.iter().position(|node| node.old_index == Some(old_index)).unwrap();
+                    let node = list.remove(from);
+                    list.insert(position, node);
+                }
+                DiffOp::Update { old_index, new_index } => {
+                    update(&mut roots, old_index, *new.raw_data()[new_index].val());
+                }
+            }
+        }
+
+        let mut result = PackedForest::new();
+        for root in &roots {
+            result.build_tree(root.val, |node_builder| {
+                for child in &root.children {
+                    add_node(child, node_builder);
+                }
+            });
+        }
+        result
+    }
+
+    #[test]
+    fn test_diff_keyed() {
+        fn build(trees: &[(char, &[(char, i32)])]) -> PackedForest<(char, i32)> {
+            let mut forest = PackedForest::new();
+            for (key, children) in trees {
+                forest.build_tree((*key, 0), |node_builder| {
+                    node_builder.add_leaf_children(children.iter().copied());
+                });
+            }
+            forest
+        }
+
+        let forests = [
+            build(&[]),
+            build(&[('r', &[('a', 1), ('b', 2), ('c', 3), ('d', 4), ('e', 5)])]),
+            build(&[('r', &[('e', 5), ('a', 1), ('b', 20), ('c', 3), ('f', 6), ('d', 4)])]),
+            build(&[('s', &[('a', 1)]), ('r', &[('d', 4), ('c', 3), ('b', 2), ('a', 1)])]),
+            build(&[('r', &[('a', 1), ('a', 2), ('x', 0)]), ('s', &[])]),
+        ];
+        for old in &forests {
+            for new in &forests {
+                let ops = diff_keyed(old, new, |(key, _)| *key);
+                assert!(apply_ops(old, new, &ops) == *new, "{:?}", ops);
+            }
+        }
+
+        assert!(diff_keyed(&forests[1], &forests[1], |(key, _)| *key).is_empty());
+        // Only "e" is moved, and "f" is inserted
+        let ops = diff_keyed(&forests[1], &forests[2], |(key, _)| *key);
+        assert_eq!(ops, [
+            DiffOp::Move { parent: Some(0), position: 0, old_index: 5, new_index: 1 },
+            DiffOp::Insert { parent: Some(0), position: 4, new_index: 5 },
+            DiffOp::Update { old_index: 2, new_index: 3 },
+        ]);
+        // Reversing the children keeps one of them in place
+        let ops = diff_keyed(&forests[1], &forests[3], |(key, _)| *key);
+        assert_eq!(ops.iter().filter(|op| matches!(op, DiffOp::Move { .. })).count(), 3);
+    }
+
+    #[test]
+    fn test_longest_increasing_subsequence() {
+        assert!(longest_increasing_subsequence(&[]).is_empty());
+        assert_eq!(longest_increasing_subsequence(&[3, 0, 1, 4, 2]), [false, true, true, false, true]);
+        assert_eq!(longest_increasing_subsequence(&[2, 1, 0]).iter().filter(|in_subsequence| **in_subsequence).count(), 1);
+    }
+}
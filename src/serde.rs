@@ -2,14 +2,12 @@
 
 use ::serde::de;
 use ::serde::de::{DeserializeSeed, SeqAccess, Visitor};
-use ::serde::ser::{SerializeSeq, SerializeStruct};
+use ::serde::ser::{SerializeSeq, SerializeStruct, SerializeTuple};
 use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::*;
 
-use std::clone::Clone;
 use std::fmt;
-use std::ops::Deref;
 
 #[derive(Deserialize)]
 struct FlatNode<T> {
@@ -17,26 +15,38 @@ struct FlatNode<T> {
     subtree_size: usize,
 }
 
+// Serializes the trees in `trees`, whose raw data is `data`, in the way that a PackedForest is serialized.
+fn serialize_trees<T: Serialize, S: Serializer>(trees: NodeIter<'_, T>, data: &[NodeData<T>], serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        let mut seq = serializer.serialize_seq(None)?;
+        for node in trees {
+            seq.serialize_element(&node)?;
+        }
+        seq.end()
+    } else {
+        let mut seq = serializer.serialize_seq(Some(data.len()))?;
+        for node in data {
+            seq.serialize_element(node)?;
+        }
+        seq.end()
+    }
+}
+
 impl<T: Serialize> Serialize for PackedForest<T> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        if serializer.is_human_readable() {
-            let mut seq = serializer.serialize_seq(None)?;
-            for node in self.iter_trees() {
-                seq.serialize_element(&node)?;
-            }
-            seq.end()
-        } else {
-            let data = self.raw_data();
+        serialize_trees(self.iter_trees(), self.raw_data(), serializer)
+    }
+}
 
-            let mut seq = serializer.serialize_seq(Some(data.len()))?;
-            for node in data {
-                seq.serialize_element(node.deref())?;
-            }
-            seq.end()
-        }
+impl<'a, T: Serialize> Serialize for SubforestView<'a, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize_trees(self.iter_trees(), self.raw_data(), serializer)
     }
 }
 
@@ -46,7 +56,7 @@ impl<'t, T: Serialize> Serialize for NodeIter<'t, T> {
         S: Serializer,
     {
         let mut seq = serializer.serialize_seq(None)?;
-        for node in (*self).clone() {
+        for node in *self {
             seq.serialize_element(&node)?;
         }
         seq.end()
@@ -65,18 +75,29 @@ impl<'t, T: Serialize> Serialize for NodeRef<'t, T> {
     }
 }
 
+// In human-readable formats, a NodeData is serialized as a struct with named fields. In compact formats, it is
+// serialized as a tuple, so that self-describing formats like CBOR or MessagePack don't store the field names
+// for every node. FlatNode can be deserialized from both, so data that was serialized as a struct by earlier
+// versions can still be read.
 impl<T: Serialize> Serialize for NodeData<T> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let mut s = serializer.serialize_struct("FlatNode", 2)?;
-        s.serialize_field("val", self.val())?;
-        s.serialize_field(
-            "subtree_size",
-            &self.subtree_size().get(),
-        )?;
-        s.end()
+        if serializer.is_human_readable() {
+            let mut s = serializer.serialize_struct("FlatNode", 2)?;
+            s.serialize_field("val", self.val())?;
+            s.serialize_field(
+                "subtree_size",
+                &self.subtree_size().get(),
+            )?;
+            s.end()
+        } else {
+            let mut s = serializer.serialize_tuple(2)?;
+            s.serialize_element(self.val())?;
+            s.serialize_element(&self.subtree_size().get())?;
+            s.end()
+        }
     }
 }
 
@@ -164,9 +185,9 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for PackedForest<T> {
                 where
                     A: SeqAccess<'de>,
                 {
-                    while let Some(_) = seq.next_element_seed(RecNodeDeserializer {
+                    while seq.next_element_seed(RecNodeDeserializer {
                         node_builder: self.node_builder,
-                    })? {}
+                    })?.is_some() {}
 
                     Ok(())
                 }
@@ -251,9 +272,9 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for PackedForest<T> {
                 where
                     A: SeqAccess<'de>,
                 {
-                    while let Some(_) = seq.next_element_seed(RootNodeDeserializer {
+                    while seq.next_element_seed(RootNodeDeserializer {
                         tree_store_mut_ref: self.tree_store_mut_ref,
-                    })? {}
+                    })?.is_some() {}
 
                     Ok(())
                 }
@@ -267,104 +288,60 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for PackedForest<T> {
 
             Ok(result)
         } else {
-            struct FlatNodeListDeserializer<'a, T> {
-                tree_store_mut_ref: &'a mut PackedForest<T>,
+            struct FlatNodeListDeserializer<T> {
+                phantom: std::marker::PhantomData<T>,
             }
 
-            impl<'de, 'a, T> DeserializeSeed<'de> for FlatNodeListDeserializer<'a, T>
+            impl<'de, T> Visitor<'de> for FlatNodeListDeserializer<T>
             where
                 T: Deserialize<'de>,
             {
-                type Value = ();
-
-                fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
-                where
-                    D: Deserializer<'de>,
-                {
-                    deserializer.deserialize_seq(self)
-                }
-            }
-
-            impl<'de, 'a, T> Visitor<'de> for FlatNodeListDeserializer<'a, T>
-            where
-                T: Deserialize<'de>,
-            {
-                type Value = ();
+                type Value = PackedForest<T>;
 
                 fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
                     write!(formatter, "a sequence")
                 }
 
-                fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error>
+                fn visit_seq<A>(self, mut seq: A) -> Result<PackedForest<T>, A::Error>
                 where
                     A: SeqAccess<'de>,
                 {
-                    // reads n elements from the SeqAccess and adds them as nodes to the node_builder
-                    fn rec_add_n_children<'de, T: Deserialize<'de>, A: SeqAccess<'de>>(
-                        seq: &mut A,
-                        n: usize,
-                        node_builder: &mut NodeBuilder<T>,
-                    ) -> Result<(), A::Error> {
-                        let mut num_read = 0;
-                        while num_read < n {
-                            if let Some(node) = seq.next_element::<FlatNode<T>>()? {
-                                num_read += 1;
-                                let max_num_left_to_read = n - num_read;
-                                if node.subtree_size == 0 {
-                                    return Err(de::Error::invalid_length(
-                                        num_read,
-                                        &"subtree_size invalid",
-                                    ));
-                                }
-                                let n_rec = node.subtree_size - 1;
-                                if n_rec > max_num_left_to_read {
-                                    return Err(de::Error::invalid_length(
-                                        num_read,
-                                        &"subtree_size invalid",
-                                    ));
-                                }
-                                let mut node_builder_rec = node_builder.get_child_builder();
-                                rec_add_n_children(seq, n_rec, &mut node_builder_rec)?;
-                                node_builder_rec.finish(node.val);
-                                num_read += n_rec;
-                            } else {
-                                return Err(de::Error::invalid_length(
-                                    num_read,
-                                    &"offset too large",
-                                ));
-                            }
-                        }
-                        Ok(())
-                    }
-
+                    // The decoder checks every subtree size, and doesn't recurse, so malformed or malicious input
+                    // gives a DecodeError instead of a panic or a stack overflow.
+                    let mut decoder = FlatNodeDecoder::new();
                     while let Some(node) = seq.next_element::<FlatNode<T>>()? {
-                        let subtree_size = node.subtree_size;
-                        if subtree_size == 0 {
-                            return Err(de::Error::invalid_length(
-                                0,
-                                &"subtree_size invalid",
-                            ));
-                        }
-                        let mut tree_builder = self.tree_store_mut_ref.get_tree_builder();
-                        rec_add_n_children(&mut seq, subtree_size-1, &mut tree_builder)?;
-                        tree_builder.finish(node.val);
+                        decoder.push(node.val, node.subtree_size).map_err(de::Error::custom)?;
                     }
-
-                    Ok(())
+                    decoder.finish().map_err(de::Error::custom)
                 }
             }
 
-            let mut result = PackedForest::new();
-
             deserializer.deserialize_seq(FlatNodeListDeserializer {
-                tree_store_mut_ref: &mut result,
-            })?;
-
-            Ok(result)
+                phantom: std::marker::PhantomData,
+            })
         }
     }
 }
 
+// A NodeId is serialized as its index, like a plain usize.
+impl Serialize for NodeId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.index().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for NodeId {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        usize::deserialize(deserializer).map(NodeId::new)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -413,4 +390,98 @@ mod tests {
         let vec2 = ::bincode::serialize(&store2).unwrap();
         assert_eq!(vec, vec2);
     }
+
+    #[test]
+    fn test_subforest_view() {
+        let store = build_store();
+        let view = store.trees_range(1..);
+        let expected = store.trees_range(1..).to_forest();
+        assert_eq!(::serde_json::ser::to_string(&view).unwrap(), ::serde_json::ser::to_string(&expected).unwrap());
+        let vec = ::bincode::serialize(&view).unwrap();
+        assert_eq!(vec, ::bincode::serialize(&expected).unwrap());
+        let store2: PackedForest<i32> = ::bincode::deserialize(&vec[..]).unwrap();
+        assert!(store2 == expected);
+    }
+
+    #[test]
+    fn test_bincode_malformed() {
+        fn encode(nodes: &[(i32, usize)]) -> Vec<u8> {
+            // bincode encodes a tuple in the same way as the FlatNode struct
+            ::bincode::serialize(nodes).unwrap()
+        }
+        fn decode_err(bytes: &[u8]) -> String {
+            ::bincode::deserialize::<PackedForest<i32>>(bytes).unwrap_err().to_string()
+        }
+
+        assert_eq!(decode_err(&encode(&[(1, 2), (2, 0)])), DecodeError::ZeroSubtreeSize { index: 1 }.to_string());
+        assert_eq!(
+            decode_err(&encode(&[(1, 2), (2, 2), (3, 1)])),
+            DecodeError::SubtreeOverflow { index: 1, subtree_size: 2, max_subtree_size: 1 }.to_string()
+        );
+        assert_eq!(
+            decode_err(&encode(&[(1, 1), (2, usize::MAX)])),
+            DecodeError::SubtreeOverflow { index: 1, subtree_size: usize::MAX, max_subtree_size: usize::MAX - 1 }.to_string()
+        );
+        assert_eq!(decode_err(&encode(&[(1, 3), (2, 1)])), DecodeError::Truncated { len: 2, expected_len: 3 }.to_string());
+
+        // Bytes that end in the middle of a node are an error of bincode itself
+        let bytes = ::bincode::serialize(&build_store()).unwrap();
+        assert!(::bincode::deserialize::<PackedForest<i32>>(&bytes[..bytes.len() - 1]).is_err());
+
+        // A very deep tree doesn't overflow the stack
+        let deep: Vec<_> = (0..1_000_000).map(|i| (i as i32, 1_000_000 - i)).collect();
+        let forest: PackedForest<i32> = ::bincode::deserialize(&encode(&deep)).unwrap();
+        assert_eq!(forest.tot_num_nodes(), 1_000_000);
+    }
+
+    #[test]
+    fn test_compact_tuple_encoding() {
+        use ::serde_test::{assert_de_tokens, assert_ser_tokens, Configure, Token};
+
+        let mut forest = PackedForest::new();
+        forest.build_tree(1, |node_builder| {
+            node_builder.add_child(2);
+        });
+
+        // Compact formats get a tuple per node, without field names
+        assert_ser_tokens(&forest.clone().compact(), &[
+            Token::Seq { len: Some(2) },
+            Token::Tuple { len: 2 },
+            Token::I32(1),
+            Token::U64(2),
+            Token::TupleEnd,
+            Token::Tuple { len: 2 },
+            Token::I32(2),
+            Token::U64(1),
+            Token::TupleEnd,
+            Token::SeqEnd,
+        ]);
+
+        // Nodes that were serialized as structs can still be deserialized
+        assert_de_tokens(&forest.clone().compact(), &[
+            Token::Seq { len: Some(2) },
+            Token::Struct { name: "FlatNode", len: 2 },
+            Token::Str("val"),
+            Token::I32(1),
+            Token::Str("subtree_size"),
+            Token::U64(2),
+            Token::StructEnd,
+            Token::Struct { name: "FlatNode", len: 2 },
+            Token::Str("val"),
+            Token::I32(2),
+            Token::Str("subtree_size"),
+            Token::U64(1),
+            Token::StructEnd,
+            Token::SeqEnd,
+        ]);
+    }
+
+    #[test]
+    fn test_node_id() {
+        let ids = vec![NodeId::new(0), NodeId::new(12)];
+        let json = ::serde_json::to_string(&ids).unwrap();
+        assert_eq!(json, "[0,12]");
+        assert_eq!(::serde_json::from_str::<Vec<NodeId>>(&json).unwrap(), ids);
+        assert!(::serde_json::from_str::<NodeId>("-1").is_err());
+    }
 }
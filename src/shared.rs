@@ -0,0 +1,238 @@
+// This file contains SharedPackedTree, a reference counted PackedTree whose nodes
+// can be referred to by owning handles, which don't borrow the tree.
+
+use crate::*;
+
+use std::sync::Arc;
+
+/// An immutable [`PackedTree`] behind an [`Arc`], which can be cloned cheaply and shared across threads.
+///
+/// Its nodes are referred to by [`SharedNodeRef`]s, which own a reference count to the tree instead of borrowing it.
+/// That means they can be stored in long-lived data structures (like caches) or sent to other threads,
+/// without having to keep the tree borrowed.
+///
+/// # Example
+/// ```
+/// use packed_tree::{PackedTree, SharedPackedTree, SharedNodeRef};
+///
+/// let tree = SharedPackedTree::new(PackedTree::new("root", |node_builder| {
+///     node_builder.build_child("a", |node_builder| {
+///         node_builder.add_child("a1");
+///     });
+///     node_builder.add_child("b");
+/// }));
+///
+/// let a: SharedNodeRef<&str> = tree.root().children().next().unwrap();
+/// drop(tree);
+///
+/// // `a` keeps the tree alive
+/// let handle = std::thread::spawn(move || {
+///     a.children().map(|child| *child.val()).collect::<Vec<_>>()
+/// });
+/// assert_eq!(handle.join().unwrap(), ["a1"]);
+/// ```
+pub struct SharedPackedTree<T> {
+    tree: Arc<PackedTree<T>>,
+}
+
+// Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
+impl<T> Clone for SharedPackedTree<T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        SharedPackedTree {
+            tree: self.tree.clone(),
+        }
+    }
+}
+
+impl<T> SharedPackedTree<T> {
+    /// Create a new [`SharedPackedTree`] from the given tree.
+    #[inline(always)]
+    pub fn new(tree: PackedTree<T>) -> SharedPackedTree<T> {
+        SharedPackedTree {
+            tree: Arc::new(tree),
+        }
+    }
+
+    /// Returns a [`SharedNodeRef`] to the tree's root.
+    #[inline(always)]
+    pub fn root(&self) -> SharedNodeRef<T> {
+        SharedNodeRef {
+            tree: self.tree.clone(),
+            index: 0,
+        }
+    }
+
+    /// Get a [`SharedNodeRef`] to the node with the given index, or `None` if the index is out of bounds.
+    ///
+    /// See [`PackedTree::get`].
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<SharedNodeRef<T>> {
+        if index < self.tree.tot_num_nodes() {
+            Some(SharedNodeRef {
+                tree: self.tree.clone(),
+                index,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a reference to the underlying [`PackedTree`].
+    #[inline(always)]
+    pub fn tree(&self) -> &PackedTree<T> {
+        &self.tree
+    }
+
+    /// Returns the underlying [`PackedTree`] if this is the only [`SharedPackedTree`] or [`SharedNodeRef`] referring to it.
+    /// Otherwise returns `Err(self)`.
+    #[inline]
+    pub fn try_unwrap(self) -> Result<PackedTree<T>, SharedPackedTree<T>> {
+        Arc::try_unwrap(self.tree).map_err(|tree| SharedPackedTree { tree })
+    }
+}
+
+impl<T> From<PackedTree<T>> for SharedPackedTree<T> {
+    #[inline(always)]
+    fn from(tree: PackedTree<T>) -> Self {
+        SharedPackedTree::new(tree)
+    }
+}
+
+impl<T> AsRef<PackedTree<T>> for SharedPackedTree<T> {
+    #[inline(always)]
+    fn as_ref(&self) -> &PackedTree<T> {
+        &self.tree
+    }
+}
+
+/// An owning handle to a node in a [`SharedPackedTree`], which keeps the tree alive.
+///
+/// Unlike a [`NodeRef`], it doesn't borrow the tree, so it is `'static` if `T` is.
+pub struct SharedNodeRef<T> {
+    tree: Arc<PackedTree<T>>,
+    index: usize,
+}
+
+// Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
+impl<T> Clone for SharedNodeRef<T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        SharedNodeRef {
+            tree: self.tree.clone(),
+            index: self.index,
+        }
+    }
+}
+
+impl<T> SharedNodeRef<T> {
+    /// Returns a [`NodeRef`] to this node, borrowing from this handle.
+    #[inline(always)]
+    pub fn node_ref(&self) -> NodeRef<'_, T> {
+        self.tree.get(self.index).unwrap()
+    }
+
+    /// Returns a reference to the value of this node.
+    #[inline(always)]
+    pub fn val(&self) -> &T {
+        self.node_ref().val()
+    }
+
+    /// Returns an iterator over [`SharedNodeRef`]s to the children of this node.
+    #[inline]
+    pub fn children(&self) -> SharedNodeIter<T> {
+        SharedNodeIter {
+            tree: self.tree.clone(),
+            next_index: self.index + 1,
+            end_index: self.index + self.num_descendants_incl_self(),
+        }
+    }
+
+    /// Returns the index of this node in the tree. See [`SharedPackedTree::get`].
+    #[inline(always)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns a [`SharedPackedTree`] referring to the tree that this node is part of.
+    #[inline(always)]
+    pub fn tree(&self) -> SharedPackedTree<T> {
+        SharedPackedTree {
+            tree: self.tree.clone(),
+        }
+    }
+
+    /// Counts the number of descendants of this node (also counting the node itself) in O(1) time.
+    #[inline(always)]
+    pub fn num_descendants_incl_self(&self) -> usize {
+        self.node_ref().num_descendants_incl_self()
+    }
+
+    /// Counts the number of descendants of this node (not counting the node itself) in O(1) time.
+    #[inline(always)]
+    pub fn num_descendants_excl_self(&self) -> usize {
+        self.node_ref().num_descendants_excl_self()
+    }
+}
+
+/// Iterates over [`SharedNodeRef`]s to the children of a node, see [`SharedNodeRef::children`].
+pub struct SharedNodeIter<T> {
+    tree: Arc<PackedTree<T>>,
+    next_index: usize,
+    end_index: usize,
+}
+
+impl<T> Iterator for SharedNodeIter<T> {
+    type Item = SharedNodeRef<T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_index >= self.end_index {
+            return None;
+        }
+        let index = self.next_index;
+        self.next_index += self.tree.get(index).unwrap().num_descendants_incl_self();
+        Some(SharedNodeRef {
+            tree: self.tree.clone(),
+            index,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync_static<S: Send + Sync + 'static>() {}
+
+    #[test]
+    fn test_shared_tree() {
+        assert_send_sync_static::<SharedNodeRef<String>>();
+        assert_send_sync_static::<SharedPackedTree<String>>();
+
+        let tree = SharedPackedTree::new(PackedTree::new(0, |node_builder| {
+            node_builder.build_child(1, |node_builder| {
+                node_builder.add_child(2);
+                node_builder.add_child(3);
+            });
+            node_builder.add_child(4);
+        }));
+
+        let children: Vec<_> = tree.root().children().collect();
+        assert_eq!(children.iter().map(|child| *child.val()).collect::<Vec<_>>(), [1, 4]);
+        assert_eq!(children.iter().map(|child| child.index()).collect::<Vec<_>>(), [1, 4]);
+        assert_eq!(children[0].children().map(|child| *child.val()).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(children[0].num_descendants_excl_self(), 2);
+        assert_eq!(children[1].children().count(), 0);
+        assert_eq!(*tree.get(3).unwrap().val(), 3);
+        assert!(tree.get(5).is_none());
+
+        let tree = match tree.try_unwrap() {
+            Ok(_) => panic!("the children still refer to the tree"),
+            Err(tree) => tree,
+        };
+        drop(children);
+        let tree = tree.try_unwrap().ok().unwrap();
+        assert_eq!(tree.tot_num_nodes(), 5);
+    }
+}
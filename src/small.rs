@@ -0,0 +1,165 @@
+// This file contains SmallPackedTree, a PackedTree that stores small trees inline instead of on the heap.
+
+use crate::*;
+
+/// A variant of [`PackedTree`] that stores up to `N` nodes inline, without allocating any memory on the heap.
+/// When a tree with more than `N` nodes is built, its nodes are moved to the heap.
+///
+/// This is useful when creating many small trees, which would otherwise each cost a heap allocation.
+/// Note that a [`SmallPackedTree`] is at least as large as `N` nodes, even if it contains fewer nodes or spilled to the heap.
+///
+/// # Example
+/// ```
+/// use packed_tree::SmallPackedTree;
+///
+/// let tree = SmallPackedTree::<_, 4>::new(0, |node_builder| {
+///     node_builder.build_child(1, |node_builder| {
+///         node_builder.add_child(2);
+///     });
+///     node_builder.add_child(3);
+/// });
+/// assert!(tree.is_inline());
+/// assert_eq!(tree.root().children().map(|child| *child.val()).collect::<Vec<_>>(), [1, 3]);
+///
+/// let big_tree = SmallPackedTree::<_, 4>::new(0, |node_builder| {
+///     node_builder.add_leaf_children(1..10);
+/// });
+/// assert!(!big_tree.is_inline());
+/// assert_eq!(big_tree.tot_num_nodes(), 10);
+/// ```
+pub struct SmallPackedTree<T, const N: usize> {
+    storage: SmallNodeStorage<T, N>,
+}
+
+impl<T, const N: usize> SmallPackedTree<T, N> {
+    /// Create a new [`SmallPackedTree`].
+    ///
+    /// See [`PackedTree::new`].
+    #[inline]
+    pub fn new(root_val: T, node_builder_cb: impl FnOnce(&mut NodeBuilder<T, SmallNodeStorage<T, N>>)) -> SmallPackedTree<T, N> {
+        let mut storage = SmallNodeStorage::new();
+        let mut builder = storage.get_tree_builder();
+        node_builder_cb(&mut builder);
+        builder.finish(root_val);
+        SmallPackedTree { storage }
+    }
+
+    /// Create a new [`SmallPackedTree`], where the root value is the return value of the given closure.
+    ///
+    /// See [`PackedTree::new_by_ret_val`].
+    #[inline]
+    pub fn new_by_ret_val(node_builder_cb: impl FnOnce(&mut NodeBuilder<T, SmallNodeStorage<T, N>>) -> T) -> SmallPackedTree<T, N> {
+        let mut storage = SmallNodeStorage::new();
+        let mut builder = storage.get_tree_builder();
+        let root_val = node_builder_cb(&mut builder);
+        builder.finish(root_val);
+        SmallPackedTree { storage }
+    }
+
+    /// Returns a [`NodeRef`] reference to the tree's root.
+    #[inline(always)]
+    pub fn root(&self) -> NodeRef<'_, T> {
+        self.storage.iter_trees().next().unwrap()
+    }
+
+    /// Returns a [`NodeRefMut`] mutable reference to the tree's root.
+    #[inline(always)]
+    pub fn root_mut(&mut self) -> NodeRefMut<'_, T> {
+        self.storage.iter_trees_mut().next().unwrap()
+    }
+
+    /// Returns `true` if the nodes are stored inline, or `false` if the tree had more than `N` nodes and they are stored on the heap.
+    #[inline(always)]
+    pub fn is_inline(&self) -> bool {
+        self.storage.is_inline()
+    }
+
+    /// Iterate over all the values in all the nodes in this tree, in pre-order order.
+    #[inline(always)]
+    pub fn iter_flattened(&self) -> FlattenedIter<'_, T> {
+        FlattenedIter::new(self.storage.as_slice())
+    }
+
+    /// Returns a read-only view over the raw data of this tree. See [`PackedTree::raw_data`].
+    #[inline(always)]
+    pub fn raw_data(&self) -> &[NodeData<T>] {
+        self.storage.as_slice()
+    }
+
+    /// Returns how many nodes are currently in this tree in O(1) time.
+    #[inline(always)]
+    pub fn tot_num_nodes(&self) -> usize {
+        self.storage.as_slice().len()
+    }
+
+    /// Converts this tree into a [`PackedTree`], moving its nodes to the heap if they are stored inline.
+    #[inline]
+    pub fn into_tree(self) -> PackedTree<T> {
+        PackedTree::try_from_forest(self.storage.into_forest()).unwrap()
+    }
+}
+
+impl<T, const N: usize> From<SmallPackedTree<T, N>> for PackedTree<T> {
+    #[inline(always)]
+    fn from(tree: SmallPackedTree<T, N>) -> Self {
+        tree.into_tree()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build<const N: usize>(num_children: u32) -> SmallPackedTree<String, N> {
+        SmallPackedTree::new("root".to_string(), |node_builder| {
+            for i in 0..num_children {
+                node_builder.build_child(format!("child {}", i), |node_builder| {
+                    node_builder.add_child(format!("grandchild {}", i));
+                });
+            }
+        })
+    }
+
+    #[test]
+    fn test_small_tree() {
+        let mut tree = build::<5>(2);
+        assert!(tree.is_inline());
+        assert_eq!(tree.tot_num_nodes(), 5);
+        assert_eq!(tree.iter_flattened().collect::<Vec<_>>(), ["root", "child 0", "grandchild 0", "child 1", "grandchild 1"]);
+        tree.root_mut().children().next().unwrap().val_mut().push('!');
+        assert_eq!(tree.root().children().next().unwrap().val(), "child 0!");
+
+        // spills to the heap while building the third child
+        let tree = build::<5>(3);
+        assert!(!tree.is_inline());
+        assert_eq!(tree.root().children().count(), 3);
+        let expected = PackedTree::new("root".to_string(), |node_builder| {
+            for i in 0..3 {
+                node_builder.build_child(format!("child {}", i), |node_builder| {
+                    node_builder.add_child(format!("grandchild {}", i));
+                });
+            }
+        });
+        assert!(tree.into_tree() == expected);
+
+        let tree = build::<8>(3);
+        assert!(tree.is_inline());
+        assert!(PackedTree::from(tree) == expected);
+
+        let tree = SmallPackedTree::<String, 0>::new_by_ret_val(|node_builder| {
+            node_builder.add_child("a".to_string());
+            "b".to_string()
+        });
+        assert!(!tree.is_inline());
+        assert_eq!(tree.raw_data().len(), 2);
+
+        // dropping a partially built tree with a spilled builder
+        let tree = SmallPackedTree::<String, 2>::new("root".to_string(), |node_builder| {
+            let mut child_builder = node_builder.get_child_builder();
+            child_builder.add_leaf_children(vec!["x".to_string(), "y".to_string(), "z".to_string()]);
+            drop(child_builder);
+            node_builder.add_child("a".to_string());
+        });
+        assert_eq!(tree.iter_flattened().collect::<Vec<_>>(), ["root", "a"]);
+    }
+}
@@ -0,0 +1,342 @@
+// This file contains TreeBuilderStack, which builds a forest from a flat sequence of push and pop operations
+// instead of nested NodeBuilders, which is convenient for iterative parsers. It is also used by PackedForest::from_depth_values
+// and by FlatNodeDecoder, which checks and decodes nodes with their subtree sizes (e.g. when deserializing).
+
+use crate::*;
+
+/// Builds a [`PackedForest`] node by node, where [`push`](TreeBuilderStack::push) opens a node,
+/// [`pop`](TreeBuilderStack::pop) closes the node that was opened last, and [`leaf`](TreeBuilderStack::leaf)
+/// adds a node without children. Every node becomes a child of the innermost node that is still open,
+/// or a new tree if no node is open.
+///
+/// Unlike [`NodeBuilder`], the value of a node is given when it is opened, and building a node doesn't borrow
+/// anything, so this can be driven by a loop (e.g. over the tokens of a parser) instead of by nested function calls.
+/// This also means that trees of any depth can be built without overflowing the stack.
+///
+/// A [`TreeBuilderStack`] owns its forest and gives it back from [`finish`](TreeBuilderStack::finish), so unlike
+/// a [`NodeBuilder`], it can be stored in a struct, returned from a function, held across an `.await`,
+/// or sent to another thread (if `T` is [`Send`]) while the forest is being built.
+///
+/// # Example
+/// ```
+/// use packed_tree::TreeBuilderStack;
+///
+/// // Parse a list of trees like "a ( b c ( d ) ) e", where the children of a node are in parentheses
+/// let tokens: Vec<&str> = "a ( b c ( d ) ) e".split(' ').collect();
+/// let mut stack = TreeBuilderStack::new();
+/// for (i, token) in tokens.iter().enumerate() {
+///     match *token {
+///         "(" => {}
+///         ")" => { stack.pop().unwrap(); }
+///         name if tokens.get(i + 1) == Some(&"(") => stack.push(name),
+///         name => stack.leaf(name),
+///     }
+/// }
+///
+/// let forest = stack.finish();
+/// assert_eq!(forest.iter_trees().count(), 2);
+/// assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), ["a", "b", "c", "d", "e"]);
+/// assert_eq!(forest.get(2).unwrap().children().count(), 1);
+/// ```
+pub struct TreeBuilderStack<T> {
+    forest: PackedForest<T>,
+    // The indices of the nodes that are still open, from the outermost to the innermost.
+    open_nodes: Vec<usize>,
+}
+
+// Not using #[derive(Default)] because it adds the T:Default bound, which is unnecessary
+impl<T> Default for TreeBuilderStack<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        TreeBuilderStack::new()
+    }
+}
+
+impl<T> TreeBuilderStack<T> {
+    /// Create a new [`TreeBuilderStack`] with an empty forest.
+    #[inline]
+    pub fn new() -> TreeBuilderStack<T> {
+        TreeBuilderStack::from_forest(PackedForest::new())
+    }
+
+    /// Create a new [`TreeBuilderStack`] that adds trees to the end of the given forest.
+    #[inline]
+    pub fn from_forest(forest: PackedForest<T>) -> TreeBuilderStack<T> {
+        TreeBuilderStack {
+            forest,
+            open_nodes: Vec::new(),
+        }
+    }
+
+    /// Opens a node with the given value. The nodes that are added until it is closed with
+    /// [`pop`](TreeBuilderStack::pop) become its descendants.
+    #[inline]
+    pub fn push(&mut self, val: T) {
+        let index = self.forest.push_open_node(val);
+        self.open_nodes.push(index);
+    }
+
+    /// Adds a node with the given value and no children.
+    #[inline]
+    pub fn leaf(&mut self, val: T) {
+        self.forest.push_open_node(val);
+    }
+
+    /// Closes the node that was opened last, and returns a [`NodeRefMut`] to it,
+    /// or `None` if there is no open node.
+    #[inline]
+    pub fn pop(&mut self) -> Option<NodeRefMut<'_, T>> {
+        let index = self.open_nodes.pop()?;
+        self.forest.close_node(index);
+        self.forest.get_mut(index)
+    }
+
+    /// Returns the number of nodes that are open, i.e. the depth at which the next node will be added.
+    #[inline(always)]
+    pub fn depth(&self) -> usize {
+        self.open_nodes.len()
+    }
+
+    /// Closes all nodes that are still open, and returns the forest.
+    #[inline]
+    pub fn finish(mut self) -> PackedForest<T> {
+        while self.pop().is_some() {}
+        self.forest
+    }
+
+    /// Returns the forest if every node that was opened has been closed, like the writers of XML libraries do
+    /// when a document is finished. Otherwise returns a [`BalanceError`].
+    ///
+    /// Together with [`pop`](TreeBuilderStack::pop), which returns `None` if no node is open, this checks that
+    /// every opened node is closed exactly once, e.g. when the nodes come from a SAX-style event source.
+    /// See also [`TreeWriter`], which checks both at the end.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{BalanceError, TreeBuilderStack};
+    ///
+    /// let mut stack = TreeBuilderStack::new();
+    /// stack.push("html");
+    /// stack.push("body");
+    /// stack.leaf("p");
+    /// assert!(stack.pop().is_some());
+    /// assert_eq!(stack.try_finish().unwrap_err(), BalanceError::UnclosedNodes { count: 1 });
+    /// ```
+    #[inline]
+    pub fn try_finish(self) -> Result<PackedForest<T>, BalanceError> {
+        match self.depth() {
+            0 => Ok(self.forest),
+            count => Err(BalanceError::UnclosedNodes { count }),
+        }
+    }
+}
+
+impl<T> PackedForest<T> {
+    /// Create a new [`PackedForest`] from the values of its nodes in pre-order, together with their depth,
+    /// where the roots of the trees have depth 0. This is how outlines and indentation-based formats describe trees:
+    /// a node is a child of the last node before it that is one level less deep.
+    ///
+    /// Returns a [`BuildError`] if a node is more than one level deeper than the node before it,
+    /// or if the first node doesn't have depth 0.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedForest, BuildError};
+    ///
+    /// let outline = [(0, "Intro"), (0, "Usage"), (1, "Install"), (1, "Build"), (2, "Flags"), (0, "License")];
+    /// let forest = PackedForest::from_depth_values(outline).unwrap();
+    /// assert_eq!(forest.iter_trees().map(|tree| *tree.val()).collect::<Vec<_>>(), ["Intro", "Usage", "License"]);
+    /// assert_eq!(forest.depths(), [0, 0, 1, 1, 2, 0]);
+    ///
+    /// let result = PackedForest::from_depth_values([(0, "a"), (2, "b")]);
+    /// assert_eq!(result, Err(BuildError::InvalidDepth { index: 1, depth: 2, max_depth: 1 }));
+    /// ```
+    pub fn from_depth_values(depth_values: impl IntoIterator<Item = (usize, T)>) -> Result<PackedForest<T>, BuildError> {
+        let mut stack = TreeBuilderStack::new();
+        for (index, (depth, val)) in depth_values.into_iter().enumerate() {
+            // Every node is pushed, so the depth of the stack is one more than the depth of the previous node.
+            let max_depth = stack.depth();
+            if depth > max_depth {
+                return Err(BuildError::InvalidDepth { index, depth, max_depth });
+            }
+            while stack.depth() > depth {
+                stack.pop();
+            }
+            stack.push(val);
+        }
+        Ok(stack.finish())
+    }
+}
+
+// Builds a forest from its nodes in pre-order, given one by one with their subtree sizes, checking that they form
+// a valid forest. Uses no recursion, so untrusted data can't overflow the stack.
+pub(crate) struct FlatNodeDecoder<T> {
+    stack: TreeBuilderStack<T>,
+    // The indices one past the end of the subtrees of the open nodes, from the outermost to the innermost.
+    ends: Vec<usize>,
+    len: usize,
+}
+
+impl<T> FlatNodeDecoder<T> {
+    #[inline]
+    pub(crate) fn new() -> FlatNodeDecoder<T> {
+        FlatNodeDecoder {
+            stack: TreeBuilderStack::new(),
+            ends: Vec::new(),
+            len: 0,
+        }
+    }
+
+    // Closes the open nodes whose subtree ends before the next node.
+    fn close_finished_nodes(&mut self) {
+        while self.ends.last() == Some(&self.len) {
+            self.ends.pop();
+            self.stack.pop();
+        }
+    }
+
+    pub(crate) fn push(&mut self, val: T, subtree_size: usize) -> Result<(), DecodeError> {
+        self.close_finished_nodes();
+        let index = self.len;
+        if subtree_size == 0 {
+            return Err(DecodeError::ZeroSubtreeSize { index });
+        }
+        let max_subtree_size = match self.ends.last() {
+            Some(end) => end - index,
+            None => usize::MAX - index,
+        };
+        if subtree_size > max_subtree_size {
+            return Err(DecodeError::SubtreeOverflow { index, subtree_size, max_subtree_size });
+        }
+        self.len += 1;
+        if subtree_size == 1 {
+            self.stack.leaf(val);
+        } else {
+            self.stack.push(val);
+            self.ends.push(index + subtree_size);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> Result<PackedForest<T>, DecodeError> {
+        self.close_finished_nodes();
+        match self.ends.first() {
+            // The subtree of the outermost open node ends last
+            Some(&expected_len) => Err(DecodeError::Truncated { len: self.len, expected_len }),
+            None => Ok(self.stack.finish()),
+        }
+    }
+}
+
+impl<T> PackedForest<T> {
+    /// Create a new [`PackedForest`] from the values of its nodes in pre-order, together with their subtree sizes
+    /// (see [`NodeData::subtree_size`]), like the nodes of [`raw_data`](PackedForest::raw_data).
+    ///
+    /// Returns a [`DecodeError`] if the subtree sizes don't describe a valid forest, so this can be used on untrusted data.
+    /// This uses no recursion, so deep trees can't overflow the stack.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedForest, DecodeError};
+    ///
+    /// let forest = PackedForest::try_from_flat_nodes(vec![('a', 3), ('b', 1), ('c', 1), ('d', 1)]).unwrap();
+    /// assert_eq!(forest.iter_trees().count(), 2);
+    /// let nodes: Vec<_> = forest.raw_data().iter().map(|node| (*node.val(), node.subtree_size().get())).collect();
+    /// assert_eq!(nodes, [('a', 3), ('b', 1), ('c', 1), ('d', 1)]);
+    ///
+    /// let result = PackedForest::try_from_flat_nodes(vec![('a', 3), ('b', 3), ('c', 1)]);
+    /// assert_eq!(result, Err(DecodeError::SubtreeOverflow { index: 1, subtree_size: 3, max_subtree_size: 2 }));
+    /// let result = PackedForest::try_from_flat_nodes(vec![('a', 3), ('b', 1)]);
+    /// assert_eq!(result, Err(DecodeError::Truncated { len: 2, expected_len: 3 }));
+    /// ```
+    pub fn try_from_flat_nodes(nodes: impl IntoIterator<Item = (T, usize)>) -> Result<PackedForest<T>, DecodeError> {
+        let mut decoder = FlatNodeDecoder::new();
+        for (val, subtree_size) in nodes {
+            decoder.push(val, subtree_size)?;
+        }
+        decoder.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_builder_stack() {
+        let mut forest = PackedForest::new();
+        forest.add_single_node_tree(0);
+        let mut stack = TreeBuilderStack::from_forest(forest);
+        assert!(stack.pop().is_none());
+        stack.push(1);
+        stack.push(2);
+        assert_eq!(stack.depth(), 2);
+        stack.leaf(3);
+        stack.leaf(4);
+
+        // The stack can be sent to another thread halfway through
+        let mut stack = std::thread::spawn(move || {
+            let node = stack.pop().unwrap();
+            assert_eq!(node.num_descendants_incl_self(), 3);
+            stack
+        }).join().unwrap();
+        stack.leaf(5);
+        assert_eq!(stack.depth(), 1);
+        // The node with value 1 is still open
+        let forest = stack.finish();
+        assert_eq!(forest.iter_trees().map(|tree| tree.num_descendants_incl_self()).collect::<Vec<_>>(), [1, 5]);
+        assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [0, 1, 2, 3, 4, 5]);
+
+        // Very deep trees don't overflow the stack
+        let mut stack = TreeBuilderStack::new();
+        for i in 0..1_000_000 {
+            stack.push(i);
+        }
+        let tree = PackedTree::try_from_forest(stack.finish()).unwrap();
+        assert_eq!(tree.root().num_descendants_incl_self(), 1_000_000);
+    }
+
+    #[test]
+    fn test_try_finish() {
+        let mut stack = TreeBuilderStack::new();
+        stack.push(1);
+        stack.leaf(2);
+        assert!(stack.pop().is_some());
+        assert!(stack.pop().is_none());
+        stack.leaf(3);
+        let forest = stack.try_finish().unwrap();
+        assert_eq!(forest.depths(), [0, 1, 0]);
+
+        let mut stack = TreeBuilderStack::new();
+        stack.push(1);
+        stack.push(2);
+        stack.leaf(3);
+        assert_eq!(stack.try_finish(), Err(BalanceError::UnclosedNodes { count: 2 }));
+    }
+
+    #[test]
+    fn test_from_depth_values() {
+        let forest = PackedForest::from_depth_values(vec![(0, 'a'), (1, 'b'), (2, 'c'), (2, 'd'), (1, 'e'), (0, 'f'), (1, 'g')]).unwrap();
+        let mut expected = PackedForest::new();
+        expected.build_tree('a', |node_builder| {
+            node_builder.build_child('b', |node_builder| {
+                node_builder.add_leaf_children(['c', 'd']);
+            });
+            node_builder.add_child('e');
+        });
+        expected.build_tree('f', |node_builder| {
+            node_builder.add_child('g');
+        });
+        assert!(forest == expected);
+
+        assert!(PackedForest::<char>::from_depth_values(vec![]).unwrap() == PackedForest::new());
+        assert_eq!(
+            PackedForest::from_depth_values(vec![(1, 'a')]),
+            Err(BuildError::InvalidDepth { index: 0, depth: 1, max_depth: 0 })
+        );
+        assert_eq!(
+            PackedForest::from_depth_values(vec![(0, 'a'), (1, 'b'), (3, 'c')]),
+            Err(BuildError::InvalidDepth { index: 2, depth: 3, max_depth: 2 })
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! [`proptest`](::proptest) strategies for generating arbitrary trees and forests.
+//!
+//! Requires the `proptest` feature.
+//!
+//! The generated trees shrink towards trees with fewer and smaller subtrees.
+//!
+//! # Example
+//! ```
+//! use packed_tree::strategy::{self, TreeParams};
+//! use proptest::prelude::*;
+//!
+//! proptest! {
+//!     // Add #[test] here to run this as a test
+//!     fn depths_are_bounded(tree in strategy::tree(any::<u8>(), TreeParams { max_depth: 3, ..TreeParams::default() })) {
+//!         prop_assert!(tree.depths().iter().all(|depth| *depth <= 3));
+//!     }
+//! }
+//! depths_are_bounded();
+//! ```
+
+use ::proptest::arbitrary::{any_with, Arbitrary};
+use ::proptest::collection::vec;
+use ::proptest::strategy::{BoxedStrategy, Strategy};
+
+use crate::*;
+
+use std::fmt::Debug;
+
+/// Bounds on the shape of the trees generated by the strategies in this module.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TreeParams {
+    /// The maximum depth of a generated tree. A tree consisting of only a root has depth 0.
+    pub max_depth: u32,
+    /// The number of nodes that generated trees should aim to have at most.
+    /// This is not a hard bound, see [`Strategy::prop_recursive`].
+    pub desired_size: u32,
+    /// The maximum number of children of a node.
+    pub max_children: usize,
+    /// The maximum number of trees in a generated forest. Ignored when generating a single tree.
+    pub max_trees: usize,
+}
+
+impl Default for TreeParams {
+    fn default() -> Self {
+        TreeParams {
+            max_depth: 4,
+            desired_size: 64,
+            max_children: 8,
+            max_trees: 4,
+        }
+    }
+}
+
+// Proptest generates and shrinks trees stored in the naive way, as NestedNodes.
+fn nested_node<S>(val_strategy: S, params: TreeParams) -> impl Strategy<Value = NestedNode<S::Value>>
+where
+    S: Strategy + Clone + 'static,
+    S::Value: Clone + Debug + 'static,
+{
+    let leaf = val_strategy.clone().prop_map(NestedNode::leaf);
+    let max_children = params.max_children;
+    leaf.prop_recursive(params.max_depth, params.desired_size, max_children as u32, move |inner| {
+        (val_strategy.clone(), vec(inner, 0..=max_children)).prop_map(|(val, children)| NestedNode { val, children })
+    })
+}
+
+/// Returns a [`Strategy`] that generates [`PackedTree`]s whose values are generated by `val_strategy`.
+pub fn tree<S>(val_strategy: S, params: TreeParams) -> impl Strategy<Value = PackedTree<S::Value>>
+where
+    S: Strategy + Clone + 'static,
+    S::Value: Clone + Debug + 'static,
+{
+    nested_node(val_strategy, params).prop_map(|root| PackedTree::from_nested(&root))
+}
+
+/// Returns a [`Strategy`] that generates [`PackedForest`]s of at most `params.max_trees` trees,
+/// whose values are generated by `val_strategy`.
+pub fn forest<S>(val_strategy: S, params: TreeParams) -> impl Strategy<Value = PackedForest<S::Value>>
+where
+    S: Strategy + Clone + 'static,
+    S::Value: Clone + Debug + 'static,
+{
+    vec(nested_node(val_strategy, params), 0..=params.max_trees).prop_map(|roots| {
+        PackedForest::merge(roots.iter().map(|root| PackedTree::from_nested(root).into()).collect())
+    })
+}
+
+impl<T> Arbitrary for PackedTree<T>
+where
+    T: Arbitrary + Clone + 'static,
+    T::Strategy: Clone,
+{
+    type Parameters = (TreeParams, T::Parameters);
+    type Strategy = BoxedStrategy<PackedTree<T>>;
+
+    fn arbitrary_with((params, val_params): Self::Parameters) -> Self::Strategy {
+        tree(any_with::<T>(val_params), params).boxed()
+    }
+}
+
+impl<T> Arbitrary for PackedForest<T>
+where
+    T: Arbitrary + Clone + 'static,
+    T::Strategy: Clone,
+{
+    type Parameters = (TreeParams, T::Parameters);
+    type Strategy = BoxedStrategy<PackedForest<T>>;
+
+    fn arbitrary_with((params, val_params): Self::Parameters) -> Self::Strategy {
+        forest(any_with::<T>(val_params), params).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_tree_bounds(tree in tree(any::<u8>(), TreeParams { max_depth: 3, max_children: 2, ..TreeParams::default() })) {
+            prop_assert!(tree.depths().iter().all(|depth| *depth <= 3));
+            prop_assert!(tree.iter_flattened().count() <= 1 + 2 + 4 + 8);
+        }
+
+        #[test]
+        fn test_forest_bounds(forest in any::<PackedForest<u8>>()) {
+            prop_assert!(forest.iter_trees().count() <= TreeParams::default().max_trees);
+        }
+    }
+}
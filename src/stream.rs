@@ -0,0 +1,586 @@
+// This file contains StreamingTreeWriter, which encodes trees to an io::Write while they are being built,
+// without ever storing them in memory, and PackedForest::read_stream, which decodes them again.
+// It also contains the versioned format, which is the same encoding preceded by a header.
+
+use crate::*;
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+type ValEncoder<'f, T> = Box<dyn FnMut(&T, &mut dyn Write) -> io::Result<()> + 'f>;
+
+// The tags that precede the parts of a node in the binary format. A node is encoded either as
+// `TAG_LEAF val` or as `TAG_START children... TAG_END val`, so the values are in post-order.
+pub(crate) const TAG_LEAF: u8 = 0;
+pub(crate) const TAG_START: u8 = 1;
+pub(crate) const TAG_END: u8 = 2;
+
+/// The bytes at the start of the versioned binary format, see [`FORMAT_VERSION`].
+pub const FORMAT_MAGIC: [u8; 4] = *b"PKTF";
+
+/// The version of the versioned binary format that is written by [`StreamingTreeWriter::with_header`]
+/// and [`PackedForest::write_versioned`], and read by [`PackedForest::read_versioned`].
+///
+/// # Format
+///
+/// The format consists of a header followed by the trees of the forest, one after the other:
+///   * The header is the 4 bytes of [`FORMAT_MAGIC`], followed by a single byte containing the version.
+///   * A leaf is encoded as the byte `0` followed by its value.
+///   * A node with children is encoded as the byte `1`, followed by its children, followed by the byte `2` and its value.
+///
+/// The values are encoded and decoded by closures that are provided by the user, so this format
+/// doesn't say anything about them. The forest ends at the end of the stream.
+///
+/// # Compatibility
+///
+/// The version is only increased when the format changes in a way that older readers can't read.
+/// Every newer version of this crate can read all the versions that older versions of this crate could write.
+/// When reading a version that is newer than [`FORMAT_VERSION`], [`FormatError::UnsupportedVersion`] is returned,
+/// rather than trying to guess what the data means.
+///
+/// [`StreamingTreeWriter::new`] and [`PackedForest::read_stream`] use the same encoding without the header,
+/// so they don't provide these guarantees.
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Encodes trees directly to an [`io::Write`] while they are being built, using the same builder-style API as
+/// [`PackedForest`], so that enormous forests can be exported without holding them in memory.
+///
+/// Every node is written as soon as it is finished, and only O(depth) memory is used while building.
+/// The values are encoded by a closure that is passed to [`new`](StreamingTreeWriter::new).
+///
+/// Since nodes can't be taken back once they are written, dropping a [`StreamingNodeBuilder`] that has children
+/// without calling [`finish`](StreamingNodeBuilder::finish) (e.g. because of an error) leaves the output incomplete.
+/// When that happens, all further writes return an error.
+///
+/// # Example
+/// ```
+/// use packed_tree::StreamingTreeWriter;
+/// use std::io::Write;
+///
+/// let mut writer = StreamingTreeWriter::new(Vec::new(), |val: &u8, w: &mut dyn Write| w.write_all(&[*val]));
+/// writer.build_tree(1, |node_builder| {
+///     node_builder.build_child(2, |node_builder| {
+///         node_builder.add_child(3)
+///     })?;
+///     node_builder.add_child(4)
+/// }).unwrap();
+/// let bytes = writer.into_inner();
+/// assert!(!bytes.is_empty());
+/// ```
+pub struct StreamingTreeWriter<'f, W, T> {
+    writer: W,
+    val_encoder: ValEncoder<'f, T>,
+    num_trees: usize,
+    poisoned: bool,
+}
+
+impl<'f, W: Write, T> StreamingTreeWriter<'f, W, T> {
+    /// Create a new [`StreamingTreeWriter`] that writes to `writer`, and encodes the values of the nodes with `val_encoder`.
+    #[inline]
+    pub fn new(writer: W, val_encoder: impl FnMut(&T, &mut dyn Write) -> io::Result<()> + 'f) -> StreamingTreeWriter<'f, W, T> {
+        StreamingTreeWriter {
+            writer,
+            val_encoder: Box::new(val_encoder),
+            num_trees: 0,
+            poisoned: false,
+        }
+    }
+
+    /// Create a new [`StreamingTreeWriter`] like [`new`](StreamingTreeWriter::new), but first write the header of the
+    /// versioned binary format (see [`FORMAT_VERSION`]), so the trees can be read with [`PackedForest::read_versioned`].
+    #[inline]
+    pub fn with_header(
+        mut writer: W,
+        val_encoder: impl FnMut(&T, &mut dyn Write) -> io::Result<()> + 'f,
+    ) -> io::Result<StreamingTreeWriter<'f, W, T>> {
+        writer.write_all(&FORMAT_MAGIC)?;
+        writer.write_all(&[FORMAT_VERSION])?;
+        Ok(StreamingTreeWriter::new(writer, val_encoder))
+    }
+
+    /// Build a tree with the given root value, and write it.
+    ///
+    /// Like [`PackedForest::build_tree`], but `node_builder_cb` returns an [`io::Result`], so that the `?` operator
+    /// can be used on the methods of [`StreamingNodeBuilder`].
+    #[inline]
+    pub fn build_tree<R>(
+        &mut self,
+        root_val: T,
+        node_builder_cb: impl FnOnce(&mut StreamingNodeBuilder<'_, 'f, W, T>) -> io::Result<R>,
+    ) -> io::Result<R> {
+        let mut builder = self.get_tree_builder();
+        let ret = node_builder_cb(&mut builder)?;
+        builder.finish(root_val)?;
+        Ok(ret)
+    }
+
+    /// Write a tree with only a single node. The parameter `val` is the value of that single node.
+    #[inline]
+    pub fn add_single_node_tree(&mut self, val: T) -> io::Result<()> {
+        self.get_tree_builder().finish(val)
+    }
+
+    /// Get a [`StreamingNodeBuilder`] that can be used to build a tree that will be written.
+    ///
+    /// See [`PackedForest::get_tree_builder`].
+    #[inline]
+    pub fn get_tree_builder(&mut self) -> StreamingNodeBuilder<'_, 'f, W, T> {
+        StreamingNodeBuilder {
+            writer: self,
+            is_root: true,
+            started: false,
+        }
+    }
+
+    /// Write a copy of the given node and all of its descendants as a tree.
+    pub fn add_tree(&mut self, tree: NodeRef<'_, T>) -> io::Result<()> {
+        self.write_subtree(tree)?;
+        self.num_trees += 1;
+        Ok(())
+    }
+
+    /// Returns the number of trees that have been written so far.
+    #[inline(always)]
+    pub fn num_trees(&self) -> usize {
+        self.num_trees
+    }
+
+    /// Returns a reference to the underlying writer.
+    #[inline(always)]
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns the underlying writer. Note that it isn't flushed.
+    #[inline(always)]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_tag(&mut self, tag: u8) -> io::Result<()> {
+        if self.poisoned {
+            return Err(io::Error::other(
+                "a StreamingNodeBuilder was dropped without being finished, so the output is incomplete",
+            ));
+        }
+        self.writer.write_all(&[tag])
+    }
+
+    fn write_val(&mut self, val: &T) -> io::Result<()> {
+        (self.val_encoder)(val, &mut self.writer)
+    }
+
+    // Writes the subtree without recursion, so deep trees can't overflow the stack.
+    fn write_subtree(&mut self, node: NodeRef<'_, T>) -> io::Result<()> {
+        let nodes = node.raw_slice();
+        // The nodes that have been started but not ended, as the index one past their last descendant and their value
+        let mut open_nodes: Vec<(usize, &T)> = Vec::new();
+        for (index, node_data) in nodes.iter().enumerate() {
+            while let Some(&(end, val)) = open_nodes.last() {
+                if end > index {
+                    break;
+                }
+                self.write_tag(TAG_END)?;
+                self.write_val(val)?;
+                open_nodes.pop();
+            }
+            let subtree_size = node_data.subtree_size().get();
+            if subtree_size == 1 {
+                self.write_tag(TAG_LEAF)?;
+                self.write_val(node_data.val())?;
+            } else {
+                self.write_tag(TAG_START)?;
+                open_nodes.push((index + subtree_size, node_data.val()));
+            }
+        }
+        for (_, val) in open_nodes.into_iter().rev() {
+            self.write_tag(TAG_END)?;
+            self.write_val(val)?;
+        }
+        Ok(())
+    }
+}
+
+/// A struct that lets you add children to a node that is currently being written by a [`StreamingTreeWriter`].
+///
+/// Like [`NodeBuilder`], but the methods that add nodes return an [`io::Result`].
+pub struct StreamingNodeBuilder<'a, 'f, W: Write, T> {
+    writer: &'a mut StreamingTreeWriter<'f, W, T>,
+    is_root: bool,
+    // Whether TAG_START has been written for this node, which happens when its first child is started.
+    started: bool,
+}
+
+impl<'a, 'f, W: Write, T> Drop for StreamingNodeBuilder<'a, 'f, W, T> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.started {
+            self.writer.poisoned = true;
+        }
+    }
+}
+
+impl<'a, 'f, W: Write, T> StreamingNodeBuilder<'a, 'f, W, T> {
+    /// Build a child node with the given value, and write it as a child of the node
+    /// that is being built by the current [`StreamingNodeBuilder`].
+    ///
+    /// See [`NodeBuilder::build_child`].
+    #[inline]
+    pub fn build_child<R>(
+        &mut self,
+        val: T,
+        child_builder_cb: impl FnOnce(&mut StreamingNodeBuilder<'_, 'f, W, T>) -> io::Result<R>,
+    ) -> io::Result<R> {
+        let mut builder = self.get_child_builder()?;
+        let ret = child_builder_cb(&mut builder)?;
+        builder.finish(val)?;
+        Ok(ret)
+    }
+
+    /// Write a child node with the given value as a child of the node that is being built by the current [`StreamingNodeBuilder`].
+    #[inline]
+    pub fn add_child(&mut self, val: T) -> io::Result<()> {
+        self.get_child_builder()?.finish(val)
+    }
+
+    /// Write a copy of the given node and all of its descendants as a child of the node that is being built.
+    pub fn add_subtree(&mut self, node: NodeRef<'_, T>) -> io::Result<()> {
+        self.start()?;
+        self.writer.write_subtree(node)
+    }
+
+    /// Get a [`StreamingNodeBuilder`] that builds a child that will be written as a child of the node
+    /// that is being built by the current [`StreamingNodeBuilder`].
+    ///
+    /// This fails if writing to the underlying writer fails, since the current node is marked as having children.
+    ///
+    /// See [`NodeBuilder::get_child_builder`].
+    #[inline]
+    pub fn get_child_builder<'b>(&'b mut self) -> io::Result<StreamingNodeBuilder<'b, 'f, W, T>> {
+        self.start()?;
+        Ok(StreamingNodeBuilder {
+            writer: &mut *self.writer,
+            is_root: false,
+            started: false,
+        })
+    }
+
+    /// Finish building the node that this [`StreamingNodeBuilder`] was building, writing its value.
+    ///
+    /// See [`NodeBuilder::finish`].
+    pub fn finish(mut self, val: T) -> io::Result<()> {
+        let tag = if self.started { TAG_END } else { TAG_LEAF };
+        self.writer.write_tag(tag)?;
+        self.writer.write_val(&val)?;
+        self.started = false;
+        if self.is_root {
+            self.writer.num_trees += 1;
+        }
+        Ok(())
+    }
+
+    fn start(&mut self) -> io::Result<()> {
+        if !self.started {
+            self.writer.write_tag(TAG_START)?;
+            self.started = true;
+        }
+        Ok(())
+    }
+}
+
+// Reads a single tag, or returns None at the end of the stream.
+fn read_tag(reader: &mut impl BufRead) -> io::Result<Option<u8>> {
+    let tag = reader.fill_buf()?.first().copied();
+    if tag.is_some() {
+        reader.consume(1);
+    }
+    Ok(tag)
+}
+
+// Reads a value. The stream ending inside the value means that it ended inside a tree.
+fn read_val<T>(reader: &mut impl BufRead, val_decoder: &mut impl FnMut(&mut dyn Read) -> io::Result<T>) -> Result<T, FormatError> {
+    val_decoder(reader).map_err(|err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => FormatError::UnexpectedEnd,
+        _ => FormatError::Io(err),
+    })
+}
+
+// Reads trees until the end of the stream, in the encoding without header.
+//
+// The value of a node comes after its descendants, so the nodes are added without a value first, and their value is
+// set when they are ended. This uses no recursion, so deeply nested input can't overflow the stack.
+fn read_trees<T>(
+    reader: &mut impl BufRead,
+    val_decoder: &mut impl FnMut(&mut dyn Read) -> io::Result<T>,
+) -> Result<PackedForest<T>, FormatError> {
+    let mut stack = TreeBuilderStack::new();
+    while let Some(tag) = read_tag(reader)? {
+        match tag {
+            TAG_LEAF => stack.leaf(Some(read_val(reader, val_decoder)?)),
+            TAG_START => stack.push(None),
+            TAG_END => {
+                if stack.depth() == 0 {
+                    return Err(FormatError::UnmatchedEnd);
+                }
+                let val = read_val(reader, val_decoder)?;
+                *stack.pop().unwrap().val_mut() = Some(val);
+            }
+            _ => return Err(FormatError::InvalidTag { tag }),
+        }
+    }
+    if stack.depth() > 0 {
+        return Err(FormatError::UnexpectedEnd);
+    }
+    Ok(stack.finish().unwrap_vals())
+}
+
+impl<T> PackedForest<T> {
+    /// Reads a forest that was written by a [`StreamingTreeWriter`], decoding the values of the nodes with `val_decoder`.
+    ///
+    /// The nodes are added to a forest while the stream is parsed, using only O(depth) extra memory for that.
+    /// Because the value of a node is stored after its descendants, the nodes are stored with an [`Option`] of their value
+    /// until then, and are moved into the returned forest once at the end. During that move both forests exist,
+    /// so the peak memory use is about twice the size of the forest. The reader is read until its end.
+    /// No recursion is used, so deeply nested input can't overflow the stack.
+    ///
+    /// Returns an error with kind [`InvalidData`](io::ErrorKind::InvalidData) if the stream isn't valid,
+    /// or [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) if it ends in the middle of a tree.
+    ///
+    /// To store forests long-term, see [`read_versioned`](PackedForest::read_versioned).
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedForest, StreamingTreeWriter};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut writer = StreamingTreeWriter::new(Vec::new(), |val: &u8, w: &mut dyn Write| w.write_all(&[*val]));
+    /// writer.build_tree(1, |node_builder| {
+    ///     node_builder.add_child(2)?;
+    ///     node_builder.add_child(3)
+    /// }).unwrap();
+    /// let bytes = writer.into_inner();
+    ///
+    /// let forest = PackedForest::read_stream(bytes.as_slice(), |r: &mut dyn Read| {
+    ///     let mut buf = [0];
+    ///     r.read_exact(&mut buf)?;
+    ///     Ok(buf[0])
+    /// }).unwrap();
+    /// assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [1, 2, 3]);
+    /// ```
+    pub fn read_stream(reader: impl Read, mut val_decoder: impl FnMut(&mut dyn Read) -> io::Result<T>) -> io::Result<PackedForest<T>> {
+        Ok(read_trees(&mut BufReader::new(reader), &mut val_decoder)?)
+    }
+
+    /// Writes this forest in the versioned binary format (see [`FORMAT_VERSION`]), encoding the values of the nodes with `val_encoder`.
+    ///
+    /// The forest can be read again with [`read_versioned`](PackedForest::read_versioned).
+    /// To write trees while they are being built, see [`StreamingTreeWriter::with_header`].
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::{PackedForest, FormatError};
+    /// use std::io::{Read, Write};
+    ///
+    /// let mut forest = PackedForest::new();
+    /// forest.build_tree(1u8, |node_builder| {
+    ///     node_builder.add_child(2);
+    /// });
+    ///
+    /// let mut bytes = Vec::new();
+    /// forest.write_versioned(&mut bytes, |val: &u8, w: &mut dyn Write| w.write_all(&[*val])).unwrap();
+    ///
+    /// let decode = |r: &mut dyn Read| {
+    ///     let mut buf = [0];
+    ///     r.read_exact(&mut buf)?;
+    ///     Ok(buf[0])
+    /// };
+    /// let read_forest = PackedForest::read_versioned(bytes.as_slice(), decode).unwrap();
+    /// assert!(read_forest == forest);
+    ///
+    /// // A version from the future
+    /// bytes[4] = 100;
+    /// let err = PackedForest::read_versioned(bytes.as_slice(), decode).unwrap_err();
+    /// assert!(matches!(err, FormatError::UnsupportedVersion { version: 100 }));
+    /// ```
+    pub fn write_versioned(&self, writer: impl Write, val_encoder: impl FnMut(&T, &mut dyn Write) -> io::Result<()>) -> io::Result<()> {
+        let mut writer = StreamingTreeWriter::with_header(writer, val_encoder)?;
+        for tree in self.iter_trees() {
+            writer.add_tree(tree)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a forest in the versioned binary format (see [`FORMAT_VERSION`]), decoding the values of the nodes with `val_decoder`.
+    ///
+    /// Like [`read_stream`](PackedForest::read_stream), but the stream has to start with a header,
+    /// and the errors say what went wrong (see [`FormatError`]).
+    pub fn read_versioned(
+        reader: impl Read,
+        mut val_decoder: impl FnMut(&mut dyn Read) -> io::Result<T>,
+    ) -> Result<PackedForest<T>, FormatError> {
+        let mut reader = BufReader::new(reader);
+        let mut header = [0; FORMAT_MAGIC.len() + 1];
+        if let Err(err) = reader.read_exact(&mut header) {
+            return Err(match err.kind() {
+                io::ErrorKind::UnexpectedEof => FormatError::InvalidMagic,
+                _ => FormatError::Io(err),
+            });
+        }
+        if header[..FORMAT_MAGIC.len()] != FORMAT_MAGIC {
+            return Err(FormatError::InvalidMagic);
+        }
+        match header[FORMAT_MAGIC.len()] {
+            // When the format changes, older versions are still read here
+            1 => read_trees(&mut reader, &mut val_decoder),
+            version => Err(FormatError::UnsupportedVersion { version }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(val: &u8, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(&[*val])
+    }
+
+    fn decode(r: &mut dyn Read) -> io::Result<u8> {
+        let mut buf = [0];
+        r.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    #[test]
+    fn test_streaming_writer() {
+        let mut writer = StreamingTreeWriter::new(Vec::new(), encode);
+        writer.build_tree(1, |node_builder| {
+            node_builder.build_child(2, |node_builder| node_builder.add_child(3))?;
+            node_builder.add_child(4)
+        }).unwrap();
+        writer.add_single_node_tree(5).unwrap();
+        assert_eq!(writer.num_trees(), 2);
+        let expected = [TAG_START, TAG_START, TAG_LEAF, 3, TAG_END, 2, TAG_LEAF, 4, TAG_END, 1, TAG_LEAF, 5];
+        assert_eq!(writer.get_ref().as_slice(), expected);
+
+        // Writing existing trees gives the same bytes
+        let mut forest = PackedForest::new();
+        forest.build_tree(1, |node_builder| {
+            node_builder.build_child(2, |node_builder| {
+                node_builder.add_child(3);
+            });
+            node_builder.add_child(4);
+        });
+        forest.add_single_node_tree(5);
+        let mut writer = StreamingTreeWriter::new(Vec::new(), encode);
+        writer.build_tree(1, |node_builder| {
+            node_builder.add_subtree(forest.get(1).unwrap())?;
+            node_builder.add_child(4)
+        }).unwrap();
+        writer.add_tree(forest.get(4).unwrap()).unwrap();
+        assert_eq!(writer.into_inner(), expected);
+
+        // Dropping an unfinished leaf doesn't write anything
+        let mut writer = StreamingTreeWriter::new(Vec::new(), encode);
+        writer.build_tree(1, |node_builder| {
+            drop(node_builder.get_child_builder()?);
+            Ok(())
+        }).unwrap();
+        assert_eq!(writer.get_ref().as_slice(), [TAG_START, TAG_END, 1]);
+
+        // Dropping an unfinished node with children makes all further writes fail
+        let result = writer.build_tree(1, |node_builder| {
+            node_builder.add_child(2)?;
+            Err::<(), _>(io::Error::other("oops"))
+        });
+        assert_eq!(result.unwrap_err().to_string(), "oops");
+        assert!(writer.add_single_node_tree(3).is_err());
+        assert_eq!(writer.num_trees(), 1);
+    }
+
+    #[test]
+    fn test_read_stream() {
+        let mut forest = PackedForest::new();
+        forest.build_tree(1, |node_builder| {
+            node_builder.build_child(2, |node_builder| {
+                node_builder.build_child(3, |node_builder| {
+                    node_builder.add_child(4);
+                });
+                node_builder.add_child(5);
+            });
+            node_builder.add_child(6);
+        });
+        forest.add_single_node_tree(7);
+        let mut writer = StreamingTreeWriter::new(Vec::new(), encode);
+        for tree in forest.iter_trees() {
+            writer.add_tree(tree).unwrap();
+        }
+        let bytes = writer.into_inner();
+        assert!(PackedForest::read_stream(bytes.as_slice(), decode).unwrap() == forest);
+        assert_eq!(PackedForest::read_stream(&[][..], decode).unwrap().tot_num_nodes(), 0);
+
+        // A node with TAG_START but without children
+        let forest = PackedForest::read_stream(&[TAG_START, TAG_END, 1][..], decode).unwrap();
+        assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [1]);
+
+        for len in 0..bytes.len() {
+            let err = PackedForest::read_stream(&bytes[..len], decode).err();
+            let is_complete = len == 0 || len == bytes.len() - 2;
+            assert_eq!(err.map(|err| err.kind()), if is_complete { None } else { Some(io::ErrorKind::UnexpectedEof) });
+        }
+        let err = PackedForest::read_stream(&[TAG_END, 1][..], decode).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = PackedForest::read_stream(&[TAG_START, 3][..], decode).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Very deep trees don't overflow the stack, neither when writing nor when reading
+        let deep = PackedForest::from_depth_values((0..1_000_000).map(|i| (i, (i % 256) as u8))).unwrap();
+        let mut writer = StreamingTreeWriter::new(Vec::new(), encode);
+        writer.add_tree(deep.iter_trees().next().unwrap()).unwrap();
+        let bytes = writer.into_inner();
+        assert!(PackedForest::read_stream(bytes.as_slice(), decode).unwrap() == deep);
+        let err = PackedForest::read_stream(&vec![TAG_START; 2_000_000][..], decode).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_versioned() {
+        let mut forest = PackedForest::new();
+        forest.build_tree(1, |node_builder| {
+            node_builder.build_child(2, |node_builder| {
+                node_builder.add_child(3);
+            });
+        });
+        forest.add_single_node_tree(4);
+        let mut bytes = Vec::new();
+        forest.write_versioned(&mut bytes, encode).unwrap();
+        assert_eq!(&bytes[..5], b"PKTF\x01");
+        assert_eq!(bytes[5..], [TAG_START, TAG_START, TAG_LEAF, 3, TAG_END, 2, TAG_END, 1, TAG_LEAF, 4]);
+        assert!(PackedForest::read_versioned(bytes.as_slice(), decode).unwrap() == forest);
+
+        let mut writer = StreamingTreeWriter::with_header(Vec::new(), encode).unwrap();
+        writer.build_tree(1, |node_builder| node_builder.add_subtree(forest.get(1).unwrap())).unwrap();
+        writer.add_single_node_tree(4).unwrap();
+        assert_eq!(writer.into_inner(), bytes);
+
+        let header_only = PackedForest::read_versioned(&bytes[..5], decode).unwrap();
+        assert_eq!(header_only.tot_num_nodes(), 0);
+        assert!(matches!(PackedForest::read_versioned(&bytes[..3], decode), Err(FormatError::InvalidMagic)));
+        assert!(matches!(PackedForest::read_versioned(&bytes[5..], decode), Err(FormatError::InvalidMagic)));
+        assert!(matches!(PackedForest::read_versioned(&bytes[..7], decode), Err(FormatError::UnexpectedEnd)));
+        assert!(matches!(PackedForest::read_versioned(&bytes[..8], decode), Err(FormatError::UnexpectedEnd)));
+        let failing_decode = |_: &mut dyn Read| -> io::Result<u8> { Err(io::Error::other("oops")) };
+        assert!(matches!(PackedForest::read_versioned(bytes.as_slice(), failing_decode), Err(FormatError::Io(_))));
+        let mut invalid = bytes.clone();
+        invalid[5] = 7;
+        assert!(matches!(PackedForest::read_versioned(invalid.as_slice(), decode), Err(FormatError::InvalidTag { tag: 7 })));
+        invalid[5] = TAG_END;
+        assert!(matches!(PackedForest::read_versioned(invalid.as_slice(), decode), Err(FormatError::UnmatchedEnd)));
+        invalid[4] = 0;
+        assert!(matches!(PackedForest::read_versioned(invalid.as_slice(), decode), Err(FormatError::UnsupportedVersion { version: 0 })));
+
+        // Deeply nested input is an error, not a stack overflow
+        let mut deep = b"PKTF\x01".to_vec();
+        deep.resize(2_000_000, TAG_START);
+        assert!(matches!(PackedForest::read_versioned(deep.as_slice(), decode), Err(FormatError::UnexpectedEnd)));
+    }
+}
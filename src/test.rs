@@ -57,7 +57,7 @@ mod tests {
             }
         }
 
-        fn get(&self) -> CheckedRef<T> {
+        fn get(&self) -> CheckedRef<'_, T> {
             let dropcnt = self.dropcnt.load(Ordering::SeqCst);
             if dropcnt > 0 {
                 panic!("Accessing while dropcnt = {} > 0", dropcnt);
@@ -70,7 +70,7 @@ mod tests {
             CheckedRef { r: self }
         }
 
-        fn get_mut(&mut self) -> CheckedRefMut<T> {
+        fn get_mut(&mut self) -> CheckedRefMut<'_, T> {
             let dropcnt = self.dropcnt.load(Ordering::SeqCst);
             if dropcnt > 0 {
                 panic!("Accessing mutably while dropcnt = {} > 0", dropcnt);
@@ -771,4 +771,1322 @@ mod tests {
         }
         assert_eq!(test.num_undropped(), 0);
     }
+
+    #[test]
+    fn test_depths() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let store = build_store(test.clone());
+            assert_eq!(store.depths(), [0, 1, 2, 2, 2, 1, 1, 2, 2, 2, 0, 1, 1, 2, 2, 2, 1]);
+            assert!(PackedForest::<i32>::new().depths().is_empty());
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_parents() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let store = build_store(test.clone());
+            let parents = store.parents();
+            assert_eq!(parents[..6], [None, Some(0), Some(1), Some(1), Some(1), Some(0)]);
+            assert_eq!(parents[10..], [None, Some(10), Some(10), Some(12), Some(12), Some(12), Some(10)]);
+            for (index, parent) in parents.iter().enumerate() {
+                if let Some(parent) = parent {
+                    let parent_node = store.get(*parent).unwrap();
+                    assert!(index > *parent && index < *parent + parent_node.num_descendants_incl_self());
+                }
+            }
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_find() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let store = build_store(test.clone());
+            assert_eq!(store.position(|node| node.val().val == 20), Some(5));
+            assert_eq!(store.find(|node| node.val().val == 20).unwrap().num_descendants_incl_self(), 1);
+            assert_eq!(store.position(|node| node.val().val == 3 && node.children().count() == 3), Some(10));
+            assert!(store.find(|node| node.val().val == 1000).is_none());
+
+            let second_tree = store.iter_trees().nth(1).unwrap();
+            let node = second_tree.find_descendant(|node| node.val().val == 20).unwrap();
+            assert_eq!(node.num_descendants_incl_self(), 4);
+            assert_eq!(second_tree.find_descendant(|node| node.val().val == 3).unwrap().val().val, 3);
+            assert!(second_tree.find_descendant(|node| node.val().val == 11).is_none());
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_descendant_at_path() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let store = build_store(test.clone());
+            let root = store.iter_trees().next().unwrap();
+            assert_eq!(root.descendant_at_path(&[]).unwrap().val().val, 2);
+            assert_eq!(root.descendant_at_path(&[2, 1]).unwrap().val().val, 32);
+            assert_eq!(root.descendant_at_path(&[0, 2]).unwrap().val().val, 13);
+            assert!(root.descendant_at_path(&[3]).is_none());
+            assert!(root.descendant_at_path(&[1, 0]).is_none());
+
+            let tree = PackedTree::new(0, |node_builder| {
+                node_builder.build_child(1, |node_builder| {
+                    node_builder.add_child(2);
+                });
+            });
+            assert_eq!(tree.get_path(&[0, 0]).map(|node| *node.val()), Some(2));
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_search_pruned() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let store = build_store(test.clone());
+            let root = store.iter_trees().next().unwrap();
+            let mut visited = Vec::new();
+            let mut num_descend_calls = 0;
+            root.search_pruned(|node| {
+                num_descend_calls += 1;
+                node.val().val != 10
+            }, |node| visited.push(node.val().val));
+            assert_eq!(visited, [2, 20, 30, 31, 32, 33]);
+            assert_eq!(num_descend_calls, 7);
+
+            let mut visited = Vec::new();
+            root.search_pruned(|_| false, |node| visited.push(node.val().val));
+            assert!(visited.is_empty());
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_add_leaf_children() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut forest = PackedForest::new();
+            forest.build_tree(Checked::new(0, test.clone()), |node_builder| {
+                node_builder.add_child(Checked::new(1, test.clone()));
+                node_builder.build_child(Checked::new(2, test.clone()), |node_builder| {
+                    node_builder.add_leaf_children((0..100).map(|i| Checked::new(100 + i, test.clone())));
+                });
+                node_builder.add_leaf_children(std::iter::empty());
+                node_builder.add_child(Checked::new(3, test.clone()));
+            });
+            let root = forest.iter_trees().next().unwrap();
+            assert_eq!(root.num_descendants_incl_self(), 104);
+            let vals: Vec<_> = root.children().map(|child| child.val().val).collect();
+            assert_eq!(vals, [1, 2, 3]);
+            let second_child = root.children().nth(1).unwrap();
+            assert!(second_child.children().enumerate().all(|(i, child)| child.val().val == 100 + i as i32 && child.num_descendants_incl_self() == 1));
+
+            // Nodes added to a NodeBuilder that is dropped without calling finish are dropped too
+            let mut node_builder = forest.get_tree_builder();
+            node_builder.add_leaf_children((0..10).map(|i| Checked::new(i, test.clone())));
+            drop(node_builder);
+            assert_eq!(forest.tot_num_nodes(), 104);
+        }
+        assert_eq!(test.num_undropped(), 0);
+
+        let tree = PackedTree::new(0, |node_builder| {
+            node_builder.build_child(1, |node_builder| {
+                node_builder.add_leaf_children_from_slice(&[10, 11, 12]);
+                node_builder.add_leaf_children_from_slice(&[]);
+            });
+            node_builder.add_leaf_children_from_slice(&[2; 50]);
+        });
+        assert_eq!(tree.tot_num_nodes(), 1 + 4 + 50);
+        assert_eq!(tree.root().children().count(), 51);
+        let vals: Vec<_> = tree.root().children().next().unwrap().children().map(|child| *child.val()).collect();
+        assert_eq!(vals, [10, 11, 12]);
+    }
+
+    #[test]
+    fn test_builder_introspection() {
+        let mut forest = PackedForest::new();
+        forest.build_tree(0, |node_builder| {
+            assert_eq!((node_builder.depth(), node_builder.num_children_so_far(), node_builder.subtree_size_so_far()), (0, 0, 1));
+            node_builder.build_child(1, |node_builder| {
+                assert_eq!(node_builder.depth(), 1);
+                node_builder.build_child(2, |node_builder| {
+                    assert_eq!(node_builder.depth(), 2);
+                });
+                node_builder.add_leaf_children_from_slice(&[3, 4]);
+                assert_eq!((node_builder.num_children_so_far(), node_builder.subtree_size_so_far()), (3, 4));
+            });
+            assert_eq!((node_builder.num_children_so_far(), node_builder.subtree_size_so_far()), (1, 5));
+
+            // A child builder that is dropped without finishing doesn't count
+            let mut child_builder = node_builder.get_child_builder();
+            child_builder.add_child(5);
+            drop(child_builder);
+            node_builder.add_leaf_children(vec![6, 7]);
+            assert_eq!((node_builder.num_children_so_far(), node_builder.subtree_size_so_far()), (3, 7));
+        });
+        forest.build_tree(8, |node_builder| {
+            assert_eq!(node_builder.depth(), 0);
+        });
+        assert_eq!(forest.tot_num_nodes(), 8);
+
+        let tree = ExactSizePackedTree::new(0, |node_builder| {
+            node_builder.build_child(1, |node_builder| {
+                assert_eq!(node_builder.depth(), 1);
+                node_builder.add_child(2);
+                assert_eq!((node_builder.num_children_so_far(), node_builder.subtree_size_so_far()), (1, 2));
+            });
+        });
+        assert_eq!(tree.root().children().len(), 1);
+    }
+
+    #[test]
+    fn test_fallible_building() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut forest = build_store(test.clone());
+            let num_nodes = forest.tot_num_nodes();
+
+            let result: Result<(), &str> = forest.build_tree_result(Checked::new(4, test.clone()), |node_builder| {
+                node_builder.add_child(Checked::new(10, test.clone()));
+                node_builder.try_build_child(Checked::new(20, test.clone()), |node_builder| {
+                    node_builder.add_child(Checked::new(21, test.clone()));
+                    Err("error")
+                })?;
+                Ok(())
+            });
+            assert_eq!(result, Err("error"));
+            assert_eq!(forest.tot_num_nodes(), num_nodes);
+
+            let result: Result<usize, &str> = forest.build_tree_result(Checked::new(4, test.clone()), |node_builder| {
+                let failed: Result<(), &str> = node_builder.try_build_child(Checked::new(10, test.clone()), |node_builder| {
+                    node_builder.add_child(Checked::new(11, test.clone()));
+                    Err("ignored error")
+                });
+                assert!(failed.is_err());
+                let child = node_builder.try_build_child_by_ret_val(|node_builder| {
+                    node_builder.add_child(Checked::new(21, test.clone()));
+                    Ok::<_, &str>(Checked::new(20, test.clone()))
+                })?;
+                assert_eq!(child.val().val, 20);
+                Ok(node_builder.num_children_so_far())
+            });
+            assert_eq!(result, Ok(1));
+            assert_eq!(forest.tot_num_nodes(), num_nodes + 3);
+            let last_tree = forest.iter_trees().last().unwrap();
+            assert_eq!(last_tree.val().val, 4);
+            assert_eq!(last_tree.children().next().unwrap().val().val, 20);
+        }
+        assert_eq!(test.num_undropped(), 0);
+
+        assert_eq!(PackedTree::try_new(0, |node_builder| {
+            node_builder.add_child(1);
+            Ok::<_, ()>(())
+        }).unwrap().tot_num_nodes(), 2);
+        assert!(PackedTree::try_new(0, |_| Err(())).is_err());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut forest = build_store(test.clone());
+            let num_nodes = forest.tot_num_nodes();
+
+            let mut node_builder = forest.get_tree_builder();
+            node_builder.build_child(Checked::new(10, test.clone()), |node_builder| {
+                node_builder.add_child(Checked::new(11, test.clone()));
+            });
+            let mut child_builder = node_builder.get_child_builder();
+            child_builder.add_child(Checked::new(21, test.clone()));
+            assert_eq!(child_builder.cancel(), 1);
+            node_builder.add_child(Checked::new(30, test.clone()));
+            assert_eq!(node_builder.cancel(), 3);
+            assert_eq!(forest.tot_num_nodes(), num_nodes);
+
+            assert_eq!(forest.get_tree_builder().cancel(), 0);
+        }
+        assert_eq!(test.num_undropped(), 0);
+
+        let mut forest = ExactSizePackedForest::new();
+        let mut node_builder = forest.get_tree_builder();
+        node_builder.add_child(1);
+        assert_eq!(node_builder.cancel(), 1);
+        assert_eq!(forest.iter_trees().count(), 0);
+    }
+
+    #[test]
+    fn test_swap_subtrees() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut store = build_store(test.clone());
+            // swap the subtrees of 10 and 30 in the first tree
+            assert_eq!(store.swap_subtrees(6, 1), Ok(()));
+            let vals: Vec<_> = store.iter_flattened().map(|val| val.val).collect();
+            assert_eq!(vals, [2, 30, 31, 32, 33, 20, 10, 11, 12, 13, 3, 10, 20, 21, 22, 23, 30]);
+            assert_eq!(store.get(6).unwrap().children().count(), 3);
+
+            // leaves in different trees
+            assert_eq!(store.swap_subtrees(5, 16), Ok(()));
+            assert_eq!(store.get(5).unwrap().val().val, 30);
+            assert_eq!(store.get(16).unwrap().val().val, 20);
+            assert_eq!(store.swap_subtrees(3, 3), Ok(()));
+
+            assert_eq!(store.swap_subtrees(0, 17), Err(SwapError::IndexOutOfBounds { index: 17 }));
+            assert_eq!(store.swap_subtrees(12, 10), Err(SwapError::Overlapping));
+            assert_eq!(store.swap_subtrees(1, 5), Err(SwapError::SizeMismatch { a_size: 4, b_size: 1 }));
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_iter_flattened_enumerated() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut store = build_store(test.clone());
+            for (index, val) in store.iter_flattened_enumerated() {
+                assert_eq!(store.get(index).unwrap().val().val, val.val);
+            }
+            assert_eq!(store.iter_flattened_enumerated().count(), store.tot_num_nodes());
+            for (index, val) in store.iter_flattened_enumerated_mut() {
+                val.val = index as i32;
+            }
+            assert!((0..store.tot_num_nodes()).all(|index| store.get(index).unwrap().val().val == index as i32));
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_preorder_range() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let store = build_store(test.clone());
+            for index in 0..store.tot_num_nodes() {
+                let node = store.get(index).unwrap();
+                assert_eq!(node.preorder_range(&store), index..(index + node.num_descendants_incl_self()));
+            }
+            let second_tree = store.iter_trees().nth(1).unwrap();
+            assert_eq!(second_tree.preorder_range(&store), 10..17);
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_preorder_range_other_forest() {
+        let tree = PackedTree::new(0, |node_builder| {
+            node_builder.add_child(1);
+        });
+        let other_tree = tree.clone();
+        tree.root().preorder_range(other_tree.as_ref());
+    }
+
+    #[test]
+    fn test_preorder_range_checks_forest() {
+        let small = PackedTree::new(0, |node_builder| {
+            node_builder.add_child(1);
+        });
+        let big = PackedTree::new(0, |node_builder| {
+            node_builder.add_leaf_children(1..1000);
+        });
+        for (node, forest) in [(small.root(), &big), (big.root(), &small), (big.root().children().nth(5).unwrap(), &small)] {
+            let result = std::panic::catch_unwind(|| node.preorder_range(forest.as_ref()));
+            assert!(result.is_err());
+        }
+        for (index, node) in big.root().children().enumerate() {
+            assert_eq!(node.preorder_range(big.as_ref()), (index + 1)..(index + 2));
+        }
+    }
+
+    #[test]
+    fn test_detach_subtree() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut store = build_store(test.clone());
+            // detach 20 (with children 21, 22, 23) from the second tree
+            let detached = store.detach_subtree(12).unwrap();
+            let detached_vals: Vec<_> = detached.iter_flattened().map(|val| val.val).collect();
+            assert_eq!(detached_vals, [20, 21, 22, 23]);
+            let vals: Vec<_> = store.iter_flattened().map(|val| val.val).collect();
+            assert_eq!(vals, [2, 10, 11, 12, 13, 20, 30, 31, 32, 33, 3, 10, 30]);
+            let second_tree = store.iter_trees().nth(1).unwrap();
+            assert_eq!(second_tree.num_descendants_incl_self(), 3);
+            assert_eq!(second_tree.children().count(), 2);
+            assert_eq!(store.iter_trees().next().unwrap().num_descendants_incl_self(), 10);
+
+            // detach a whole tree
+            let first_tree = store.detach_subtree(0).unwrap();
+            assert_eq!(first_tree.tot_num_nodes(), 10);
+            assert_eq!(store.iter_trees().count(), 1);
+            assert!(store.detach_subtree(3).is_none());
+
+            let mut tree = PackedTree::try_from_forest(store).unwrap();
+            assert!(tree.detach_subtree(0).is_none());
+            assert_eq!(tree.detach_subtree(2).unwrap().root().val().val, 30);
+            assert_eq!(tree.tot_num_nodes(), 2);
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_exact_size_forest_into_iter() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut forest = ExactSizePackedForest::new();
+            assert!(forest.is_empty());
+            forest.build_tree(Checked::new(1, test.clone()), |node_builder| {
+                node_builder.add_child(Checked::new(2, test.clone()));
+                node_builder.add_child(Checked::new(3, test.clone()));
+            });
+            forest.add_single_node_tree(Checked::new(4, test.clone()));
+            forest.build_tree_by_ret_val(|node_builder| {
+                node_builder.add_child(Checked::new(6, test.clone()));
+                Checked::new(5, test.clone())
+            });
+            assert_eq!(forest.num_trees(), 3);
+            assert!(!forest.is_empty());
+
+            let mut iter = (&forest).into_iter();
+            assert_eq!(iter.len(), 3);
+            iter.next();
+            assert_eq!(iter.len(), 2);
+            assert_eq!(iter.next().unwrap().val().val, 4);
+            assert_eq!(iter.len(), 1);
+
+            for mut tree in &mut forest {
+                tree.val_mut().val *= 10;
+            }
+
+            let vals: Vec<_> = forest.iter_trees().map(|tree| tree.val().val).collect();
+            assert_eq!(vals, [10, 40, 50]);
+
+            let mut trees = forest.into_iter();
+            assert_eq!(trees.len(), 3);
+            let first = trees.next().unwrap();
+            assert_eq!(first.root().val().val, 10);
+            assert_eq!(first.root().children().len(), 2);
+            assert_eq!(first.tot_num_nodes(), 3);
+            assert_eq!(trees.len(), 2);
+            // drop the remaining trees without iterating over them
+        }
+        assert_eq!(test.num_undropped(), 0);
+
+        let mut forest = ExactSizePackedForest::new();
+        forest.add_single_node_tree(1);
+        assert_eq!(forest.drain_trees().len(), 1);
+        assert!(forest.is_empty());
+        forest.add_single_node_tree(1);
+        forest.clear();
+        assert_eq!(forest.num_trees(), 0);
+    }
+
+    #[test]
+    fn test_iter_trees_indexed() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut store = build_store(test.clone());
+            let roots: Vec<_> = store.iter_trees_indexed().map(|(tree_index, tree)| (tree_index, tree.val().val)).collect();
+            assert_eq!(roots, [(0, 2), (1, 3)]);
+
+            for (tree_index, mut tree) in store.iter_trees_mut_indexed() {
+                tree.val_mut().val += tree_index as i32 * 100;
+            }
+
+            let mut drained = Vec::new();
+            for (tree_index, tree) in store.drain_trees_indexed() {
+                drained.push((tree_index, tree.val.val, tree.children.count()));
+            }
+            assert_eq!(drained, [(0, 2, 3), (1, 103, 3)]);
+            assert_eq!(store.tot_num_nodes(), 0);
+        }
+        assert_eq!(test.num_undropped(), 0);
+
+        let mut forest = ExactSizePackedForest::new();
+        forest.add_single_node_tree(1);
+        forest.add_single_node_tree(2);
+        assert_eq!(forest.iter_trees_indexed().len(), 2);
+        assert_eq!(forest.drain_trees_indexed().map(|(tree_index, tree)| (tree_index, tree.val)).collect::<Vec<_>>(), [(0, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn test_children_values() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut store = build_store(test.clone());
+            let vals: Vec<_> = store.get(0).unwrap().children_values().map(|val| val.val).collect();
+            assert_eq!(vals, [10, 20, 30]);
+            assert_eq!(store.get(5).unwrap().children_values().count(), 0);
+
+            for val in store.get_mut(10).unwrap().children_values_mut() {
+                val.val += 1;
+            }
+            let vals: Vec<_> = store.iter_flattened().map(|val| val.val).collect();
+            assert_eq!(vals, [2, 10, 11, 12, 13, 20, 30, 31, 32, 33, 3, 11, 21, 21, 22, 23, 31]);
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_raw_slice() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let store = build_store(test.clone());
+            let node = store.get(12).unwrap();
+            let raw_slice = node.raw_slice();
+            assert_eq!(raw_slice.len(), node.num_descendants_incl_self());
+            let vals: Vec<_> = raw_slice.iter().map(|node_data| node_data.val().val).collect();
+            assert_eq!(vals, [20, 21, 22, 23]);
+            assert_eq!(raw_slice[0].subtree_size().get(), 4);
+            assert!(std::ptr::eq(raw_slice, &store.raw_data()[node.preorder_range(&store)]));
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut forest = PackedForest::new();
+        forest.build_tree(1u64, |node_builder| {
+            node_builder.build_child(2, |node_builder| {
+                node_builder.add_child(3);
+            });
+            node_builder.add_child(4);
+        });
+        forest.add_single_node_tree(5);
+        let bytes = forest.as_bytes();
+        assert_eq!(bytes.len(), 5 * std::mem::size_of::<NodeData<u64>>());
+        assert!(PackedForest::<u64>::try_from_bytes(bytes).unwrap() == forest);
+        let trees: Vec<_> = NodeIter::<u64>::try_from_bytes(bytes).unwrap().map(|tree| *tree.val()).collect();
+        assert_eq!(trees, [1, 5]);
+        assert!(PackedForest::<u64>::try_from_bytes(&[]).unwrap().iter_trees().next().is_none());
+
+        // Unaligned bytes can only be copied
+        let mut buffer = vec![0u64; bytes.len() / 8 + 1];
+        let unaligned = &mut bytemuck::cast_slice_mut::<u64, u8>(&mut buffer)[1..(bytes.len() + 1)];
+        unaligned.copy_from_slice(bytes);
+        assert!(PackedForest::<u64>::try_from_bytes(unaligned).unwrap() == forest);
+        assert_eq!(NodeIter::<u64>::try_from_bytes(unaligned).err(), Some(FromBytesError::Misaligned));
+
+        assert_eq!(
+            PackedForest::<u64>::try_from_bytes(&bytes[1..]).err(),
+            Some(FromBytesError::InvalidLength { len: bytes.len() - 1 })
+        );
+        assert_eq!(
+            PackedForest::<u64>::try_from_bytes(&vec![0; bytes.len()]).err(),
+            Some(FromBytesError::InvalidSubtreeSize { index: 0 })
+        );
+        // After swapping the first two nodes, the subtree of node 1 extends past the end of its parent's subtree
+        let node_size = std::mem::size_of::<NodeData<u64>>();
+        let mut swapped = bytes.to_vec();
+        swapped[..(2 * node_size)].rotate_left(node_size);
+        assert_eq!(
+            PackedForest::<u64>::try_from_bytes(&swapped).err(),
+            Some(FromBytesError::InvalidSubtreeSize { index: 1 })
+        );
+    }
+
+    #[test]
+    fn test_capacity_overflow() {
+        let mut forest = PackedForest::new();
+        forest.build_tree((), |node_builder| {
+            node_builder.add_leaf_children_from_slice(&[(), ()]);
+            let too_many = vec![(); usize::MAX];
+            let err = node_builder.try_add_leaf_children_from_slice(&too_many).unwrap_err();
+            assert_eq!(err, AddNodesError::Capacity(CapacityError { capacity: max_num_nodes::<(), ()>() }));
+            assert_eq!(node_builder.subtree_size_so_far(), 3);
+        });
+        assert_eq!(forest.tot_num_nodes(), 3);
+
+        let result = std::panic::catch_unwind(|| {
+            PackedTree::new((), |node_builder| {
+                node_builder.add_leaf_children_from_slice(&[(); 3]);
+                node_builder.add_leaf_children_from_slice(&[(); usize::MAX - 2]);
+            })
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_index() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut store = build_store(test.clone());
+            assert_eq!(store[0].val, 2);
+            assert_eq!(store[12].val, 20);
+            store[12].val += 1;
+            assert_eq!(store.get(12).unwrap().val().val, 21);
+            assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| store[17].val)).is_err());
+
+            let mut tree = store.detach_subtree(10).unwrap();
+            assert_eq!(tree[0].val, 3);
+            tree[2].val = 100;
+            assert_eq!(tree.root().children().nth(1).unwrap().val().val, 100);
+            assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| tree[7].val = 0)).is_err());
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_iter_mut_split_at() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut store = build_store(test.clone());
+            let (first, rest) = store.get_mut(0).unwrap().into_children().split_at(2);
+            assert_eq!(first.num_remaining_nodes_incl_descendants(), 5);
+            assert_eq!(rest.num_remaining_nodes_incl_descendants(), 4);
+            for (mut a, mut b) in first.zip(rest) {
+                a.val_mut().val += 1;
+                b.val_mut().val += 2;
+            }
+            let vals: Vec<_> = store.iter_flattened().map(|val| val.val).take(10).collect();
+            assert_eq!(vals, [2, 11, 11, 12, 13, 20, 32, 31, 32, 33]);
+
+            let (first, rest) = store.iter_trees_mut().split_at(5);
+            assert_eq!(first.count(), 2);
+            assert_eq!(rest.count(), 0);
+            let (first, rest) = store.iter_trees_mut().split_at(0);
+            assert_eq!(first.count(), 0);
+            assert_eq!(rest.map(|tree| tree.val().val).collect::<Vec<_>>(), [2, 3]);
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_sorted_by_key() {
+        let mut forest = PackedForest::new();
+        forest.build_tree(5, |node_builder| {
+            node_builder.build_child(30, |node_builder| {
+                node_builder.add_leaf_children(vec![33, 31, 32]);
+            });
+            node_builder.add_child(10);
+            node_builder.build_child(20, |node_builder| {
+                node_builder.add_leaf_children(vec![22, 21]);
+            });
+            node_builder.add_child(11);
+        });
+        forest.add_single_node_tree(1);
+
+        let mut num_calls = 0;
+        let sorted = forest.sorted_by_key(|val| {
+            num_calls += 1;
+            *val
+        });
+        assert_eq!(num_calls, forest.tot_num_nodes() - 2);
+        assert_eq!(sorted.iter_flattened().copied().collect::<Vec<_>>(), [5, 10, 11, 20, 21, 22, 30, 31, 32, 33, 1]);
+        assert_eq!(sorted.get(3).unwrap().num_descendants_incl_self(), 3);
+
+        // The sort is stable
+        let sorted = forest.sorted_by_key(|val| *val / 10);
+        assert_eq!(sorted.iter_flattened().copied().collect::<Vec<_>>(), [5, 10, 11, 20, 22, 21, 30, 33, 31, 32, 1]);
+
+        let tree = PackedTree::new(0, |node_builder| {
+            node_builder.add_leaf_children(vec![3, 1, 2]);
+        });
+        let sorted = tree.sorted_by_key(|val| std::cmp::Reverse(*val));
+        assert_eq!(sorted.iter_flattened().copied().collect::<Vec<_>>(), [0, 3, 2, 1]);
+
+        // Trees without children are copied unchanged
+        let mut leaves = PackedForest::new();
+        for val in [3, 1, 2] {
+            leaves.add_single_node_tree(val);
+        }
+        let sorted = leaves.sorted_by_key(|_| -> u32 { panic!("no children to sort") });
+        assert_eq!(sorted.iter_flattened().copied().collect::<Vec<_>>(), [3, 1, 2]);
+
+        // Deep trees don't overflow the stack
+        let chain = PackedTree::unfold_iterative(0, |n| (*n, if *n < 999_999 { vec![n + 1, 1_000_000 + n] } else { vec![] }));
+        let sorted = chain.sorted_by_key(|val| std::cmp::Reverse(*val));
+        assert_eq!(sorted.tot_num_nodes(), chain.tot_num_nodes());
+        assert_eq!(sorted.iter_flattened().take(4).copied().collect::<Vec<_>>(), [0, 1_000_000, 1, 1_000_001]);
+    }
+
+    #[test]
+    fn test_map_children_order() {
+        let mut forest = PackedForest::new();
+        forest.build_tree(5, |node_builder| {
+            node_builder.build_child(30, |node_builder| {
+                node_builder.add_leaf_children(vec![31, 32, 33]);
+            });
+            node_builder.add_child(10);
+            node_builder.build_child(20, |node_builder| {
+                node_builder.add_leaf_children(vec![21, 22]);
+            });
+        });
+        forest.add_single_node_tree(1);
+
+        let mut num_calls = 0;
+        let same = forest.map_children_order(|_, _, _| num_calls += 1);
+        assert_eq!(num_calls, 3);
+        assert!(same == forest);
+
+        let sorted = forest.map_children_order(|node, children, order| {
+            assert_eq!(children.len(), node.children().count());
+            order.sort_by_key(|&i| *children[i].val());
+        });
+        assert!(sorted == forest.sorted_by_key(|val| *val));
+
+        let reversed = forest.reverse_children();
+        assert_eq!(reversed.iter_flattened().copied().collect::<Vec<_>>(), [5, 20, 22, 21, 10, 30, 33, 32, 31, 1]);
+        assert_eq!(reversed.get(1).unwrap().num_descendants_incl_self(), 3);
+        assert!(reversed.reverse_children() == forest);
+
+        let rotated = forest.rotate_children(1);
+        assert_eq!(rotated.iter_flattened().copied().collect::<Vec<_>>(), [5, 10, 20, 22, 21, 30, 32, 33, 31, 1]);
+        assert!(forest.rotate_children(6) == forest);
+
+        let tree = PackedTree::new(0, |node_builder| {
+            node_builder.add_leaf_children(vec![1, 2, 3]);
+        });
+        assert_eq!(tree.rotate_children(5).iter_flattened().copied().collect::<Vec<_>>(), [0, 3, 1, 2]);
+        assert_eq!(tree.reverse_children().iter_flattened().copied().collect::<Vec<_>>(), [0, 3, 2, 1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_map_children_order_duplicate() {
+        let tree = PackedTree::new(0, |node_builder| {
+            node_builder.add_leaf_children(vec![1, 2]);
+        });
+        tree.map_children_order(|_, _, order| order[1] = order[0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_map_children_order_out_of_bounds() {
+        let tree = PackedTree::new(0, |node_builder| {
+            node_builder.add_leaf_children(vec![1, 2]);
+        });
+        tree.map_children_order(|_, _, order| order[0] = 2);
+    }
+
+    #[test]
+    fn test_unwrap_nodes() {
+        let tree = PackedTree::new(0, |node_builder| {
+            node_builder.build_child(-1, |node_builder| {
+                node_builder.build_child(-2, |node_builder| {
+                    node_builder.add_leaf_children(vec![1, 2]);
+                });
+                node_builder.add_child(3);
+            });
+            node_builder.build_child(4, |node_builder| {
+                node_builder.add_child(-5);
+            });
+        });
+
+        let mut visited = Vec::new();
+        let unwrapped = tree.as_ref().unwrap_nodes(|node| {
+            visited.push(*node.val());
+            *node.val() < 0
+        });
+        assert_eq!(visited, tree.iter_flattened().copied().collect::<Vec<_>>());
+        assert_eq!(unwrapped.iter_flattened().copied().collect::<Vec<_>>(), [0, 1, 2, 3, 4]);
+        let root = unwrapped.iter_trees().next().unwrap();
+        assert_eq!(root.children().map(|child| *child.val()).collect::<Vec<_>>(), [1, 2, 3, 4]);
+        assert_eq!(root.num_descendants_incl_self(), 5);
+
+        // Removing a root turns its children into trees
+        let unwrapped = tree.as_ref().unwrap_nodes(|node| *node.val() <= 0);
+        assert_eq!(unwrapped.iter_trees().map(|tree| *tree.val()).collect::<Vec<_>>(), [1, 2, 3, 4]);
+        assert_eq!(unwrapped.tot_num_nodes(), 4);
+
+        assert!(tree.as_ref().unwrap_nodes(|_| false) == *tree.as_ref());
+        assert_eq!(tree.as_ref().unwrap_nodes(|_| true).tot_num_nodes(), 0);
+    }
+
+    #[test]
+    fn test_filter_with_ancestors() {
+        let mut forest = PackedForest::new();
+        forest.build_tree(0, |node_builder| {
+            node_builder.build_child(1, |node_builder| {
+                node_builder.build_child(2, |node_builder| {
+                    node_builder.add_leaf_children(vec![13, 4]);
+                });
+                node_builder.add_child(5);
+            });
+            node_builder.build_child(16, |node_builder| {
+                node_builder.add_child(7);
+            });
+        });
+        forest.build_tree(8, |node_builder| {
+            node_builder.add_child(9);
+        });
+        forest.add_single_node_tree(10);
+
+        let mut visited = Vec::new();
+        let filtered = forest.filter_with_ancestors(|node| {
+            visited.push(*node.val());
+            *node.val() >= 10
+        });
+        assert_eq!(visited, forest.iter_flattened().copied().collect::<Vec<_>>());
+        assert_eq!(filtered.iter_flattened().copied().collect::<Vec<_>>(), [0, 1, 2, 13, 16, 10]);
+        assert_eq!(filtered.iter_trees().map(|tree| tree.num_descendants_incl_self()).collect::<Vec<_>>(), [5, 1]);
+        assert_eq!(filtered.get(1).unwrap().num_descendants_incl_self(), 3);
+
+        assert!(forest.filter_with_ancestors(|_| true) == forest);
+        assert_eq!(forest.filter_with_ancestors(|_| false).tot_num_nodes(), 0);
+        let filtered = forest.filter_with_ancestors(|node| *node.val() == 9);
+        assert_eq!(filtered.iter_flattened().copied().collect::<Vec<_>>(), [8, 9]);
+    }
+
+    #[test]
+    fn test_collect_subtrees() {
+        let mut forest = PackedForest::new();
+        forest.build_tree(0, |node_builder| {
+            node_builder.build_child(11, |node_builder| {
+                node_builder.add_child(12);
+                node_builder.build_child(3, |node_builder| {
+                    node_builder.add_child(14);
+                });
+            });
+            node_builder.add_child(5);
+            node_builder.build_child(6, |node_builder| {
+                node_builder.add_leaf_children(vec![17, 8]);
+            });
+        });
+        forest.add_single_node_tree(19);
+
+        let mut visited = Vec::new();
+        let collected = forest.collect_subtrees(|node| {
+            visited.push(*node.val());
+            *node.val() >= 10
+        });
+        // The descendants of a matching node aren't visited
+        assert_eq!(visited, [0, 11, 5, 6, 17, 8, 19]);
+        assert_eq!(collected.iter_flattened().copied().collect::<Vec<_>>(), [11, 12, 3, 14, 17, 19]);
+        assert_eq!(collected.iter_trees().map(|tree| tree.num_descendants_incl_self()).collect::<Vec<_>>(), [4, 1, 1]);
+        assert_eq!(collected.get(2).unwrap().num_descendants_incl_self(), 2);
+
+        assert!(forest.collect_subtrees(|_| true) == forest);
+        assert_eq!(forest.collect_subtrees(|_| false).tot_num_nodes(), 0);
+        let tree = PackedTree::try_from_forest(forest.collect_subtrees(|node| *node.val() == 6)).unwrap();
+        assert_eq!(tree.collect_subtrees(|node| *node.val() % 2 == 0).iter_flattened().copied().collect::<Vec<_>>(), [6, 17, 8]);
+    }
+
+    #[test]
+    fn test_peek() {
+        let test = Arc::new(CheckedTest::new());
+        let mut forest = build_store(test.clone());
+
+        let mut trees = forest.iter_trees();
+        assert_eq!(*trees.peek().unwrap().val().get(), 2);
+        assert_eq!(*trees.peek().unwrap().val().get(), 2);
+        assert_eq!(*trees.next().unwrap().val().get(), 2);
+        assert_eq!(*trees.peek().unwrap().val().get(), 3);
+        trees.next();
+        assert!(trees.peek().is_none());
+
+        let mut trees = forest.iter_trees_mut();
+        assert_eq!(*trees.peek().unwrap().val().get(), 2);
+        trees.next();
+        *trees.peek_mut().unwrap().val_mut().get_mut() = 4;
+        assert_eq!(*trees.peek().unwrap().val().get(), 4);
+        assert_eq!(trees.peek_mut().unwrap().children().count(), 3);
+        assert_eq!(*trees.next().unwrap().val().get(), 4);
+        assert!(trees.peek().is_none());
+        assert!(trees.peek_mut().is_none());
+
+        drop(forest);
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_node_ref_eq_hash() {
+        use std::collections::HashMap;
+
+        let tree = PackedTree::new(0, |node_builder| {
+            node_builder.build_child(1, |node_builder| {
+                node_builder.add_child(2);
+            });
+            node_builder.build_child(1, |node_builder| {
+                node_builder.add_child(2);
+            });
+            node_builder.build_child(1, |node_builder| {
+                node_builder.add_child(3);
+            });
+            node_builder.add_child(1);
+        });
+        let children: Vec<_> = tree.root().children().collect();
+        assert!(children[0] == children[1]);
+        assert!(children[0] != children[2]);
+        assert!(children[0] != children[3]);
+        assert!(tree.root() == tree.root());
+        assert!(tree.root() != children[0]);
+
+        let other = PackedTree::new(1, |node_builder| {
+            node_builder.add_child(2);
+        });
+        assert!(other.root() == children[1]);
+
+        let mut counts = HashMap::new();
+        for node in tree.root().children() {
+            *counts.entry(node).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&other.root()], 2);
+        assert_eq!(counts[&tree.get(7).unwrap()], 1);
+    }
+
+    #[test]
+    fn test_add_subtree_copy() {
+        let source = PackedTree::new(1, |node_builder| {
+            node_builder.build_child(2, |node_builder| {
+                node_builder.add_child(3);
+            });
+            node_builder.add_child(4);
+        });
+
+        let mut forest = PackedForest::new();
+        forest.build_tree(0, |node_builder| {
+            node_builder.add_child(5);
+            node_builder.add_subtree_copy(source.root());
+            node_builder.build_child(6, |node_builder| {
+                node_builder.add_subtree_copy(source.get(1).unwrap());
+            });
+            assert_eq!(node_builder.num_children_so_far(), 3);
+        });
+        forest.build_tree(7, |node_builder| {
+            node_builder.add_subtree_copy(source.get(3).unwrap());
+        });
+
+        let mut expected = PackedForest::new();
+        expected.build_tree(0, |node_builder| {
+            node_builder.add_child(5);
+            node_builder.build_child(1, |node_builder| {
+                node_builder.build_child(2, |node_builder| {
+                    node_builder.add_child(3);
+                });
+                node_builder.add_child(4);
+            });
+            node_builder.build_child(6, |node_builder| {
+                node_builder.build_child(2, |node_builder| {
+                    node_builder.add_child(3);
+                });
+            });
+        });
+        expected.build_tree(7, |node_builder| {
+            node_builder.add_child(4);
+        });
+        assert!(forest == expected);
+
+        // Copying into a tree that is dropped before being finished
+        let mut forest = PackedForest::new();
+        let mut builder = forest.get_tree_builder();
+        builder.add_subtree_copy(source.root());
+        drop(builder);
+        assert_eq!(forest.tot_num_nodes(), 0);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let test = Arc::new(CheckedTest::new());
+        let mut forest = PackedForest::new();
+        forest.build_tree(Checked::new(0, test.clone()), |node_builder| {
+            assert_eq!(node_builder.max_depth(), None);
+            node_builder.set_max_depth(2);
+            node_builder.build_child(Checked::new(1, test.clone()), |node_builder| {
+                assert_eq!(node_builder.max_depth(), Some(2));
+                node_builder.add_child(Checked::new(2, test.clone()));
+                assert_eq!(node_builder.try_get_child_builder().unwrap().try_get_child_builder().err(), Some(DepthError { max_depth: 2 }));
+                // Adding no children never fails
+                node_builder.build_child(Checked::new(3, test.clone()), |node_builder| {
+                    node_builder.add_leaf_children(std::iter::empty());
+                });
+            });
+        });
+        assert_eq!(forest.iter_flattened().map(|val| *val.get()).collect::<Vec<_>>(), [0, 1, 2, 3]);
+
+        let source = PackedTree::new(0, |node_builder| {
+            node_builder.build_child(1, |node_builder| {
+                node_builder.add_child(2);
+            });
+            node_builder.add_child(3);
+        });
+        let mut copies = PackedForest::new();
+        copies.build_tree(0, |node_builder| {
+            node_builder.set_max_depth(3);
+            node_builder.add_subtree_copy(source.root());
+        });
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            copies.build_tree(0, |node_builder| {
+                node_builder.set_max_depth(2);
+                node_builder.add_subtree_copy(source.root());
+            });
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            copies.build_tree(0, |node_builder| {
+                node_builder.set_max_depth(0);
+                node_builder.add_leaf_children_from_slice(&[1]);
+            });
+        }));
+        assert!(result.is_err());
+        assert_eq!(copies.tot_num_nodes(), 5);
+
+        let mut limited = PackedForest::new();
+        limited.build_tree(0, |node_builder| {
+            node_builder.set_max_depth(1);
+            node_builder.build_child(1, |node_builder| {
+                // A child can't lift the limit it inherited
+                node_builder.set_max_depth(5);
+                assert_eq!(node_builder.max_depth(), Some(1));
+                assert_eq!(node_builder.try_add_leaf_children_from_slice(&[2, 3]), Err(AddNodesError::Depth(DepthError { max_depth: 1 })));
+                assert_eq!(node_builder.try_add_leaf_children_from_slice(&[]), Ok(()));
+            });
+        });
+        assert_eq!(limited.tot_num_nodes(), 2);
+
+        drop(forest);
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_merge() {
+        let test = Arc::new(CheckedTest::new());
+        let forests = vec![build_store(test.clone()), PackedForest::new(), build_store(test.clone())];
+        let num_nodes = forests[0].tot_num_nodes();
+        let forest = PackedForest::merge(forests);
+        assert_eq!(forest.tot_num_nodes(), 2 * num_nodes);
+        assert_eq!(forest.iter_trees().map(|tree| *tree.val().get()).collect::<Vec<_>>(), [2, 3, 2, 3]);
+        assert_eq!(forest.iter_flattened().map(|val| *val.get()).sum::<i32>(), 2 * 323);
+        assert_eq!(forest.iter_trees().nth(2).unwrap().num_descendants_incl_self(), forest.iter_trees().next().unwrap().num_descendants_incl_self());
+        assert!(PackedForest::<i32>::merge(Vec::new()).iter_trees().next().is_none());
+
+        drop(forest);
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let mut forest = PackedForest::new();
+        forest.build_tree(1u64, |node_builder| {
+            node_builder.build_child(2, |node_builder| {
+                node_builder.add_child(3);
+            });
+            node_builder.add_child(4);
+        });
+        forest.build_tree(5, |_| {});
+        let copy = forest.duplicate();
+        assert!(copy == forest);
+        assert_eq!(copy.capacity(), forest.tot_num_nodes());
+        assert!(PackedForest::<u64>::new().duplicate() == PackedForest::new());
+
+        let tree = PackedTree::try_from_forest(forest.trees_range(..1).to_forest()).unwrap();
+        let mut copy = tree.duplicate();
+        *copy.root_mut().val_mut() = 10;
+        assert_eq!(*tree.root().val(), 1);
+        assert_eq!(copy.iter_flattened().copied().collect::<Vec<_>>(), [10, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_iter_range() {
+        let test = Arc::new(CheckedTest::new());
+        let forest = build_store(test.clone());
+        for node in forest.iter_trees().flat_map(|tree| tree.iter_with_depth().map(|(_, node)| node)) {
+            let mut nodes = forest.iter_range(node.preorder_range(&forest)).unwrap();
+            assert_eq!(nodes.next().unwrap().raw_slice().as_ptr(), node.raw_slice().as_ptr());
+            assert!(nodes.next().is_none());
+        }
+        let num_nodes = forest.tot_num_nodes();
+        assert_eq!(forest.iter_range(0..num_nodes).unwrap().count(), 2);
+        // The last child of the first tree, and the second tree
+        assert_eq!(forest.iter_range(6..num_nodes).unwrap().map(|node| *node.val().get()).collect::<Vec<_>>(), [30, 3]);
+        assert_eq!(forest.iter_range(3..3).unwrap().count(), 0);
+        assert!(forest.iter_range(0..1).is_none());
+        assert!(forest.iter_range(5..num_nodes + 1).is_none());
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 3..2;
+        assert!(forest.iter_range(reversed).is_none());
+
+        drop(forest);
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_exact_size_fallible_building() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut forest = ExactSizePackedForest::new();
+            forest.add_single_node_tree(Checked::new(1, test.clone()));
+
+            let result: Result<(), &str> = forest.build_tree_result(Checked::new(2, test.clone()), |node_builder| {
+                node_builder.add_child(Checked::new(3, test.clone()));
+                node_builder.try_build_child(Checked::new(4, test.clone()), |node_builder| {
+                    node_builder.add_child(Checked::new(5, test.clone()));
+                    Err("error")
+                })
+            });
+            assert_eq!(result, Err("error"));
+            assert_eq!(forest.num_trees(), 1);
+
+            let result: Result<usize, &str> = forest.build_tree_result(Checked::new(2, test.clone()), |node_builder| {
+                let failed: Result<(), &str> = node_builder.try_build_child(Checked::new(3, test.clone()), |_| Err("ignored error"));
+                assert!(failed.is_err());
+                node_builder.try_build_child(Checked::new(4, test.clone()), |node_builder| {
+                    node_builder.add_child(Checked::new(5, test.clone()));
+                    Ok::<_, &str>(())
+                })?;
+                Ok(node_builder.num_children_so_far())
+            });
+            assert_eq!(result, Ok(1));
+            assert_eq!(forest.num_trees(), 2);
+            let last_tree = forest.iter_trees().last().unwrap();
+            assert_eq!(last_tree.num_children(), 1);
+            assert_eq!(last_tree.children().next().unwrap().num_children(), 1);
+        }
+        assert_eq!(test.num_undropped(), 0);
+
+        let tree = ExactSizePackedTree::try_new(0, |node_builder| {
+            node_builder.add_child(1);
+            Ok::<_, ()>(())
+        }).unwrap();
+        assert_eq!(tree.root().num_children(), 1);
+        assert!(ExactSizePackedTree::try_new(0, |_| Err(())).is_err());
+    }
+
+    #[test]
+    fn test_finish_idx() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut forest = build_store(test.clone());
+            let num_nodes = forest.tot_num_nodes();
+            let mut builder = forest.get_tree_builder();
+            let mut child_builder = builder.get_child_builder();
+            let grandchild_index = child_builder.get_child_builder().finish_idx(Checked::new(2, test.clone()));
+            let child_index = child_builder.finish_idx(Checked::new(1, test.clone()));
+            let root_index = builder.finish_idx(Checked::new(0, test.clone()));
+            assert_eq!((root_index, child_index, grandchild_index), (num_nodes, num_nodes + 1, num_nodes + 2));
+            assert_eq!(*forest[child_index].get(), 1);
+            assert_eq!(*forest[grandchild_index].get(), 2);
+
+            let mut forest = ExactSizePackedForest::new();
+            forest.add_single_node_tree(Checked::new(0, test.clone()));
+            let mut builder = forest.get_tree_builder();
+            let child_index = builder.get_child_builder().finish_idx(Checked::new(2, test.clone()));
+            assert_eq!(builder.finish_idx(Checked::new(1, test.clone())), 1);
+            assert_eq!(child_index, 2);
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_with_indices() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut forest = build_store(test.clone());
+            let num_trees = forest.iter_trees().count();
+            assert_eq!(forest.iter_trees().num_remaining_nodes(), num_trees);
+            assert_eq!(forest.iter_trees().with_indices().map(|(index, _)| index).collect::<Vec<_>>(), (0..num_trees).collect::<Vec<_>>());
+
+            let mut trees = forest.iter_trees_mut().with_indices();
+            trees.next();
+            assert_eq!((trees.next_index(), trees.len(), trees.num_nodes()), (1, num_trees - 1, num_trees));
+            for (index, mut tree) in trees {
+                *tree.val_mut() = Checked::new(index as i32, test.clone());
+            }
+            assert_eq!(*forest.iter_trees().last().unwrap().val().get(), num_trees as i32 - 1);
+
+            // The nodes that aren't drained are dropped with the iterator
+            let mut drain = forest.drain_trees().with_indices();
+            let (index, node) = drain.next().unwrap();
+            assert_eq!(index, 0);
+            let num_children = node.children.num_remaining_nodes();
+            assert_eq!(node.children.with_indices().len(), num_children);
+            assert_eq!(drain.len(), num_trees - 1);
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_replace_take_map_val() {
+        let mut tree = ExactSizePackedTree::new("a".to_string(), |node_builder| {
+            node_builder.add_child("b".to_string());
+        });
+        let mut root = tree.root_mut();
+        assert_eq!(root.replace_val("c".to_string()), "a");
+        root.map_val_in_place(|val| val + "d");
+        let mut child = root.into_children().next().unwrap();
+        assert_eq!(child.take_val(), "b");
+        assert_eq!(tree.iter_flattened().collect::<Vec<_>>(), ["cd", ""]);
+
+        // The value doesn't need to implement Default, and isn't dropped while it's moved around
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut tree = PackedTree::new(Checked::new(1, test.clone()), |_| {});
+            tree.root_mut().map_val_in_place(|val| Checked::new(*val.get() + 1, test.clone()));
+            assert_eq!(*tree.root().val().get(), 2);
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_forest_concatenation() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let forest = build_store(test.clone());
+            let num_nodes = forest.tot_num_nodes();
+            let num_trees = forest.iter_trees().count();
+            let tree = PackedTree::new(Checked::new(-1, test.clone()), |node_builder| {
+                node_builder.add_child(Checked::new(-2, test.clone()));
+            });
+
+            let mut sum = build_store(test.clone()) + tree + forest;
+            assert_eq!(sum.tot_num_nodes(), 2 * num_nodes + 2);
+            assert_eq!(sum.iter_trees().count(), 2 * num_trees + 1);
+            assert_eq!(*sum[num_nodes + 1].get(), -2);
+
+            sum += PackedForest::new();
+            sum.extend(vec![PackedTree::new(Checked::new(-3, test.clone()), |_| {})]);
+            assert_eq!(*sum.iter_trees().last().unwrap().val().get(), -3);
+
+            let collected: PackedForest<_> = sum.into_trees().collect();
+            assert_eq!(collected.tot_num_nodes(), 2 * num_nodes + 3);
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_tree_convert_error() {
+        use std::convert::TryFrom;
+
+        let mut forest = ExactSizePackedForest::new();
+        assert_eq!(ExactSizePackedTree::<i32>::try_from(forest.clone()).unwrap_err(), TreeConvertError::Empty);
+        forest.add_single_node_tree(1);
+        assert!(ExactSizePackedTree::try_from(forest.clone()).is_ok());
+        forest.add_single_node_tree(2);
+        forest.add_single_node_tree(3);
+        let err = ExactSizePackedTree::try_from(forest).unwrap_err();
+        assert_eq!(err, TreeConvertError::MultipleRoots { count: 3 });
+        assert_eq!(err.to_string(), "the forest has 3 trees, but a tree needs exactly one root");
+    }
+
+    #[test]
+    fn test_flattened_iter_types() {
+        // The iterators have names, so they can be stored in a struct
+        struct Values<'t> {
+            forest_values: FlattenedIter<'t, Checked<i32>>,
+            children_values: ChildrenValues<'t, Checked<i32>>,
+        }
+
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut forest = build_store(test.clone());
+            let num_nodes = forest.tot_num_nodes();
+            let first_tree = forest.iter_trees().next().unwrap();
+            let mut values = Values {
+                forest_values: forest.iter_flattened(),
+                children_values: first_tree.children_values(),
+            };
+            assert_eq!(values.forest_values.len(), num_nodes);
+            assert_eq!(*values.forest_values.clone().next_back().unwrap().get(), *forest.raw_data()[num_nodes - 1].val().get());
+            assert_eq!(values.children_values.clone().count(), first_tree.children().count());
+            assert!(values.forest_values.next().is_some());
+            assert_eq!(values.forest_values.len(), num_nodes - 1);
+
+            for val in forest.iter_flattened_mut().rev() {
+                *val.get_mut() += 1;
+            }
+            let mut drain = forest.drain_flattened();
+            assert_eq!(drain.len(), num_nodes);
+            assert!(drain.next_back().is_some());
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_sort_trees_by_key() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut forest = build_store(test.clone());
+            forest.add_single_node_tree(Checked::new(-1, test.clone()));
+            let num_nodes = forest.tot_num_nodes();
+            let mut sizes: Vec<usize> = forest.iter_trees().map(|tree| tree.num_descendants_incl_self()).collect();
+
+            let mut num_calls = 0;
+            forest.sort_trees_by_key(|tree| {
+                num_calls += 1;
+                tree.num_descendants_incl_self()
+            });
+            assert_eq!(num_calls, sizes.len());
+            sizes.sort();
+            assert_eq!(forest.iter_trees().map(|tree| tree.num_descendants_incl_self()).collect::<Vec<_>>(), sizes);
+            assert_eq!(forest.tot_num_nodes(), num_nodes);
+            assert_eq!(*forest.iter_trees().next().unwrap().val().get(), -1);
+
+            // The sort is stable
+            let roots: Vec<i32> = forest.iter_trees().map(|tree| *tree.val().get()).collect();
+            forest.sort_trees_by_key(|_| 0);
+            assert_eq!(forest.iter_trees().map(|tree| *tree.val().get()).collect::<Vec<_>>(), roots);
+            forest.sort_trees_by_key(|tree| std::cmp::Reverse(*tree.val().get()));
+            assert_eq!(*forest.iter_trees().last().unwrap().val().get(), -1);
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_partition() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let forest = build_store(test.clone());
+            let num_nodes = forest.tot_num_nodes();
+            let roots: Vec<i32> = forest.iter_trees().map(|tree| *tree.val().get()).collect();
+
+            let mut num_calls = 0;
+            let (small, large) = forest.partition(|tree| {
+                num_calls += 1;
+                tree.num_descendants_incl_self() <= 2
+            });
+            assert_eq!(num_calls, roots.len());
+            assert!(small.iter_trees().all(|tree| tree.num_descendants_incl_self() <= 2));
+            assert!(large.iter_trees().all(|tree| tree.num_descendants_incl_self() > 2));
+            assert_eq!(small.tot_num_nodes() + large.tot_num_nodes(), num_nodes);
+
+            // Both forests keep the original order
+            let mut small_roots = small.iter_trees().map(|tree| *tree.val().get()).peekable();
+            let mut large_roots = large.iter_trees().map(|tree| *tree.val().get()).peekable();
+            for root in roots {
+                if small_roots.peek() == Some(&root) {
+                    small_roots.next();
+                } else {
+                    assert_eq!(large_roots.next(), Some(root));
+                }
+            }
+            assert!(small_roots.next().is_none() && large_roots.next().is_none());
+
+            let (all, none) = large.partition(|_| true);
+            assert_eq!(none.tot_num_nodes(), 0);
+            assert!(all.tot_num_nodes() > 0);
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    fn test_swap_trees() {
+        let test = Arc::new(CheckedTest::new());
+        {
+            let mut forest = build_store(test.clone());
+            forest.add_single_node_tree(Checked::new(-1, test.clone()));
+            let trees_before: Vec<(i32, usize)> = forest.iter_trees().map(|tree| (*tree.val().get(), tree.num_descendants_incl_self())).collect();
+            let last = trees_before.len() - 1;
+
+            forest.swap_trees(last, 0);
+            let mut expected = trees_before.clone();
+            expected.swap(0, last);
+            assert_eq!(forest.iter_trees().map(|tree| (*tree.val().get(), tree.num_descendants_incl_self())).collect::<Vec<_>>(), expected);
+
+            forest.swap_trees(1, 1);
+            forest.swap_trees(0, last);
+            assert_eq!(forest.iter_trees().map(|tree| (*tree.val().get(), tree.num_descendants_incl_self())).collect::<Vec<_>>(), trees_before);
+        }
+        assert_eq!(test.num_undropped(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_swap_trees_out_of_bounds() {
+        let mut forest = PackedForest::new();
+        forest.add_single_node_tree(1);
+        forest.add_single_node_tree(2);
+        forest.swap_trees(0, 2);
+    }
 }
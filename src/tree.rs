@@ -11,12 +11,18 @@ use crate::*;
 /// If you want to store multiple trees in the same `Vec`, see [`PackedForest`].
 /// 
 /// See the [module-level documentation](index.html) for more information.
+///
+/// A `PackedTree<T>` is a [`MetaPackedTree`] with the [`NoMeta`] policy, which is where its methods are documented.
+pub type PackedTree<T> = MetaPackedTree<T, NoMeta>;
+
+/// A variant of [`PackedTree`] that stores extra data for every node next to its value,
+/// where the [`NodePolicy`] `P` decides what that data is. See [`MetaPackedForest`].
 #[derive(Eq, PartialEq, Hash, Clone)]
-pub struct PackedTree<T> {
-    forest: PackedForest<T>,
+pub struct MetaPackedTree<T, P: NodePolicy> {
+    forest: MetaPackedForest<T, P>,
 }
 
-impl<T> PackedTree<T> {
+impl<T, P: NodePolicy> MetaPackedTree<T, P> {
     /// Create a new `PackedTree`.
     ///
     /// The parameter `val` is the value that the root node will have.
@@ -27,10 +33,10 @@ impl<T> PackedTree<T> {
     /// For more complex use cases, see [`new_by_ret_val`](PackedTree::new_by_ret_val) and
     /// [`try_from_forest`](PackedTree::try_from_forest).
     #[inline]
-    pub fn new(root_val: T, node_builder_cb: impl FnOnce(&mut NodeBuilder<T>)) -> PackedTree<T> {
-        let mut forest = PackedForest::new();
+    pub fn new(root_val: T, node_builder_cb: impl FnOnce(&mut MetaNodeBuilder<T, P>)) -> MetaPackedTree<T, P> {
+        let mut forest = MetaPackedForest::new();
         forest.build_tree(root_val, node_builder_cb);
-        PackedTree { forest }
+        MetaPackedTree { forest }
     }
 
     /// Create a new `PackedTree`, where the root value is the return value of the given closure.
@@ -43,10 +49,23 @@ impl<T> PackedTree<T> {
     ///
     /// For more complex use cases, see [`PackedTree::try_from_forest`].
     #[inline]
-    pub fn new_by_ret_val(node_builder_cb: impl FnOnce(&mut NodeBuilder<T>) -> T) -> PackedTree<T> {
-        let mut forest = PackedForest::new();
+    pub fn new_by_ret_val(node_builder_cb: impl FnOnce(&mut MetaNodeBuilder<T, P>) -> T) -> MetaPackedTree<T, P> {
+        let mut forest = MetaPackedForest::new();
         forest.build_tree_by_ret_val(node_builder_cb);
-        PackedTree { forest }
+        MetaPackedTree { forest }
+    }
+
+    /// Create a new `PackedTree`, unless building it fails.
+    ///
+    /// Like [`new`](PackedTree::new), but `node_builder_cb` returns a [`Result`]. If it returns `Err`,
+    /// all nodes that were added so far are dropped and the error is returned.
+    ///
+    /// See also [`PackedForest::build_tree_result`] and [`NodeBuilder::try_build_child`].
+    #[inline]
+    pub fn try_new<E>(root_val: T, node_builder_cb: impl FnOnce(&mut MetaNodeBuilder<T, P>) -> Result<(), E>) -> Result<MetaPackedTree<T, P>, E> {
+        let mut forest = MetaPackedForest::new();
+        forest.build_tree_result(root_val, node_builder_cb)?;
+        Ok(MetaPackedTree { forest })
     }
 
     /// Create a new `PackedTree` from the given [`PackedForest`]. Returns `None` when the forest doesn't have exactly 1 tree.
@@ -57,14 +76,15 @@ impl<T> PackedTree<T> {
     ///     also return the return value of the closure used to construct them, unlike [`PackedForest::build_tree`].
     /// 
     /// In those cases you can construct a [`PackedForest`], and then use this method to construct the
-    /// [`PackedTree`]. Alternatively, [`std::convert::TryFrom`] is also possible.
+    /// [`PackedTree`]. Alternatively, [`std::convert::TryFrom`] is also possible, which returns a [`TreeConvertError`]
+    /// that says why the forest isn't a tree.
     #[inline(always)]
-    pub fn try_from_forest(forest: PackedForest<T>) -> Option<PackedTree<T>> {
+    pub fn try_from_forest(forest: MetaPackedForest<T, P>) -> Option<MetaPackedTree<T, P>> {
         let mut iter = forest.iter_trees();
         match iter.next() {
             Some(_) => {
                 if iter.next().is_none() {
-                    Some(PackedTree {
+                    Some(MetaPackedTree {
                         forest
                     })
                 } else {
@@ -77,13 +97,13 @@ impl<T> PackedTree<T> {
 
     /// Returns a [`NodeRef`] reference to the tree's root.
     #[inline(always)]
-    pub fn root(&self) -> NodeRef<T> {
+    pub fn root(&self) -> MetaNodeRef<'_, T, P> {
         self.forest.iter_trees().next().unwrap()
     }
 
     /// Returns a [`NodeRefMut`] mutable reference to the tree's root.
     #[inline(always)]
-    pub fn root_mut(&mut self) -> NodeRefMut<T> {
+    pub fn root_mut(&mut self) -> MetaNodeRefMut<'_, T, P> {
         self.forest.iter_trees_mut().next().unwrap()
     }
 
@@ -93,7 +113,7 @@ impl<T> PackedTree<T> {
     /// them in a depth-first search. So the index of the root is 0, the index of its first child (if any) is 1,
     /// the index of that first child's first child (if any) is 2, etc.
     #[inline(always)]
-    pub fn get(&self, index: usize) -> Option<NodeRef<T>> {
+    pub fn get(&self, index: impl Into<NodeId>) -> Option<MetaNodeRef<'_, T, P>> {
         self.forest.get(index)
     }
 
@@ -103,23 +123,31 @@ impl<T> PackedTree<T> {
     /// them in a depth-first search. So the index of the root is 0, the index of its first child (if any) is 1,
     /// the index of that first child's first child (if any) is 2, etc.
     #[inline(always)]
-    pub fn get_mut(&mut self, index: usize) -> Option<NodeRefMut<T>> {
+    pub fn get_mut(&mut self, index: impl Into<NodeId>) -> Option<MetaNodeRefMut<'_, T, P>> {
         self.forest.get_mut(index)
     }
 
     /// Get a [`NodeRef`] to the node with the given index.
     /// 
     /// Does **not** check that the given index is in bounds, and is therefore unsafe.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be smaller than [`tot_num_nodes`](Self::tot_num_nodes).
     #[inline(always)]
-    pub unsafe fn get_unchecked(&self, index: usize) -> NodeRef<T> {
+    pub unsafe fn get_unchecked(&self, index: impl Into<NodeId>) -> MetaNodeRef<'_, T, P> {
         self.forest.get_unchecked(index)
     }
 
     /// Get a [`NodeRefMut`] to the node with the given index.
     /// 
     /// Does **not** check that the given index is in bounds, and is therefore unsafe.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be smaller than [`tot_num_nodes`](Self::tot_num_nodes).
     #[inline(always)]
-    pub unsafe fn get_unchecked_mut(&mut self, index: usize) -> NodeRefMut<T> {
+    pub unsafe fn get_unchecked_mut(&mut self, index: impl Into<NodeId>) -> MetaNodeRefMut<'_, T, P> {
         self.forest.get_unchecked_mut(index)
     }
 
@@ -131,35 +159,43 @@ impl<T> PackedTree<T> {
     /// something needs to be responsible for actually owning the data until all of it
     /// is drained. That something in this case is the [`PackedTreeDrain`]
     #[inline(always)]
-    pub fn drain(self) -> PackedTreeDrain<T> {
-        PackedTreeDrain {
+    pub fn drain(self) -> MetaPackedTreeDrain<T, P> {
+        MetaPackedTreeDrain {
             forest: self.forest
         }
     }
 
     /// Iterate over all the values in all the nodes in this tree, in pre-order order.
     #[inline(always)]
-    pub fn iter_flattened<'a>(
-        &'a self,
-    ) -> std::iter::Map<std::slice::Iter<'a, NodeData<T>>, impl FnMut(&'a NodeData<T>) -> &'a T>
-    {
+    pub fn iter_flattened(&self) -> FlattenedIter<'_, T, P::Meta> {
         self.forest.iter_flattened()
     }
 
     /// Iterate over all the values in all the nodes in this tree mutably, in pre-order order.
     #[inline(always)]
-    pub fn iter_flattened_mut<'a>(
-        &'a mut self,
-    ) -> std::iter::Map<
-        std::slice::IterMut<'a, NodeData<T>>,
-        impl FnMut(&'a mut NodeData<T>) -> &'a mut T,
-    > {
+    pub fn iter_flattened_mut(&mut self) -> FlattenedIterMut<'_, T, P::Meta> {
         self.forest.iter_flattened_mut()
     }
 
+    /// Iterate over all the values in all the nodes in this tree, in pre-order order, together with the index of each node.
+    ///
+    /// See [`PackedForest::iter_flattened_enumerated`].
+    #[inline(always)]
+    pub fn iter_flattened_enumerated(&self) -> std::iter::Enumerate<FlattenedIter<'_, T, P::Meta>> {
+        self.forest.iter_flattened_enumerated()
+    }
+
+    /// Iterate over all the values in all the nodes in this tree mutably, in pre-order order, together with the index of each node.
+    ///
+    /// See [`PackedForest::iter_flattened_enumerated_mut`].
+    #[inline(always)]
+    pub fn iter_flattened_enumerated_mut(&mut self) -> std::iter::Enumerate<FlattenedIterMut<'_, T, P::Meta>> {
+        self.forest.iter_flattened_enumerated_mut()
+    }
+
     /// Read-only view of the raw data.
     #[inline(always)]
-    pub fn raw_data(&self) -> &Vec<NodeData<T>> {
+    pub fn raw_data(&self) -> &Vec<NodeData<T, P::Meta>> {
         self.forest.raw_data()
     }
 
@@ -168,43 +204,195 @@ impl<T> PackedTree<T> {
     pub fn tot_num_nodes(&self) -> usize {
         self.forest.tot_num_nodes()
     }
+
 }
 
-impl<T> TryFrom<PackedForest<T>> for PackedTree<T> {
-    type Error = ();
+impl<T> PackedTree<T> {
+    /// Swap the subtrees that have the nodes with indices `a_index` and `b_index` as their roots.
+    ///
+    /// See [`PackedForest::swap_subtrees`].
+    #[inline]
+    pub fn swap_subtrees(&mut self, a_index: usize, b_index: usize) -> Result<(), SwapError> {
+        self.forest.swap_subtrees(a_index, b_index)
+    }
+
+    /// Remove the node with the given index and all of its descendants from the tree, and return them as a new [`PackedTree`].
+    /// Returns `None` if the index is out of bounds, or if it is 0, since the root can't be removed.
+    ///
+    /// See [`PackedForest::detach_subtree`].
+    #[inline]
+    pub fn detach_subtree(&mut self, index: usize) -> Option<PackedTree<T>> {
+        if index == 0 {
+            None
+        } else {
+            self.forest.detach_subtree(index)
+        }
+    }
+
+    /// Returns an iterator over the subtrees whose nodes have the pre-order indices in the given range,
+    /// or `None` if the range doesn't consist of whole subtrees or is out of bounds. See [`PackedForest::iter_range`].
     #[inline(always)]
-    fn try_from(forest: PackedForest<T>) -> Result<Self, Self::Error> {
-        match PackedTree::try_from_forest(forest) {
-            Some(tree) => Ok(tree),
-            None => Err(())
+    pub fn iter_range(&self, range: std::ops::Range<usize>) -> Option<NodeIter<'_, T>> {
+        self.forest.iter_range(range)
+    }
+
+    /// Returns a copy of this tree, copying all nodes with a single memory copy. See [`PackedForest::duplicate`].
+    #[inline]
+    pub fn duplicate(&self) -> PackedTree<T> where T: Copy {
+        PackedTree {
+            forest: self.forest.duplicate(),
+        }
+    }
+
+    /// Returns a new forest with a copy of every maximal subtree whose root matches `predicate`, each as its own tree.
+    ///
+    /// See [`PackedForest::collect_subtrees`].
+    #[inline]
+    pub fn collect_subtrees<'t>(&'t self, predicate: impl FnMut(NodeRef<'t, T>) -> bool) -> PackedForest<T> where T: Clone {
+        self.forest.collect_subtrees(predicate)
+    }
+
+    /// Returns the depth of every node in the tree, indexed by pre-order index. The root has depth 0.
+    ///
+    /// See [`PackedForest::depths`].
+    #[inline]
+    pub fn depths(&self) -> Vec<usize> {
+        self.forest.depths()
+    }
+
+    /// Returns the depth of the node with the given index (0 for the root), or `None` if the index is out of bounds.
+    ///
+    /// See [`PackedForest::depth_of`].
+    #[inline]
+    pub fn depth_of(&self, index: impl Into<NodeId>) -> Option<usize> {
+        self.forest.depth_of(index)
+    }
+
+    /// Build a [`DepthIndex`] for this tree, to look up the depth of nodes in O(1) time.
+    #[inline]
+    pub fn depth_index(&self) -> DepthIndex {
+        DepthIndex::new(&self.forest)
+    }
+
+    /// Returns the index of the parent of every node in the tree, indexed by pre-order index.
+    /// The entry of the root is `None`.
+    ///
+    /// See [`PackedForest::parents`].
+    #[inline]
+    pub fn parents(&self) -> Vec<Option<usize>> {
+        self.forest.parents()
+    }
+
+    /// Returns a copy of this tree where the children of every node are sorted by the key that `key_fn` returns for their values.
+    ///
+    /// See [`PackedForest::sorted_by_key`].
+    #[inline]
+    pub fn sorted_by_key<K: Ord>(&self, key_fn: impl FnMut(&T) -> K) -> PackedTree<T> where T: Clone {
+        PackedTree::try_from_forest(self.forest.sorted_by_key(key_fn)).unwrap()
+    }
+
+    /// Returns a copy of this tree where the children of every node are reordered by `f`.
+    ///
+    /// See [`PackedForest::map_children_order`].
+    #[inline]
+    pub fn map_children_order<'t>(
+        &'t self,
+        f: impl FnMut(NodeRef<'t, T>, &[NodeRef<'t, T>], &mut [usize]),
+    ) -> PackedTree<T> where T: Clone {
+        PackedTree::try_from_forest(self.forest.map_children_order(f)).unwrap()
+    }
+
+    /// Returns a copy of this tree where the order of the children of every node is reversed.
+    ///
+    /// See [`PackedForest::reverse_children`].
+    #[inline]
+    pub fn reverse_children(&self) -> PackedTree<T> where T: Clone {
+        PackedTree::try_from_forest(self.forest.reverse_children()).unwrap()
+    }
+
+    /// Returns a copy of this tree where the children of every node are rotated to the left by `n` places.
+    ///
+    /// See [`PackedForest::rotate_children`].
+    #[inline]
+    pub fn rotate_children(&self, n: usize) -> PackedTree<T> where T: Clone {
+        PackedTree::try_from_forest(self.forest.rotate_children(n)).unwrap()
+    }
+
+    /// Returns the first node, in pre-order, for which `predicate` returns `true`, or `None` if there is no such node.
+    #[inline]
+    pub fn find<'t>(&'t self, predicate: impl FnMut(NodeRef<'t, T>) -> bool) -> Option<NodeRef<'t, T>> {
+        self.forest.find(predicate)
+    }
+
+    /// Returns the index of the first node, in pre-order, for which `predicate` returns `true`,
+    /// or `None` if there is no such node.
+    #[inline]
+    pub fn position<'t>(&'t self, predicate: impl FnMut(NodeRef<'t, T>) -> bool) -> Option<usize> {
+        self.forest.position(predicate)
+    }
+
+    /// Get a [`NodeRef`] to the node that is reached from the root by following the given path of child indices,
+    /// or `None` if there is no such node. An empty path refers to the root.
+    ///
+    /// See [`NodeRef::descendant_at_path`].
+    #[inline]
+    pub fn get_path(&self, path: &[usize]) -> Option<NodeRef<'_, T>> {
+        self.root().descendant_at_path(path)
+    }
+}
+
+/// Like [`PackedTree::try_from_forest`], but the error says why the forest isn't a tree.
+///
+/// # Example
+/// ```
+/// use packed_tree::{PackedForest, PackedTree, TreeConvertError};
+/// use std::convert::TryFrom;
+///
+/// let mut forest = PackedForest::new();
+/// forest.add_single_node_tree(1);
+/// forest.add_single_node_tree(2);
+/// assert_eq!(PackedTree::try_from(forest).unwrap_err(), TreeConvertError::MultipleRoots { count: 2 });
+/// assert_eq!(PackedTree::<i32>::try_from(PackedForest::new()).unwrap_err(), TreeConvertError::Empty);
+/// ```
+impl<T, P: NodePolicy> TryFrom<MetaPackedForest<T, P>> for MetaPackedTree<T, P> {
+    type Error = TreeConvertError;
+    #[inline]
+    fn try_from(forest: MetaPackedForest<T, P>) -> Result<Self, Self::Error> {
+        match forest.num_trees() {
+            0 => Err(TreeConvertError::Empty),
+            1 => Ok(MetaPackedTree { forest }),
+            count => Err(TreeConvertError::MultipleRoots { count }),
         }
     }
 }
 
-impl<T> AsRef<PackedForest<T>> for PackedTree<T> {
+impl<T, P: NodePolicy> AsRef<MetaPackedForest<T, P>> for MetaPackedTree<T, P> {
     #[inline(always)]
-    fn as_ref(&self) -> &PackedForest<T> {
+    fn as_ref(&self) -> &MetaPackedForest<T, P> {
         &self.forest
     }
 }
 
-impl<T> From<PackedTree<T>> for PackedForest<T> {
+impl<T, P: NodePolicy> From<MetaPackedTree<T, P>> for MetaPackedForest<T, P> {
     #[inline(always)]
-    fn from(tree: PackedTree<T>) -> Self {
+    fn from(tree: MetaPackedTree<T, P>) -> Self {
         tree.forest
     }
 }
 
 /// A [`PackedTree`] that is being drained. See [`PackedTree::drain`].
-pub struct PackedTreeDrain<T> {
-    forest: PackedForest<T>,
+pub type PackedTreeDrain<T> = MetaPackedTreeDrain<T, NoMeta>;
+
+/// A [`MetaPackedTree`] that is being drained. See [`MetaPackedTree::drain`].
+pub struct MetaPackedTreeDrain<T, P: NodePolicy> {
+    forest: MetaPackedForest<T, P>,
 }
 
-impl<T> PackedTreeDrain<T> {
-    /// Returns a [`NodeDrain`] that contains the value of the root node and a draining iterator
+impl<T, P: NodePolicy> MetaPackedTreeDrain<T, P> {
+    /// Returns a [`MetaNodeDrain`] that contains the value of the root node and a draining iterator
     /// of its children, or `None` if this tree has already been drained.
     #[inline(always)]
-    pub fn drain_root(&mut self) -> Option<NodeDrain<T>> {
+    pub fn drain_root(&mut self) -> Option<MetaNodeDrain<'_, T, P>> {
         self.forest.drain_trees().next()
     }
 
@@ -216,9 +404,7 @@ impl<T> PackedTreeDrain<T> {
     /// **WARNING:** Leaking the returned iterator without iterating over all of its values will leak the
     /// values that were not iterated over. They will still be removed from the tree though.
     #[inline(always)]
-    pub fn drain_flattened(
-        &mut self,
-    ) -> std::iter::Map<std::vec::Drain<NodeData<T>>, impl FnMut(NodeData<T>) -> T> {
+    pub fn drain_flattened(&mut self) -> FlattenedDrain<'_, T, P::Meta> {
         self.forest.drain_flattened()
     }
 }
@@ -0,0 +1,181 @@
+// This file contains functions that build trees by repeatedly expanding seeds into nodes,
+// which is the opposite of the builder pattern: the tree is described by a rule instead of by code that builds it.
+
+use crate::*;
+
+fn add_unfolded_children<S, T>(seeds: Vec<S>, f: &mut impl FnMut(&S) -> (T, Vec<S>), node_builder: &mut NodeBuilder<T>) {
+    for seed in seeds {
+        let (val, child_seeds) = f(&seed);
+        node_builder.build_child(val, |node_builder| add_unfolded_children(child_seeds, f, node_builder));
+    }
+}
+
+impl<T> PackedForest<T> {
+    /// Builds a tree by expanding `seed`, and adds it to this forest.
+    ///
+    /// The function `f` is called with a seed, and returns the value of the node for that seed and the seeds of its children,
+    /// which are expanded in the same way. The nodes are expanded in pre-order.
+    ///
+    /// See [`PackedTree::unfold`].
+    #[inline]
+    pub fn add_unfolded_tree<S>(&mut self, seed: S, mut f: impl FnMut(&S) -> (T, Vec<S>)) {
+        let (val, child_seeds) = f(&seed);
+        self.build_tree(val, |node_builder| add_unfolded_children(child_seeds, &mut f, node_builder));
+    }
+
+    /// Create a new [`PackedForest`] with a tree for every seed in `seeds`, where each tree is built by expanding its seed with `f`.
+    ///
+    /// See [`PackedTree::unfold`].
+    pub fn unfold<S>(seeds: impl IntoIterator<Item = S>, mut f: impl FnMut(&S) -> (T, Vec<S>)) -> PackedForest<T> {
+        let mut forest = PackedForest::new();
+        for seed in seeds {
+            forest.add_unfolded_tree(seed, &mut f);
+        }
+        forest
+    }
+
+    /// Like [`add_unfolded_tree`](PackedForest::add_unfolded_tree), but uses an explicit stack instead of recursion.
+    ///
+    /// See [`PackedTree::unfold_iterative`].
+    pub fn add_unfolded_tree_iterative<S>(&mut self, seed: S, mut f: impl FnMut(&S) -> (T, Vec<S>)) {
+        // If `f` panics, the partially built tree is removed again.
+        let guard = TruncateGuard { len: self.tot_num_nodes(), forest: self };
+        let (val, child_seeds) = f(&seed);
+        let root_index = guard.forest.push_open_node(val);
+        let mut stack = vec![(root_index, child_seeds.into_iter())];
+        while let Some((index, child_seeds)) = stack.last_mut() {
+            match child_seeds.next() {
+                Some(seed) => {
+                    let (val, child_seeds) = f(&seed);
+                    let child_index = guard.forest.push_open_node(val);
+                    stack.push((child_index, child_seeds.into_iter()));
+                }
+                None => {
+                    guard.forest.close_node(*index);
+                    stack.pop();
+                }
+            }
+        }
+        std::mem::forget(guard);
+    }
+
+    /// Like [`unfold`](PackedForest::unfold), but uses an explicit stack instead of recursion.
+    ///
+    /// See [`PackedTree::unfold_iterative`].
+    pub fn unfold_iterative<S>(seeds: impl IntoIterator<Item = S>, mut f: impl FnMut(&S) -> (T, Vec<S>)) -> PackedForest<T> {
+        let mut forest = PackedForest::new();
+        for seed in seeds {
+            forest.add_unfolded_tree_iterative(seed, &mut f);
+        }
+        forest
+    }
+}
+
+// Removes the nodes that were added to a forest after it was created, unless it is forgotten.
+struct TruncateGuard<'a, T> {
+    forest: &'a mut PackedForest<T>,
+    len: usize,
+}
+
+impl<'a, T> Drop for TruncateGuard<'a, T> {
+    fn drop(&mut self) {
+        self.forest.truncate_trees(self.len);
+    }
+}
+
+impl<T> PackedTree<T> {
+    /// Create a new [`PackedTree`] by expanding `seed`.
+    ///
+    /// The function `f` is called with a seed, and returns the value of the node for that seed and the seeds of its children,
+    /// which are expanded in the same way, until there are no more seeds. The nodes are expanded in pre-order.
+    ///
+    /// This is often more natural than the builder pattern of [`new`](PackedTree::new) for trees that are generated by rules.
+    ///
+    /// Like the builder pattern, this recurses once for every level of the tree, so very deep trees can overflow the stack.
+    /// For those, use [`unfold_iterative`](PackedTree::unfold_iterative).
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// // A number n > 1 has the children n / 2 and n / 3
+    /// let tree = PackedTree::unfold(6u32, |n| {
+    ///     let children = if *n > 1 { vec![n / 2, n / 3] } else { vec![] };
+    ///     (*n, children)
+    /// });
+    /// assert_eq!(tree.iter_flattened().copied().collect::<Vec<_>>(), [6, 3, 1, 1, 2, 1, 0]);
+    /// ```
+    #[inline]
+    pub fn unfold<S>(seed: S, f: impl FnMut(&S) -> (T, Vec<S>)) -> PackedTree<T> {
+        let mut forest = PackedForest::new();
+        forest.add_unfolded_tree(seed, f);
+        PackedTree::try_from_forest(forest).unwrap()
+    }
+
+    /// Like [`unfold`](PackedTree::unfold), but uses an explicit stack instead of recursion, so it works for trees of any depth,
+    /// like a linked list with millions of nodes. The nodes are expanded in the same order.
+    ///
+    /// If `f` panics, no nodes are added.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedTree;
+    ///
+    /// // A path of a million nodes, which is too deep to build recursively
+    /// let tree = PackedTree::unfold_iterative(0u32, |n| (*n, if *n < 999_999 { vec![n + 1] } else { vec![] }));
+    /// assert_eq!(tree.tot_num_nodes(), 1_000_000);
+    /// assert_eq!(tree.iter_flattened().last(), Some(&999_999));
+    /// ```
+    #[inline]
+    pub fn unfold_iterative<S>(seed: S, f: impl FnMut(&S) -> (T, Vec<S>)) -> PackedTree<T> {
+        let mut forest = PackedForest::new();
+        forest.add_unfolded_tree_iterative(seed, f);
+        PackedTree::try_from_forest(forest).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unfold() {
+        // A complete binary tree of depth 2, with the path to each node as value
+        let tree = PackedTree::unfold(String::new(), |path| {
+            let children = if path.len() < 2 { vec![format!("{}L", path), format!("{}R", path)] } else { vec![] };
+            (path.clone(), children)
+        });
+        assert_eq!(tree.iter_flattened().map(|val| val.as_str()).collect::<Vec<_>>(), ["", "L", "LL", "LR", "R", "RL", "RR"]);
+
+        let mut calls = 0;
+        let forest = PackedForest::unfold(vec![2, 0, 1], |n| {
+            calls += 1;
+            (*n, (0..*n).collect())
+        });
+        assert_eq!(calls, forest.tot_num_nodes());
+        assert_eq!(forest.iter_trees().count(), 3);
+        assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [2, 0, 1, 0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_unfold_iterative() {
+        let expand = |n: &u32| (*n, (0..*n).collect());
+        assert_eq!(PackedForest::unfold_iterative(vec![3, 0, 2], expand), PackedForest::unfold(vec![3, 0, 2], expand));
+
+        let depth = 1_000_000;
+        let tree = PackedTree::unfold_iterative(0, |n| (*n, if *n + 1 < depth { vec![n + 1] } else { vec![] }));
+        assert_eq!(tree.tot_num_nodes(), depth);
+        assert_eq!(tree.raw_data()[depth / 2].subtree_size().get(), depth / 2);
+
+        // A panic removes the partially built tree
+        let mut forest = PackedForest::unfold_iterative(vec![1], expand);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            forest.add_unfolded_tree_iterative(3, |n| {
+                assert!(*n != 1);
+                (*n, (0..*n).collect())
+            });
+        }));
+        assert!(result.is_err());
+        assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [1, 0]);
+    }
+}
@@ -0,0 +1,151 @@
+// This file contains SubforestView, a read-only view of a contiguous range of the trees in a PackedForest.
+
+use crate::*;
+
+use std::ops::{Bound, RangeBounds};
+
+/// A read-only view of a contiguous range of the trees in a [`PackedForest`], see [`PackedForest::trees_range`].
+///
+/// Since the nodes of those trees are stored contiguously, the view is just a slice of the nodes of the forest,
+/// and it can be used like a (read-only) [`PackedForest`] that only contains those trees.
+/// When the `serde` feature is enabled, it is serialized in the same way as a [`PackedForest`] with those trees.
+pub struct SubforestView<'a, T> {
+    trees: NodeIter<'a, T>,
+}
+
+// Not using #[derive(Copy)] because it adds the T:Copy bound, which is unnecessary
+impl<'a, T> Copy for SubforestView<'a, T> {}
+
+// Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
+impl<'a, T> Clone for SubforestView<'a, T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> SubforestView<'a, T> {
+    /// Returns an iterator over the trees in this view.
+    #[inline(always)]
+    pub fn iter_trees(&self) -> NodeIter<'a, T> {
+        self.trees
+    }
+
+    /// Returns the number of trees in this view. This takes O(n) time, where n is the number of trees.
+    #[inline]
+    pub fn num_trees(&self) -> usize {
+        self.trees.count()
+    }
+
+    /// Iterate over all the values in all the nodes of all the trees in this view, in pre-order order.
+    #[inline(always)]
+    pub fn iter_flattened(&self) -> FlattenedIter<'a, T> {
+        FlattenedIter::new(self.raw_data())
+    }
+
+    /// Returns a read-only view over the raw data of the nodes in this view. See [`PackedForest::raw_data`].
+    #[inline(always)]
+    pub fn raw_data(&self) -> &'a [NodeData<T>] {
+        self.trees.raw_slice()
+    }
+
+    /// Returns how many nodes are in all the trees in this view in O(1) time.
+    #[inline(always)]
+    pub fn tot_num_nodes(&self) -> usize {
+        self.trees.num_remaining_nodes_incl_descendants()
+    }
+
+    /// Returns a [`PackedForest`] with copies of the trees in this view.
+    pub fn to_forest(&self) -> PackedForest<T> where T: Clone {
+        let mut forest = PackedForest::with_capacity(self.tot_num_nodes());
+        for tree in self.iter_trees() {
+            forest.build_tree(tree.val().clone(), |node_builder| {
+                for child in tree.children() {
+                    node_builder.add_cloned_subtree(child);
+                }
+            });
+        }
+        forest
+    }
+}
+
+impl<T> PackedForest<T> {
+    /// Returns a read-only view of the trees in this forest with indices in the given range,
+    /// where the first tree has index 0, the second tree has index 1, etc. (as in [`iter_trees`](PackedForest::iter_trees)).
+    ///
+    /// This takes O(n) time, where n is the end of the range.
+    ///
+    /// # Panics
+    /// Panics if the start of the range is greater than its end, or if the end is greater than the number of trees.
+    ///
+    /// # Example
+    /// ```
+    /// use packed_tree::PackedForest;
+    ///
+    /// let mut forest = PackedForest::new();
+    /// for i in 0..4 {
+    ///     forest.build_tree(i * 10, |node_builder| {
+    ///         node_builder.add_child(i * 10 + 1);
+    ///     });
+    /// }
+    ///
+    /// let view = forest.trees_range(1..3);
+    /// assert_eq!(view.num_trees(), 2);
+    /// assert_eq!(view.tot_num_nodes(), 4);
+    /// assert_eq!(view.iter_flattened().copied().collect::<Vec<_>>(), [10, 11, 20, 21]);
+    /// ```
+    pub fn trees_range(&self, range: impl RangeBounds<usize>) -> SubforestView<'_, T> {
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let (before, rest) = self.iter_trees().split_at(start);
+        assert_eq!(before.count(), start, "range start {} is out of bounds", start);
+        let end = match range.end_bound() {
+            Bound::Included(end) => Some(end + 1),
+            Bound::Excluded(end) => Some(*end),
+            Bound::Unbounded => None,
+        };
+        let trees = match end {
+            Some(end) => {
+                assert!(start <= end, "range starts at {} but ends at {}", start, end);
+                let (trees, _) = rest.split_at(end - start);
+                assert_eq!(trees.count(), end - start, "range end {} is out of bounds", end);
+                trees
+            }
+            None => rest,
+        };
+        SubforestView { trees }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trees_range() {
+        let mut forest = PackedForest::new();
+        for i in 0..5 {
+            forest.build_tree(i, |node_builder| {
+                node_builder.add_leaf_children(0..i);
+            });
+        }
+
+        let view = forest.trees_range(1..=2);
+        assert_eq!(view.iter_trees().map(|tree| *tree.val()).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(view.tot_num_nodes(), 5);
+        assert_eq!(view.raw_data(), &forest.raw_data()[1..6]);
+        let copy = view.to_forest();
+        assert_eq!(copy.iter_flattened().copied().collect::<Vec<_>>(), [1, 0, 2, 0, 1]);
+
+        assert_eq!(forest.trees_range(..).tot_num_nodes(), forest.tot_num_nodes());
+        assert_eq!(forest.trees_range(3..).num_trees(), 2);
+        assert_eq!(forest.trees_range(..0).num_trees(), 0);
+        assert_eq!(forest.trees_range(5..5).tot_num_nodes(), 0);
+
+        assert!(std::panic::catch_unwind(|| forest.trees_range(2..6)).is_err());
+        assert!(std::panic::catch_unwind(|| forest.trees_range(6..)).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+// This file contains TreeWriter, a push-style API for building a forest with start_node/end_node calls,
+// like the writers of XML libraries, that checks that the calls are balanced when it is finished.
+
+use crate::*;
+
+/// Writes a [`PackedForest`] node by node: [`start_node`](TreeWriter::start_node) opens a node,
+/// [`end_node`](TreeWriter::end_node) closes the node that was opened last, and [`leaf`](TreeWriter::leaf)
+/// writes a node without children. Every node becomes a child of the innermost node that is still open,
+/// or a new tree if no node is open.
+///
+/// This is the interface of XML writers and of SAX-style event handlers, so code that emits trees that way can
+/// drive it directly. It is a thin wrapper around a [`TreeBuilderStack`], but unlike
+/// [`TreeBuilderStack::pop`], which reports a missing node right away,
+/// a [`TreeWriter`] only checks that every started node was ended exactly once when it is
+/// [`finish`](TreeWriter::finish)ed, so the calls that write the nodes don't have to handle errors.
+///
+/// # Example
+/// ```
+/// use packed_tree::{BalanceError, TreeWriter};
+///
+/// let mut writer = TreeWriter::new();
+/// writer.start_node("html");
+/// writer.start_node("body");
+/// writer.leaf("h1");
+/// writer.leaf("p");
+/// writer.end_node();
+/// writer.end_node();
+/// let forest = writer.finish().unwrap();
+/// assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), ["html", "body", "h1", "p"]);
+/// assert_eq!(forest.depths(), [0, 1, 2, 2]);
+///
+/// let mut writer = TreeWriter::new();
+/// writer.start_node("html");
+/// writer.leaf("body");
+/// assert_eq!(writer.finish(), Err(BalanceError::UnclosedNodes { count: 1 }));
+/// ```
+pub struct TreeWriter<T> {
+    stack: TreeBuilderStack<T>,
+    num_nodes: usize,
+    // The first unmatched call to end_node, which is reported by finish.
+    error: Option<BalanceError>,
+}
+
+// Not using #[derive(Default)] because it adds the T:Default bound, which is unnecessary
+impl<T> Default for TreeWriter<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        TreeWriter::new()
+    }
+}
+
+impl<T> TreeWriter<T> {
+    /// Create a new [`TreeWriter`] with an empty forest.
+    #[inline]
+    pub fn new() -> TreeWriter<T> {
+        TreeWriter::from_forest(PackedForest::new())
+    }
+
+    /// Create a new [`TreeWriter`] that writes trees to the end of the given forest.
+    #[inline]
+    pub fn from_forest(forest: PackedForest<T>) -> TreeWriter<T> {
+        TreeWriter {
+            num_nodes: forest.tot_num_nodes(),
+            stack: TreeBuilderStack::from_forest(forest),
+            error: None,
+        }
+    }
+
+    /// Opens a node with the given value. The nodes that are written until it is closed with
+    /// [`end_node`](TreeWriter::end_node) become its descendants.
+    #[inline]
+    pub fn start_node(&mut self, val: T) {
+        self.stack.push(val);
+        self.num_nodes += 1;
+    }
+
+    /// Closes the node that was opened last.
+    ///
+    /// If no node is open, nothing happens, and [`finish`](TreeWriter::finish) returns [`BalanceError::UnmatchedEnd`].
+    #[inline]
+    pub fn end_node(&mut self) {
+        if self.stack.pop().is_none() && self.error.is_none() {
+            self.error = Some(BalanceError::UnmatchedEnd { num_nodes: self.num_nodes });
+        }
+    }
+
+    /// Writes a node with the given value and no children.
+    #[inline]
+    pub fn leaf(&mut self, val: T) {
+        self.stack.leaf(val);
+        self.num_nodes += 1;
+    }
+
+    /// Returns the number of nodes that are open, i.e. the depth at which the next node will be written.
+    #[inline(always)]
+    pub fn depth(&self) -> usize {
+        self.stack.depth()
+    }
+
+    /// Returns the number of nodes in the forest, including the nodes that are still open.
+    #[inline(always)]
+    pub fn num_nodes(&self) -> usize {
+        self.num_nodes
+    }
+
+    /// Returns the forest, if every node that was started has been ended exactly once.
+    ///
+    /// Otherwise returns a [`BalanceError`]. If [`end_node`](TreeWriter::end_node) was called while no node was open,
+    /// that is reported, even if nodes are also still open.
+    pub fn finish(self) -> Result<PackedForest<T>, BalanceError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.stack.try_finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_writer() {
+        let mut forest = PackedForest::new();
+        forest.add_single_node_tree(0);
+        let mut writer = TreeWriter::from_forest(forest);
+        writer.start_node(1);
+        writer.leaf(2);
+        writer.start_node(3);
+        writer.leaf(4);
+        assert_eq!(writer.depth(), 2);
+        assert_eq!(writer.num_nodes(), 5);
+        writer.end_node();
+        writer.end_node();
+        writer.leaf(5);
+        let forest = writer.finish().unwrap();
+        assert_eq!(forest.iter_flattened().copied().collect::<Vec<_>>(), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(forest.depths(), [0, 0, 1, 1, 2, 0]);
+        assert!(TreeWriter::<i32>::new().finish().unwrap() == PackedForest::new());
+
+        // Unmatched ends are reported, even if they are followed by more nodes
+        let mut writer = TreeWriter::new();
+        writer.leaf(1);
+        writer.end_node();
+        writer.start_node(2);
+        writer.end_node();
+        writer.end_node();
+        assert_eq!(writer.finish(), Err(BalanceError::UnmatchedEnd { num_nodes: 1 }));
+
+        let mut writer = TreeWriter::new();
+        writer.start_node(1);
+        writer.start_node(2);
+        writer.end_node();
+        writer.start_node(3);
+        assert_eq!(writer.finish(), Err(BalanceError::UnclosedNodes { count: 2 }));
+    }
+}
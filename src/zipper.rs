@@ -0,0 +1,211 @@
+// This file contains TreeZipper, a cursor into a PackedTree that moves by returning new cursors
+// instead of mutating itself, in the style of functional zippers.
+
+use crate::*;
+
+/// An immutable cursor into a [`PackedTree`] that points at a single node, and that can move to its parent,
+/// its children and its siblings. Moving returns a new [`TreeZipper`] (or `None` if there is no such node),
+/// and leaves the original one unchanged, so it can still be used to go back.
+///
+/// A [`TreeZipper`] stores the pre-order index of its node and of all its ancestors, so going up or to the right
+/// takes O(1) time. Going down to the `i`th child or to the left takes O(i) time (or O(number of siblings) time),
+/// since the children of a node have to be skipped one by one.
+///
+/// # Example
+/// ```
+/// use packed_tree::PackedTree;
+///
+/// let tree = PackedTree::new("root", |node_builder| {
+///     node_builder.build_child("a", |node_builder| {
+///         node_builder.add_child("a1");
+///     });
+///     node_builder.add_child("b");
+/// });
+///
+/// let root = tree.zipper();
+/// let a1 = root.down(0).and_then(|a| a.down(0)).unwrap();
+/// assert_eq!(*a1.val(), "a1");
+/// assert_eq!(a1.depth(), 2);
+///
+/// let b = a1.up().and_then(|a| a.right()).unwrap();
+/// assert_eq!(*b.val(), "b");
+/// assert!(b.right().is_none());
+/// assert_eq!(*b.left().unwrap().val(), "a");
+///
+/// // Moving doesn't change the original zipper
+/// assert_eq!(*a1.val(), "a1");
+/// assert_eq!(*root.val(), "root");
+/// ```
+pub struct TreeZipper<'t, T> {
+    tree: &'t PackedTree<T>,
+    // The pre-order indices of the ancestors of the current node, from the root down to its parent.
+    ancestors: Vec<usize>,
+    index: usize,
+}
+
+// Not using #[derive(Clone)] because it adds the T:Clone bound, which is unnecessary
+impl<'t, T> Clone for TreeZipper<'t, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        TreeZipper {
+            tree: self.tree,
+            ancestors: self.ancestors.clone(),
+            index: self.index,
+        }
+    }
+}
+
+impl<'t, T> TreeZipper<'t, T> {
+    /// Create a new [`TreeZipper`] that points at the root of the given tree.
+    #[inline]
+    pub fn new(tree: &'t PackedTree<T>) -> TreeZipper<'t, T> {
+        TreeZipper {
+            tree,
+            ancestors: Vec::new(),
+            index: 0,
+        }
+    }
+
+    // Returns a zipper with the same ancestors that points at the node with the given index.
+    #[inline]
+    fn with_index(&self, index: usize) -> TreeZipper<'t, T> {
+        TreeZipper {
+            tree: self.tree,
+            ancestors: self.ancestors.clone(),
+            index,
+        }
+    }
+
+    // Returns the index one past the last descendant of the node with the given index.
+    #[inline(always)]
+    fn subtree_end(&self, index: usize) -> usize {
+        index + self.tree.raw_data()[index].subtree_size().get()
+    }
+
+    /// Returns the node that this zipper points at.
+    #[inline]
+    pub fn node(&self) -> NodeRef<'t, T> {
+        self.tree.get(self.index).unwrap()
+    }
+
+    /// Returns the value of the node that this zipper points at.
+    #[inline(always)]
+    pub fn val(&self) -> &'t T {
+        self.tree.raw_data()[self.index].val()
+    }
+
+    /// Returns the pre-order index of the node that this zipper points at, i.e. its index in [`PackedTree::raw_data`].
+    #[inline(always)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the depth of the node that this zipper points at. The root has depth 0.
+    #[inline(always)]
+    pub fn depth(&self) -> usize {
+        self.ancestors.len()
+    }
+
+    /// Returns a zipper that points at the `i`th child (starting from 0) of the current node,
+    /// or `None` if it has no such child.
+    pub fn down(&self, i: usize) -> Option<TreeZipper<'t, T>> {
+        let end = self.subtree_end(self.index);
+        let mut child = self.index + 1;
+        for _ in 0..i {
+            if child >= end {
+                return None;
+            }
+            child = self.subtree_end(child);
+        }
+        if child >= end {
+            return None;
+        }
+        let mut ancestors = self.ancestors.clone();
+        ancestors.push(self.index);
+        Some(TreeZipper { tree: self.tree, ancestors, index: child })
+    }
+
+    /// Returns a zipper that points at the parent of the current node, or `None` if it is the root.
+    #[inline]
+    pub fn up(&self) -> Option<TreeZipper<'t, T>> {
+        let (&parent, ancestors) = self.ancestors.split_last()?;
+        Some(TreeZipper { tree: self.tree, ancestors: ancestors.to_vec(), index: parent })
+    }
+
+    /// Returns a zipper that points at the previous sibling of the current node, or `None` if it is the first child
+    /// of its parent or the root.
+    pub fn left(&self) -> Option<TreeZipper<'t, T>> {
+        let parent = *self.ancestors.last()?;
+        let mut sibling = parent + 1;
+        if sibling == self.index {
+            return None;
+        }
+        loop {
+            let next = self.subtree_end(sibling);
+            if next == self.index {
+                return Some(self.with_index(sibling));
+            }
+            sibling = next;
+        }
+    }
+
+    /// Returns a zipper that points at the next sibling of the current node, or `None` if it is the last child
+    /// of its parent or the root.
+    #[inline]
+    pub fn right(&self) -> Option<TreeZipper<'t, T>> {
+        let parent = *self.ancestors.last()?;
+        let next = self.subtree_end(self.index);
+        if next < self.subtree_end(parent) {
+            Some(self.with_index(next))
+        } else {
+            None
+        }
+    }
+
+    /// Returns a zipper that points at the root of the tree.
+    #[inline]
+    pub fn root(&self) -> TreeZipper<'t, T> {
+        TreeZipper {
+            tree: self.tree,
+            ancestors: Vec::new(),
+            index: 0,
+        }
+    }
+}
+
+impl<T> PackedTree<T> {
+    /// Returns a [`TreeZipper`] that points at the root of this tree.
+    #[inline]
+    pub fn zipper(&self) -> TreeZipper<'_, T> {
+        TreeZipper::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zipper() {
+        let tree = PackedTree::new(0, |node_builder| {
+            node_builder.build_child(1, |node_builder| {
+                node_builder.add_leaf_children([2, 3, 4]);
+            });
+            node_builder.add_child(5);
+        });
+        let root = tree.zipper();
+        assert!(root.up().is_none() && root.left().is_none() && root.right().is_none());
+        assert!(root.down(2).is_none() && root.down(5).is_none());
+
+        let four = root.down(0).unwrap().down(2).unwrap();
+        assert_eq!((*four.val(), four.index(), four.depth()), (4, 4, 2));
+        assert!(four.right().is_none() && four.down(0).is_none());
+        let two = four.left().unwrap().left().unwrap();
+        assert_eq!(*two.val(), 2);
+        assert!(two.left().is_none());
+        assert_eq!(*two.right().unwrap().val(), 3);
+        assert_eq!(two.up().unwrap().node().num_descendants_incl_self(), 4);
+        assert_eq!(*two.up().unwrap().right().unwrap().val(), 5);
+        assert_eq!(two.root().index(), 0);
+    }
+}